@@ -0,0 +1,203 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The history of the gossiped configuration a service has applied.
+//!
+//! Every time a service applies a new `gossip.toml`, once it matches the schema of the package,
+//! the supervisor records the version in the `config_history` directory of the service, as
+//! `<version_number>.json`. Rejected versions are never recorded. Only the most recent versions
+//! are kept, so that an earlier one can be gossiped again with `hab config rollback`.
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use hcore::fs as hfs;
+use rustc_serialize::json;
+use time;
+
+use error::{Error, Result};
+use gossip_file::GossipFile;
+
+/// The name of the directory in the service path holding the history
+pub const CONFIG_HISTORY_DIR: &'static str = "config_history";
+/// How many versions of the configuration we keep
+pub const DEFAULT_CONFIG_HISTORY_SIZE: usize = 10;
+
+/// A version of the configuration, as it was applied.
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct ConfigVersion {
+    pub version_number: u64,
+    pub checksum: String,
+    /// When the version was applied, in RFC 3339 format
+    pub applied_at: String,
+    /// The TOML of the configuration
+    pub body: String,
+}
+
+/// The versions of the configuration a service has applied.
+#[derive(Debug)]
+pub struct ConfigHistory {
+    dir: PathBuf,
+    size: usize,
+}
+
+impl ConfigHistory {
+    pub fn new<P: Into<PathBuf>>(dir: P, size: usize) -> ConfigHistory {
+        ConfigHistory {
+            dir: dir.into(),
+            size: size,
+        }
+    }
+
+    /// The history of a service, kept in its service path.
+    pub fn for_service(service: &str) -> ConfigHistory {
+        ConfigHistory::new(hfs::svc_path(service).join(CONFIG_HISTORY_DIR),
+                           DEFAULT_CONFIG_HISTORY_SIZE)
+    }
+
+    /// Records a gossip file that was just applied, then forgets the oldest versions beyond the
+    /// size of the history.
+    pub fn record(&self, gf: &GossipFile) -> Result<()> {
+        let version = ConfigVersion {
+            version_number: gf.version_number(),
+            checksum: gf.checksum().to_string(),
+            applied_at: format!("{}", time::now_utc().rfc3339()),
+            body: String::from_utf8_lossy(gf.body()).into_owned(),
+        };
+        try!(fs::create_dir_all(&self.dir));
+        let path = self.path_for(version.version_number);
+        let tmp_path = path.with_extension("json.write");
+        {
+            let mut file = try!(File::create(&tmp_path));
+            try!(file.write_all(try!(json::encode(&version)).as_bytes()));
+        }
+        try!(fs::rename(&tmp_path, &path));
+
+        let numbers = try!(self.version_numbers());
+        if numbers.len() > self.size {
+            for number in &numbers[..numbers.len() - self.size] {
+                try!(fs::remove_file(self.path_for(*number)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the versions we remember, oldest first.
+    pub fn versions(&self) -> Result<Vec<ConfigVersion>> {
+        let mut versions = Vec::new();
+        for number in try!(self.version_numbers()) {
+            versions.push(try!(self.read(&self.path_for(number))));
+        }
+        Ok(versions)
+    }
+
+    /// Returns a version of the configuration.
+    ///
+    /// # Failures
+    ///
+    /// * The version is not in the history, or cannot be read
+    pub fn get(&self, version_number: u64) -> Result<ConfigVersion> {
+        let path = self.path_for(version_number);
+        if !path.exists() {
+            return Err(Error::ConfigVersionNotFound(version_number));
+        }
+        self.read(&path)
+    }
+
+    /// The highest version number we remember, if any.
+    pub fn latest_version_number(&self) -> Result<Option<u64>> {
+        Ok(try!(self.version_numbers()).pop())
+    }
+
+    fn read(&self, path: &Path) -> Result<ConfigVersion> {
+        let mut file = try!(File::open(path));
+        let mut buf = String::new();
+        try!(file.read_to_string(&mut buf));
+        Ok(try!(json::decode(&buf)))
+    }
+
+    fn path_for(&self, version_number: u64) -> PathBuf {
+        self.dir.join(format!("{}.json", version_number))
+    }
+
+    /// The version numbers in the history, sorted.
+    fn version_numbers(&self) -> Result<Vec<u64>> {
+        let mut numbers = Vec::new();
+        if !self.dir.is_dir() {
+            return Ok(numbers);
+        }
+        for entry in try!(fs::read_dir(&self.dir)) {
+            let path = try!(entry).path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(number) = path.file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok()) {
+                numbers.push(number);
+            }
+        }
+        numbers.sort();
+        Ok(numbers)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use tempdir::TempDir;
+
+    use hcore::service::ServiceGroup;
+    use gossip_file::GossipFile;
+    use super::ConfigHistory;
+
+    fn gossip_file(body: &str, version_number: u64) -> GossipFile {
+        GossipFile::from_body(ServiceGroup::from_str("redis.default").unwrap(),
+                              body.as_bytes().to_vec(),
+                              version_number)
+            .unwrap()
+    }
+
+    #[test]
+    fn records_and_returns_versions() {
+        let dir = TempDir::new("config_history").unwrap();
+        let history = ConfigHistory::new(dir.path(), 10);
+        assert!(history.versions().unwrap().is_empty());
+        history.record(&gossip_file("port=1\n", 3)).unwrap();
+        history.record(&gossip_file("port=2\n", 12)).unwrap();
+
+        let versions = history.versions().unwrap();
+        let numbers: Vec<u64> = versions.iter().map(|v| v.version_number).collect();
+        assert_eq!(numbers, vec![3, 12]);
+        assert_eq!(history.get(3).unwrap().body, "port=1\n");
+        assert_eq!(history.get(3).unwrap().checksum,
+                   gossip_file("port=1\n", 3).checksum());
+        assert_eq!(history.latest_version_number().unwrap(), Some(12));
+        assert!(history.get(4).is_err());
+    }
+
+    #[test]
+    fn forgets_the_oldest_versions() {
+        let dir = TempDir::new("config_history").unwrap();
+        let history = ConfigHistory::new(dir.path(), 2);
+        for number in 1..5 {
+            history.record(&gossip_file(&format!("port={}\n", number), number)).unwrap();
+        }
+        let numbers: Vec<u64> =
+            history.versions().unwrap().iter().map(|v| v.version_number).collect();
+        assert_eq!(numbers, vec![3, 4]);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sealed control requests, which change the state of a service through the supervisor's HTTP
+//! API.
+//!
+//! The sealed message is:
+//!
+//! ```text
+//! <request path>
+//! <seconds since the epoch>
+//! <request id>
+//! <request body, if any>
+//! ```
+//!
+//! It can be sealed in one of two ways:
+//!
+//! * With the ring key, which the supervisor shares with every member of its gossip ring:
+//!
+//!   ```text
+//!   RING-1
+//!   <ring key name with revision>
+//!   <nonce, base64>
+//!   <ciphertext, base64>
+//!   ```
+//!
+//! * With a user key, for the service key of the service being controlled. This is the same
+//!   `BOX-1` format used to encrypt configuration for a service; the supervisor must hold the
+//!   service's secret key and the user's public key.
+//!
+//! Every request has an id of its own, so a supervisor can refuse a request it has seen before.
+
+use std::str;
+
+use hcore::crypto::{BoxKeyPair, SymKey};
+use rustc_serialize::base64::{ToBase64, STANDARD};
+use time;
+use uuid::Uuid;
+
+use error::Result;
+
+pub static RING_FORMAT_VERSION: &'static str = "RING-1";
+pub static BOX_FORMAT_VERSION: &'static str = "BOX-1";
+
+/// A control request, before it is sealed or once it is opened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlRequest {
+    /// The path the request was sealed for
+    pub path: String,
+    /// When the request was sealed, in seconds since the epoch
+    pub timestamp: i64,
+    /// The id of the request, unique to it
+    pub id: String,
+    /// The body of the request
+    pub body: String,
+}
+
+impl ControlRequest {
+    pub fn new(path: &str, body: &str) -> ControlRequest {
+        ControlRequest {
+            path: path.to_string(),
+            timestamp: time::get_time().sec,
+            id: Uuid::new_v4().simple().to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    /// Reads an opened request; `None` if it is malformed.
+    pub fn from_plaintext(plaintext: &[u8]) -> Option<ControlRequest> {
+        let plaintext = match str::from_utf8(plaintext) {
+            Ok(plaintext) => plaintext,
+            Err(_) => return None,
+        };
+        let mut parts = plaintext.splitn(4, '\n');
+        let path = match parts.next() {
+            Some(path) if !path.is_empty() => path.to_string(),
+            _ => return None,
+        };
+        let timestamp = match parts.next().and_then(|t| t.parse::<i64>().ok()) {
+            Some(timestamp) => timestamp,
+            None => return None,
+        };
+        let id = match parts.next() {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => return None,
+        };
+        Some(ControlRequest {
+            path: path,
+            timestamp: timestamp,
+            id: id,
+            body: parts.next().unwrap_or("").to_string(),
+        })
+    }
+
+    /// Seals the request with a ring key.
+    ///
+    /// # Failures
+    ///
+    /// * The secret part of the ring key is not present
+    pub fn seal_with_ring_key(&self, ring_key: &SymKey) -> Result<String> {
+        let (nonce, ciphertext) = try!(ring_key.encrypt(self.to_plaintext().as_bytes()));
+        Ok(format!("{}\n{}\n{}\n{}",
+                   RING_FORMAT_VERSION,
+                   ring_key.name_with_rev(),
+                   nonce.to_base64(STANDARD),
+                   ciphertext.to_base64(STANDARD)))
+    }
+
+    /// Seals the request with a user key, for a service key.
+    ///
+    /// # Failures
+    ///
+    /// * The secret part of the user key or the public part of the service key is not present
+    pub fn seal_with_user_key(&self,
+                              user_key: &BoxKeyPair,
+                              service_key: &BoxKeyPair)
+                              -> Result<String> {
+        let sealed = try!(user_key.encrypt(self.to_plaintext().as_bytes(), service_key));
+        Ok(try!(String::from_utf8(sealed)))
+    }
+
+    fn to_plaintext(&self) -> String {
+        format!("{}\n{}\n{}\n{}", self.path, self.timestamp, self.id, self.body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ControlRequest;
+
+    #[test]
+    fn plaintext_round_trip() {
+        let request = ControlRequest::new("/services", "core/redis\nwith a newline");
+        let opened = ControlRequest::from_plaintext(request.to_plaintext().as_bytes()).unwrap();
+        assert_eq!(opened, request);
+    }
+
+    #[test]
+    fn every_request_has_its_own_id() {
+        assert!(ControlRequest::new("/start", "").id != ControlRequest::new("/start", "").id);
+    }
+
+    #[test]
+    fn from_plaintext_needs_an_id() {
+        assert!(ControlRequest::from_plaintext(b"/start\n0\n").is_none());
+        assert!(ControlRequest::from_plaintext(b"/start\n0").is_none());
+        assert!(ControlRequest::from_plaintext(b"/start\n0\nabc\n").is_some());
+    }
+}
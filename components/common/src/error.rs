@@ -21,6 +21,7 @@ use std::string;
 
 use depot_client;
 use hcore;
+use protobuf;
 use rustc_serialize::json;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -29,16 +30,19 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     ArtifactIdentMismatch((String, String, String)),
     CantUploadGossipToml,
+    ConfigVersionNotFound(u64),
     CryptoKeyError(String),
     GossipFileRelativePath(String),
     DepotClient(depot_client::Error),
     FileNameError,
     HabitatCore(hcore::Error),
+    InvalidEvent(String),
     InvalidTomlError(String),
     /// Occurs when making lower level IO calls.
     IO(io::Error),
     JsonDecode(json::DecoderError),
     JsonEncode(json::EncoderError),
+    Protobuf(protobuf::ProtobufError),
     RootRequired,
     StrFromUtf8Error(str::Utf8Error),
     StringFromUtf8Error(string::FromUtf8Error),
@@ -57,6 +61,9 @@ impl fmt::Display for Error {
             Error::CantUploadGossipToml => {
                 format!("Can't upload gossip.toml, it's a reserved file name")
             }
+            Error::ConfigVersionNotFound(n) => {
+                format!("Configuration version {} is not in the history", n)
+            }
             Error::CryptoKeyError(ref s) => format!("Missing or invalid key: {}", s),
             Error::GossipFileRelativePath(ref s) => {
                 format!("Path for gossip file cannot have relative components (eg: ..): {}",
@@ -65,10 +72,12 @@ impl fmt::Display for Error {
             Error::DepotClient(ref err) => format!("{}", err),
             Error::FileNameError => format!("Failed to extract a filename"),
             Error::HabitatCore(ref e) => format!("{}", e),
+            Error::InvalidEvent(ref e) => format!("Invalid event: {}", e),
             Error::InvalidTomlError(ref e) => format!("Invalid TOML: {}", e),
             Error::IO(ref err) => format!("{}", err),
             Error::JsonDecode(ref e) => format!("JSON decoding error: {}", e),
             Error::JsonEncode(ref e) => format!("JSON encoding error: {}", e),
+            Error::Protobuf(ref e) => format!("{}", e),
             Error::RootRequired => {
                 "Root or administrator permissions required to complete operation".to_string()
             }
@@ -87,6 +96,7 @@ impl error::Error for Error {
                 "Artifact ident does not match expected ident"
             }
             Error::CantUploadGossipToml => "Can't upload gossip.toml, it's a reserved filename",
+            Error::ConfigVersionNotFound(_) => "Configuration version is not in the history",
             Error::CryptoKeyError(_) => "Missing or invalid key",
             Error::GossipFileRelativePath(_) => {
                 "Path for gossip file cannot have relative components (eg: ..)"
@@ -94,10 +104,12 @@ impl error::Error for Error {
             Error::DepotClient(ref err) => err.description(),
             Error::FileNameError => "Failed to extract a filename from a path",
             Error::HabitatCore(ref err) => err.description(),
+            Error::InvalidEvent(_) => "Invalid event",
             Error::InvalidTomlError(_) => "Invalid TOML",
             Error::IO(ref err) => err.description(),
             Error::JsonDecode(_) => "JSON decoding error: {:?}",
             Error::JsonEncode(_) => "JSON encoding error",
+            Error::Protobuf(ref err) => err.description(),
             Error::RootRequired => {
                 "Root or administrator permissions required to complete operation"
             }
@@ -138,6 +150,12 @@ impl From<json::EncoderError> for Error {
     }
 }
 
+impl From<protobuf::ProtobufError> for Error {
+    fn from(err: protobuf::ProtobufError) -> Self {
+        Error::Protobuf(err)
+    }
+}
+
 impl From<str::Utf8Error> for Error {
     fn from(err: str::Utf8Error) -> Self {
         Error::StrFromUtf8Error(err)
@@ -0,0 +1,363 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Events are small messages gossiped to a service group, such as cache invalidations or
+//! maintenance notices. Each has a topic, and expires a number of seconds after it was published.
+//!
+//! Events piggyback on the failure detector like every other rumor, so their bodies are kept
+//! small.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use hcore::service::ServiceGroup;
+use time;
+use uuid::Uuid;
+
+use error::{Error, Result};
+use message::swim;
+use wire_message::{ProtoConvert, uuid_from_proto};
+
+/// The largest body an event can carry, in bytes
+pub const MAX_EVENT_BODY_BYTES: usize = 1024;
+/// The longest topic an event can have
+pub const MAX_EVENT_TOPIC_LEN: usize = 64;
+/// The longest an event can live, in seconds
+pub const MAX_EVENT_TTL_SECS: u64 = 3600;
+/// How long an event lives if its publisher doesn't say
+pub const DEFAULT_EVENT_TTL_SECS: u64 = 300;
+/// How many events a service keeps for its clients; older ones are dropped even if they have
+/// not expired
+pub const MAX_EVENTS_KEPT: usize = 100;
+
+/// An event for a service group.
+#[derive(Clone, Debug, PartialEq, Eq, RustcDecodable, RustcEncodable)]
+pub struct Event {
+    pub id: Uuid,
+    pub service_group: ServiceGroup,
+    pub topic: String,
+    pub body: String,
+    /// When the event was published, in seconds since the epoch
+    pub published_at: i64,
+    /// How many seconds after it was published the event expires
+    pub ttl_secs: u64,
+}
+
+impl Event {
+    /// Creates an event, published now.
+    ///
+    /// # Failures
+    ///
+    /// * The topic is empty, too long, or has characters other than letters, digits, `.`, `_`
+    ///   and `-`
+    /// * The body is larger than `MAX_EVENT_BODY_BYTES`
+    /// * The TTL is 0, or longer than `MAX_EVENT_TTL_SECS`
+    pub fn new(service_group: ServiceGroup,
+               topic: &str,
+               body: String,
+               ttl_secs: u64)
+               -> Result<Event> {
+        let event = Event {
+            id: Uuid::new_v4(),
+            service_group: service_group,
+            topic: topic.to_string(),
+            body: body,
+            published_at: time::get_time().sec,
+            ttl_secs: ttl_secs,
+        };
+        try!(event.validate());
+        Ok(event)
+    }
+
+    /// Checks the event is within the limits on its topic, body and TTL. Events we receive are
+    /// checked too, as they may come from anyone with the ring key.
+    pub fn validate(&self) -> Result<()> {
+        if self.topic.is_empty() || self.topic.len() > MAX_EVENT_TOPIC_LEN ||
+           !self.topic.chars().all(is_topic_char) {
+            return Err(Error::InvalidEvent(format!("topic must be 1 to {} letters, digits, \
+                                                    `.`, `_` or `-`; got `{}`",
+                                                   MAX_EVENT_TOPIC_LEN,
+                                                   self.topic)));
+        }
+        if self.body.len() > MAX_EVENT_BODY_BYTES {
+            return Err(Error::InvalidEvent(format!("body is {} bytes; the limit is {}",
+                                                   self.body.len(),
+                                                   MAX_EVENT_BODY_BYTES)));
+        }
+        if self.ttl_secs == 0 || self.ttl_secs > MAX_EVENT_TTL_SECS {
+            return Err(Error::InvalidEvent(format!("TTL must be 1 to {} seconds; got {}",
+                                                   MAX_EVENT_TTL_SECS,
+                                                   self.ttl_secs)));
+        }
+        Ok(())
+    }
+
+    /// When the event expires, in seconds since the epoch.
+    pub fn expires_at(&self) -> i64 {
+        self.published_at + self.ttl_secs as i64
+    }
+
+    /// Whether the event has expired, by our clock.
+    pub fn is_expired(&self) -> bool {
+        time::get_time().sec >= self.expires_at()
+    }
+}
+
+fn is_topic_char(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '.' | '_' | '-' => true,
+        _ => false,
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} event {} for {}", self.topic, self.id, self.service_group)
+    }
+}
+
+impl ProtoConvert for Event {
+    type Proto = swim::Event;
+
+    fn to_proto(&self) -> swim::Event {
+        let mut proto = swim::Event::new();
+        proto.set_id(self.id.to_string());
+        proto.set_service_group(self.service_group.to_proto());
+        proto.set_topic(self.topic.clone());
+        proto.set_body(self.body.clone());
+        proto.set_published_at(self.published_at);
+        proto.set_ttl_secs(self.ttl_secs);
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Event) -> Result<Event> {
+        Ok(Event {
+            id: try!(uuid_from_proto(proto.get_id())),
+            service_group: try!(ServiceGroup::from_proto(proto.take_service_group())),
+            topic: proto.take_topic(),
+            body: proto.take_body(),
+            published_at: proto.get_published_at(),
+            ttl_secs: proto.get_ttl_secs(),
+        })
+    }
+}
+
+/// An event, numbered in the order a service received it.
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable)]
+pub struct SequencedEvent {
+    pub sequence: u64,
+    pub event: Event,
+}
+
+/// Wakes whoever waits for the next event a service receives.
+#[derive(Clone)]
+pub struct EventNotifier {
+    /// The sequence number of the last event received
+    last_sequence: Arc<(Mutex<u64>, Condvar)>,
+}
+
+impl EventNotifier {
+    fn new() -> EventNotifier {
+        EventNotifier { last_sequence: Arc::new((Mutex::new(0), Condvar::new())) }
+    }
+
+    fn notify(&self, sequence: u64) {
+        let &(ref lock, ref condvar) = &*self.last_sequence;
+        *lock.lock().unwrap() = sequence;
+        condvar.notify_all();
+    }
+
+    /// Waits up to `timeout` for an event to be received after `sequence`. Returns true if one
+    /// was.
+    pub fn wait(&self, sequence: u64, timeout: Duration) -> bool {
+        let &(ref lock, ref condvar) = &*self.last_sequence;
+        let deadline = Instant::now() + timeout;
+        let mut last_sequence = lock.lock().unwrap();
+        while *last_sequence <= sequence {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            last_sequence = condvar.wait_timeout(last_sequence, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
+
+impl fmt::Debug for EventNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventNotifier")
+    }
+}
+
+/// The unexpired events of a service group a service has received.
+#[derive(Debug)]
+pub struct EventList {
+    my_service_group: ServiceGroup,
+    events: VecDeque<SequencedEvent>,
+    /// The sequence number of the last event we received
+    last_sequence: u64,
+    /// The sequence number of the last event we ran the event hook for
+    delivered: u64,
+    notifier: EventNotifier,
+}
+
+impl EventList {
+    pub fn new(service_group: ServiceGroup) -> EventList {
+        EventList {
+            my_service_group: service_group,
+            events: VecDeque::new(),
+            last_sequence: 0,
+            delivered: 0,
+            notifier: EventNotifier::new(),
+        }
+    }
+
+    /// The sequence number of the last event we received.
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Notifies its holders of every event we receive from now on, so they can wait for one
+    /// without holding on to the event list.
+    pub fn notifier(&self) -> EventNotifier {
+        self.notifier.clone()
+    }
+
+    /// Takes an event we learned about. Returns true if it is an unexpired event for our service
+    /// group that we had not received yet.
+    pub fn process(&mut self, event: Event) -> bool {
+        self.prune_expired();
+        if event.service_group != self.my_service_group || event.is_expired() ||
+           self.events.iter().any(|e| e.event.id == event.id) {
+            return false;
+        }
+        self.last_sequence += 1;
+        self.events.push_back(SequencedEvent {
+            sequence: self.last_sequence,
+            event: event,
+        });
+        if self.events.len() > MAX_EVENTS_KEPT {
+            self.events.pop_front();
+        }
+        self.notifier.notify(self.last_sequence);
+        true
+    }
+
+    /// The unexpired events received after `sequence`, oldest first; only those with `topic`, if
+    /// one is given.
+    pub fn since(&self, sequence: u64, topic: Option<&str>) -> Vec<SequencedEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.sequence > sequence && !e.event.is_expired())
+            .filter(|e| topic.map_or(true, |topic| e.event.topic == topic))
+            .cloned()
+            .collect()
+    }
+
+    /// The unexpired events we have not run the event hook for yet, oldest first. They are
+    /// counted as delivered from now on.
+    pub fn undelivered(&mut self) -> Vec<Event> {
+        let events = self.since(self.delivered, None).into_iter().map(|e| e.event).collect();
+        self.delivered = self.last_sequence;
+        events
+    }
+
+    fn prune_expired(&mut self) {
+        let events = self.events.drain(..).filter(|e| !e.event.is_expired()).collect();
+        self.events = events;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+
+    use hcore::service::ServiceGroup;
+
+    use super::{Event, EventList, MAX_EVENT_BODY_BYTES, MAX_EVENT_TTL_SECS};
+
+    fn group() -> ServiceGroup {
+        ServiceGroup::from_str("redis.default").unwrap()
+    }
+
+    fn event(topic: &str) -> Event {
+        Event::new(group(), topic, String::from("flush"), 60).unwrap()
+    }
+
+    #[test]
+    fn new_checks_limits() {
+        assert!(Event::new(group(), "cache", String::from("flush"), 60).is_ok());
+        assert!(Event::new(group(), "", String::new(), 60).is_err());
+        assert!(Event::new(group(), "cache invalidation", String::new(), 60).is_err());
+        let body = ::std::iter::repeat("x").take(MAX_EVENT_BODY_BYTES + 1).collect::<String>();
+        assert!(Event::new(group(), "cache", body, 60).is_err());
+        assert!(Event::new(group(), "cache", String::new(), 0).is_err());
+        assert!(Event::new(group(), "cache", String::new(), MAX_EVENT_TTL_SECS + 1).is_err());
+    }
+
+    #[test]
+    fn process_takes_new_events_for_our_group() {
+        let mut el = EventList::new(group());
+        let e = event("cache");
+        assert!(el.process(e.clone()));
+        assert!(!el.process(e));
+
+        let mut other = event("cache");
+        other.service_group = ServiceGroup::from_str("redis.production").unwrap();
+        assert!(!el.process(other));
+
+        let mut expired = event("cache");
+        expired.published_at -= 61;
+        assert!(!el.process(expired));
+    }
+
+    #[test]
+    fn since_filters_by_sequence_and_topic() {
+        let mut el = EventList::new(group());
+        el.process(event("cache"));
+        el.process(event("maintenance"));
+        el.process(event("cache"));
+        assert_eq!(el.since(0, None).len(), 3);
+        assert_eq!(el.since(1, None).len(), 2);
+        let cache = el.since(0, Some("cache"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache[1].sequence, 3);
+    }
+
+    #[test]
+    fn undelivered_returns_each_event_once() {
+        let mut el = EventList::new(group());
+        el.process(event("cache"));
+        assert_eq!(el.undelivered().len(), 1);
+        assert!(el.undelivered().is_empty());
+        el.process(event("cache"));
+        assert_eq!(el.undelivered().len(), 1);
+    }
+
+    #[test]
+    fn notifier_wakes_waiters_on_new_events() {
+        let mut el = EventList::new(group());
+        let notifier = el.notifier();
+        let sequence = el.last_sequence();
+        assert!(!notifier.wait(sequence, Duration::from_millis(10)));
+        let waiter = thread::spawn(move || notifier.wait(sequence, Duration::from_secs(10)));
+        el.process(event("cache"));
+        assert!(waiter.join().unwrap());
+    }
+}
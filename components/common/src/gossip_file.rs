@@ -29,7 +29,10 @@ use rustc_serialize::{Encodable, Encoder};
 use rustc_serialize::hex::ToHex;
 use time::{SteadyTime, Duration};
 use toml;
+
 use error::{Error, Result};
+use message::swim;
+use wire_message::ProtoConvert;
 
 const IDEMPOTENCY_INTERVAL_MINUTES: i64 = 5;
 
@@ -179,6 +182,18 @@ impl GossipFile {
         }
     }
 
+    pub fn version_number(&self) -> u64 {
+        self.version_number
+    }
+
+    pub fn checksum(&self) -> &str {
+        &self.checksum
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
     pub fn on_disk_path(&self) -> PathBuf {
         if &self.file_name == GOSSIP_TOML {
             fs::svc_path(&self.service_group.service).join(&self.file_name)
@@ -259,6 +274,34 @@ impl PartialEq for GossipFile {
     }
 }
 
+impl ProtoConvert for GossipFile {
+    type Proto = swim::GossipFile;
+
+    fn to_proto(&self) -> swim::GossipFile {
+        let mut proto = swim::GossipFile::new();
+        proto.set_service_group(self.service_group.to_proto());
+        proto.set_file_name(self.file_name.clone());
+        proto.set_body(self.body.clone());
+        proto.set_checksum(self.checksum.clone());
+        proto.set_version_number(self.version_number);
+        proto.set_written(self.written);
+        proto.set_encrypted(self.encrypted);
+        proto
+    }
+
+    fn from_proto(mut proto: swim::GossipFile) -> Result<GossipFile> {
+        Ok(GossipFile {
+            service_group: try!(ServiceGroup::from_proto(proto.take_service_group())),
+            file_name: proto.take_file_name(),
+            body: proto.take_body(),
+            checksum: proto.take_checksum(),
+            version_number: proto.get_version_number(),
+            written: proto.get_written(),
+            encrypted: proto.get_encrypted(),
+        })
+    }
+}
+
 
 /// try to parse the string as Toml
 fn is_data_toml(body: String) -> Result<()> {
@@ -332,6 +375,8 @@ pub struct GossipFileList {
     gossip_files: HashMap<(ServiceGroup, String), GossipFile>,
     next_idempotency_check: SteadyTime,
     pub file_write_retries: HashMap<String, FileWriteRetry>,
+    /// The `gossip.toml` we last wrote, until the service has applied it
+    written_config: Option<GossipFile>,
 }
 
 impl GossipFileList {
@@ -342,6 +387,7 @@ impl GossipFileList {
             next_idempotency_check: SteadyTime::now() +
                                     Duration::minutes(IDEMPOTENCY_INTERVAL_MINUTES),
             file_write_retries: HashMap::new(),
+            written_config: None,
         }
     }
 
@@ -438,11 +484,20 @@ impl GossipFileList {
             }
             if gf.file_name == GOSSIP_TOML {
                 needs_reconfigure = true;
+                if written {
+                    self.written_config = Some(gf.clone());
+                }
             }
         }
         Ok((needs_file_updated, needs_reconfigure))
     }
 
+    /// Takes the `gossip.toml` we last wrote, if we wrote one since we were last asked. Once the
+    /// service has validated and applied it, it is recorded in the configuration history.
+    pub fn take_written_config(&mut self) -> Option<GossipFile> {
+        self.written_config.take()
+    }
+
     pub fn process(&mut self, remote_gf: GossipFile) -> bool {
         if let Some(mut current_gf) = self.get_mut(&remote_gf.service_group, &remote_gf.file_name) {
             return current_gf.update_via(remote_gf);
@@ -20,6 +20,7 @@ extern crate ansi_term;
 extern crate log;
 extern crate openssl;
 extern crate pbr;
+extern crate protobuf;
 extern crate regex;
 extern crate rustc_serialize;
 #[cfg(test)]
@@ -27,11 +28,16 @@ extern crate tempdir;
 extern crate term;
 extern crate time;
 extern crate toml;
+extern crate uuid;
 
 pub use self::error::{Error, Result};
 
 pub mod command;
+pub mod config_history;
+pub mod control;
+pub mod event;
 pub mod gossip_file;
 pub mod error;
+pub mod message;
 pub mod ui;
 pub mod wire_message;
@@ -0,0 +1,6522 @@
+// This file is generated. Do not edit
+// @generated
+
+// https://github.com/Manishearth/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy)]
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+#![allow(dead_code)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(unused_imports)]
+
+use protobuf::Message as Message_imported_for_functions;
+use protobuf::ProtobufEnum as ProtobufEnum_imported_for_functions;
+
+#[derive(Clone,Default)]
+pub struct ServiceGroup {
+    // message fields
+    service: ::protobuf::SingularField<::std::string::String>,
+    group: ::protobuf::SingularField<::std::string::String>,
+    organization: ::protobuf::SingularField<::std::string::String>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for ServiceGroup {}
+
+impl ServiceGroup {
+    pub fn new() -> ServiceGroup {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static ServiceGroup {
+        static mut instance: ::protobuf::lazy::Lazy<ServiceGroup> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ServiceGroup,
+        };
+        unsafe {
+            instance.get(|| {
+                ServiceGroup {
+                    service: ::protobuf::SingularField::none(),
+                    group: ::protobuf::SingularField::none(),
+                    organization: ::protobuf::SingularField::none(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string service = 1;
+
+    pub fn clear_service(&mut self) {
+        self.service.clear();
+    }
+
+    pub fn has_service(&self) -> bool {
+        self.service.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_service(&mut self, v: ::std::string::String) {
+        self.service = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_service(&mut self) -> &mut ::std::string::String {
+        if self.service.is_none() {
+            self.service.set_default();
+        };
+        self.service.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_service(&mut self) -> ::std::string::String {
+        self.service.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_service(&self) -> &str {
+        match self.service.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string group = 2;
+
+    pub fn clear_group(&mut self) {
+        self.group.clear();
+    }
+
+    pub fn has_group(&self) -> bool {
+        self.group.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_group(&mut self, v: ::std::string::String) {
+        self.group = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_group(&mut self) -> &mut ::std::string::String {
+        if self.group.is_none() {
+            self.group.set_default();
+        };
+        self.group.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_group(&mut self) -> ::std::string::String {
+        self.group.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_group(&self) -> &str {
+        match self.group.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional string organization = 3;
+
+    pub fn clear_organization(&mut self) {
+        self.organization.clear();
+    }
+
+    pub fn has_organization(&self) -> bool {
+        self.organization.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_organization(&mut self, v: ::std::string::String) {
+        self.organization = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_organization(&mut self) -> &mut ::std::string::String {
+        if self.organization.is_none() {
+            self.organization.set_default();
+        };
+        self.organization.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_organization(&mut self) -> ::std::string::String {
+        self.organization.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_organization(&self) -> &str {
+        match self.organization.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+}
+
+impl ::protobuf::Message for ServiceGroup {
+    fn is_initialized(&self) -> bool {
+        if self.service.is_none() {
+            return false;
+        };
+        if self.group.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.service));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.group));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.organization));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.service.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.group.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.organization.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.service.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.group.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.organization.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<ServiceGroup>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for ServiceGroup {
+    fn new() -> ServiceGroup {
+        ServiceGroup::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<ServiceGroup>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "service",
+                    ServiceGroup::has_service,
+                    ServiceGroup::get_service,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "group",
+                    ServiceGroup::has_group,
+                    ServiceGroup::get_group,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "organization",
+                    ServiceGroup::has_organization,
+                    ServiceGroup::get_organization,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<ServiceGroup>(
+                    "ServiceGroup",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for ServiceGroup {
+    fn clear(&mut self) {
+        self.clear_service();
+        self.clear_group();
+        self.clear_organization();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for ServiceGroup {
+    fn eq(&self, other: &ServiceGroup) -> bool {
+        self.service == other.service &&
+        self.group == other.group &&
+        self.organization == other.organization &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for ServiceGroup {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Peer {
+    // message fields
+    member_id: ::protobuf::SingularField<::std::string::String>,
+    listening_on: ::protobuf::SingularField<::std::string::String>,
+    proxy_through: ::protobuf::SingularField<::std::string::String>,
+    proxy_to: ::protobuf::SingularField<::std::string::String>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Peer {}
+
+impl Peer {
+    pub fn new() -> Peer {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Peer {
+        static mut instance: ::protobuf::lazy::Lazy<Peer> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Peer,
+        };
+        unsafe {
+            instance.get(|| {
+                Peer {
+                    member_id: ::protobuf::SingularField::none(),
+                    listening_on: ::protobuf::SingularField::none(),
+                    proxy_through: ::protobuf::SingularField::none(),
+                    proxy_to: ::protobuf::SingularField::none(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string member_id = 1;
+
+    pub fn clear_member_id(&mut self) {
+        self.member_id.clear();
+    }
+
+    pub fn has_member_id(&self) -> bool {
+        self.member_id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_member_id(&mut self, v: ::std::string::String) {
+        self.member_id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_member_id(&mut self) -> &mut ::std::string::String {
+        if self.member_id.is_none() {
+            self.member_id.set_default();
+        };
+        self.member_id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_member_id(&mut self) -> ::std::string::String {
+        self.member_id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_member_id(&self) -> &str {
+        match self.member_id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string listening_on = 2;
+
+    pub fn clear_listening_on(&mut self) {
+        self.listening_on.clear();
+    }
+
+    pub fn has_listening_on(&self) -> bool {
+        self.listening_on.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_listening_on(&mut self, v: ::std::string::String) {
+        self.listening_on = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_listening_on(&mut self) -> &mut ::std::string::String {
+        if self.listening_on.is_none() {
+            self.listening_on.set_default();
+        };
+        self.listening_on.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_listening_on(&mut self) -> ::std::string::String {
+        self.listening_on.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_listening_on(&self) -> &str {
+        match self.listening_on.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional string proxy_through = 3;
+
+    pub fn clear_proxy_through(&mut self) {
+        self.proxy_through.clear();
+    }
+
+    pub fn has_proxy_through(&self) -> bool {
+        self.proxy_through.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_proxy_through(&mut self, v: ::std::string::String) {
+        self.proxy_through = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_proxy_through(&mut self) -> &mut ::std::string::String {
+        if self.proxy_through.is_none() {
+            self.proxy_through.set_default();
+        };
+        self.proxy_through.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_proxy_through(&mut self) -> ::std::string::String {
+        self.proxy_through.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_proxy_through(&self) -> &str {
+        match self.proxy_through.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional string proxy_to = 4;
+
+    pub fn clear_proxy_to(&mut self) {
+        self.proxy_to.clear();
+    }
+
+    pub fn has_proxy_to(&self) -> bool {
+        self.proxy_to.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_proxy_to(&mut self, v: ::std::string::String) {
+        self.proxy_to = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_proxy_to(&mut self) -> &mut ::std::string::String {
+        if self.proxy_to.is_none() {
+            self.proxy_to.set_default();
+        };
+        self.proxy_to.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_proxy_to(&mut self) -> ::std::string::String {
+        self.proxy_to.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_proxy_to(&self) -> &str {
+        match self.proxy_to.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+}
+
+impl ::protobuf::Message for Peer {
+    fn is_initialized(&self) -> bool {
+        if self.member_id.is_none() {
+            return false;
+        };
+        if self.listening_on.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.member_id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.listening_on));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.proxy_through));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.proxy_to));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.member_id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.listening_on.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.proxy_through.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.proxy_to.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.member_id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.listening_on.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.proxy_through.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.proxy_to.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Peer>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Peer {
+    fn new() -> Peer {
+        Peer::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Peer>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "member_id",
+                    Peer::has_member_id,
+                    Peer::get_member_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "listening_on",
+                    Peer::has_listening_on,
+                    Peer::get_listening_on,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "proxy_through",
+                    Peer::has_proxy_through,
+                    Peer::get_proxy_through,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "proxy_to",
+                    Peer::has_proxy_to,
+                    Peer::get_proxy_to,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Peer>(
+                    "Peer",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Peer {
+    fn clear(&mut self) {
+        self.clear_member_id();
+        self.clear_listening_on();
+        self.clear_proxy_through();
+        self.clear_proxy_to();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Peer {
+    fn eq(&self, other: &Peer) -> bool {
+        self.member_id == other.member_id &&
+        self.listening_on == other.listening_on &&
+        self.proxy_through == other.proxy_through &&
+        self.proxy_to == other.proxy_to &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Peer {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Member {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    hostname: ::protobuf::SingularField<::std::string::String>,
+    ip: ::protobuf::SingularField<::std::string::String>,
+    gossip_listener: ::protobuf::SingularField<::std::string::String>,
+    incarnation: ::std::option::Option<u64>,
+    health: ::std::option::Option<Health>,
+    permanent: ::std::option::Option<bool>,
+    protocol_version: ::std::option::Option<u32>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Member {}
+
+impl Member {
+    pub fn new() -> Member {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Member {
+        static mut instance: ::protobuf::lazy::Lazy<Member> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Member,
+        };
+        unsafe {
+            instance.get(|| {
+                Member {
+                    id: ::protobuf::SingularField::none(),
+                    hostname: ::protobuf::SingularField::none(),
+                    ip: ::protobuf::SingularField::none(),
+                    gossip_listener: ::protobuf::SingularField::none(),
+                    incarnation: ::std::option::Option::None,
+                    health: ::std::option::Option::None,
+                    permanent: ::std::option::Option::None,
+                    protocol_version: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string hostname = 2;
+
+    pub fn clear_hostname(&mut self) {
+        self.hostname.clear();
+    }
+
+    pub fn has_hostname(&self) -> bool {
+        self.hostname.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_hostname(&mut self, v: ::std::string::String) {
+        self.hostname = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_hostname(&mut self) -> &mut ::std::string::String {
+        if self.hostname.is_none() {
+            self.hostname.set_default();
+        };
+        self.hostname.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_hostname(&mut self) -> ::std::string::String {
+        self.hostname.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_hostname(&self) -> &str {
+        match self.hostname.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string ip = 3;
+
+    pub fn clear_ip(&mut self) {
+        self.ip.clear();
+    }
+
+    pub fn has_ip(&self) -> bool {
+        self.ip.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ip(&mut self, v: ::std::string::String) {
+        self.ip = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_ip(&mut self) -> &mut ::std::string::String {
+        if self.ip.is_none() {
+            self.ip.set_default();
+        };
+        self.ip.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_ip(&mut self) -> ::std::string::String {
+        self.ip.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_ip(&self) -> &str {
+        match self.ip.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string gossip_listener = 4;
+
+    pub fn clear_gossip_listener(&mut self) {
+        self.gossip_listener.clear();
+    }
+
+    pub fn has_gossip_listener(&self) -> bool {
+        self.gossip_listener.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_gossip_listener(&mut self, v: ::std::string::String) {
+        self.gossip_listener = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_gossip_listener(&mut self) -> &mut ::std::string::String {
+        if self.gossip_listener.is_none() {
+            self.gossip_listener.set_default();
+        };
+        self.gossip_listener.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_gossip_listener(&mut self) -> ::std::string::String {
+        self.gossip_listener.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_gossip_listener(&self) -> &str {
+        match self.gossip_listener.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required uint64 incarnation = 5;
+
+    pub fn clear_incarnation(&mut self) {
+        self.incarnation = ::std::option::Option::None;
+    }
+
+    pub fn has_incarnation(&self) -> bool {
+        self.incarnation.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_incarnation(&mut self, v: u64) {
+        self.incarnation = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_incarnation(&self) -> u64 {
+        self.incarnation.unwrap_or(0)
+    }
+
+    // required .swim.Health health = 6;
+
+    pub fn clear_health(&mut self) {
+        self.health = ::std::option::Option::None;
+    }
+
+    pub fn has_health(&self) -> bool {
+        self.health.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_health(&mut self, v: Health) {
+        self.health = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_health(&self) -> Health {
+        self.health.unwrap_or(Health::ALIVE)
+    }
+
+    // required bool permanent = 7;
+
+    pub fn clear_permanent(&mut self) {
+        self.permanent = ::std::option::Option::None;
+    }
+
+    pub fn has_permanent(&self) -> bool {
+        self.permanent.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_permanent(&mut self, v: bool) {
+        self.permanent = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_permanent(&self) -> bool {
+        self.permanent.unwrap_or(false)
+    }
+
+    // optional uint32 protocol_version = 8;
+
+    pub fn clear_protocol_version(&mut self) {
+        self.protocol_version = ::std::option::Option::None;
+    }
+
+    pub fn has_protocol_version(&self) -> bool {
+        self.protocol_version.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_protocol_version(&mut self, v: u32) {
+        self.protocol_version = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_protocol_version(&self) -> u32 {
+        self.protocol_version.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for Member {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.hostname.is_none() {
+            return false;
+        };
+        if self.ip.is_none() {
+            return false;
+        };
+        if self.gossip_listener.is_none() {
+            return false;
+        };
+        if self.incarnation.is_none() {
+            return false;
+        };
+        if self.health.is_none() {
+            return false;
+        };
+        if self.permanent.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.hostname));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.ip));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.gossip_listener));
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.incarnation = ::std::option::Option::Some(tmp);
+                },
+                6 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.health = ::std::option::Option::Some(tmp);
+                },
+                7 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.permanent = ::std::option::Option::Some(tmp);
+                },
+                8 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint32());
+                    self.protocol_version = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.hostname.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.ip.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.gossip_listener.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        for value in self.incarnation.iter() {
+            my_size += ::protobuf::rt::value_size(5, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        for value in self.health.iter() {
+            my_size += ::protobuf::rt::enum_size(6, *value);
+        };
+        if self.permanent.is_some() {
+            my_size += 2;
+        };
+        for value in self.protocol_version.iter() {
+            my_size += ::protobuf::rt::value_size(8, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.hostname.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.ip.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.gossip_listener.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        if let Some(v) = self.incarnation {
+            try!(os.write_uint64(5, v));
+        };
+        if let Some(v) = self.health {
+            try!(os.write_enum(6, v.value()));
+        };
+        if let Some(v) = self.permanent {
+            try!(os.write_bool(7, v));
+        };
+        if let Some(v) = self.protocol_version {
+            try!(os.write_uint32(8, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Member>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Member {
+    fn new() -> Member {
+        Member::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Member>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    Member::has_id,
+                    Member::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "hostname",
+                    Member::has_hostname,
+                    Member::get_hostname,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "ip",
+                    Member::has_ip,
+                    Member::get_ip,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "gossip_listener",
+                    Member::has_gossip_listener,
+                    Member::get_gossip_listener,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "incarnation",
+                    Member::has_incarnation,
+                    Member::get_incarnation,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "health",
+                    Member::has_health,
+                    Member::get_health,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "permanent",
+                    Member::has_permanent,
+                    Member::get_permanent,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u32_accessor(
+                    "protocol_version",
+                    Member::has_protocol_version,
+                    Member::get_protocol_version,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Member>(
+                    "Member",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Member {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_hostname();
+        self.clear_ip();
+        self.clear_gossip_listener();
+        self.clear_incarnation();
+        self.clear_health();
+        self.clear_permanent();
+        self.clear_protocol_version();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Member {
+    fn eq(&self, other: &Member) -> bool {
+        self.id == other.id &&
+        self.hostname == other.hostname &&
+        self.ip == other.ip &&
+        self.gossip_listener == other.gossip_listener &&
+        self.incarnation == other.incarnation &&
+        self.health == other.health &&
+        self.permanent == other.permanent &&
+        self.protocol_version == other.protocol_version &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Member {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Exposes {
+    // message fields
+    ports: ::protobuf::RepeatedField<::std::string::String>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Exposes {}
+
+impl Exposes {
+    pub fn new() -> Exposes {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Exposes {
+        static mut instance: ::protobuf::lazy::Lazy<Exposes> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Exposes,
+        };
+        unsafe {
+            instance.get(|| {
+                Exposes {
+                    ports: ::protobuf::RepeatedField::new(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // repeated string ports = 1;
+
+    pub fn clear_ports(&mut self) {
+        self.ports.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ports(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.ports = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_ports(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.ports
+    }
+
+    // Take field
+    pub fn take_ports(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.ports, ::protobuf::RepeatedField::new())
+    }
+
+    pub fn get_ports(&self) -> &[::std::string::String] {
+        &self.ports
+    }
+}
+
+impl ::protobuf::Message for Exposes {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.ports));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.ports.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        for v in self.ports.iter() {
+            try!(os.write_string(1, &v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Exposes>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Exposes {
+    fn new() -> Exposes {
+        Exposes::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Exposes>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_repeated_string_accessor(
+                    "ports",
+                    Exposes::get_ports,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Exposes>(
+                    "Exposes",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Exposes {
+    fn clear(&mut self) {
+        self.clear_ports();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Exposes {
+    fn eq(&self, other: &Exposes) -> bool {
+        self.ports == other.ports &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Exposes {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct CensusEntry {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    member_id: ::protobuf::SingularField<::std::string::String>,
+    hostname: ::protobuf::SingularField<::std::string::String>,
+    ip: ::protobuf::SingularField<::std::string::String>,
+    suitability: ::std::option::Option<u64>,
+    port: ::protobuf::SingularField<::std::string::String>,
+    exposes: ::protobuf::SingularPtrField<Exposes>,
+    leader: ::std::option::Option<bool>,
+    follower: ::std::option::Option<bool>,
+    data_init: ::std::option::Option<bool>,
+    vote: ::protobuf::SingularField<::std::string::String>,
+    election: ::std::option::Option<bool>,
+    needs_write: ::std::option::Option<bool>,
+    initialized: ::std::option::Option<bool>,
+    keep_me: ::std::option::Option<bool>,
+    service: ::protobuf::SingularField<::std::string::String>,
+    group: ::protobuf::SingularField<::std::string::String>,
+    alive: ::std::option::Option<bool>,
+    suspect: ::std::option::Option<bool>,
+    confirmed: ::std::option::Option<bool>,
+    detached: ::std::option::Option<bool>,
+    package: ::protobuf::SingularField<::std::string::String>,
+    health: ::std::option::Option<HealthStatus>,
+    restarts: ::std::option::Option<u64>,
+    last_exit: ::protobuf::SingularField<::std::string::String>,
+    bad_releases: ::protobuf::RepeatedField<::std::string::String>,
+    cfg: ::protobuf::SingularField<::std::string::String>,
+    incarnation: ::std::option::Option<u64>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for CensusEntry {}
+
+impl CensusEntry {
+    pub fn new() -> CensusEntry {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static CensusEntry {
+        static mut instance: ::protobuf::lazy::Lazy<CensusEntry> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const CensusEntry,
+        };
+        unsafe {
+            instance.get(|| {
+                CensusEntry {
+                    id: ::protobuf::SingularField::none(),
+                    member_id: ::protobuf::SingularField::none(),
+                    hostname: ::protobuf::SingularField::none(),
+                    ip: ::protobuf::SingularField::none(),
+                    suitability: ::std::option::Option::None,
+                    port: ::protobuf::SingularField::none(),
+                    exposes: ::protobuf::SingularPtrField::none(),
+                    leader: ::std::option::Option::None,
+                    follower: ::std::option::Option::None,
+                    data_init: ::std::option::Option::None,
+                    vote: ::protobuf::SingularField::none(),
+                    election: ::std::option::Option::None,
+                    needs_write: ::std::option::Option::None,
+                    initialized: ::std::option::Option::None,
+                    keep_me: ::std::option::Option::None,
+                    service: ::protobuf::SingularField::none(),
+                    group: ::protobuf::SingularField::none(),
+                    alive: ::std::option::Option::None,
+                    suspect: ::std::option::Option::None,
+                    confirmed: ::std::option::Option::None,
+                    detached: ::std::option::Option::None,
+                    package: ::protobuf::SingularField::none(),
+                    health: ::std::option::Option::None,
+                    restarts: ::std::option::Option::None,
+                    last_exit: ::protobuf::SingularField::none(),
+                    bad_releases: ::protobuf::RepeatedField::new(),
+                    cfg: ::protobuf::SingularField::none(),
+                    incarnation: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string member_id = 2;
+
+    pub fn clear_member_id(&mut self) {
+        self.member_id.clear();
+    }
+
+    pub fn has_member_id(&self) -> bool {
+        self.member_id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_member_id(&mut self, v: ::std::string::String) {
+        self.member_id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_member_id(&mut self) -> &mut ::std::string::String {
+        if self.member_id.is_none() {
+            self.member_id.set_default();
+        };
+        self.member_id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_member_id(&mut self) -> ::std::string::String {
+        self.member_id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_member_id(&self) -> &str {
+        match self.member_id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string hostname = 3;
+
+    pub fn clear_hostname(&mut self) {
+        self.hostname.clear();
+    }
+
+    pub fn has_hostname(&self) -> bool {
+        self.hostname.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_hostname(&mut self, v: ::std::string::String) {
+        self.hostname = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_hostname(&mut self) -> &mut ::std::string::String {
+        if self.hostname.is_none() {
+            self.hostname.set_default();
+        };
+        self.hostname.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_hostname(&mut self) -> ::std::string::String {
+        self.hostname.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_hostname(&self) -> &str {
+        match self.hostname.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string ip = 4;
+
+    pub fn clear_ip(&mut self) {
+        self.ip.clear();
+    }
+
+    pub fn has_ip(&self) -> bool {
+        self.ip.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ip(&mut self, v: ::std::string::String) {
+        self.ip = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_ip(&mut self) -> &mut ::std::string::String {
+        if self.ip.is_none() {
+            self.ip.set_default();
+        };
+        self.ip.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_ip(&mut self) -> ::std::string::String {
+        self.ip.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_ip(&self) -> &str {
+        match self.ip.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required uint64 suitability = 5;
+
+    pub fn clear_suitability(&mut self) {
+        self.suitability = ::std::option::Option::None;
+    }
+
+    pub fn has_suitability(&self) -> bool {
+        self.suitability.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_suitability(&mut self, v: u64) {
+        self.suitability = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_suitability(&self) -> u64 {
+        self.suitability.unwrap_or(0)
+    }
+
+    // optional string port = 6;
+
+    pub fn clear_port(&mut self) {
+        self.port.clear();
+    }
+
+    pub fn has_port(&self) -> bool {
+        self.port.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_port(&mut self, v: ::std::string::String) {
+        self.port = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_port(&mut self) -> &mut ::std::string::String {
+        if self.port.is_none() {
+            self.port.set_default();
+        };
+        self.port.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_port(&mut self) -> ::std::string::String {
+        self.port.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_port(&self) -> &str {
+        match self.port.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional .swim.Exposes exposes = 7;
+
+    pub fn clear_exposes(&mut self) {
+        self.exposes.clear();
+    }
+
+    pub fn has_exposes(&self) -> bool {
+        self.exposes.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_exposes(&mut self, v: Exposes) {
+        self.exposes = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_exposes(&mut self) -> &mut Exposes {
+        if self.exposes.is_none() {
+            self.exposes.set_default();
+        };
+        self.exposes.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_exposes(&mut self) -> Exposes {
+        self.exposes.take().unwrap_or_else(|| Exposes::new())
+    }
+
+    pub fn get_exposes(&self) -> &Exposes {
+        self.exposes.as_ref().unwrap_or_else(|| Exposes::default_instance())
+    }
+
+    // required bool leader = 8;
+
+    pub fn clear_leader(&mut self) {
+        self.leader = ::std::option::Option::None;
+    }
+
+    pub fn has_leader(&self) -> bool {
+        self.leader.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_leader(&mut self, v: bool) {
+        self.leader = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_leader(&self) -> bool {
+        self.leader.unwrap_or(false)
+    }
+
+    // required bool follower = 9;
+
+    pub fn clear_follower(&mut self) {
+        self.follower = ::std::option::Option::None;
+    }
+
+    pub fn has_follower(&self) -> bool {
+        self.follower.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_follower(&mut self, v: bool) {
+        self.follower = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_follower(&self) -> bool {
+        self.follower.unwrap_or(false)
+    }
+
+    // required bool data_init = 10;
+
+    pub fn clear_data_init(&mut self) {
+        self.data_init = ::std::option::Option::None;
+    }
+
+    pub fn has_data_init(&self) -> bool {
+        self.data_init.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_data_init(&mut self, v: bool) {
+        self.data_init = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_data_init(&self) -> bool {
+        self.data_init.unwrap_or(false)
+    }
+
+    // optional string vote = 11;
+
+    pub fn clear_vote(&mut self) {
+        self.vote.clear();
+    }
+
+    pub fn has_vote(&self) -> bool {
+        self.vote.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_vote(&mut self, v: ::std::string::String) {
+        self.vote = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_vote(&mut self) -> &mut ::std::string::String {
+        if self.vote.is_none() {
+            self.vote.set_default();
+        };
+        self.vote.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_vote(&mut self) -> ::std::string::String {
+        self.vote.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_vote(&self) -> &str {
+        match self.vote.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional bool election = 12;
+
+    pub fn clear_election(&mut self) {
+        self.election = ::std::option::Option::None;
+    }
+
+    pub fn has_election(&self) -> bool {
+        self.election.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_election(&mut self, v: bool) {
+        self.election = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_election(&self) -> bool {
+        self.election.unwrap_or(false)
+    }
+
+    // optional bool needs_write = 13;
+
+    pub fn clear_needs_write(&mut self) {
+        self.needs_write = ::std::option::Option::None;
+    }
+
+    pub fn has_needs_write(&self) -> bool {
+        self.needs_write.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_needs_write(&mut self, v: bool) {
+        self.needs_write = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_needs_write(&self) -> bool {
+        self.needs_write.unwrap_or(false)
+    }
+
+    // required bool initialized = 14;
+
+    pub fn clear_initialized(&mut self) {
+        self.initialized = ::std::option::Option::None;
+    }
+
+    pub fn has_initialized(&self) -> bool {
+        self.initialized.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_initialized(&mut self, v: bool) {
+        self.initialized = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_initialized(&self) -> bool {
+        self.initialized.unwrap_or(false)
+    }
+
+    // required bool keep_me = 15;
+
+    pub fn clear_keep_me(&mut self) {
+        self.keep_me = ::std::option::Option::None;
+    }
+
+    pub fn has_keep_me(&self) -> bool {
+        self.keep_me.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_keep_me(&mut self, v: bool) {
+        self.keep_me = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_keep_me(&self) -> bool {
+        self.keep_me.unwrap_or(false)
+    }
+
+    // required string service = 16;
+
+    pub fn clear_service(&mut self) {
+        self.service.clear();
+    }
+
+    pub fn has_service(&self) -> bool {
+        self.service.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_service(&mut self, v: ::std::string::String) {
+        self.service = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_service(&mut self) -> &mut ::std::string::String {
+        if self.service.is_none() {
+            self.service.set_default();
+        };
+        self.service.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_service(&mut self) -> ::std::string::String {
+        self.service.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_service(&self) -> &str {
+        match self.service.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string group = 17;
+
+    pub fn clear_group(&mut self) {
+        self.group.clear();
+    }
+
+    pub fn has_group(&self) -> bool {
+        self.group.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_group(&mut self, v: ::std::string::String) {
+        self.group = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_group(&mut self) -> &mut ::std::string::String {
+        if self.group.is_none() {
+            self.group.set_default();
+        };
+        self.group.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_group(&mut self) -> ::std::string::String {
+        self.group.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_group(&self) -> &str {
+        match self.group.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required bool alive = 18;
+
+    pub fn clear_alive(&mut self) {
+        self.alive = ::std::option::Option::None;
+    }
+
+    pub fn has_alive(&self) -> bool {
+        self.alive.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_alive(&mut self, v: bool) {
+        self.alive = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_alive(&self) -> bool {
+        self.alive.unwrap_or(false)
+    }
+
+    // required bool suspect = 19;
+
+    pub fn clear_suspect(&mut self) {
+        self.suspect = ::std::option::Option::None;
+    }
+
+    pub fn has_suspect(&self) -> bool {
+        self.suspect.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_suspect(&mut self, v: bool) {
+        self.suspect = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_suspect(&self) -> bool {
+        self.suspect.unwrap_or(false)
+    }
+
+    // required bool confirmed = 20;
+
+    pub fn clear_confirmed(&mut self) {
+        self.confirmed = ::std::option::Option::None;
+    }
+
+    pub fn has_confirmed(&self) -> bool {
+        self.confirmed.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_confirmed(&mut self, v: bool) {
+        self.confirmed = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_confirmed(&self) -> bool {
+        self.confirmed.unwrap_or(false)
+    }
+
+    // required bool detached = 21;
+
+    pub fn clear_detached(&mut self) {
+        self.detached = ::std::option::Option::None;
+    }
+
+    pub fn has_detached(&self) -> bool {
+        self.detached.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_detached(&mut self, v: bool) {
+        self.detached = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_detached(&self) -> bool {
+        self.detached.unwrap_or(false)
+    }
+
+    // optional string package = 22;
+
+    pub fn clear_package(&mut self) {
+        self.package.clear();
+    }
+
+    pub fn has_package(&self) -> bool {
+        self.package.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_package(&mut self, v: ::std::string::String) {
+        self.package = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_package(&mut self) -> &mut ::std::string::String {
+        if self.package.is_none() {
+            self.package.set_default();
+        };
+        self.package.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_package(&mut self) -> ::std::string::String {
+        self.package.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_package(&self) -> &str {
+        match self.package.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional .swim.HealthStatus health = 24;
+
+    pub fn clear_health(&mut self) {
+        self.health = ::std::option::Option::None;
+    }
+
+    pub fn has_health(&self) -> bool {
+        self.health.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_health(&mut self, v: HealthStatus) {
+        self.health = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_health(&self) -> HealthStatus {
+        self.health.unwrap_or(HealthStatus::OK)
+    }
+
+    // optional uint64 restarts = 25;
+
+    pub fn clear_restarts(&mut self) {
+        self.restarts = ::std::option::Option::None;
+    }
+
+    pub fn has_restarts(&self) -> bool {
+        self.restarts.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_restarts(&mut self, v: u64) {
+        self.restarts = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_restarts(&self) -> u64 {
+        self.restarts.unwrap_or(0)
+    }
+
+    // optional string last_exit = 26;
+
+    pub fn clear_last_exit(&mut self) {
+        self.last_exit.clear();
+    }
+
+    pub fn has_last_exit(&self) -> bool {
+        self.last_exit.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_last_exit(&mut self, v: ::std::string::String) {
+        self.last_exit = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_last_exit(&mut self) -> &mut ::std::string::String {
+        if self.last_exit.is_none() {
+            self.last_exit.set_default();
+        };
+        self.last_exit.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_last_exit(&mut self) -> ::std::string::String {
+        self.last_exit.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_last_exit(&self) -> &str {
+        match self.last_exit.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // repeated string bad_releases = 27;
+
+    pub fn clear_bad_releases(&mut self) {
+        self.bad_releases.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_bad_releases(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.bad_releases = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_bad_releases(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.bad_releases
+    }
+
+    // Take field
+    pub fn take_bad_releases(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.bad_releases, ::protobuf::RepeatedField::new())
+    }
+
+    pub fn get_bad_releases(&self) -> &[::std::string::String] {
+        &self.bad_releases
+    }
+
+    // optional string cfg = 28;
+
+    pub fn clear_cfg(&mut self) {
+        self.cfg.clear();
+    }
+
+    pub fn has_cfg(&self) -> bool {
+        self.cfg.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_cfg(&mut self, v: ::std::string::String) {
+        self.cfg = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_cfg(&mut self) -> &mut ::std::string::String {
+        if self.cfg.is_none() {
+            self.cfg.set_default();
+        };
+        self.cfg.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_cfg(&mut self) -> ::std::string::String {
+        self.cfg.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_cfg(&self) -> &str {
+        match self.cfg.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required uint64 incarnation = 29;
+
+    pub fn clear_incarnation(&mut self) {
+        self.incarnation = ::std::option::Option::None;
+    }
+
+    pub fn has_incarnation(&self) -> bool {
+        self.incarnation.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_incarnation(&mut self, v: u64) {
+        self.incarnation = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_incarnation(&self) -> u64 {
+        self.incarnation.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for CensusEntry {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.member_id.is_none() {
+            return false;
+        };
+        if self.hostname.is_none() {
+            return false;
+        };
+        if self.ip.is_none() {
+            return false;
+        };
+        if self.suitability.is_none() {
+            return false;
+        };
+        if self.leader.is_none() {
+            return false;
+        };
+        if self.follower.is_none() {
+            return false;
+        };
+        if self.data_init.is_none() {
+            return false;
+        };
+        if self.initialized.is_none() {
+            return false;
+        };
+        if self.keep_me.is_none() {
+            return false;
+        };
+        if self.service.is_none() {
+            return false;
+        };
+        if self.group.is_none() {
+            return false;
+        };
+        if self.alive.is_none() {
+            return false;
+        };
+        if self.suspect.is_none() {
+            return false;
+        };
+        if self.confirmed.is_none() {
+            return false;
+        };
+        if self.detached.is_none() {
+            return false;
+        };
+        if self.incarnation.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.member_id));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.hostname));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.ip));
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.suitability = ::std::option::Option::Some(tmp);
+                },
+                6 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.port));
+                },
+                7 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.exposes));
+                },
+                8 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.leader = ::std::option::Option::Some(tmp);
+                },
+                9 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.follower = ::std::option::Option::Some(tmp);
+                },
+                10 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.data_init = ::std::option::Option::Some(tmp);
+                },
+                11 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.vote));
+                },
+                12 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.election = ::std::option::Option::Some(tmp);
+                },
+                13 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.needs_write = ::std::option::Option::Some(tmp);
+                },
+                14 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.initialized = ::std::option::Option::Some(tmp);
+                },
+                15 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.keep_me = ::std::option::Option::Some(tmp);
+                },
+                16 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.service));
+                },
+                17 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.group));
+                },
+                18 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.alive = ::std::option::Option::Some(tmp);
+                },
+                19 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.suspect = ::std::option::Option::Some(tmp);
+                },
+                20 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.confirmed = ::std::option::Option::Some(tmp);
+                },
+                21 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.detached = ::std::option::Option::Some(tmp);
+                },
+                22 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.package));
+                },
+                24 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.health = ::std::option::Option::Some(tmp);
+                },
+                25 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.restarts = ::std::option::Option::Some(tmp);
+                },
+                26 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.last_exit));
+                },
+                27 => {
+                    try!(::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.bad_releases));
+                },
+                28 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.cfg));
+                },
+                29 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.incarnation = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.member_id.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.hostname.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.ip.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        for value in self.suitability.iter() {
+            my_size += ::protobuf::rt::value_size(5, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        for value in self.port.iter() {
+            my_size += ::protobuf::rt::string_size(6, &value);
+        };
+        for value in self.exposes.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        if self.leader.is_some() {
+            my_size += 2;
+        };
+        if self.follower.is_some() {
+            my_size += 2;
+        };
+        if self.data_init.is_some() {
+            my_size += 2;
+        };
+        for value in self.vote.iter() {
+            my_size += ::protobuf::rt::string_size(11, &value);
+        };
+        if self.election.is_some() {
+            my_size += 2;
+        };
+        if self.needs_write.is_some() {
+            my_size += 2;
+        };
+        if self.initialized.is_some() {
+            my_size += 2;
+        };
+        if self.keep_me.is_some() {
+            my_size += 2;
+        };
+        for value in self.service.iter() {
+            my_size += ::protobuf::rt::string_size(16, &value);
+        };
+        for value in self.group.iter() {
+            my_size += ::protobuf::rt::string_size(17, &value);
+        };
+        if self.alive.is_some() {
+            my_size += 3;
+        };
+        if self.suspect.is_some() {
+            my_size += 3;
+        };
+        if self.confirmed.is_some() {
+            my_size += 3;
+        };
+        if self.detached.is_some() {
+            my_size += 3;
+        };
+        for value in self.package.iter() {
+            my_size += ::protobuf::rt::string_size(22, &value);
+        };
+        for value in self.health.iter() {
+            my_size += ::protobuf::rt::enum_size(24, *value);
+        };
+        for value in self.restarts.iter() {
+            my_size += ::protobuf::rt::value_size(25, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        for value in self.last_exit.iter() {
+            my_size += ::protobuf::rt::string_size(26, &value);
+        };
+        for value in self.bad_releases.iter() {
+            my_size += ::protobuf::rt::string_size(27, &value);
+        };
+        for value in self.cfg.iter() {
+            my_size += ::protobuf::rt::string_size(28, &value);
+        };
+        for value in self.incarnation.iter() {
+            my_size += ::protobuf::rt::value_size(29, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.member_id.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.hostname.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.ip.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        if let Some(v) = self.suitability {
+            try!(os.write_uint64(5, v));
+        };
+        if let Some(v) = self.port.as_ref() {
+            try!(os.write_string(6, &v));
+        };
+        if let Some(v) = self.exposes.as_ref() {
+            try!(os.write_tag(7, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.leader {
+            try!(os.write_bool(8, v));
+        };
+        if let Some(v) = self.follower {
+            try!(os.write_bool(9, v));
+        };
+        if let Some(v) = self.data_init {
+            try!(os.write_bool(10, v));
+        };
+        if let Some(v) = self.vote.as_ref() {
+            try!(os.write_string(11, &v));
+        };
+        if let Some(v) = self.election {
+            try!(os.write_bool(12, v));
+        };
+        if let Some(v) = self.needs_write {
+            try!(os.write_bool(13, v));
+        };
+        if let Some(v) = self.initialized {
+            try!(os.write_bool(14, v));
+        };
+        if let Some(v) = self.keep_me {
+            try!(os.write_bool(15, v));
+        };
+        if let Some(v) = self.service.as_ref() {
+            try!(os.write_string(16, &v));
+        };
+        if let Some(v) = self.group.as_ref() {
+            try!(os.write_string(17, &v));
+        };
+        if let Some(v) = self.alive {
+            try!(os.write_bool(18, v));
+        };
+        if let Some(v) = self.suspect {
+            try!(os.write_bool(19, v));
+        };
+        if let Some(v) = self.confirmed {
+            try!(os.write_bool(20, v));
+        };
+        if let Some(v) = self.detached {
+            try!(os.write_bool(21, v));
+        };
+        if let Some(v) = self.package.as_ref() {
+            try!(os.write_string(22, &v));
+        };
+        if let Some(v) = self.health {
+            try!(os.write_enum(24, v.value()));
+        };
+        if let Some(v) = self.restarts {
+            try!(os.write_uint64(25, v));
+        };
+        if let Some(v) = self.last_exit.as_ref() {
+            try!(os.write_string(26, &v));
+        };
+        for v in self.bad_releases.iter() {
+            try!(os.write_string(27, &v));
+        };
+        if let Some(v) = self.cfg.as_ref() {
+            try!(os.write_string(28, &v));
+        };
+        if let Some(v) = self.incarnation {
+            try!(os.write_uint64(29, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<CensusEntry>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for CensusEntry {
+    fn new() -> CensusEntry {
+        CensusEntry::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<CensusEntry>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    CensusEntry::has_id,
+                    CensusEntry::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "member_id",
+                    CensusEntry::has_member_id,
+                    CensusEntry::get_member_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "hostname",
+                    CensusEntry::has_hostname,
+                    CensusEntry::get_hostname,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "ip",
+                    CensusEntry::has_ip,
+                    CensusEntry::get_ip,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "suitability",
+                    CensusEntry::has_suitability,
+                    CensusEntry::get_suitability,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "port",
+                    CensusEntry::has_port,
+                    CensusEntry::get_port,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "exposes",
+                    CensusEntry::has_exposes,
+                    CensusEntry::get_exposes,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "leader",
+                    CensusEntry::has_leader,
+                    CensusEntry::get_leader,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "follower",
+                    CensusEntry::has_follower,
+                    CensusEntry::get_follower,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "data_init",
+                    CensusEntry::has_data_init,
+                    CensusEntry::get_data_init,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "vote",
+                    CensusEntry::has_vote,
+                    CensusEntry::get_vote,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "election",
+                    CensusEntry::has_election,
+                    CensusEntry::get_election,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "needs_write",
+                    CensusEntry::has_needs_write,
+                    CensusEntry::get_needs_write,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "initialized",
+                    CensusEntry::has_initialized,
+                    CensusEntry::get_initialized,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "keep_me",
+                    CensusEntry::has_keep_me,
+                    CensusEntry::get_keep_me,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "service",
+                    CensusEntry::has_service,
+                    CensusEntry::get_service,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "group",
+                    CensusEntry::has_group,
+                    CensusEntry::get_group,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "alive",
+                    CensusEntry::has_alive,
+                    CensusEntry::get_alive,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "suspect",
+                    CensusEntry::has_suspect,
+                    CensusEntry::get_suspect,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "confirmed",
+                    CensusEntry::has_confirmed,
+                    CensusEntry::get_confirmed,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "detached",
+                    CensusEntry::has_detached,
+                    CensusEntry::get_detached,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "package",
+                    CensusEntry::has_package,
+                    CensusEntry::get_package,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "health",
+                    CensusEntry::has_health,
+                    CensusEntry::get_health,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "restarts",
+                    CensusEntry::has_restarts,
+                    CensusEntry::get_restarts,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "last_exit",
+                    CensusEntry::has_last_exit,
+                    CensusEntry::get_last_exit,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_repeated_string_accessor(
+                    "bad_releases",
+                    CensusEntry::get_bad_releases,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "cfg",
+                    CensusEntry::has_cfg,
+                    CensusEntry::get_cfg,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "incarnation",
+                    CensusEntry::has_incarnation,
+                    CensusEntry::get_incarnation,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<CensusEntry>(
+                    "CensusEntry",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for CensusEntry {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_member_id();
+        self.clear_hostname();
+        self.clear_ip();
+        self.clear_suitability();
+        self.clear_port();
+        self.clear_exposes();
+        self.clear_leader();
+        self.clear_follower();
+        self.clear_data_init();
+        self.clear_vote();
+        self.clear_election();
+        self.clear_needs_write();
+        self.clear_initialized();
+        self.clear_keep_me();
+        self.clear_service();
+        self.clear_group();
+        self.clear_alive();
+        self.clear_suspect();
+        self.clear_confirmed();
+        self.clear_detached();
+        self.clear_package();
+        self.clear_health();
+        self.clear_restarts();
+        self.clear_last_exit();
+        self.clear_bad_releases();
+        self.clear_cfg();
+        self.clear_incarnation();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for CensusEntry {
+    fn eq(&self, other: &CensusEntry) -> bool {
+        self.id == other.id &&
+        self.member_id == other.member_id &&
+        self.hostname == other.hostname &&
+        self.ip == other.ip &&
+        self.suitability == other.suitability &&
+        self.port == other.port &&
+        self.exposes == other.exposes &&
+        self.leader == other.leader &&
+        self.follower == other.follower &&
+        self.data_init == other.data_init &&
+        self.vote == other.vote &&
+        self.election == other.election &&
+        self.needs_write == other.needs_write &&
+        self.initialized == other.initialized &&
+        self.keep_me == other.keep_me &&
+        self.service == other.service &&
+        self.group == other.group &&
+        self.alive == other.alive &&
+        self.suspect == other.suspect &&
+        self.confirmed == other.confirmed &&
+        self.detached == other.detached &&
+        self.package == other.package &&
+        self.health == other.health &&
+        self.restarts == other.restarts &&
+        self.last_exit == other.last_exit &&
+        self.bad_releases == other.bad_releases &&
+        self.cfg == other.cfg &&
+        self.incarnation == other.incarnation &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for CensusEntry {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Election {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    service: ::protobuf::SingularField<::std::string::String>,
+    group: ::protobuf::SingularField<::std::string::String>,
+    leader_id: ::protobuf::SingularField<::std::string::String>,
+    suitability: ::std::option::Option<u32>,
+    critical: ::std::option::Option<bool>,
+    votes: ::protobuf::RepeatedField<::std::string::String>,
+    status: ::std::option::Option<ElectionStatus>,
+    term: ::std::option::Option<u32>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Election {}
+
+impl Election {
+    pub fn new() -> Election {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Election {
+        static mut instance: ::protobuf::lazy::Lazy<Election> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Election,
+        };
+        unsafe {
+            instance.get(|| {
+                Election {
+                    id: ::protobuf::SingularField::none(),
+                    service: ::protobuf::SingularField::none(),
+                    group: ::protobuf::SingularField::none(),
+                    leader_id: ::protobuf::SingularField::none(),
+                    suitability: ::std::option::Option::None,
+                    critical: ::std::option::Option::None,
+                    votes: ::protobuf::RepeatedField::new(),
+                    status: ::std::option::Option::None,
+                    term: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string service = 2;
+
+    pub fn clear_service(&mut self) {
+        self.service.clear();
+    }
+
+    pub fn has_service(&self) -> bool {
+        self.service.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_service(&mut self, v: ::std::string::String) {
+        self.service = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_service(&mut self) -> &mut ::std::string::String {
+        if self.service.is_none() {
+            self.service.set_default();
+        };
+        self.service.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_service(&mut self) -> ::std::string::String {
+        self.service.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_service(&self) -> &str {
+        match self.service.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string group = 3;
+
+    pub fn clear_group(&mut self) {
+        self.group.clear();
+    }
+
+    pub fn has_group(&self) -> bool {
+        self.group.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_group(&mut self, v: ::std::string::String) {
+        self.group = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_group(&mut self) -> &mut ::std::string::String {
+        if self.group.is_none() {
+            self.group.set_default();
+        };
+        self.group.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_group(&mut self) -> ::std::string::String {
+        self.group.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_group(&self) -> &str {
+        match self.group.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string leader_id = 4;
+
+    pub fn clear_leader_id(&mut self) {
+        self.leader_id.clear();
+    }
+
+    pub fn has_leader_id(&self) -> bool {
+        self.leader_id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_leader_id(&mut self, v: ::std::string::String) {
+        self.leader_id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_leader_id(&mut self) -> &mut ::std::string::String {
+        if self.leader_id.is_none() {
+            self.leader_id.set_default();
+        };
+        self.leader_id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_leader_id(&mut self) -> ::std::string::String {
+        self.leader_id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_leader_id(&self) -> &str {
+        match self.leader_id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required uint32 suitability = 5;
+
+    pub fn clear_suitability(&mut self) {
+        self.suitability = ::std::option::Option::None;
+    }
+
+    pub fn has_suitability(&self) -> bool {
+        self.suitability.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_suitability(&mut self, v: u32) {
+        self.suitability = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_suitability(&self) -> u32 {
+        self.suitability.unwrap_or(0)
+    }
+
+    // optional bool critical = 6;
+
+    pub fn clear_critical(&mut self) {
+        self.critical = ::std::option::Option::None;
+    }
+
+    pub fn has_critical(&self) -> bool {
+        self.critical.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_critical(&mut self, v: bool) {
+        self.critical = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_critical(&self) -> bool {
+        self.critical.unwrap_or(false)
+    }
+
+    // repeated string votes = 7;
+
+    pub fn clear_votes(&mut self) {
+        self.votes.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_votes(&mut self, v: ::protobuf::RepeatedField<::std::string::String>) {
+        self.votes = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_votes(&mut self) -> &mut ::protobuf::RepeatedField<::std::string::String> {
+        &mut self.votes
+    }
+
+    // Take field
+    pub fn take_votes(&mut self) -> ::protobuf::RepeatedField<::std::string::String> {
+        ::std::mem::replace(&mut self.votes, ::protobuf::RepeatedField::new())
+    }
+
+    pub fn get_votes(&self) -> &[::std::string::String] {
+        &self.votes
+    }
+
+    // required .swim.ElectionStatus status = 8;
+
+    pub fn clear_status(&mut self) {
+        self.status = ::std::option::Option::None;
+    }
+
+    pub fn has_status(&self) -> bool {
+        self.status.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_status(&mut self, v: ElectionStatus) {
+        self.status = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_status(&self) -> ElectionStatus {
+        self.status.unwrap_or(ElectionStatus::RUNNING)
+    }
+
+    // required uint32 term = 9;
+
+    pub fn clear_term(&mut self) {
+        self.term = ::std::option::Option::None;
+    }
+
+    pub fn has_term(&self) -> bool {
+        self.term.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_term(&mut self, v: u32) {
+        self.term = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_term(&self) -> u32 {
+        self.term.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for Election {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.service.is_none() {
+            return false;
+        };
+        if self.group.is_none() {
+            return false;
+        };
+        if self.leader_id.is_none() {
+            return false;
+        };
+        if self.suitability.is_none() {
+            return false;
+        };
+        if self.status.is_none() {
+            return false;
+        };
+        if self.term.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.service));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.group));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.leader_id));
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint32());
+                    self.suitability = ::std::option::Option::Some(tmp);
+                },
+                6 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.critical = ::std::option::Option::Some(tmp);
+                },
+                7 => {
+                    try!(::protobuf::rt::read_repeated_string_into(wire_type, is, &mut self.votes));
+                },
+                8 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.status = ::std::option::Option::Some(tmp);
+                },
+                9 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint32());
+                    self.term = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.service.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.group.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.leader_id.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        for value in self.suitability.iter() {
+            my_size += ::protobuf::rt::value_size(5, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        if self.critical.is_some() {
+            my_size += 2;
+        };
+        for value in self.votes.iter() {
+            my_size += ::protobuf::rt::string_size(7, &value);
+        };
+        for value in self.status.iter() {
+            my_size += ::protobuf::rt::enum_size(8, *value);
+        };
+        for value in self.term.iter() {
+            my_size += ::protobuf::rt::value_size(9, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.service.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.group.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.leader_id.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        if let Some(v) = self.suitability {
+            try!(os.write_uint32(5, v));
+        };
+        if let Some(v) = self.critical {
+            try!(os.write_bool(6, v));
+        };
+        for v in self.votes.iter() {
+            try!(os.write_string(7, &v));
+        };
+        if let Some(v) = self.status {
+            try!(os.write_enum(8, v.value()));
+        };
+        if let Some(v) = self.term {
+            try!(os.write_uint32(9, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Election>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Election {
+    fn new() -> Election {
+        Election::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Election>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    Election::has_id,
+                    Election::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "service",
+                    Election::has_service,
+                    Election::get_service,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "group",
+                    Election::has_group,
+                    Election::get_group,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "leader_id",
+                    Election::has_leader_id,
+                    Election::get_leader_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u32_accessor(
+                    "suitability",
+                    Election::has_suitability,
+                    Election::get_suitability,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "critical",
+                    Election::has_critical,
+                    Election::get_critical,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_repeated_string_accessor(
+                    "votes",
+                    Election::get_votes,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "status",
+                    Election::has_status,
+                    Election::get_status,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u32_accessor(
+                    "term",
+                    Election::has_term,
+                    Election::get_term,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Election>(
+                    "Election",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Election {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_service();
+        self.clear_group();
+        self.clear_leader_id();
+        self.clear_suitability();
+        self.clear_critical();
+        self.clear_votes();
+        self.clear_status();
+        self.clear_term();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Election {
+    fn eq(&self, other: &Election) -> bool {
+        self.id == other.id &&
+        self.service == other.service &&
+        self.group == other.group &&
+        self.leader_id == other.leader_id &&
+        self.suitability == other.suitability &&
+        self.critical == other.critical &&
+        self.votes == other.votes &&
+        self.status == other.status &&
+        self.term == other.term &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Election {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct GossipFile {
+    // message fields
+    service_group: ::protobuf::SingularPtrField<ServiceGroup>,
+    file_name: ::protobuf::SingularField<::std::string::String>,
+    body: ::protobuf::SingularField<::std::vec::Vec<u8>>,
+    checksum: ::protobuf::SingularField<::std::string::String>,
+    version_number: ::std::option::Option<u64>,
+    written: ::std::option::Option<bool>,
+    encrypted: ::std::option::Option<bool>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for GossipFile {}
+
+impl GossipFile {
+    pub fn new() -> GossipFile {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static GossipFile {
+        static mut instance: ::protobuf::lazy::Lazy<GossipFile> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const GossipFile,
+        };
+        unsafe {
+            instance.get(|| {
+                GossipFile {
+                    service_group: ::protobuf::SingularPtrField::none(),
+                    file_name: ::protobuf::SingularField::none(),
+                    body: ::protobuf::SingularField::none(),
+                    checksum: ::protobuf::SingularField::none(),
+                    version_number: ::std::option::Option::None,
+                    written: ::std::option::Option::None,
+                    encrypted: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required .swim.ServiceGroup service_group = 1;
+
+    pub fn clear_service_group(&mut self) {
+        self.service_group.clear();
+    }
+
+    pub fn has_service_group(&self) -> bool {
+        self.service_group.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_service_group(&mut self, v: ServiceGroup) {
+        self.service_group = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_service_group(&mut self) -> &mut ServiceGroup {
+        if self.service_group.is_none() {
+            self.service_group.set_default();
+        };
+        self.service_group.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_service_group(&mut self) -> ServiceGroup {
+        self.service_group.take().unwrap_or_else(|| ServiceGroup::new())
+    }
+
+    pub fn get_service_group(&self) -> &ServiceGroup {
+        self.service_group.as_ref().unwrap_or_else(|| ServiceGroup::default_instance())
+    }
+
+    // required string file_name = 2;
+
+    pub fn clear_file_name(&mut self) {
+        self.file_name.clear();
+    }
+
+    pub fn has_file_name(&self) -> bool {
+        self.file_name.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_file_name(&mut self, v: ::std::string::String) {
+        self.file_name = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_file_name(&mut self) -> &mut ::std::string::String {
+        if self.file_name.is_none() {
+            self.file_name.set_default();
+        };
+        self.file_name.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_file_name(&mut self) -> ::std::string::String {
+        self.file_name.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_file_name(&self) -> &str {
+        match self.file_name.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required bytes body = 3;
+
+    pub fn clear_body(&mut self) {
+        self.body.clear();
+    }
+
+    pub fn has_body(&self) -> bool {
+        self.body.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_body(&mut self, v: ::std::vec::Vec<u8>) {
+        self.body = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_body(&mut self) -> &mut ::std::vec::Vec<u8> {
+        if self.body.is_none() {
+            self.body.set_default();
+        };
+        self.body.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_body(&mut self) -> ::std::vec::Vec<u8> {
+        self.body.take().unwrap_or_else(|| ::std::vec::Vec::new())
+    }
+
+    pub fn get_body(&self) -> &[u8] {
+        match self.body.as_ref() {
+            Some(v) => &v,
+            None => &[],
+        }
+    }
+
+    // required string checksum = 4;
+
+    pub fn clear_checksum(&mut self) {
+        self.checksum.clear();
+    }
+
+    pub fn has_checksum(&self) -> bool {
+        self.checksum.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_checksum(&mut self, v: ::std::string::String) {
+        self.checksum = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_checksum(&mut self) -> &mut ::std::string::String {
+        if self.checksum.is_none() {
+            self.checksum.set_default();
+        };
+        self.checksum.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_checksum(&mut self) -> ::std::string::String {
+        self.checksum.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_checksum(&self) -> &str {
+        match self.checksum.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required uint64 version_number = 5;
+
+    pub fn clear_version_number(&mut self) {
+        self.version_number = ::std::option::Option::None;
+    }
+
+    pub fn has_version_number(&self) -> bool {
+        self.version_number.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_version_number(&mut self, v: u64) {
+        self.version_number = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_version_number(&self) -> u64 {
+        self.version_number.unwrap_or(0)
+    }
+
+    // required bool written = 6;
+
+    pub fn clear_written(&mut self) {
+        self.written = ::std::option::Option::None;
+    }
+
+    pub fn has_written(&self) -> bool {
+        self.written.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_written(&mut self, v: bool) {
+        self.written = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_written(&self) -> bool {
+        self.written.unwrap_or(false)
+    }
+
+    // required bool encrypted = 7;
+
+    pub fn clear_encrypted(&mut self) {
+        self.encrypted = ::std::option::Option::None;
+    }
+
+    pub fn has_encrypted(&self) -> bool {
+        self.encrypted.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_encrypted(&mut self, v: bool) {
+        self.encrypted = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_encrypted(&self) -> bool {
+        self.encrypted.unwrap_or(false)
+    }
+}
+
+impl ::protobuf::Message for GossipFile {
+    fn is_initialized(&self) -> bool {
+        if self.service_group.is_none() {
+            return false;
+        };
+        if self.file_name.is_none() {
+            return false;
+        };
+        if self.body.is_none() {
+            return false;
+        };
+        if self.checksum.is_none() {
+            return false;
+        };
+        if self.version_number.is_none() {
+            return false;
+        };
+        if self.written.is_none() {
+            return false;
+        };
+        if self.encrypted.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.service_group));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.file_name));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_bytes_into(wire_type, is, &mut self.body));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.checksum));
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.version_number = ::std::option::Option::Some(tmp);
+                },
+                6 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.written = ::std::option::Option::Some(tmp);
+                },
+                7 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_bool());
+                    self.encrypted = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.service_group.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.file_name.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.body.iter() {
+            my_size += ::protobuf::rt::bytes_size(3, &value);
+        };
+        for value in self.checksum.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        for value in self.version_number.iter() {
+            my_size += ::protobuf::rt::value_size(5, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        if self.written.is_some() {
+            my_size += 2;
+        };
+        if self.encrypted.is_some() {
+            my_size += 2;
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.service_group.as_ref() {
+            try!(os.write_tag(1, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.file_name.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.body.as_ref() {
+            try!(os.write_bytes(3, &v));
+        };
+        if let Some(v) = self.checksum.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        if let Some(v) = self.version_number {
+            try!(os.write_uint64(5, v));
+        };
+        if let Some(v) = self.written {
+            try!(os.write_bool(6, v));
+        };
+        if let Some(v) = self.encrypted {
+            try!(os.write_bool(7, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<GossipFile>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for GossipFile {
+    fn new() -> GossipFile {
+        GossipFile::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<GossipFile>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "service_group",
+                    GossipFile::has_service_group,
+                    GossipFile::get_service_group,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "file_name",
+                    GossipFile::has_file_name,
+                    GossipFile::get_file_name,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bytes_accessor(
+                    "body",
+                    GossipFile::has_body,
+                    GossipFile::get_body,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "checksum",
+                    GossipFile::has_checksum,
+                    GossipFile::get_checksum,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "version_number",
+                    GossipFile::has_version_number,
+                    GossipFile::get_version_number,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "written",
+                    GossipFile::has_written,
+                    GossipFile::get_written,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bool_accessor(
+                    "encrypted",
+                    GossipFile::has_encrypted,
+                    GossipFile::get_encrypted,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<GossipFile>(
+                    "GossipFile",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for GossipFile {
+    fn clear(&mut self) {
+        self.clear_service_group();
+        self.clear_file_name();
+        self.clear_body();
+        self.clear_checksum();
+        self.clear_version_number();
+        self.clear_written();
+        self.clear_encrypted();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for GossipFile {
+    fn eq(&self, other: &GossipFile) -> bool {
+        self.service_group == other.service_group &&
+        self.file_name == other.file_name &&
+        self.body == other.body &&
+        self.checksum == other.checksum &&
+        self.version_number == other.version_number &&
+        self.written == other.written &&
+        self.encrypted == other.encrypted &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for GossipFile {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct RingKeyRotation {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    name_with_rev: ::protobuf::SingularField<::std::string::String>,
+    key: ::protobuf::SingularField<::std::string::String>,
+    stage: ::std::option::Option<RotationStage>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for RingKeyRotation {}
+
+impl RingKeyRotation {
+    pub fn new() -> RingKeyRotation {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static RingKeyRotation {
+        static mut instance: ::protobuf::lazy::Lazy<RingKeyRotation> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const RingKeyRotation,
+        };
+        unsafe {
+            instance.get(|| {
+                RingKeyRotation {
+                    id: ::protobuf::SingularField::none(),
+                    name_with_rev: ::protobuf::SingularField::none(),
+                    key: ::protobuf::SingularField::none(),
+                    stage: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string name_with_rev = 2;
+
+    pub fn clear_name_with_rev(&mut self) {
+        self.name_with_rev.clear();
+    }
+
+    pub fn has_name_with_rev(&self) -> bool {
+        self.name_with_rev.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_name_with_rev(&mut self, v: ::std::string::String) {
+        self.name_with_rev = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_name_with_rev(&mut self) -> &mut ::std::string::String {
+        if self.name_with_rev.is_none() {
+            self.name_with_rev.set_default();
+        };
+        self.name_with_rev.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_name_with_rev(&mut self) -> ::std::string::String {
+        self.name_with_rev.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_name_with_rev(&self) -> &str {
+        match self.name_with_rev.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string key = 3;
+
+    pub fn clear_key(&mut self) {
+        self.key.clear();
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_key(&mut self, v: ::std::string::String) {
+        self.key = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_key(&mut self) -> &mut ::std::string::String {
+        if self.key.is_none() {
+            self.key.set_default();
+        };
+        self.key.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_key(&mut self) -> ::std::string::String {
+        self.key.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_key(&self) -> &str {
+        match self.key.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required .swim.RotationStage stage = 4;
+
+    pub fn clear_stage(&mut self) {
+        self.stage = ::std::option::Option::None;
+    }
+
+    pub fn has_stage(&self) -> bool {
+        self.stage.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_stage(&mut self, v: RotationStage) {
+        self.stage = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_stage(&self) -> RotationStage {
+        self.stage.unwrap_or(RotationStage::DISTRIBUTE)
+    }
+}
+
+impl ::protobuf::Message for RingKeyRotation {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.name_with_rev.is_none() {
+            return false;
+        };
+        if self.key.is_none() {
+            return false;
+        };
+        if self.stage.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.name_with_rev));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.key));
+                },
+                4 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.stage = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.name_with_rev.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.key.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.stage.iter() {
+            my_size += ::protobuf::rt::enum_size(4, *value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.name_with_rev.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.key.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.stage {
+            try!(os.write_enum(4, v.value()));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<RingKeyRotation>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for RingKeyRotation {
+    fn new() -> RingKeyRotation {
+        RingKeyRotation::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<RingKeyRotation>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    RingKeyRotation::has_id,
+                    RingKeyRotation::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "name_with_rev",
+                    RingKeyRotation::has_name_with_rev,
+                    RingKeyRotation::get_name_with_rev,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "key",
+                    RingKeyRotation::has_key,
+                    RingKeyRotation::get_key,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "stage",
+                    RingKeyRotation::has_stage,
+                    RingKeyRotation::get_stage,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<RingKeyRotation>(
+                    "RingKeyRotation",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for RingKeyRotation {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_name_with_rev();
+        self.clear_key();
+        self.clear_stage();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for RingKeyRotation {
+    fn eq(&self, other: &RingKeyRotation) -> bool {
+        self.id == other.id &&
+        self.name_with_rev == other.name_with_rev &&
+        self.key == other.key &&
+        self.stage == other.stage &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for RingKeyRotation {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Event {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    service_group: ::protobuf::SingularPtrField<ServiceGroup>,
+    topic: ::protobuf::SingularField<::std::string::String>,
+    body: ::protobuf::SingularField<::std::string::String>,
+    published_at: ::std::option::Option<i64>,
+    ttl_secs: ::std::option::Option<u64>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Event {}
+
+impl Event {
+    pub fn new() -> Event {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Event {
+        static mut instance: ::protobuf::lazy::Lazy<Event> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Event,
+        };
+        unsafe {
+            instance.get(|| {
+                Event {
+                    id: ::protobuf::SingularField::none(),
+                    service_group: ::protobuf::SingularPtrField::none(),
+                    topic: ::protobuf::SingularField::none(),
+                    body: ::protobuf::SingularField::none(),
+                    published_at: ::std::option::Option::None,
+                    ttl_secs: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required .swim.ServiceGroup service_group = 2;
+
+    pub fn clear_service_group(&mut self) {
+        self.service_group.clear();
+    }
+
+    pub fn has_service_group(&self) -> bool {
+        self.service_group.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_service_group(&mut self, v: ServiceGroup) {
+        self.service_group = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_service_group(&mut self) -> &mut ServiceGroup {
+        if self.service_group.is_none() {
+            self.service_group.set_default();
+        };
+        self.service_group.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_service_group(&mut self) -> ServiceGroup {
+        self.service_group.take().unwrap_or_else(|| ServiceGroup::new())
+    }
+
+    pub fn get_service_group(&self) -> &ServiceGroup {
+        self.service_group.as_ref().unwrap_or_else(|| ServiceGroup::default_instance())
+    }
+
+    // required string topic = 3;
+
+    pub fn clear_topic(&mut self) {
+        self.topic.clear();
+    }
+
+    pub fn has_topic(&self) -> bool {
+        self.topic.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_topic(&mut self, v: ::std::string::String) {
+        self.topic = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_topic(&mut self) -> &mut ::std::string::String {
+        if self.topic.is_none() {
+            self.topic.set_default();
+        };
+        self.topic.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_topic(&mut self) -> ::std::string::String {
+        self.topic.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_topic(&self) -> &str {
+        match self.topic.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string body = 4;
+
+    pub fn clear_body(&mut self) {
+        self.body.clear();
+    }
+
+    pub fn has_body(&self) -> bool {
+        self.body.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_body(&mut self, v: ::std::string::String) {
+        self.body = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_body(&mut self) -> &mut ::std::string::String {
+        if self.body.is_none() {
+            self.body.set_default();
+        };
+        self.body.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_body(&mut self) -> ::std::string::String {
+        self.body.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_body(&self) -> &str {
+        match self.body.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required int64 published_at = 5;
+
+    pub fn clear_published_at(&mut self) {
+        self.published_at = ::std::option::Option::None;
+    }
+
+    pub fn has_published_at(&self) -> bool {
+        self.published_at.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_published_at(&mut self, v: i64) {
+        self.published_at = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_published_at(&self) -> i64 {
+        self.published_at.unwrap_or(0)
+    }
+
+    // required uint64 ttl_secs = 6;
+
+    pub fn clear_ttl_secs(&mut self) {
+        self.ttl_secs = ::std::option::Option::None;
+    }
+
+    pub fn has_ttl_secs(&self) -> bool {
+        self.ttl_secs.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ttl_secs(&mut self, v: u64) {
+        self.ttl_secs = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_ttl_secs(&self) -> u64 {
+        self.ttl_secs.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for Event {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.service_group.is_none() {
+            return false;
+        };
+        if self.topic.is_none() {
+            return false;
+        };
+        if self.body.is_none() {
+            return false;
+        };
+        if self.published_at.is_none() {
+            return false;
+        };
+        if self.ttl_secs.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.service_group));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.topic));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.body));
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_int64());
+                    self.published_at = ::std::option::Option::Some(tmp);
+                },
+                6 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.ttl_secs = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.service_group.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.topic.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.body.iter() {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        for value in self.published_at.iter() {
+            my_size += ::protobuf::rt::value_size(5, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        for value in self.ttl_secs.iter() {
+            my_size += ::protobuf::rt::value_size(6, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.service_group.as_ref() {
+            try!(os.write_tag(2, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.topic.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.body.as_ref() {
+            try!(os.write_string(4, &v));
+        };
+        if let Some(v) = self.published_at {
+            try!(os.write_int64(5, v));
+        };
+        if let Some(v) = self.ttl_secs {
+            try!(os.write_uint64(6, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Event>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Event {
+    fn new() -> Event {
+        Event::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Event>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    Event::has_id,
+                    Event::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "service_group",
+                    Event::has_service_group,
+                    Event::get_service_group,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "topic",
+                    Event::has_topic,
+                    Event::get_topic,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "body",
+                    Event::has_body,
+                    Event::get_body,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_i64_accessor(
+                    "published_at",
+                    Event::has_published_at,
+                    Event::get_published_at,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "ttl_secs",
+                    Event::has_ttl_secs,
+                    Event::get_ttl_secs,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Event>(
+                    "Event",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Event {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_service_group();
+        self.clear_topic();
+        self.clear_body();
+        self.clear_published_at();
+        self.clear_ttl_secs();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        self.id == other.id &&
+        self.service_group == other.service_group &&
+        self.topic == other.topic &&
+        self.body == other.body &&
+        self.published_at == other.published_at &&
+        self.ttl_secs == other.ttl_secs &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Event {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Departure {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    member_id: ::protobuf::SingularField<::std::string::String>,
+    incarnation: ::std::option::Option<u64>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Departure {}
+
+impl Departure {
+    pub fn new() -> Departure {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Departure {
+        static mut instance: ::protobuf::lazy::Lazy<Departure> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Departure,
+        };
+        unsafe {
+            instance.get(|| {
+                Departure {
+                    id: ::protobuf::SingularField::none(),
+                    member_id: ::protobuf::SingularField::none(),
+                    incarnation: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required string member_id = 2;
+
+    pub fn clear_member_id(&mut self) {
+        self.member_id.clear();
+    }
+
+    pub fn has_member_id(&self) -> bool {
+        self.member_id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_member_id(&mut self, v: ::std::string::String) {
+        self.member_id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_member_id(&mut self) -> &mut ::std::string::String {
+        if self.member_id.is_none() {
+            self.member_id.set_default();
+        };
+        self.member_id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_member_id(&mut self) -> ::std::string::String {
+        self.member_id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_member_id(&self) -> &str {
+        match self.member_id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional uint64 incarnation = 3;
+
+    pub fn clear_incarnation(&mut self) {
+        self.incarnation = ::std::option::Option::None;
+    }
+
+    pub fn has_incarnation(&self) -> bool {
+        self.incarnation.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_incarnation(&mut self, v: u64) {
+        self.incarnation = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_incarnation(&self) -> u64 {
+        self.incarnation.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for Departure {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.member_id.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.member_id));
+                },
+                3 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint64());
+                    self.incarnation = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.member_id.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.incarnation.iter() {
+            my_size += ::protobuf::rt::value_size(3, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.member_id.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.incarnation {
+            try!(os.write_uint64(3, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Departure>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Departure {
+    fn new() -> Departure {
+        Departure::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Departure>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    Departure::has_id,
+                    Departure::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "member_id",
+                    Departure::has_member_id,
+                    Departure::get_member_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u64_accessor(
+                    "incarnation",
+                    Departure::has_incarnation,
+                    Departure::get_incarnation,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Departure>(
+                    "Departure",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Departure {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_member_id();
+        self.clear_incarnation();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Departure {
+    fn eq(&self, other: &Departure) -> bool {
+        self.id == other.id &&
+        self.member_id == other.member_id &&
+        self.incarnation == other.incarnation &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Departure {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Rumor {
+    // message fields
+    id: ::protobuf::SingularField<::std::string::String>,
+    field_type: ::std::option::Option<RumorType>,
+    member: ::protobuf::SingularPtrField<Member>,
+    census_entry: ::protobuf::SingularPtrField<CensusEntry>,
+    election: ::protobuf::SingularPtrField<Election>,
+    gossip_file: ::protobuf::SingularPtrField<GossipFile>,
+    ring_key_rotation: ::protobuf::SingularPtrField<RingKeyRotation>,
+    event: ::protobuf::SingularPtrField<Event>,
+    departure: ::protobuf::SingularPtrField<Departure>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Rumor {}
+
+impl Rumor {
+    pub fn new() -> Rumor {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Rumor {
+        static mut instance: ::protobuf::lazy::Lazy<Rumor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Rumor,
+        };
+        unsafe {
+            instance.get(|| {
+                Rumor {
+                    id: ::protobuf::SingularField::none(),
+                    field_type: ::std::option::Option::None,
+                    member: ::protobuf::SingularPtrField::none(),
+                    census_entry: ::protobuf::SingularPtrField::none(),
+                    election: ::protobuf::SingularPtrField::none(),
+                    gossip_file: ::protobuf::SingularPtrField::none(),
+                    ring_key_rotation: ::protobuf::SingularPtrField::none(),
+                    event: ::protobuf::SingularPtrField::none(),
+                    departure: ::protobuf::SingularPtrField::none(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required string id = 1;
+
+    pub fn clear_id(&mut self) {
+        self.id.clear();
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_id(&mut self, v: ::std::string::String) {
+        self.id = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_id(&mut self) -> &mut ::std::string::String {
+        if self.id.is_none() {
+            self.id.set_default();
+        };
+        self.id.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_id(&mut self) -> ::std::string::String {
+        self.id.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_id(&self) -> &str {
+        match self.id.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // required .swim.RumorType type = 2;
+
+    pub fn clear_field_type(&mut self) {
+        self.field_type = ::std::option::Option::None;
+    }
+
+    pub fn has_field_type(&self) -> bool {
+        self.field_type.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_field_type(&mut self, v: RumorType) {
+        self.field_type = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_field_type(&self) -> RumorType {
+        self.field_type.unwrap_or(RumorType::MEMBER)
+    }
+
+    // optional .swim.Member member = 3;
+
+    pub fn clear_member(&mut self) {
+        self.member.clear();
+    }
+
+    pub fn has_member(&self) -> bool {
+        self.member.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_member(&mut self, v: Member) {
+        self.member = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_member(&mut self) -> &mut Member {
+        if self.member.is_none() {
+            self.member.set_default();
+        };
+        self.member.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_member(&mut self) -> Member {
+        self.member.take().unwrap_or_else(|| Member::new())
+    }
+
+    pub fn get_member(&self) -> &Member {
+        self.member.as_ref().unwrap_or_else(|| Member::default_instance())
+    }
+
+    // optional .swim.CensusEntry census_entry = 4;
+
+    pub fn clear_census_entry(&mut self) {
+        self.census_entry.clear();
+    }
+
+    pub fn has_census_entry(&self) -> bool {
+        self.census_entry.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_census_entry(&mut self, v: CensusEntry) {
+        self.census_entry = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_census_entry(&mut self) -> &mut CensusEntry {
+        if self.census_entry.is_none() {
+            self.census_entry.set_default();
+        };
+        self.census_entry.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_census_entry(&mut self) -> CensusEntry {
+        self.census_entry.take().unwrap_or_else(|| CensusEntry::new())
+    }
+
+    pub fn get_census_entry(&self) -> &CensusEntry {
+        self.census_entry.as_ref().unwrap_or_else(|| CensusEntry::default_instance())
+    }
+
+    // optional .swim.Election election = 5;
+
+    pub fn clear_election(&mut self) {
+        self.election.clear();
+    }
+
+    pub fn has_election(&self) -> bool {
+        self.election.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_election(&mut self, v: Election) {
+        self.election = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_election(&mut self) -> &mut Election {
+        if self.election.is_none() {
+            self.election.set_default();
+        };
+        self.election.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_election(&mut self) -> Election {
+        self.election.take().unwrap_or_else(|| Election::new())
+    }
+
+    pub fn get_election(&self) -> &Election {
+        self.election.as_ref().unwrap_or_else(|| Election::default_instance())
+    }
+
+    // optional .swim.GossipFile gossip_file = 6;
+
+    pub fn clear_gossip_file(&mut self) {
+        self.gossip_file.clear();
+    }
+
+    pub fn has_gossip_file(&self) -> bool {
+        self.gossip_file.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_gossip_file(&mut self, v: GossipFile) {
+        self.gossip_file = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_gossip_file(&mut self) -> &mut GossipFile {
+        if self.gossip_file.is_none() {
+            self.gossip_file.set_default();
+        };
+        self.gossip_file.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_gossip_file(&mut self) -> GossipFile {
+        self.gossip_file.take().unwrap_or_else(|| GossipFile::new())
+    }
+
+    pub fn get_gossip_file(&self) -> &GossipFile {
+        self.gossip_file.as_ref().unwrap_or_else(|| GossipFile::default_instance())
+    }
+
+    // optional .swim.RingKeyRotation ring_key_rotation = 7;
+
+    pub fn clear_ring_key_rotation(&mut self) {
+        self.ring_key_rotation.clear();
+    }
+
+    pub fn has_ring_key_rotation(&self) -> bool {
+        self.ring_key_rotation.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_ring_key_rotation(&mut self, v: RingKeyRotation) {
+        self.ring_key_rotation = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_ring_key_rotation(&mut self) -> &mut RingKeyRotation {
+        if self.ring_key_rotation.is_none() {
+            self.ring_key_rotation.set_default();
+        };
+        self.ring_key_rotation.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_ring_key_rotation(&mut self) -> RingKeyRotation {
+        self.ring_key_rotation.take().unwrap_or_else(|| RingKeyRotation::new())
+    }
+
+    pub fn get_ring_key_rotation(&self) -> &RingKeyRotation {
+        self.ring_key_rotation.as_ref().unwrap_or_else(|| RingKeyRotation::default_instance())
+    }
+
+    // optional .swim.Event event = 8;
+
+    pub fn clear_event(&mut self) {
+        self.event.clear();
+    }
+
+    pub fn has_event(&self) -> bool {
+        self.event.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_event(&mut self, v: Event) {
+        self.event = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_event(&mut self) -> &mut Event {
+        if self.event.is_none() {
+            self.event.set_default();
+        };
+        self.event.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_event(&mut self) -> Event {
+        self.event.take().unwrap_or_else(|| Event::new())
+    }
+
+    pub fn get_event(&self) -> &Event {
+        self.event.as_ref().unwrap_or_else(|| Event::default_instance())
+    }
+
+    // optional .swim.Departure departure = 9;
+
+    pub fn clear_departure(&mut self) {
+        self.departure.clear();
+    }
+
+    pub fn has_departure(&self) -> bool {
+        self.departure.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_departure(&mut self, v: Departure) {
+        self.departure = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_departure(&mut self) -> &mut Departure {
+        if self.departure.is_none() {
+            self.departure.set_default();
+        };
+        self.departure.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_departure(&mut self) -> Departure {
+        self.departure.take().unwrap_or_else(|| Departure::new())
+    }
+
+    pub fn get_departure(&self) -> &Departure {
+        self.departure.as_ref().unwrap_or_else(|| Departure::default_instance())
+    }
+}
+
+impl ::protobuf::Message for Rumor {
+    fn is_initialized(&self) -> bool {
+        if self.id.is_none() {
+            return false;
+        };
+        if self.field_type.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.id));
+                },
+                2 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.field_type = ::std::option::Option::Some(tmp);
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.member));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.census_entry));
+                },
+                5 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.election));
+                },
+                6 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.gossip_file));
+                },
+                7 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.ring_key_rotation));
+                },
+                8 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.event));
+                },
+                9 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.departure));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.id.iter() {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in self.field_type.iter() {
+            my_size += ::protobuf::rt::enum_size(2, *value);
+        };
+        for value in self.member.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.census_entry.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.election.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.gossip_file.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.ring_key_rotation.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.event.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.departure.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.id.as_ref() {
+            try!(os.write_string(1, &v));
+        };
+        if let Some(v) = self.field_type {
+            try!(os.write_enum(2, v.value()));
+        };
+        if let Some(v) = self.member.as_ref() {
+            try!(os.write_tag(3, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.census_entry.as_ref() {
+            try!(os.write_tag(4, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.election.as_ref() {
+            try!(os.write_tag(5, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.gossip_file.as_ref() {
+            try!(os.write_tag(6, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.ring_key_rotation.as_ref() {
+            try!(os.write_tag(7, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.event.as_ref() {
+            try!(os.write_tag(8, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.departure.as_ref() {
+            try!(os.write_tag(9, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Rumor>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Rumor {
+    fn new() -> Rumor {
+        Rumor::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Rumor>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "id",
+                    Rumor::has_id,
+                    Rumor::get_id,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "type",
+                    Rumor::has_field_type,
+                    Rumor::get_field_type,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "member",
+                    Rumor::has_member,
+                    Rumor::get_member,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "census_entry",
+                    Rumor::has_census_entry,
+                    Rumor::get_census_entry,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "election",
+                    Rumor::has_election,
+                    Rumor::get_election,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "gossip_file",
+                    Rumor::has_gossip_file,
+                    Rumor::get_gossip_file,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "ring_key_rotation",
+                    Rumor::has_ring_key_rotation,
+                    Rumor::get_ring_key_rotation,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "event",
+                    Rumor::has_event,
+                    Rumor::get_event,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "departure",
+                    Rumor::has_departure,
+                    Rumor::get_departure,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Rumor>(
+                    "Rumor",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Rumor {
+    fn clear(&mut self) {
+        self.clear_id();
+        self.clear_field_type();
+        self.clear_member();
+        self.clear_census_entry();
+        self.clear_election();
+        self.clear_gossip_file();
+        self.clear_ring_key_rotation();
+        self.clear_event();
+        self.clear_departure();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Rumor {
+    fn eq(&self, other: &Rumor) -> bool {
+        self.id == other.id &&
+        self.field_type == other.field_type &&
+        self.member == other.member &&
+        self.census_entry == other.census_entry &&
+        self.election == other.election &&
+        self.gossip_file == other.gossip_file &&
+        self.ring_key_rotation == other.ring_key_rotation &&
+        self.event == other.event &&
+        self.departure == other.departure &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Rumor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct RumorList {
+    // message fields
+    rumors: ::protobuf::RepeatedField<Rumor>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for RumorList {}
+
+impl RumorList {
+    pub fn new() -> RumorList {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static RumorList {
+        static mut instance: ::protobuf::lazy::Lazy<RumorList> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const RumorList,
+        };
+        unsafe {
+            instance.get(|| {
+                RumorList {
+                    rumors: ::protobuf::RepeatedField::new(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // repeated .swim.Rumor rumors = 1;
+
+    pub fn clear_rumors(&mut self) {
+        self.rumors.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_rumors(&mut self, v: ::protobuf::RepeatedField<Rumor>) {
+        self.rumors = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_rumors(&mut self) -> &mut ::protobuf::RepeatedField<Rumor> {
+        &mut self.rumors
+    }
+
+    // Take field
+    pub fn take_rumors(&mut self) -> ::protobuf::RepeatedField<Rumor> {
+        ::std::mem::replace(&mut self.rumors, ::protobuf::RepeatedField::new())
+    }
+
+    pub fn get_rumors(&self) -> &[Rumor] {
+        &self.rumors
+    }
+}
+
+impl ::protobuf::Message for RumorList {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    try!(::protobuf::rt::read_repeated_message_into(wire_type, is, &mut self.rumors));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.rumors.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        for v in self.rumors.iter() {
+            try!(os.write_tag(1, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<RumorList>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for RumorList {
+    fn new() -> RumorList {
+        RumorList::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<RumorList>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_repeated_message_accessor(
+                    "rumors",
+                    RumorList::get_rumors,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<RumorList>(
+                    "RumorList",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for RumorList {
+    fn clear(&mut self) {
+        self.clear_rumors();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for RumorList {
+    fn eq(&self, other: &RumorList) -> bool {
+        self.rumors == other.rumors &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for RumorList {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,Default)]
+pub struct Swim {
+    // message fields
+    field_type: ::std::option::Option<SwimType>,
+    peer: ::protobuf::SingularPtrField<Peer>,
+    rumors: ::protobuf::SingularPtrField<RumorList>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Swim {}
+
+impl Swim {
+    pub fn new() -> Swim {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Swim {
+        static mut instance: ::protobuf::lazy::Lazy<Swim> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Swim,
+        };
+        unsafe {
+            instance.get(|| {
+                Swim {
+                    field_type: ::std::option::Option::None,
+                    peer: ::protobuf::SingularPtrField::none(),
+                    rumors: ::protobuf::SingularPtrField::none(),
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required .swim.SwimType type = 1;
+
+    pub fn clear_field_type(&mut self) {
+        self.field_type = ::std::option::Option::None;
+    }
+
+    pub fn has_field_type(&self) -> bool {
+        self.field_type.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_field_type(&mut self, v: SwimType) {
+        self.field_type = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_field_type(&self) -> SwimType {
+        self.field_type.unwrap_or(SwimType::PING)
+    }
+
+    // optional .swim.Peer peer = 2;
+
+    pub fn clear_peer(&mut self) {
+        self.peer.clear();
+    }
+
+    pub fn has_peer(&self) -> bool {
+        self.peer.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_peer(&mut self, v: Peer) {
+        self.peer = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_peer(&mut self) -> &mut Peer {
+        if self.peer.is_none() {
+            self.peer.set_default();
+        };
+        self.peer.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_peer(&mut self) -> Peer {
+        self.peer.take().unwrap_or_else(|| Peer::new())
+    }
+
+    pub fn get_peer(&self) -> &Peer {
+        self.peer.as_ref().unwrap_or_else(|| Peer::default_instance())
+    }
+
+    // required .swim.RumorList rumors = 3;
+
+    pub fn clear_rumors(&mut self) {
+        self.rumors.clear();
+    }
+
+    pub fn has_rumors(&self) -> bool {
+        self.rumors.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_rumors(&mut self, v: RumorList) {
+        self.rumors = ::protobuf::SingularPtrField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_rumors(&mut self) -> &mut RumorList {
+        if self.rumors.is_none() {
+            self.rumors.set_default();
+        };
+        self.rumors.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_rumors(&mut self) -> RumorList {
+        self.rumors.take().unwrap_or_else(|| RumorList::new())
+    }
+
+    pub fn get_rumors(&self) -> &RumorList {
+        self.rumors.as_ref().unwrap_or_else(|| RumorList::default_instance())
+    }
+}
+
+impl ::protobuf::Message for Swim {
+    fn is_initialized(&self) -> bool {
+        if self.field_type.is_none() {
+            return false;
+        };
+        if self.rumors.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.field_type = ::std::option::Option::Some(tmp);
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.peer));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_message_into(wire_type, is, &mut self.rumors));
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.field_type.iter() {
+            my_size += ::protobuf::rt::enum_size(1, *value);
+        };
+        for value in self.peer.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        for value in self.rumors.iter() {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint32_size(len) + len;
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.field_type {
+            try!(os.write_enum(1, v.value()));
+        };
+        if let Some(v) = self.peer.as_ref() {
+            try!(os.write_tag(2, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        if let Some(v) = self.rumors.as_ref() {
+            try!(os.write_tag(3, ::protobuf::wire_format::WireTypeLengthDelimited));
+            try!(os.write_raw_varint32(v.get_cached_size()));
+            try!(v.write_to_with_cached_sizes(os));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Swim>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Swim {
+    fn new() -> Swim {
+        Swim::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Swim>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "type",
+                    Swim::has_field_type,
+                    Swim::get_field_type,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "peer",
+                    Swim::has_peer,
+                    Swim::get_peer,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_message_accessor(
+                    "rumors",
+                    Swim::has_rumors,
+                    Swim::get_rumors,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Swim>(
+                    "Swim",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Swim {
+    fn clear(&mut self) {
+        self.clear_field_type();
+        self.clear_peer();
+        self.clear_rumors();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Swim {
+    fn eq(&self, other: &Swim) -> bool {
+        self.field_type == other.field_type &&
+        self.peer == other.peer &&
+        self.rumors == other.rumors &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Swim {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum Health {
+    ALIVE = 0,
+    SUSPECT = 1,
+    CONFIRMED = 2,
+    DEPARTED = 3,
+}
+
+impl ::protobuf::ProtobufEnum for Health {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<Health> {
+        match value {
+            0 => ::std::option::Option::Some(Health::ALIVE),
+            1 => ::std::option::Option::Some(Health::SUSPECT),
+            2 => ::std::option::Option::Some(Health::CONFIRMED),
+            3 => ::std::option::Option::Some(Health::DEPARTED),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [Health] = &[
+            Health::ALIVE,
+            Health::SUSPECT,
+            Health::CONFIRMED,
+            Health::DEPARTED,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<Health>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("Health", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for Health {
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum HealthStatus {
+    OK = 0,
+    WARNING = 1,
+    CRITICAL = 2,
+    UNKNOWN = 3,
+}
+
+impl ::protobuf::ProtobufEnum for HealthStatus {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<HealthStatus> {
+        match value {
+            0 => ::std::option::Option::Some(HealthStatus::OK),
+            1 => ::std::option::Option::Some(HealthStatus::WARNING),
+            2 => ::std::option::Option::Some(HealthStatus::CRITICAL),
+            3 => ::std::option::Option::Some(HealthStatus::UNKNOWN),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [HealthStatus] = &[
+            HealthStatus::OK,
+            HealthStatus::WARNING,
+            HealthStatus::CRITICAL,
+            HealthStatus::UNKNOWN,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<HealthStatus>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("HealthStatus", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for HealthStatus {
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum ElectionStatus {
+    RUNNING = 0,
+    FINISHED = 1,
+}
+
+impl ::protobuf::ProtobufEnum for ElectionStatus {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<ElectionStatus> {
+        match value {
+            0 => ::std::option::Option::Some(ElectionStatus::RUNNING),
+            1 => ::std::option::Option::Some(ElectionStatus::FINISHED),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [ElectionStatus] = &[
+            ElectionStatus::RUNNING,
+            ElectionStatus::FINISHED,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<ElectionStatus>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("ElectionStatus", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for ElectionStatus {
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum RotationStage {
+    DISTRIBUTE = 0,
+    ACTIVATE = 1,
+    RETIRE = 2,
+}
+
+impl ::protobuf::ProtobufEnum for RotationStage {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<RotationStage> {
+        match value {
+            0 => ::std::option::Option::Some(RotationStage::DISTRIBUTE),
+            1 => ::std::option::Option::Some(RotationStage::ACTIVATE),
+            2 => ::std::option::Option::Some(RotationStage::RETIRE),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [RotationStage] = &[
+            RotationStage::DISTRIBUTE,
+            RotationStage::ACTIVATE,
+            RotationStage::RETIRE,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<RotationStage>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("RotationStage", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for RotationStage {
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum RumorType {
+    MEMBER = 0,
+    CENSUS_ENTRY = 1,
+    ELECTION = 2,
+    GOSSIP_FILE = 3,
+    BLANK = 4,
+    RING_KEY_ROTATION = 5,
+    EVENT = 6,
+    DEPARTURE = 7,
+}
+
+impl ::protobuf::ProtobufEnum for RumorType {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<RumorType> {
+        match value {
+            0 => ::std::option::Option::Some(RumorType::MEMBER),
+            1 => ::std::option::Option::Some(RumorType::CENSUS_ENTRY),
+            2 => ::std::option::Option::Some(RumorType::ELECTION),
+            3 => ::std::option::Option::Some(RumorType::GOSSIP_FILE),
+            4 => ::std::option::Option::Some(RumorType::BLANK),
+            5 => ::std::option::Option::Some(RumorType::RING_KEY_ROTATION),
+            6 => ::std::option::Option::Some(RumorType::EVENT),
+            7 => ::std::option::Option::Some(RumorType::DEPARTURE),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [RumorType] = &[
+            RumorType::MEMBER,
+            RumorType::CENSUS_ENTRY,
+            RumorType::ELECTION,
+            RumorType::GOSSIP_FILE,
+            RumorType::BLANK,
+            RumorType::RING_KEY_ROTATION,
+            RumorType::EVENT,
+            RumorType::DEPARTURE,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<RumorType>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("RumorType", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for RumorType {
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum SwimType {
+    PING = 0,
+    ACK = 1,
+    PINGREQ = 2,
+    INJECT = 3,
+}
+
+impl ::protobuf::ProtobufEnum for SwimType {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<SwimType> {
+        match value {
+            0 => ::std::option::Option::Some(SwimType::PING),
+            1 => ::std::option::Option::Some(SwimType::ACK),
+            2 => ::std::option::Option::Some(SwimType::PINGREQ),
+            3 => ::std::option::Option::Some(SwimType::INJECT),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [SwimType] = &[
+            SwimType::PING,
+            SwimType::ACK,
+            SwimType::PINGREQ,
+            SwimType::INJECT,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<SwimType>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("SwimType", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for SwimType {
+}
+
+static file_descriptor_proto_data: &'static [u8] = &[
+    0x0a, 0x14, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x63, 0x6f, 0x6c, 0x73, 0x2f, 0x73, 0x77, 0x69, 0x6d,
+    0x2e, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x12, 0x04, 0x73, 0x77, 0x69, 0x6d, 0x22, 0x44, 0x0a, 0x0c,
+    0x53, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x47, 0x72, 0x6f, 0x75, 0x70, 0x12, 0x0f, 0x0a, 0x07,
+    0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12, 0x0d, 0x0a,
+    0x05, 0x67, 0x72, 0x6f, 0x75, 0x70, 0x18, 0x02, 0x20, 0x02, 0x28, 0x09, 0x12, 0x14, 0x0a, 0x0c,
+    0x6f, 0x72, 0x67, 0x61, 0x6e, 0x69, 0x7a, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x18, 0x03, 0x20, 0x01,
+    0x28, 0x09, 0x22, 0x58, 0x0a, 0x04, 0x50, 0x65, 0x65, 0x72, 0x12, 0x11, 0x0a, 0x09, 0x6d, 0x65,
+    0x6d, 0x62, 0x65, 0x72, 0x5f, 0x69, 0x64, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12, 0x14, 0x0a,
+    0x0c, 0x6c, 0x69, 0x73, 0x74, 0x65, 0x6e, 0x69, 0x6e, 0x67, 0x5f, 0x6f, 0x6e, 0x18, 0x02, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x15, 0x0a, 0x0d, 0x70, 0x72, 0x6f, 0x78, 0x79, 0x5f, 0x74, 0x68, 0x72,
+    0x6f, 0x75, 0x67, 0x68, 0x18, 0x03, 0x20, 0x01, 0x28, 0x09, 0x12, 0x10, 0x0a, 0x08, 0x70, 0x72,
+    0x6f, 0x78, 0x79, 0x5f, 0x74, 0x6f, 0x18, 0x04, 0x20, 0x01, 0x28, 0x09, 0x22, 0xab, 0x01, 0x0a,
+    0x06, 0x4d, 0x65, 0x6d, 0x62, 0x65, 0x72, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x10, 0x0a, 0x08, 0x68, 0x6f, 0x73, 0x74, 0x6e, 0x61, 0x6d, 0x65, 0x18,
+    0x02, 0x20, 0x02, 0x28, 0x09, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x70, 0x18, 0x03, 0x20, 0x02, 0x28,
+    0x09, 0x12, 0x17, 0x0a, 0x0f, 0x67, 0x6f, 0x73, 0x73, 0x69, 0x70, 0x5f, 0x6c, 0x69, 0x73, 0x74,
+    0x65, 0x6e, 0x65, 0x72, 0x18, 0x04, 0x20, 0x02, 0x28, 0x09, 0x12, 0x13, 0x0a, 0x0b, 0x69, 0x6e,
+    0x63, 0x61, 0x72, 0x6e, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x18, 0x05, 0x20, 0x02, 0x28, 0x04, 0x12,
+    0x1c, 0x0a, 0x06, 0x68, 0x65, 0x61, 0x6c, 0x74, 0x68, 0x18, 0x06, 0x20, 0x02, 0x28, 0x0e, 0x32,
+    0x0c, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x48, 0x65, 0x61, 0x6c, 0x74, 0x68, 0x12, 0x11, 0x0a,
+    0x09, 0x70, 0x65, 0x72, 0x6d, 0x61, 0x6e, 0x65, 0x6e, 0x74, 0x18, 0x07, 0x20, 0x02, 0x28, 0x08,
+    0x12, 0x18, 0x0a, 0x10, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x63, 0x6f, 0x6c, 0x5f, 0x76, 0x65, 0x72,
+    0x73, 0x69, 0x6f, 0x6e, 0x18, 0x08, 0x20, 0x01, 0x28, 0x0d, 0x22, 0x18, 0x0a, 0x07, 0x45, 0x78,
+    0x70, 0x6f, 0x73, 0x65, 0x73, 0x12, 0x0d, 0x0a, 0x05, 0x70, 0x6f, 0x72, 0x74, 0x73, 0x18, 0x01,
+    0x20, 0x03, 0x28, 0x09, 0x22, 0xa3, 0x04, 0x0a, 0x0b, 0x43, 0x65, 0x6e, 0x73, 0x75, 0x73, 0x45,
+    0x6e, 0x74, 0x72, 0x79, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09,
+    0x12, 0x11, 0x0a, 0x09, 0x6d, 0x65, 0x6d, 0x62, 0x65, 0x72, 0x5f, 0x69, 0x64, 0x18, 0x02, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x10, 0x0a, 0x08, 0x68, 0x6f, 0x73, 0x74, 0x6e, 0x61, 0x6d, 0x65, 0x18,
+    0x03, 0x20, 0x02, 0x28, 0x09, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x70, 0x18, 0x04, 0x20, 0x02, 0x28,
+    0x09, 0x12, 0x13, 0x0a, 0x0b, 0x73, 0x75, 0x69, 0x74, 0x61, 0x62, 0x69, 0x6c, 0x69, 0x74, 0x79,
+    0x18, 0x05, 0x20, 0x02, 0x28, 0x04, 0x12, 0x0c, 0x0a, 0x04, 0x70, 0x6f, 0x72, 0x74, 0x18, 0x06,
+    0x20, 0x01, 0x28, 0x09, 0x12, 0x1e, 0x0a, 0x07, 0x65, 0x78, 0x70, 0x6f, 0x73, 0x65, 0x73, 0x18,
+    0x07, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0d, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x45, 0x78, 0x70,
+    0x6f, 0x73, 0x65, 0x73, 0x12, 0x0e, 0x0a, 0x06, 0x6c, 0x65, 0x61, 0x64, 0x65, 0x72, 0x18, 0x08,
+    0x20, 0x02, 0x28, 0x08, 0x12, 0x10, 0x0a, 0x08, 0x66, 0x6f, 0x6c, 0x6c, 0x6f, 0x77, 0x65, 0x72,
+    0x18, 0x09, 0x20, 0x02, 0x28, 0x08, 0x12, 0x11, 0x0a, 0x09, 0x64, 0x61, 0x74, 0x61, 0x5f, 0x69,
+    0x6e, 0x69, 0x74, 0x18, 0x0a, 0x20, 0x02, 0x28, 0x08, 0x12, 0x0c, 0x0a, 0x04, 0x76, 0x6f, 0x74,
+    0x65, 0x18, 0x0b, 0x20, 0x01, 0x28, 0x09, 0x12, 0x10, 0x0a, 0x08, 0x65, 0x6c, 0x65, 0x63, 0x74,
+    0x69, 0x6f, 0x6e, 0x18, 0x0c, 0x20, 0x01, 0x28, 0x08, 0x12, 0x13, 0x0a, 0x0b, 0x6e, 0x65, 0x65,
+    0x64, 0x73, 0x5f, 0x77, 0x72, 0x69, 0x74, 0x65, 0x18, 0x0d, 0x20, 0x01, 0x28, 0x08, 0x12, 0x13,
+    0x0a, 0x0b, 0x69, 0x6e, 0x69, 0x74, 0x69, 0x61, 0x6c, 0x69, 0x7a, 0x65, 0x64, 0x18, 0x0e, 0x20,
+    0x02, 0x28, 0x08, 0x12, 0x0f, 0x0a, 0x07, 0x6b, 0x65, 0x65, 0x70, 0x5f, 0x6d, 0x65, 0x18, 0x0f,
+    0x20, 0x02, 0x28, 0x08, 0x12, 0x0f, 0x0a, 0x07, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x18,
+    0x10, 0x20, 0x02, 0x28, 0x09, 0x12, 0x0d, 0x0a, 0x05, 0x67, 0x72, 0x6f, 0x75, 0x70, 0x18, 0x11,
+    0x20, 0x02, 0x28, 0x09, 0x12, 0x0d, 0x0a, 0x05, 0x61, 0x6c, 0x69, 0x76, 0x65, 0x18, 0x12, 0x20,
+    0x02, 0x28, 0x08, 0x12, 0x0f, 0x0a, 0x07, 0x73, 0x75, 0x73, 0x70, 0x65, 0x63, 0x74, 0x18, 0x13,
+    0x20, 0x02, 0x28, 0x08, 0x12, 0x11, 0x0a, 0x09, 0x63, 0x6f, 0x6e, 0x66, 0x69, 0x72, 0x6d, 0x65,
+    0x64, 0x18, 0x14, 0x20, 0x02, 0x28, 0x08, 0x12, 0x10, 0x0a, 0x08, 0x64, 0x65, 0x74, 0x61, 0x63,
+    0x68, 0x65, 0x64, 0x18, 0x15, 0x20, 0x02, 0x28, 0x08, 0x12, 0x0f, 0x0a, 0x07, 0x70, 0x61, 0x63,
+    0x6b, 0x61, 0x67, 0x65, 0x18, 0x16, 0x20, 0x01, 0x28, 0x09, 0x12, 0x22, 0x0a, 0x06, 0x68, 0x65,
+    0x61, 0x6c, 0x74, 0x68, 0x18, 0x18, 0x20, 0x01, 0x28, 0x0e, 0x32, 0x12, 0x2e, 0x73, 0x77, 0x69,
+    0x6d, 0x2e, 0x48, 0x65, 0x61, 0x6c, 0x74, 0x68, 0x53, 0x74, 0x61, 0x74, 0x75, 0x73, 0x12, 0x10,
+    0x0a, 0x08, 0x72, 0x65, 0x73, 0x74, 0x61, 0x72, 0x74, 0x73, 0x18, 0x19, 0x20, 0x01, 0x28, 0x04,
+    0x12, 0x11, 0x0a, 0x09, 0x6c, 0x61, 0x73, 0x74, 0x5f, 0x65, 0x78, 0x69, 0x74, 0x18, 0x1a, 0x20,
+    0x01, 0x28, 0x09, 0x12, 0x14, 0x0a, 0x0c, 0x62, 0x61, 0x64, 0x5f, 0x72, 0x65, 0x6c, 0x65, 0x61,
+    0x73, 0x65, 0x73, 0x18, 0x1b, 0x20, 0x03, 0x28, 0x09, 0x12, 0x0b, 0x0a, 0x03, 0x63, 0x66, 0x67,
+    0x18, 0x1c, 0x20, 0x01, 0x28, 0x09, 0x12, 0x13, 0x0a, 0x0b, 0x69, 0x6e, 0x63, 0x61, 0x72, 0x6e,
+    0x61, 0x74, 0x69, 0x6f, 0x6e, 0x18, 0x1d, 0x20, 0x02, 0x28, 0x04, 0x4a, 0x04, 0x08, 0x17, 0x10,
+    0x18, 0x52, 0x07, 0x68, 0x65, 0x61, 0x6c, 0x74, 0x68, 0x79, 0x22, 0xb3, 0x01, 0x0a, 0x08, 0x45,
+    0x6c, 0x65, 0x63, 0x74, 0x69, 0x6f, 0x6e, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x0f, 0x0a, 0x07, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x18, 0x02,
+    0x20, 0x02, 0x28, 0x09, 0x12, 0x0d, 0x0a, 0x05, 0x67, 0x72, 0x6f, 0x75, 0x70, 0x18, 0x03, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x11, 0x0a, 0x09, 0x6c, 0x65, 0x61, 0x64, 0x65, 0x72, 0x5f, 0x69, 0x64,
+    0x18, 0x04, 0x20, 0x02, 0x28, 0x09, 0x12, 0x13, 0x0a, 0x0b, 0x73, 0x75, 0x69, 0x74, 0x61, 0x62,
+    0x69, 0x6c, 0x69, 0x74, 0x79, 0x18, 0x05, 0x20, 0x02, 0x28, 0x0d, 0x12, 0x10, 0x0a, 0x08, 0x63,
+    0x72, 0x69, 0x74, 0x69, 0x63, 0x61, 0x6c, 0x18, 0x06, 0x20, 0x01, 0x28, 0x08, 0x12, 0x0d, 0x0a,
+    0x05, 0x76, 0x6f, 0x74, 0x65, 0x73, 0x18, 0x07, 0x20, 0x03, 0x28, 0x09, 0x12, 0x24, 0x0a, 0x06,
+    0x73, 0x74, 0x61, 0x74, 0x75, 0x73, 0x18, 0x08, 0x20, 0x02, 0x28, 0x0e, 0x32, 0x14, 0x2e, 0x73,
+    0x77, 0x69, 0x6d, 0x2e, 0x45, 0x6c, 0x65, 0x63, 0x74, 0x69, 0x6f, 0x6e, 0x53, 0x74, 0x61, 0x74,
+    0x75, 0x73, 0x12, 0x0c, 0x0a, 0x04, 0x74, 0x65, 0x72, 0x6d, 0x18, 0x09, 0x20, 0x02, 0x28, 0x0d,
+    0x22, 0xa6, 0x01, 0x0a, 0x0a, 0x47, 0x6f, 0x73, 0x73, 0x69, 0x70, 0x46, 0x69, 0x6c, 0x65, 0x12,
+    0x29, 0x0a, 0x0d, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x5f, 0x67, 0x72, 0x6f, 0x75, 0x70,
+    0x18, 0x01, 0x20, 0x02, 0x28, 0x0b, 0x32, 0x12, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x53, 0x65,
+    0x72, 0x76, 0x69, 0x63, 0x65, 0x47, 0x72, 0x6f, 0x75, 0x70, 0x12, 0x11, 0x0a, 0x09, 0x66, 0x69,
+    0x6c, 0x65, 0x5f, 0x6e, 0x61, 0x6d, 0x65, 0x18, 0x02, 0x20, 0x02, 0x28, 0x09, 0x12, 0x0c, 0x0a,
+    0x04, 0x62, 0x6f, 0x64, 0x79, 0x18, 0x03, 0x20, 0x02, 0x28, 0x0c, 0x12, 0x10, 0x0a, 0x08, 0x63,
+    0x68, 0x65, 0x63, 0x6b, 0x73, 0x75, 0x6d, 0x18, 0x04, 0x20, 0x02, 0x28, 0x09, 0x12, 0x16, 0x0a,
+    0x0e, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x5f, 0x6e, 0x75, 0x6d, 0x62, 0x65, 0x72, 0x18,
+    0x05, 0x20, 0x02, 0x28, 0x04, 0x12, 0x0f, 0x0a, 0x07, 0x77, 0x72, 0x69, 0x74, 0x74, 0x65, 0x6e,
+    0x18, 0x06, 0x20, 0x02, 0x28, 0x08, 0x12, 0x11, 0x0a, 0x09, 0x65, 0x6e, 0x63, 0x72, 0x79, 0x70,
+    0x74, 0x65, 0x64, 0x18, 0x07, 0x20, 0x02, 0x28, 0x08, 0x22, 0x65, 0x0a, 0x0f, 0x52, 0x69, 0x6e,
+    0x67, 0x4b, 0x65, 0x79, 0x52, 0x6f, 0x74, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x12, 0x0a, 0x0a, 0x02,
+    0x69, 0x64, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12, 0x15, 0x0a, 0x0d, 0x6e, 0x61, 0x6d, 0x65,
+    0x5f, 0x77, 0x69, 0x74, 0x68, 0x5f, 0x72, 0x65, 0x76, 0x18, 0x02, 0x20, 0x02, 0x28, 0x09, 0x12,
+    0x0b, 0x0a, 0x03, 0x6b, 0x65, 0x79, 0x18, 0x03, 0x20, 0x02, 0x28, 0x09, 0x12, 0x22, 0x0a, 0x05,
+    0x73, 0x74, 0x61, 0x67, 0x65, 0x18, 0x04, 0x20, 0x02, 0x28, 0x0e, 0x32, 0x13, 0x2e, 0x73, 0x77,
+    0x69, 0x6d, 0x2e, 0x52, 0x6f, 0x74, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x53, 0x74, 0x61, 0x67, 0x65,
+    0x22, 0x83, 0x01, 0x0a, 0x05, 0x45, 0x76, 0x65, 0x6e, 0x74, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64,
+    0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12, 0x29, 0x0a, 0x0d, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63,
+    0x65, 0x5f, 0x67, 0x72, 0x6f, 0x75, 0x70, 0x18, 0x02, 0x20, 0x02, 0x28, 0x0b, 0x32, 0x12, 0x2e,
+    0x73, 0x77, 0x69, 0x6d, 0x2e, 0x53, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x47, 0x72, 0x6f, 0x75,
+    0x70, 0x12, 0x0d, 0x0a, 0x05, 0x74, 0x6f, 0x70, 0x69, 0x63, 0x18, 0x03, 0x20, 0x02, 0x28, 0x09,
+    0x12, 0x0c, 0x0a, 0x04, 0x62, 0x6f, 0x64, 0x79, 0x18, 0x04, 0x20, 0x02, 0x28, 0x09, 0x12, 0x14,
+    0x0a, 0x0c, 0x70, 0x75, 0x62, 0x6c, 0x69, 0x73, 0x68, 0x65, 0x64, 0x5f, 0x61, 0x74, 0x18, 0x05,
+    0x20, 0x02, 0x28, 0x03, 0x12, 0x10, 0x0a, 0x08, 0x74, 0x74, 0x6c, 0x5f, 0x73, 0x65, 0x63, 0x73,
+    0x18, 0x06, 0x20, 0x02, 0x28, 0x04, 0x22, 0x3f, 0x0a, 0x09, 0x44, 0x65, 0x70, 0x61, 0x72, 0x74,
+    0x75, 0x72, 0x65, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12,
+    0x11, 0x0a, 0x09, 0x6d, 0x65, 0x6d, 0x62, 0x65, 0x72, 0x5f, 0x69, 0x64, 0x18, 0x02, 0x20, 0x02,
+    0x28, 0x09, 0x12, 0x13, 0x0a, 0x0b, 0x69, 0x6e, 0x63, 0x61, 0x72, 0x6e, 0x61, 0x74, 0x69, 0x6f,
+    0x6e, 0x18, 0x03, 0x20, 0x01, 0x28, 0x04, 0x22, 0xb4, 0x02, 0x0a, 0x05, 0x52, 0x75, 0x6d, 0x6f,
+    0x72, 0x12, 0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20, 0x02, 0x28, 0x09, 0x12, 0x1d, 0x0a,
+    0x04, 0x74, 0x79, 0x70, 0x65, 0x18, 0x02, 0x20, 0x02, 0x28, 0x0e, 0x32, 0x0f, 0x2e, 0x73, 0x77,
+    0x69, 0x6d, 0x2e, 0x52, 0x75, 0x6d, 0x6f, 0x72, 0x54, 0x79, 0x70, 0x65, 0x12, 0x1c, 0x0a, 0x06,
+    0x6d, 0x65, 0x6d, 0x62, 0x65, 0x72, 0x18, 0x03, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0c, 0x2e, 0x73,
+    0x77, 0x69, 0x6d, 0x2e, 0x4d, 0x65, 0x6d, 0x62, 0x65, 0x72, 0x12, 0x27, 0x0a, 0x0c, 0x63, 0x65,
+    0x6e, 0x73, 0x75, 0x73, 0x5f, 0x65, 0x6e, 0x74, 0x72, 0x79, 0x18, 0x04, 0x20, 0x01, 0x28, 0x0b,
+    0x32, 0x11, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x43, 0x65, 0x6e, 0x73, 0x75, 0x73, 0x45, 0x6e,
+    0x74, 0x72, 0x79, 0x12, 0x20, 0x0a, 0x08, 0x65, 0x6c, 0x65, 0x63, 0x74, 0x69, 0x6f, 0x6e, 0x18,
+    0x05, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0e, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x45, 0x6c, 0x65,
+    0x63, 0x74, 0x69, 0x6f, 0x6e, 0x12, 0x25, 0x0a, 0x0b, 0x67, 0x6f, 0x73, 0x73, 0x69, 0x70, 0x5f,
+    0x66, 0x69, 0x6c, 0x65, 0x18, 0x06, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x10, 0x2e, 0x73, 0x77, 0x69,
+    0x6d, 0x2e, 0x47, 0x6f, 0x73, 0x73, 0x69, 0x70, 0x46, 0x69, 0x6c, 0x65, 0x12, 0x30, 0x0a, 0x11,
+    0x72, 0x69, 0x6e, 0x67, 0x5f, 0x6b, 0x65, 0x79, 0x5f, 0x72, 0x6f, 0x74, 0x61, 0x74, 0x69, 0x6f,
+    0x6e, 0x18, 0x07, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x15, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x52,
+    0x69, 0x6e, 0x67, 0x4b, 0x65, 0x79, 0x52, 0x6f, 0x74, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x12, 0x1a,
+    0x0a, 0x05, 0x65, 0x76, 0x65, 0x6e, 0x74, 0x18, 0x08, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0b, 0x2e,
+    0x73, 0x77, 0x69, 0x6d, 0x2e, 0x45, 0x76, 0x65, 0x6e, 0x74, 0x12, 0x22, 0x0a, 0x09, 0x64, 0x65,
+    0x70, 0x61, 0x72, 0x74, 0x75, 0x72, 0x65, 0x18, 0x09, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0f, 0x2e,
+    0x73, 0x77, 0x69, 0x6d, 0x2e, 0x44, 0x65, 0x70, 0x61, 0x72, 0x74, 0x75, 0x72, 0x65, 0x22, 0x28,
+    0x0a, 0x09, 0x52, 0x75, 0x6d, 0x6f, 0x72, 0x4c, 0x69, 0x73, 0x74, 0x12, 0x1b, 0x0a, 0x06, 0x72,
+    0x75, 0x6d, 0x6f, 0x72, 0x73, 0x18, 0x01, 0x20, 0x03, 0x28, 0x0b, 0x32, 0x0b, 0x2e, 0x73, 0x77,
+    0x69, 0x6d, 0x2e, 0x52, 0x75, 0x6d, 0x6f, 0x72, 0x22, 0x5f, 0x0a, 0x04, 0x53, 0x77, 0x69, 0x6d,
+    0x12, 0x1c, 0x0a, 0x04, 0x74, 0x79, 0x70, 0x65, 0x18, 0x01, 0x20, 0x02, 0x28, 0x0e, 0x32, 0x0e,
+    0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e, 0x53, 0x77, 0x69, 0x6d, 0x54, 0x79, 0x70, 0x65, 0x12, 0x18,
+    0x0a, 0x04, 0x70, 0x65, 0x65, 0x72, 0x18, 0x02, 0x20, 0x01, 0x28, 0x0b, 0x32, 0x0a, 0x2e, 0x73,
+    0x77, 0x69, 0x6d, 0x2e, 0x50, 0x65, 0x65, 0x72, 0x12, 0x1f, 0x0a, 0x06, 0x72, 0x75, 0x6d, 0x6f,
+    0x72, 0x73, 0x18, 0x03, 0x20, 0x02, 0x28, 0x0b, 0x32, 0x0f, 0x2e, 0x73, 0x77, 0x69, 0x6d, 0x2e,
+    0x52, 0x75, 0x6d, 0x6f, 0x72, 0x4c, 0x69, 0x73, 0x74, 0x2a, 0x3d, 0x0a, 0x06, 0x48, 0x65, 0x61,
+    0x6c, 0x74, 0x68, 0x12, 0x09, 0x0a, 0x05, 0x41, 0x4c, 0x49, 0x56, 0x45, 0x10, 0x00, 0x12, 0x0b,
+    0x0a, 0x07, 0x53, 0x55, 0x53, 0x50, 0x45, 0x43, 0x54, 0x10, 0x01, 0x12, 0x0d, 0x0a, 0x09, 0x43,
+    0x4f, 0x4e, 0x46, 0x49, 0x52, 0x4d, 0x45, 0x44, 0x10, 0x02, 0x12, 0x0c, 0x0a, 0x08, 0x44, 0x45,
+    0x50, 0x41, 0x52, 0x54, 0x45, 0x44, 0x10, 0x03, 0x2a, 0x3e, 0x0a, 0x0c, 0x48, 0x65, 0x61, 0x6c,
+    0x74, 0x68, 0x53, 0x74, 0x61, 0x74, 0x75, 0x73, 0x12, 0x06, 0x0a, 0x02, 0x4f, 0x4b, 0x10, 0x00,
+    0x12, 0x0b, 0x0a, 0x07, 0x57, 0x41, 0x52, 0x4e, 0x49, 0x4e, 0x47, 0x10, 0x01, 0x12, 0x0c, 0x0a,
+    0x08, 0x43, 0x52, 0x49, 0x54, 0x49, 0x43, 0x41, 0x4c, 0x10, 0x02, 0x12, 0x0b, 0x0a, 0x07, 0x55,
+    0x4e, 0x4b, 0x4e, 0x4f, 0x57, 0x4e, 0x10, 0x03, 0x2a, 0x2b, 0x0a, 0x0e, 0x45, 0x6c, 0x65, 0x63,
+    0x74, 0x69, 0x6f, 0x6e, 0x53, 0x74, 0x61, 0x74, 0x75, 0x73, 0x12, 0x0b, 0x0a, 0x07, 0x52, 0x55,
+    0x4e, 0x4e, 0x49, 0x4e, 0x47, 0x10, 0x00, 0x12, 0x0c, 0x0a, 0x08, 0x46, 0x49, 0x4e, 0x49, 0x53,
+    0x48, 0x45, 0x44, 0x10, 0x01, 0x2a, 0x39, 0x0a, 0x0d, 0x52, 0x6f, 0x74, 0x61, 0x74, 0x69, 0x6f,
+    0x6e, 0x53, 0x74, 0x61, 0x67, 0x65, 0x12, 0x0e, 0x0a, 0x0a, 0x44, 0x49, 0x53, 0x54, 0x52, 0x49,
+    0x42, 0x55, 0x54, 0x45, 0x10, 0x00, 0x12, 0x0c, 0x0a, 0x08, 0x41, 0x43, 0x54, 0x49, 0x56, 0x41,
+    0x54, 0x45, 0x10, 0x01, 0x12, 0x0a, 0x0a, 0x06, 0x52, 0x45, 0x54, 0x49, 0x52, 0x45, 0x10, 0x02,
+    0x2a, 0x84, 0x01, 0x0a, 0x09, 0x52, 0x75, 0x6d, 0x6f, 0x72, 0x54, 0x79, 0x70, 0x65, 0x12, 0x0a,
+    0x0a, 0x06, 0x4d, 0x45, 0x4d, 0x42, 0x45, 0x52, 0x10, 0x00, 0x12, 0x10, 0x0a, 0x0c, 0x43, 0x45,
+    0x4e, 0x53, 0x55, 0x53, 0x5f, 0x45, 0x4e, 0x54, 0x52, 0x59, 0x10, 0x01, 0x12, 0x0c, 0x0a, 0x08,
+    0x45, 0x4c, 0x45, 0x43, 0x54, 0x49, 0x4f, 0x4e, 0x10, 0x02, 0x12, 0x0f, 0x0a, 0x0b, 0x47, 0x4f,
+    0x53, 0x53, 0x49, 0x50, 0x5f, 0x46, 0x49, 0x4c, 0x45, 0x10, 0x03, 0x12, 0x09, 0x0a, 0x05, 0x42,
+    0x4c, 0x41, 0x4e, 0x4b, 0x10, 0x04, 0x12, 0x15, 0x0a, 0x11, 0x52, 0x49, 0x4e, 0x47, 0x5f, 0x4b,
+    0x45, 0x59, 0x5f, 0x52, 0x4f, 0x54, 0x41, 0x54, 0x49, 0x4f, 0x4e, 0x10, 0x05, 0x12, 0x09, 0x0a,
+    0x05, 0x45, 0x56, 0x45, 0x4e, 0x54, 0x10, 0x06, 0x12, 0x0d, 0x0a, 0x09, 0x44, 0x45, 0x50, 0x41,
+    0x52, 0x54, 0x55, 0x52, 0x45, 0x10, 0x07, 0x2a, 0x36, 0x0a, 0x08, 0x53, 0x77, 0x69, 0x6d, 0x54,
+    0x79, 0x70, 0x65, 0x12, 0x08, 0x0a, 0x04, 0x50, 0x49, 0x4e, 0x47, 0x10, 0x00, 0x12, 0x07, 0x0a,
+    0x03, 0x41, 0x43, 0x4b, 0x10, 0x01, 0x12, 0x0b, 0x0a, 0x07, 0x50, 0x49, 0x4e, 0x47, 0x52, 0x45,
+    0x51, 0x10, 0x02, 0x12, 0x0a, 0x0a, 0x06, 0x49, 0x4e, 0x4a, 0x45, 0x43, 0x54, 0x10, 0x03,
+];
+
+static mut file_descriptor_proto_lazy: ::protobuf::lazy::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::lazy::Lazy {
+    lock: ::protobuf::lazy::ONCE_INIT,
+    ptr: 0 as *const ::protobuf::descriptor::FileDescriptorProto,
+};
+
+fn parse_descriptor_proto() -> ::protobuf::descriptor::FileDescriptorProto {
+    ::protobuf::parse_from_bytes(file_descriptor_proto_data).unwrap()
+}
+
+pub fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    unsafe {
+        file_descriptor_proto_lazy.get(|| {
+            parse_descriptor_proto()
+        })
+    }
+}
@@ -0,0 +1,528 @@
+// This file is generated. Do not edit
+// @generated
+
+// https://github.com/Manishearth/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy)]
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+#![allow(dead_code)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(unused_imports)]
+
+use protobuf::Message as Message_imported_for_functions;
+use protobuf::ProtobufEnum as ProtobufEnum_imported_for_functions;
+
+#[derive(Clone,Default)]
+pub struct Wire {
+    // message fields
+    format: ::std::option::Option<Format>,
+    version: ::protobuf::SingularField<::std::string::String>,
+    key: ::protobuf::SingularField<::std::string::String>,
+    nonce: ::protobuf::SingularField<::std::vec::Vec<u8>>,
+    msg_bytes: ::protobuf::SingularField<::std::vec::Vec<u8>>,
+    protocol_version: ::std::option::Option<u32>,
+    // special fields
+    unknown_fields: ::protobuf::UnknownFields,
+    cached_size: ::std::cell::Cell<u32>,
+}
+
+// see codegen.rs for the explanation why impl Sync explicitly
+unsafe impl ::std::marker::Sync for Wire {}
+
+impl Wire {
+    pub fn new() -> Wire {
+        ::std::default::Default::default()
+    }
+
+    pub fn default_instance() -> &'static Wire {
+        static mut instance: ::protobuf::lazy::Lazy<Wire> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const Wire,
+        };
+        unsafe {
+            instance.get(|| {
+                Wire {
+                    format: ::std::option::Option::None,
+                    version: ::protobuf::SingularField::none(),
+                    key: ::protobuf::SingularField::none(),
+                    nonce: ::protobuf::SingularField::none(),
+                    msg_bytes: ::protobuf::SingularField::none(),
+                    protocol_version: ::std::option::Option::None,
+                    unknown_fields: ::protobuf::UnknownFields::new(),
+                    cached_size: ::std::cell::Cell::new(0),
+                }
+            })
+        }
+    }
+
+    // required .wire.Format format = 1;
+
+    pub fn clear_format(&mut self) {
+        self.format = ::std::option::Option::None;
+    }
+
+    pub fn has_format(&self) -> bool {
+        self.format.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_format(&mut self, v: Format) {
+        self.format = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_format(&self) -> Format {
+        self.format.unwrap_or(Format::PLAIN)
+    }
+
+    // required string version = 2;
+
+    pub fn clear_version(&mut self) {
+        self.version.clear();
+    }
+
+    pub fn has_version(&self) -> bool {
+        self.version.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_version(&mut self, v: ::std::string::String) {
+        self.version = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_version(&mut self) -> &mut ::std::string::String {
+        if self.version.is_none() {
+            self.version.set_default();
+        };
+        self.version.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_version(&mut self) -> ::std::string::String {
+        self.version.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_version(&self) -> &str {
+        match self.version.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional string key = 3;
+
+    pub fn clear_key(&mut self) {
+        self.key.clear();
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_key(&mut self, v: ::std::string::String) {
+        self.key = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_key(&mut self) -> &mut ::std::string::String {
+        if self.key.is_none() {
+            self.key.set_default();
+        };
+        self.key.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_key(&mut self) -> ::std::string::String {
+        self.key.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+
+    pub fn get_key(&self) -> &str {
+        match self.key.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+
+    // optional bytes nonce = 4;
+
+    pub fn clear_nonce(&mut self) {
+        self.nonce.clear();
+    }
+
+    pub fn has_nonce(&self) -> bool {
+        self.nonce.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_nonce(&mut self, v: ::std::vec::Vec<u8>) {
+        self.nonce = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_nonce(&mut self) -> &mut ::std::vec::Vec<u8> {
+        if self.nonce.is_none() {
+            self.nonce.set_default();
+        };
+        self.nonce.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_nonce(&mut self) -> ::std::vec::Vec<u8> {
+        self.nonce.take().unwrap_or_else(|| ::std::vec::Vec::new())
+    }
+
+    pub fn get_nonce(&self) -> &[u8] {
+        match self.nonce.as_ref() {
+            Some(v) => &v,
+            None => &[],
+        }
+    }
+
+    // required bytes msg_bytes = 5;
+
+    pub fn clear_msg_bytes(&mut self) {
+        self.msg_bytes.clear();
+    }
+
+    pub fn has_msg_bytes(&self) -> bool {
+        self.msg_bytes.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_msg_bytes(&mut self, v: ::std::vec::Vec<u8>) {
+        self.msg_bytes = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_msg_bytes(&mut self) -> &mut ::std::vec::Vec<u8> {
+        if self.msg_bytes.is_none() {
+            self.msg_bytes.set_default();
+        };
+        self.msg_bytes.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_msg_bytes(&mut self) -> ::std::vec::Vec<u8> {
+        self.msg_bytes.take().unwrap_or_else(|| ::std::vec::Vec::new())
+    }
+
+    pub fn get_msg_bytes(&self) -> &[u8] {
+        match self.msg_bytes.as_ref() {
+            Some(v) => &v,
+            None => &[],
+        }
+    }
+
+    // optional uint32 protocol_version = 6;
+
+    pub fn clear_protocol_version(&mut self) {
+        self.protocol_version = ::std::option::Option::None;
+    }
+
+    pub fn has_protocol_version(&self) -> bool {
+        self.protocol_version.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_protocol_version(&mut self, v: u32) {
+        self.protocol_version = ::std::option::Option::Some(v);
+    }
+
+    pub fn get_protocol_version(&self) -> u32 {
+        self.protocol_version.unwrap_or(0)
+    }
+}
+
+impl ::protobuf::Message for Wire {
+    fn is_initialized(&self) -> bool {
+        if self.format.is_none() {
+            return false;
+        };
+        if self.version.is_none() {
+            return false;
+        };
+        if self.msg_bytes.is_none() {
+            return false;
+        };
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream) -> ::protobuf::ProtobufResult<()> {
+        while !try!(is.eof()) {
+            let (field_number, wire_type) = try!(is.read_tag_unpack());
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_enum());
+                    self.format = ::std::option::Option::Some(tmp);
+                },
+                2 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.version));
+                },
+                3 => {
+                    try!(::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.key));
+                },
+                4 => {
+                    try!(::protobuf::rt::read_singular_bytes_into(wire_type, is, &mut self.nonce));
+                },
+                5 => {
+                    try!(::protobuf::rt::read_singular_bytes_into(wire_type, is, &mut self.msg_bytes));
+                },
+                6 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    };
+                    let tmp = try!(is.read_uint32());
+                    self.protocol_version = ::std::option::Option::Some(tmp);
+                },
+                _ => {
+                    try!(::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields()));
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        for value in self.format.iter() {
+            my_size += ::protobuf::rt::enum_size(1, *value);
+        };
+        for value in self.version.iter() {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        for value in self.key.iter() {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in self.nonce.iter() {
+            my_size += ::protobuf::rt::bytes_size(4, &value);
+        };
+        for value in self.msg_bytes.iter() {
+            my_size += ::protobuf::rt::bytes_size(5, &value);
+        };
+        for value in self.protocol_version.iter() {
+            my_size += ::protobuf::rt::value_size(6, *value, ::protobuf::wire_format::WireTypeVarint);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.format {
+            try!(os.write_enum(1, v.value()));
+        };
+        if let Some(v) = self.version.as_ref() {
+            try!(os.write_string(2, &v));
+        };
+        if let Some(v) = self.key.as_ref() {
+            try!(os.write_string(3, &v));
+        };
+        if let Some(v) = self.nonce.as_ref() {
+            try!(os.write_bytes(4, &v));
+        };
+        if let Some(v) = self.msg_bytes.as_ref() {
+            try!(os.write_bytes(5, &v));
+        };
+        if let Some(v) = self.protocol_version {
+            try!(os.write_uint32(6, v));
+        };
+        try!(os.write_unknown_fields(self.get_unknown_fields()));
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn type_id(&self) -> ::std::any::TypeId {
+        ::std::any::TypeId::of::<Wire>()
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self as &::std::any::Any
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        ::protobuf::MessageStatic::descriptor_static(None::<Self>)
+    }
+}
+
+impl ::protobuf::MessageStatic for Wire {
+    fn new() -> Wire {
+        Wire::new()
+    }
+
+    fn descriptor_static(_: ::std::option::Option<Wire>) -> &'static ::protobuf::reflect::MessageDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::MessageDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                let mut fields = ::std::vec::Vec::new();
+                fields.push(::protobuf::reflect::accessor::make_singular_enum_accessor(
+                    "format",
+                    Wire::has_format,
+                    Wire::get_format,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "version",
+                    Wire::has_version,
+                    Wire::get_version,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_string_accessor(
+                    "key",
+                    Wire::has_key,
+                    Wire::get_key,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bytes_accessor(
+                    "nonce",
+                    Wire::has_nonce,
+                    Wire::get_nonce,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_bytes_accessor(
+                    "msg_bytes",
+                    Wire::has_msg_bytes,
+                    Wire::get_msg_bytes,
+                ));
+                fields.push(::protobuf::reflect::accessor::make_singular_u32_accessor(
+                    "protocol_version",
+                    Wire::has_protocol_version,
+                    Wire::get_protocol_version,
+                ));
+                ::protobuf::reflect::MessageDescriptor::new::<Wire>(
+                    "Wire",
+                    fields,
+                    file_descriptor_proto()
+                )
+            })
+        }
+    }
+}
+
+impl ::protobuf::Clear for Wire {
+    fn clear(&mut self) {
+        self.clear_format();
+        self.clear_version();
+        self.clear_key();
+        self.clear_nonce();
+        self.clear_msg_bytes();
+        self.clear_protocol_version();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::cmp::PartialEq for Wire {
+    fn eq(&self, other: &Wire) -> bool {
+        self.format == other.format &&
+        self.version == other.version &&
+        self.key == other.key &&
+        self.nonce == other.nonce &&
+        self.msg_bytes == other.msg_bytes &&
+        self.protocol_version == other.protocol_version &&
+        self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl ::std::fmt::Debug for Wire {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum Format {
+    PLAIN = 0,
+    ENCRYPTED = 1,
+}
+
+impl ::protobuf::ProtobufEnum for Format {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<Format> {
+        match value {
+            0 => ::std::option::Option::Some(Format::PLAIN),
+            1 => ::std::option::Option::Some(Format::ENCRYPTED),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [Format] = &[
+            Format::PLAIN,
+            Format::ENCRYPTED,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static(_: Option<Format>) -> &'static ::protobuf::reflect::EnumDescriptor {
+        static mut descriptor: ::protobuf::lazy::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::lazy::Lazy {
+            lock: ::protobuf::lazy::ONCE_INIT,
+            ptr: 0 as *const ::protobuf::reflect::EnumDescriptor,
+        };
+        unsafe {
+            descriptor.get(|| {
+                ::protobuf::reflect::EnumDescriptor::new("Format", file_descriptor_proto())
+            })
+        }
+    }
+}
+
+impl ::std::marker::Copy for Format {
+}
+
+static file_descriptor_proto_data: &'static [u8] = &[
+    0x0a, 0x14, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x63, 0x6f, 0x6c, 0x73, 0x2f, 0x77, 0x69, 0x72, 0x65,
+    0x2e, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x12, 0x04, 0x77, 0x69, 0x72, 0x65, 0x22, 0x7e, 0x0a, 0x04,
+    0x57, 0x69, 0x72, 0x65, 0x12, 0x1c, 0x0a, 0x06, 0x66, 0x6f, 0x72, 0x6d, 0x61, 0x74, 0x18, 0x01,
+    0x20, 0x02, 0x28, 0x0e, 0x32, 0x0c, 0x2e, 0x77, 0x69, 0x72, 0x65, 0x2e, 0x46, 0x6f, 0x72, 0x6d,
+    0x61, 0x74, 0x12, 0x0f, 0x0a, 0x07, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x18, 0x02, 0x20,
+    0x02, 0x28, 0x09, 0x12, 0x0b, 0x0a, 0x03, 0x6b, 0x65, 0x79, 0x18, 0x03, 0x20, 0x01, 0x28, 0x09,
+    0x12, 0x0d, 0x0a, 0x05, 0x6e, 0x6f, 0x6e, 0x63, 0x65, 0x18, 0x04, 0x20, 0x01, 0x28, 0x0c, 0x12,
+    0x11, 0x0a, 0x09, 0x6d, 0x73, 0x67, 0x5f, 0x62, 0x79, 0x74, 0x65, 0x73, 0x18, 0x05, 0x20, 0x02,
+    0x28, 0x0c, 0x12, 0x18, 0x0a, 0x10, 0x70, 0x72, 0x6f, 0x74, 0x6f, 0x63, 0x6f, 0x6c, 0x5f, 0x76,
+    0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x18, 0x06, 0x20, 0x01, 0x28, 0x0d, 0x2a, 0x22, 0x0a, 0x06,
+    0x46, 0x6f, 0x72, 0x6d, 0x61, 0x74, 0x12, 0x09, 0x0a, 0x05, 0x50, 0x4c, 0x41, 0x49, 0x4e, 0x10,
+    0x00, 0x12, 0x0d, 0x0a, 0x09, 0x45, 0x4e, 0x43, 0x52, 0x59, 0x50, 0x54, 0x45, 0x44, 0x10, 0x01,
+];
+
+static mut file_descriptor_proto_lazy: ::protobuf::lazy::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::lazy::Lazy {
+    lock: ::protobuf::lazy::ONCE_INIT,
+    ptr: 0 as *const ::protobuf::descriptor::FileDescriptorProto,
+};
+
+fn parse_descriptor_proto() -> ::protobuf::descriptor::FileDescriptorProto {
+    ::protobuf::parse_from_bytes(file_descriptor_proto_data).unwrap()
+}
+
+pub fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    unsafe {
+        file_descriptor_proto_lazy.get(|| {
+            parse_descriptor_proto()
+        })
+    }
+}
@@ -19,15 +19,88 @@
 //! over a UTP socket between Supervisors or from a CLI to one or more Supervisors. The message can
 //! optionally be encrypted or decrypted with a symmetric encryption key. The message itself
 //! contains extra information if encryption is used, and blank if the message is plaintext.
+//!
+//! Every message carries the version of the protocol its sender speaks, and the encoding of its
+//! contents: JSON, which every supervisor understands, or protocol buffers, which supervisors
+//! understand from protocol version 2 on. The protocol buffer messages are defined in the
+//! `protocols` directory and generated into the `message` module. A sender only uses protocol
+//! buffers once it knows the receiver understands them, so supervisors of different versions can
+//! share a ring while an upgrade rolls through it. A receiver tells the two apart by the first
+//! bytes of the message.
 
 use std::str;
 
 use hcore::crypto::SymKey;
+use hcore::service::ServiceGroup;
+use protobuf::{self, Message};
 use rustc_serialize::{Decodable, Encodable, json};
+use uuid::Uuid;
 
 use error::{Error, Result};
+use message::{swim, wire};
 
 const WIRE_VERSION: &'static str = "WIRE-1";
+/// The version of the protocol we speak. Messages from supervisors which predate versioning carry
+/// no version, and are treated as version 1.
+///
+/// * 2: messages can be binary encoded
+/// * 3: ring key rotations can be gossiped
+/// * 4: events can be gossiped
+/// * 5: members can depart the ring
+pub const PROTOCOL_VERSION: u32 = 5;
+/// The first protocol version which understands binary encoded messages.
+pub const BINARY_PROTOCOL_VERSION: u32 = 2;
+/// Binary encoded messages start with these bytes, followed by a `wire::Wire` protocol buffer;
+/// JSON encoded ones always start with `{`.
+const BINARY_MAGIC: &'static [u8] = b"HAB\x00";
+
+/// A message which can be sent as a protocol buffer.
+pub trait ProtoConvert: Sized {
+    /// The protocol buffer message it is sent as
+    type Proto: protobuf::MessageStatic;
+
+    /// Converts it to its protocol buffer message.
+    fn to_proto(&self) -> Self::Proto;
+
+    /// Converts it back from its protocol buffer message.
+    ///
+    /// # Errors
+    ///
+    /// * If the protocol buffer message does not hold a valid value
+    fn from_proto(proto: Self::Proto) -> Result<Self>;
+}
+
+impl ProtoConvert for ServiceGroup {
+    type Proto = swim::ServiceGroup;
+
+    fn to_proto(&self) -> swim::ServiceGroup {
+        let mut proto = swim::ServiceGroup::new();
+        proto.set_service(self.service.clone());
+        proto.set_group(self.group.clone());
+        if let Some(ref organization) = self.organization {
+            proto.set_organization(organization.clone());
+        }
+        proto
+    }
+
+    fn from_proto(mut proto: swim::ServiceGroup) -> Result<ServiceGroup> {
+        let organization = if proto.has_organization() {
+            Some(proto.take_organization())
+        } else {
+            None
+        };
+        Ok(ServiceGroup::new(proto.take_service(), proto.take_group(), organization))
+    }
+}
+
+/// Parses an id sent in a protocol buffer message.
+///
+/// # Errors
+///
+/// * If `id` is not a UUID
+pub fn uuid_from_proto(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|e| Error::WireDecode(format!("Invalid id {}: {}", id, e)))
+}
 
 /// The types of valid messages: currently `Plain` or `Encrypted`.
 #[derive(Debug, PartialEq, RustcEncodable, RustcDecodable)]
@@ -38,6 +111,48 @@ pub enum MessageFormat {
     Encrypted,
 }
 
+/// How a message, and its contents, are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum Encoding {
+    /// JSON, which every version of the protocol understands.
+    Json,
+    /// Protocol buffers, understood from `BINARY_PROTOCOL_VERSION` on.
+    Binary,
+}
+
+impl Encoding {
+    /// Returns the most compact encoding a peer speaking `protocol_version` understands. When we
+    /// don't know which version a peer speaks, it is JSON.
+    pub fn for_protocol(protocol_version: Option<u32>) -> Encoding {
+        match protocol_version {
+            Some(version) if version >= BINARY_PROTOCOL_VERSION => Encoding::Binary,
+            _ => Encoding::Json,
+        }
+    }
+
+    fn encode<T: Encodable + ProtoConvert>(&self, msg: &T) -> Result<Vec<u8>> {
+        match *self {
+            Encoding::Json => json_encode(msg),
+            Encoding::Binary => Ok(try!(msg.to_proto().write_to_bytes())),
+        }
+    }
+
+    fn decode<T: Decodable + ProtoConvert>(&self, bytes: &[u8]) -> Result<T> {
+        match *self {
+            Encoding::Json => json_decode(bytes),
+            Encoding::Binary => T::from_proto(try!(protobuf::parse_from_bytes(bytes))),
+        }
+    }
+}
+
+fn json_encode<T: Encodable>(msg: &T) -> Result<Vec<u8>> {
+    Ok(try!(json::encode(msg)).into_bytes())
+}
+
+fn json_decode<T: Decodable>(bytes: &[u8]) -> Result<T> {
+    Ok(try!(json::decode(try!(str::from_utf8(bytes)))))
+}
+
 /// A message to be serialized and deserialized for the purposes of communication.
 ///
 /// A message can be encrypted, denoted by the `format` field being set to
@@ -60,6 +175,11 @@ pub struct WireMessage {
     /// A byte vector containing the raw message if plaintext or the the encrypted message if
     /// encrypted.
     msg_bytes: Vec<u8>,
+    /// The version of the protocol the sender speaks; `None` if it predates versioning.
+    pub protocol_version: Option<u32>,
+    /// The encoding of the message's contents; `None` if the sender predates versioning, which
+    /// means JSON.
+    encoding: Option<Encoding>,
 }
 
 impl WireMessage {
@@ -73,7 +193,7 @@ impl WireMessage {
     /// extern crate habitat_common;
     /// extern crate rustc_serialize;
     ///
-    /// use habitat_common::wire_message::{MessageFormat, WireMessage};
+    /// use habitat_common::wire_message::{MessageFormat, PROTOCOL_VERSION, WireMessage};
     /// use rustc_serialize::{Decodable, Encodable};
     ///
     /// #[derive(RustcEncodable, RustcDecodable)]
@@ -90,6 +210,8 @@ impl WireMessage {
     ///     assert_eq!(plain.format, MessageFormat::Plain);
     ///     // The message format has a version
     ///     assert_eq!(plain.version, "WIRE-1".to_string());
+    ///     // The message carries the version of the protocol we speak
+    ///     assert_eq!(plain.protocol_version, Some(PROTOCOL_VERSION));
     /// }
     /// ```
     ///
@@ -97,12 +219,30 @@ impl WireMessage {
     ///
     /// * If the `msg` cannot be encoded into bytes
     pub fn plain<T: Encodable>(msg: &T) -> Result<WireMessage> {
+        WireMessage::plain_bytes(try!(json_encode(msg)), Encoding::Json)
+    }
+
+    /// Creates a new plaintext (unencrypted) `WireMessage`, with its contents in the given
+    /// encoding.
+    ///
+    /// # Errors
+    ///
+    /// * If the `msg` cannot be encoded into bytes
+    pub fn plain_with_encoding<T>(msg: &T, encoding: Encoding) -> Result<WireMessage>
+        where T: Encodable + ProtoConvert
+    {
+        WireMessage::plain_bytes(try!(encoding.encode(msg)), encoding)
+    }
+
+    fn plain_bytes(msg_bytes: Vec<u8>, encoding: Encoding) -> Result<WireMessage> {
         Ok(WireMessage {
             format: MessageFormat::Plain,
             version: WIRE_VERSION.to_string(),
             key: None,
             nonce: None,
-            msg_bytes: try!(json::encode(&msg)).into_bytes(),
+            msg_bytes: msg_bytes,
+            protocol_version: Some(PROTOCOL_VERSION),
+            encoding: Some(encoding),
         })
     }
 
@@ -148,16 +288,143 @@ impl WireMessage {
     /// * If the message can't be encoded to bytes
     /// * If a crypto error occurs when encrypting
     pub fn encrypted<T: Encodable>(msg: &T, sym_key: &SymKey) -> Result<WireMessage> {
-        let (nonce, ciphertext) = try!(sym_key.encrypt(try!(json::encode(&msg)).as_bytes()));
+        WireMessage::encrypted_bytes(&try!(json_encode(msg)), sym_key, Encoding::Json)
+    }
+
+    /// Creates a new encrypted `WireMessage`, with its contents in the given encoding.
+    ///
+    /// # Errors
+    ///
+    /// * If the message can't be encoded to bytes
+    /// * If a crypto error occurs when encrypting
+    pub fn encrypted_with_encoding<T>(msg: &T,
+                                      sym_key: &SymKey,
+                                      encoding: Encoding)
+                                      -> Result<WireMessage>
+        where T: Encodable + ProtoConvert
+    {
+        WireMessage::encrypted_bytes(&try!(encoding.encode(msg)), sym_key, encoding)
+    }
+
+    fn encrypted_bytes(msg_bytes: &[u8],
+                       sym_key: &SymKey,
+                       encoding: Encoding)
+                       -> Result<WireMessage> {
+        let (nonce, ciphertext) = try!(sym_key.encrypt(msg_bytes));
         Ok(WireMessage {
             format: MessageFormat::Encrypted,
             version: WIRE_VERSION.to_string(),
             key: Some(sym_key.name_with_rev()),
             nonce: Some(nonce),
             msg_bytes: ciphertext,
+            protocol_version: Some(PROTOCOL_VERSION),
+            encoding: Some(encoding),
         })
     }
 
+    /// Reads a `WireMessage` sent to us, in either encoding.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// extern crate habitat_common;
+    /// extern crate rustc_serialize;
+    ///
+    /// use habitat_common::wire_message::{Encoding, WireMessage};
+    /// use rustc_serialize::{Decodable, Encodable};
+    ///
+    /// #[derive(Debug, PartialEq, RustcEncodable, RustcDecodable)]
+    /// pub struct Person {
+    ///     pub given_name: String,
+    ///     pub surname: String,
+    /// }
+    ///
+    /// fn main() {
+    ///     let may = Person { given_name: "Brian".to_string(), surname: "May".to_string() };
+    ///     let bytes = WireMessage::plain(&may).unwrap().to_bytes().unwrap();
+    ///     let wire_msg = WireMessage::from_bytes(&bytes).unwrap();
+    ///
+    ///     // The message was read in the encoding it was sent with
+    ///     assert_eq!(wire_msg.encoding(), Encoding::Json);
+    ///     let result: Person = wire_msg.msg(None).unwrap();
+    ///     assert_eq!(result, may);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * If the bytes are not a `WireMessage` in either encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<WireMessage> {
+        if !bytes.starts_with(BINARY_MAGIC) {
+            return json_decode(bytes);
+        }
+        let mut proto: wire::Wire =
+            try!(protobuf::parse_from_bytes(&bytes[BINARY_MAGIC.len()..]));
+        let format = match proto.get_format() {
+            wire::Format::PLAIN => MessageFormat::Plain,
+            wire::Format::ENCRYPTED => MessageFormat::Encrypted,
+        };
+        Ok(WireMessage {
+            format: format,
+            version: proto.take_version(),
+            key: if proto.has_key() {
+                Some(proto.take_key())
+            } else {
+                None
+            },
+            nonce: if proto.has_nonce() {
+                Some(proto.take_nonce())
+            } else {
+                None
+            },
+            msg_bytes: proto.take_msg_bytes(),
+            protocol_version: if proto.has_protocol_version() {
+                Some(proto.get_protocol_version())
+            } else {
+                None
+            },
+            encoding: Some(Encoding::Binary),
+        })
+    }
+
+    /// Returns the bytes to send for this message. A message with JSON contents is sent as JSON,
+    /// so supervisors which predate versioning can read it.
+    ///
+    /// # Errors
+    ///
+    /// * If the message can't be encoded to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.encoding() == Encoding::Json {
+            return json_encode(self);
+        }
+        let mut proto = wire::Wire::new();
+        proto.set_format(match self.format {
+            MessageFormat::Plain => wire::Format::PLAIN,
+            MessageFormat::Encrypted => wire::Format::ENCRYPTED,
+        });
+        proto.set_version(self.version.clone());
+        if let Some(ref key) = self.key {
+            proto.set_key(key.clone());
+        }
+        if let Some(ref nonce) = self.nonce {
+            proto.set_nonce(nonce.clone());
+        }
+        proto.set_msg_bytes(self.msg_bytes.clone());
+        if let Some(protocol_version) = self.protocol_version {
+            proto.set_protocol_version(protocol_version);
+        }
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&try!(proto.write_to_bytes()));
+        Ok(bytes)
+    }
+
+    /// The encoding of the message's contents.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or(Encoding::Json)
+    }
+
     /// # Examples
     ///
     /// Basic usage:
@@ -197,12 +464,103 @@ impl WireMessage {
     /// * If the message can't be decoded from bytes
     /// * If the wire message is malformed, that is, missing fields required for encrypted messages
     /// * If a required key with revision is not present for decrypting
+    /// * If the message is plaintext, but we were given a key
+    /// * If the message is not JSON encoded
     pub fn msg<T: Decodable>(&self, sym_key: Option<&SymKey>) -> Result<T> {
+        self.msg_with_keys(sym_key)
+    }
+
+    /// Decodes the message, decrypting it with whichever of `sym_keys` it was encrypted with. A
+    /// supervisor uses this to accept messages encrypted with any revision of its ring key while
+    /// the ring key is rotated.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// extern crate habitat_common;
+    /// extern crate habitat_core;
+    /// extern crate rustc_serialize;
+    /// extern crate tempdir;
+    ///
+    /// use habitat_core::crypto::SymKey;
+    /// use habitat_common::wire_message::WireMessage;
+    /// use rustc_serialize::{Decodable, Encodable};
+    /// use tempdir::TempDir;
+    ///
+    /// #[derive(Debug, PartialEq, RustcEncodable, RustcDecodable)]
+    /// pub struct Person {
+    ///     pub given_name: String,
+    ///     pub surname: String,
+    /// }
+    ///
+    /// fn main() {
+    ///     let cache = TempDir::new("key_cache").unwrap();
+    ///     let beyonce = SymKey::generate_pair_for_ring("beyonce", cache.path()).unwrap();
+    ///     let jayz = SymKey::generate_pair_for_ring("jayz", cache.path()).unwrap();
+    ///
+    ///     let peart = Person { given_name: "Neil".to_string(), surname: "Peart".to_string() };
+    ///     let encrypted = WireMessage::encrypted(&peart, &jayz).unwrap();
+    ///     let result: Person = encrypted.msg_with_keys(&[beyonce.clone(), jayz]).unwrap();
+    ///     assert_eq!(result, peart);
+    ///
+    ///     // None of the keys opens the message
+    ///     assert!(encrypted.msg_with_keys::<Person, _>(&[beyonce]).is_err());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * If the message can't be decoded from bytes
+    /// * If the wire message is malformed, that is, missing fields required for encrypted messages
+    /// * If none of the keys is the one the message was encrypted with
+    /// * If the message is plaintext, but we were given keys
+    /// * If the message is not JSON encoded
+    pub fn msg_with_keys<'a, T, I>(&self, sym_keys: I) -> Result<T>
+        where T: Decodable,
+              I: IntoIterator<Item = &'a SymKey>
+    {
+        let msg = try!(self.open(sym_keys));
+        match self.encoding() {
+            Encoding::Json => json_decode(&msg),
+            Encoding::Binary => {
+                Err(Error::WireDecode("Message is encoded as a protocol buffer".to_string()))
+            }
+        }
+    }
+
+    /// Decodes the message in whichever encoding it was sent with, decrypting it with whichever
+    /// of `sym_keys` it was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// * If the message can't be decoded from bytes
+    /// * If the wire message is malformed, that is, missing fields required for encrypted messages
+    /// * If none of the keys is the one the message was encrypted with
+    /// * If the message is plaintext, but we were given keys
+    pub fn proto_msg_with_keys<'a, T, I>(&self, sym_keys: I) -> Result<T>
+        where T: Decodable + ProtoConvert,
+              I: IntoIterator<Item = &'a SymKey>
+    {
+        let msg = try!(self.open(sym_keys));
+        self.encoding().decode(&msg)
+    }
+
+    /// Returns the encoded contents of the message, decrypted with whichever of `sym_keys` it was
+    /// encrypted with.
+    fn open<'a, I>(&self, sym_keys: I) -> Result<Vec<u8>>
+        where I: IntoIterator<Item = &'a SymKey>
+    {
+        let sym_keys: Vec<&SymKey> = sym_keys.into_iter().collect();
         match self.format {
+            MessageFormat::Plain if sym_keys.is_empty() => Ok(self.msg_bytes.clone()),
             MessageFormat::Plain => {
-                let msg_str = try!(str::from_utf8(&self.msg_bytes));
-                let decoded: T = try!(json::decode(msg_str));
-                Ok(decoded)
+                let names: Vec<String> = sym_keys.iter().map(|k| k.name_with_rev()).collect();
+                let msg = format!("Message is not encrypted, but we only accept messages \
+                                   encrypted with key {}",
+                                  names.join(", "));
+                Err(Error::WireDecode(msg))
             }
             MessageFormat::Encrypted => {
                 let key_name_with_rev = match self.key.as_ref() {
@@ -219,25 +577,26 @@ impl WireMessage {
                             .to_string()))
                     }
                 };
-                let sym_key = match sym_key.as_ref() {
+                let sym_key = match sym_keys.iter()
+                    .find(|k| key_name_with_rev == &k.name_with_rev()) {
                     Some(k) => k,
-                    None => {
+                    None if sym_keys.is_empty() => {
                         let msg = format!("Message is encrypted with key {} but \
                                           no key is loaded to decrypt.",
                                           key_name_with_rev);
                         return Err(Error::WireDecode(msg));
                     }
+                    None => {
+                        let names: Vec<String> =
+                            sym_keys.iter().map(|k| k.name_with_rev()).collect();
+                        let msg = format!("Loaded key {} does not match message encrypted with \
+                                           key {}",
+                                          names.join(", "),
+                                          key_name_with_rev);
+                        return Err(Error::WireDecode(msg));
+                    }
                 };
-                if key_name_with_rev != &sym_key.name_with_rev() {
-                    let msg = format!("Loaded key {} does not match message encrypted with key {}",
-                                      &sym_key.name_with_rev(),
-                                      key_name_with_rev);
-                    return Err(Error::WireDecode(msg));
-                }
-                let msg = try!(sym_key.decrypt(&nonce[..], &self.msg_bytes[..]));
-                let msg_str = try!(str::from_utf8(&msg));
-                let decoded: T = try!(json::decode(msg_str));
-                Ok(decoded)
+                Ok(try!(sym_key.decrypt(&nonce[..], &self.msg_bytes[..])))
             }
         }
     }
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::cmp::{Ordering, PartialOrd};
 use std::env;
 use std::fs::{DirEntry, File};
@@ -25,6 +25,14 @@ use error::{Error, Result};
 use fs::{self, PKG_PATH};
 use package::{Identifiable, MetaFile, PackageIdent};
 
+/// A service group a package's service can be bound to, and the configuration keys it needs the
+/// members of that service group to export.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bind {
+    pub name: String,
+    pub keys: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct PackageInstall {
     ident: PackageIdent,
@@ -111,6 +119,32 @@ impl PackageInstall {
         self.read_deps(MetaFile::TDeps)
     }
 
+    /// The configuration keys the package's service exports to the services bound to it, by
+    /// the name they are exported as
+    ///
+    /// # Failures
+    ///
+    /// * The package contains an Exports metafile but it could not be read or it was malformed
+    pub fn exports(&self) -> Result<BTreeMap<String, String>> {
+        let mut exports = BTreeMap::new();
+        match self.read_metafile(MetaFile::Exports) {
+            Ok(body) => {
+                for line in body.lines().filter(|l| !l.trim().is_empty()) {
+                    let mut parts = line.splitn(2, '=').map(|p| p.trim());
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(key)) if !name.is_empty() && !key.is_empty() => {
+                            exports.insert(name.to_string(), key.to_string());
+                        }
+                        _ => return Err(Error::MetaFileMalformed(MetaFile::Exports)),
+                    }
+                }
+                Ok(exports)
+            }
+            Err(Error::MetaFileNotFound(MetaFile::Exports)) => Ok(exports),
+            Err(e) => Err(e),
+        }
+    }
+
     /// A vector of ports we expose
     pub fn exposes(&self) -> Result<Vec<String>> {
         match self.read_metafile(MetaFile::Exposes) {
@@ -128,6 +162,25 @@ impl PackageInstall {
         }
     }
 
+    /// The binds the package's service requires before it can start
+    ///
+    /// # Failures
+    ///
+    /// * The package contains a Binds metafile but it could not be read or it was malformed
+    pub fn binds(&self) -> Result<Vec<Bind>> {
+        self.read_binds(MetaFile::Binds)
+    }
+
+    /// The binds the package's service can use, but can start without
+    ///
+    /// # Failures
+    ///
+    /// * The package contains a BindsOptional metafile but it could not be read or it was
+    ///   malformed
+    pub fn binds_optional(&self) -> Result<Vec<Bind>> {
+        self.read_binds(MetaFile::BindsOptional)
+    }
+
     pub fn ident(&self) -> &PackageIdent {
         &self.ident
     }
@@ -243,6 +296,31 @@ impl PackageInstall {
         }
     }
 
+    /// Returns the name of the signal that stops the package's service
+    /// or None if the package doesn't contain a SHUTDOWN_SIGNAL Metafile
+    pub fn shutdown_signal(&self) -> Result<Option<String>> {
+        match self.read_metafile(MetaFile::ShutdownSignal) {
+            Ok(body) => Ok(Some(body)),
+            Err(Error::MetaFileNotFound(MetaFile::ShutdownSignal)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns how long, in seconds, the package's service has to stop
+    /// or None if the package doesn't contain a SHUTDOWN_TIMEOUT Metafile
+    pub fn shutdown_timeout(&self) -> Result<Option<u64>> {
+        match self.read_metafile(MetaFile::ShutdownTimeout) {
+            Ok(body) => {
+                match body.parse::<u64>() {
+                    Ok(secs) => Ok(Some(secs)),
+                    Err(_) => Err(Error::MetaFileMalformed(MetaFile::ShutdownTimeout)),
+                }
+            }
+            Err(Error::MetaFileNotFound(MetaFile::ShutdownTimeout)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Read the contents of a given metafile.
     ///
     /// # Failures
@@ -296,6 +374,30 @@ impl PackageInstall {
         }
     }
 
+    /// Reads metafiles containing binds, one per line, as the bind name and the keys it must
+    /// export separated by spaces: `database=port username password`.
+    ///
+    /// # Failures
+    ///
+    /// * Contents of the metafile could not be read
+    /// * A line of the metafile has no `=`, or no bind name
+    fn read_binds(&self, file: MetaFile) -> Result<Vec<Bind>> {
+        let mut binds = vec![];
+        match self.read_metafile(file.clone()) {
+            Ok(body) => {
+                for line in body.lines().filter(|l| !l.trim().is_empty()) {
+                    match parse_bind(line) {
+                        Some(bind) => binds.push(bind),
+                        None => return Err(Error::MetaFileMalformed(file)),
+                    }
+                }
+                Ok(binds)
+            }
+            Err(Error::MetaFileNotFound(_)) => Ok(binds),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Attempts to load the extracted package for each direct dependency and returns a
     /// `Package` struct representation of each in the returned vector.
     ///
@@ -410,3 +512,40 @@ impl PackageInstall {
         Ok(())
     }
 }
+
+fn parse_bind(line: &str) -> Option<Bind> {
+    let mut parts = line.splitn(2, '=');
+    let name = match parts.next().map(|n| n.trim()) {
+        Some(name) if !name.is_empty() => name,
+        _ => return None,
+    };
+    let keys = match parts.next() {
+        Some(keys) => keys.split_whitespace().map(|k| k.to_string()).collect(),
+        None => return None,
+    };
+    Some(Bind {
+        name: name.to_string(),
+        keys: keys,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_bind, Bind};
+
+    #[test]
+    fn parses_binds() {
+        assert_eq!(parse_bind("database=port username"),
+                   Some(Bind {
+                       name: String::from("database"),
+                       keys: vec![String::from("port"), String::from("username")],
+                   }));
+        assert_eq!(parse_bind("cache="),
+                   Some(Bind {
+                       name: String::from("cache"),
+                       keys: vec![],
+                   }));
+        assert_eq!(parse_bind("database"), None);
+        assert_eq!(parse_bind("=port"), None);
+    }
+}
@@ -19,23 +19,28 @@ pub mod plan;
 
 pub use self::archive::{FromArchive, PackageArchive};
 pub use self::ident::{Identifiable, PackageIdent};
-pub use self::install::PackageInstall;
+pub use self::install::{Bind, PackageInstall};
 pub use self::plan::Plan;
 
 use std::fmt;
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum MetaFile {
+    Binds,
+    BindsOptional,
     CFlags,
     Config,
     Deps,
     TDeps,
+    Exports,
     Exposes,
     Ident,
     LdRunPath,
     LdFlags,
     Manifest,
     Path,
+    ShutdownSignal,
+    ShutdownTimeout,
     SvcUser,
     SvcGroup,
 }
@@ -43,16 +48,21 @@ pub enum MetaFile {
 impl fmt::Display for MetaFile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let id = match *self {
+            MetaFile::Binds => "BINDS",
+            MetaFile::BindsOptional => "BINDS_OPTIONAL",
             MetaFile::CFlags => "CFLAGS",
             MetaFile::Config => "default.toml",
             MetaFile::Deps => "DEPS",
             MetaFile::TDeps => "TDEPS",
+            MetaFile::Exports => "EXPORTS",
             MetaFile::Exposes => "EXPOSES",
             MetaFile::Ident => "IDENT",
             MetaFile::LdRunPath => "LD_RUN_PATH",
             MetaFile::LdFlags => "LDFLAGS",
             MetaFile::Manifest => "MANIFEST",
             MetaFile::Path => "PATH",
+            MetaFile::ShutdownSignal => "SHUTDOWN_SIGNAL",
+            MetaFile::ShutdownTimeout => "SHUTDOWN_TIMEOUT",
             MetaFile::SvcUser => "SVC_USER",
             MetaFile::SvcGroup => "SVC_GROUP",
         };
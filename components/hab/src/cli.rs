@@ -53,6 +53,29 @@ pub fn get() -> App<'static, 'static> {
             (aliases: &["co", "con", "conf", "confi"])
             (@setting ArgRequiredElseHelp)
             (subcommand: sub_config_apply().aliases(&["a", "ap", "app", "appl"]))
+            (subcommand: sub_config_rollback().aliases(&["r", "ro", "rol", "roll"]))
+        )
+        (@subcommand event =>
+            (about: "Commands relating to Habitat events")
+            (aliases: &["e", "ev", "eve", "even"])
+            (@setting ArgRequiredElseHelp)
+            (@subcommand publish =>
+                (about: "Gossips an event to a group of Habitat Supervisors")
+                (aliases: &["p", "pu", "pub", "publ", "publi", "publis"])
+                (@arg SERVICE_GROUP: +required {valid_service_group}
+                    "Target service group (ex: redis.default)")
+                (@arg TOPIC: +required
+                    "The topic of the event; letters, digits, `.`, `_` and `-` (ex: cache)")
+                (@arg BODY: "The body of the event, up to 1024 bytes (default: <stdin>)")
+                (@arg TTL: --ttl +takes_value
+                    "Seconds until the event expires, up to 3600 (default: 300)")
+                (@arg ORG: --org +takes_value "Name of service organization")
+                (@arg PEER: -p --peer +takes_value
+                    "A comma-delimited list of one or more Habitat Supervisor peers to infect \
+                    (default: 127.0.0.1:9634)")
+                (@arg RING: -r --ring +takes_value
+                    "Ring key name, which will encrypt communication messages")
+            )
         )
         (@subcommand file =>
             (about: "Commands relating to Habitat files")
@@ -214,6 +237,18 @@ pub fn get() -> App<'static, 'static> {
             (about: "Commands relating to Habitat rings")
             (aliases: &["r", "ri", "rin"])
             (@setting ArgRequiredElseHelp)
+            (@subcommand evict =>
+                (about: "Removes a dead Habitat Supervisor from a ring")
+                (aliases: &["e", "ev", "evi", "evic"])
+                (@arg MEMBER_ID: +required
+                    "The member id of the Supervisor to remove \
+                    (ex: f0cc478e-6347-4372-807d-6a55373a7fc6)")
+                (@arg PEER: -p --peer +takes_value
+                    "A comma-delimited list of one or more Habitat Supervisor peers to infect \
+                    (default: 127.0.0.1:9634)")
+                (@arg RING: -r --ring +takes_value
+                    "Ring key name, which will encrypt communication messages")
+            )
             (@subcommand key =>
                 (about: "Commands relating to Habitat ring keys")
                 (aliases: &["k", "ke"])
@@ -233,12 +268,43 @@ pub fn get() -> App<'static, 'static> {
                     (aliases: &["g", "ge", "gen", "gene", "gener", "genera", "generat"])
                     (@arg RING: +required +takes_value "Ring key name")
                 )
+                (@subcommand rotate =>
+                    (about: "Generates a new revision of a ring key, gossips it to a ring and \
+                    retires the older revisions")
+                    (aliases: &["r", "ro", "rot", "rota", "rotat"])
+                    (@arg RING: +required +takes_value "Ring key name")
+                    (@arg PEER: -p --peer +takes_value
+                        "A comma-delimited list of one or more Habitat Supervisor peers to infect \
+                        (default: 127.0.0.1:9634)")
+                    (@arg WAIT: -w --wait +takes_value
+                        "Seconds to wait for each stage of the rotation to reach the whole ring \
+                        (default: 30)")
+                )
             )
         )
         (@subcommand service =>
             (about: "Commands relating to Habitat services")
             (aliases: &["se", "ser", "serv", "servi", "servic"])
             (@setting ArgRequiredElseHelp)
+            (@subcommand control =>
+                (about: "Starts, stops, restarts, takes down, signals or unloads a service \
+                running under a Habitat Supervisor")
+                (aliases: &["c", "co", "con", "cont", "contr", "contro"])
+                (@arg SERVICE_GROUP: +required {valid_service_group}
+                    "Target service group (ex: redis.default)")
+                (@arg ACTION: +required
+                    "One of start, stop, restart, down, signal or unload")
+                (@arg SIGNAL: "The signal to send with the signal action (ex: HUP)")
+                (@arg SIDECAR: -s --sidecar +takes_value
+                    "The Habitat Supervisor sidecar to send the request to \
+                    (default: 127.0.0.1:9631)")
+                (@arg RING: -r --ring +takes_value
+                    "Ring key name, which will seal the request")
+                (@arg ORG: --org +takes_value
+                    "Name of service organization, to seal the request for its service key")
+                (@arg USER: --user +takes_value
+                    "Name of the user key, to seal the request for the service key")
+            )
             (@subcommand key =>
                 (about: "Commands relating to Habitat service keys")
                 (aliases: &["k", "ke"])
@@ -319,6 +385,26 @@ fn sub_config_apply() -> App<'static, 'static> {
     )
 }
 
+fn sub_config_rollback() -> App<'static, 'static> {
+    clap_app!(@subcommand rollback =>
+        (about: "Gossips an earlier version of a configuration to a group of Habitat Supervisors")
+        (@arg PEER: -p --peer +takes_value
+            "A comma-delimited list of one or more Habitat Supervisor peers to infect \
+            (default: 127.0.0.1:9634)")
+        (@arg RING: -r --ring +takes_value
+            "Ring key name, which will encrypt communication messages")
+        (@arg SERVICE_GROUP: +required {valid_service_group}
+            "Target service group (ex: redis.default)")
+        (@arg VERSION_NUMBER: +required
+            "The version number of the configuration to roll back to, from the history kept by \
+            a local Supervisor running the service (ex: 42)")
+        (@arg NEW_VERSION_NUMBER: +required
+            "The version number to apply it as, which must be higher than the version the \
+            Supervisors have (ex: 44)")
+        (@arg ORG: --org +takes_value "Name of service organization")
+    )
+}
+
 fn sub_pkg_build() -> App<'static, 'static> {
     let sub = clap_app!(@subcommand build =>
         (about: "Builds a Plan using a Studio")
@@ -58,3 +58,62 @@ pub mod apply {
         Ok(())
     }
 }
+
+pub mod rollback {
+    use common::config_history::ConfigHistory;
+    use common::gossip_file::GossipFile;
+    use common::ui::{Status, UI};
+    use hcore::crypto::SymKey;
+    use hcore::service::ServiceGroup;
+
+    use error::{Error, Result};
+    use gossip::{self, hab_gossip};
+
+    /// Gossips an earlier version of a service group's configuration, read from the history kept
+    /// by the local supervisor. Supervisors only accept a configuration with a higher version
+    /// number than the one they have, so the earlier version is gossiped as `new_number`, which
+    /// must be higher than the version the ring has.
+    ///
+    /// # Failures
+    ///
+    /// * The version is not in the local history
+    /// * `new_number` is not higher than every version in the local history
+    pub fn start(ui: &mut UI,
+                 peers: &Vec<String>,
+                 ring_key: Option<&SymKey>,
+                 sg: &ServiceGroup,
+                 number: u64,
+                 new_number: u64)
+                 -> Result<()> {
+        try!(ui.begin(format!("Rolling back configuration to version {}", number)));
+        let history = ConfigHistory::for_service(&sg.service);
+        let version = try!(history.get(number));
+        // The local history may lag behind the ring, but never runs ahead of it
+        if try!(history.latest_version_number()).map_or(false, |latest| new_number <= latest) {
+            return Err(Error::ArgumentError("The new version number must be higher than every \
+                                             version in the configuration history"));
+        }
+        let file = try!(GossipFile::from_body(sg.clone(), version.body.into(), new_number));
+        let rumor = hab_gossip::Rumor::gossip_file(file);
+
+        let mut list = hab_gossip::RumorList::new();
+        list.add_rumor(rumor);
+
+        if let Some(ring_key) = ring_key {
+            try!(ui.status(Status::Encrypting,
+                           format!("communication to \"{}\" ring with {}",
+                                   &ring_key.name,
+                                   &ring_key.name_with_rev())));
+        }
+        try!(ui.status(Status::Applying,
+                       format!("version {} of the configuration for {} as version {} into \
+                                ring via {:?}",
+                               number,
+                               &sg,
+                               new_number,
+                               &peers)));
+        try!(gossip::send_rumors_to_peers(&peers, ring_key, &list));
+        try!(ui.end(format!("Rolled back configuration to version {}", number)));
+        Ok(())
+    }
+}
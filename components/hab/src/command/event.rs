@@ -0,0 +1,53 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod publish {
+    use common::event::Event;
+    use common::ui::{Status, UI};
+    use hcore::crypto::SymKey;
+    use hcore::service::ServiceGroup;
+
+    use error::Result;
+    use gossip::{self, hab_gossip};
+
+    /// Gossips an event to a service group. Supervisors running the service pass it to their
+    /// `event` hook and their `/events` endpoint until it expires, `ttl` seconds from now.
+    pub fn start(ui: &mut UI,
+                 peers: &Vec<String>,
+                 ring_key: Option<&SymKey>,
+                 sg: &ServiceGroup,
+                 topic: &str,
+                 body: String,
+                 ttl: u64)
+                 -> Result<()> {
+        try!(ui.begin(format!("Publishing {} event", topic)));
+        let event = try!(Event::new(sg.clone(), topic, body, ttl));
+        let rumor = hab_gossip::Rumor::event(event);
+
+        let mut list = hab_gossip::RumorList::new();
+        list.add_rumor(rumor);
+
+        if let Some(ring_key) = ring_key {
+            try!(ui.status(Status::Encrypting,
+                           format!("communication to \"{}\" ring with {}",
+                                   &ring_key.name,
+                                   &ring_key.name_with_rev())));
+        }
+        try!(ui.status(Status::Applying,
+                       format!("{} event for {} into ring via {:?}", topic, &sg, &peers)));
+        try!(gossip::send_rumors_to_peers(&peers, ring_key, &list));
+        try!(ui.end(format!("Published {} event", topic)));
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@
 
 pub mod cli;
 pub mod config;
+pub mod event;
 pub mod file;
 pub mod pkg;
 pub mod origin;
@@ -12,6 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod evict {
+    use common::ui::{Status, UI};
+    use hcore::crypto::SymKey;
+    use uuid::Uuid;
+
+    use error::{Error, Result};
+    use gossip::{self, hab_gossip};
+
+    /// Gossips the departure of a dead member, so every Supervisor removes it from its census and
+    /// stops trying to reach it. A member which is still alive refutes its departure.
+    pub fn start(ui: &mut UI,
+                 peers: &Vec<String>,
+                 ring_key: Option<&SymKey>,
+                 member_id: &str)
+                 -> Result<()> {
+        try!(ui.begin(format!("Evicting member {}", member_id)));
+        let member_id = match Uuid::parse_str(member_id) {
+            Ok(member_id) => member_id,
+            Err(_) => return Err(Error::ArgumentError("MEMBER_ID must be a member id")),
+        };
+        let mut list = hab_gossip::RumorList::new();
+        list.add_rumor(hab_gossip::Rumor::departure(member_id));
+
+        if let Some(ring_key) = ring_key {
+            try!(ui.status(Status::Encrypting,
+                           format!("communication to \"{}\" ring with {}",
+                                   &ring_key.name,
+                                   &ring_key.name_with_rev())));
+        }
+        try!(ui.status(Status::Applying,
+                       format!("departure of {} into ring via {:?}", member_id, &peers)));
+        try!(gossip::send_rumors_to_peers(&peers, ring_key, &list));
+        try!(ui.end(format!("Evicted member {}", member_id)));
+        Ok(())
+    }
+}
+
 pub mod key {
     pub mod export {
         use std::io;
@@ -66,4 +103,82 @@ pub mod key {
             Ok(())
         }
     }
+
+    pub mod rotate {
+        use std::fs::File;
+        use std::io::Read;
+        use std::path::Path;
+        use std::thread;
+        use std::time::Duration;
+
+        use common::ui::{Status, UI};
+        use hcore::crypto::SymKey;
+        use uuid::Uuid;
+
+        use error::Result;
+        use gossip::{self, hab_gossip};
+        use gossip::hab_gossip::RotationStage;
+
+        /// Rotates a ring to a new revision of its key. The new revision is gossiped encrypted
+        /// with the current one, and every supervisor accepts messages encrypted with either.
+        /// Once it has had `wait` seconds to reach the ring, supervisors are told to send with it,
+        /// and after another `wait` seconds, to retire the older revisions.
+        pub fn start(ui: &mut UI,
+                     peers: &Vec<String>,
+                     ring: &str,
+                     wait: u64,
+                     cache: &Path)
+                     -> Result<()> {
+            try!(ui.begin(format!("Rotating ring key for {}", ring)));
+            let current = try!(SymKey::get_latest_pair_for(ring, cache));
+            let new = try!(SymKey::generate_pair_for_ring(ring, cache));
+            try!(ui.status(Status::Creating,
+                           format!("ring key {}", &new.name_with_rev())));
+            let path = try!(SymKey::get_secret_key_path(&new.name_with_rev(), cache));
+            let mut key = String::new();
+            try!(try!(File::open(&path)).read_to_string(&mut key));
+            let mut rotation = hab_gossip::RingKeyRotation {
+                id: Uuid::new_v4(),
+                name_with_rev: new.name_with_rev(),
+                key: key,
+                stage: RotationStage::Distribute,
+            };
+
+            try!(ui.status(Status::Applying,
+                           format!("ring key {} into ring via {:?}",
+                                   &new.name_with_rev(),
+                                   &peers)));
+            try!(send(&peers, &current, &rotation));
+            try!(wait_for_ring(ui, wait));
+
+            rotation.stage = RotationStage::Activate;
+            try!(ui.status(Status::Using,
+                           format!("ring key {} to encrypt communication", &new.name_with_rev())));
+            try!(send(&peers, &current, &rotation));
+            try!(wait_for_ring(ui, wait));
+
+            rotation.stage = RotationStage::Retire;
+            try!(ui.status(Status::Deleting,
+                           format!("ring key {}", &current.name_with_rev())));
+            try!(send(&peers, &new, &rotation));
+            try!(ui.end(format!("Rotated ring key to {}.", &new.name_with_rev())));
+            Ok(())
+        }
+
+        fn send(peers: &Vec<String>,
+                ring_key: &SymKey,
+                rotation: &hab_gossip::RingKeyRotation)
+                -> Result<()> {
+            let mut list = hab_gossip::RumorList::new();
+            list.add_rumor(hab_gossip::Rumor::ring_key_rotation(rotation.clone()));
+            gossip::send_rumors_to_peers(peers, Some(ring_key), &list)
+        }
+
+        fn wait_for_ring(ui: &mut UI, wait: u64) -> Result<()> {
+            try!(ui.status(Status::Custom('⌛', "Waiting".to_string()),
+                           format!("{} seconds for the ring to catch up", wait)));
+            thread::sleep(Duration::from_secs(wait));
+            Ok(())
+        }
+    }
 }
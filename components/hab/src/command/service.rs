@@ -35,3 +35,106 @@ pub mod key {
         }
     }
 }
+
+pub mod control {
+    use std::io::Read;
+
+    use common::control::ControlRequest;
+    use common::ui::{Status, UI};
+    use hcore::crypto::{BoxKeyPair, SymKey};
+    use hcore::service::ServiceGroup;
+    use hyper::Client;
+
+    use error::{Error, Result};
+
+    /// The port a Supervisor's sidecar listens on, unless it was told otherwise
+    pub const DEFAULT_SIDECAR_PORT: u16 = 9631;
+
+    /// The key a control request is sealed with.
+    pub enum Seal<'a> {
+        /// The ring key of the Supervisor's ring
+        Ring(&'a SymKey),
+        /// A user key, for the service key of the service being controlled
+        User(&'a BoxKeyPair, &'a BoxKeyPair),
+    }
+
+    /// Asks the Supervisor whose sidecar listens on `sidecar` to start, stop, restart, take down,
+    /// signal or unload the service of `service_group`. `signal` names the signal to send with
+    /// the `signal` action.
+    ///
+    /// # Failures
+    ///
+    /// * The action is not one we know, or a signal is missing
+    /// * The request cannot be sealed with `seal`
+    /// * The sidecar cannot be reached, or refuses the request
+    pub fn start(ui: &mut UI,
+                 sidecar: &str,
+                 service_group: &ServiceGroup,
+                 action: &str,
+                 signal: Option<&str>,
+                 seal: Seal)
+                 -> Result<()> {
+        let path = try!(path_for(&service_group.service, action, signal));
+        try!(ui.begin(format!("Sending {} to {}", action, service_group)));
+        let request = ControlRequest::new(&path, "");
+        let sealed = match seal {
+            Seal::Ring(ring_key) => {
+                try!(ui.status(Status::Encrypting,
+                               format!("request with {}", &ring_key.name_with_rev())));
+                try!(request.seal_with_ring_key(ring_key))
+            }
+            Seal::User(user_pair, service_pair) => {
+                try!(ui.status(Status::Encrypting,
+                               format!("request for {} with {}",
+                                       &service_pair.name_with_rev(),
+                                       &user_pair.name_with_rev())));
+                try!(request.seal_with_user_key(user_pair, service_pair))
+            }
+        };
+        try!(ui.status(Status::Applying, format!("{} via {}", &path, sidecar)));
+        let client = Client::new();
+        let mut response = match client.post(&format!("http://{}{}", sidecar, path))
+            .body(&sealed[..])
+            .send() {
+            Ok(response) => response,
+            Err(e) => return Err(Error::ControlRequest(format!("{}", e))),
+        };
+        let mut body = String::new();
+        try!(response.read_to_string(&mut body));
+        if !response.status.is_success() {
+            return Err(Error::ControlRequest(format!("{}: {}", response.status, body.trim())));
+        }
+        try!(ui.end(format!("{} {}: {}", action, service_group, body.trim())));
+        Ok(())
+    }
+
+    fn path_for(service: &str, action: &str, signal: Option<&str>) -> Result<String> {
+        match (action, signal) {
+            ("start", None) | ("stop", None) | ("restart", None) | ("down", None) |
+            ("unload", None) => Ok(format!("/services/{}/{}", service, action)),
+            ("signal", Some(signal)) => Ok(format!("/services/{}/signal/{}", service, signal)),
+            ("signal", None) => Err(Error::ArgumentError("The signal action needs a SIGNAL")),
+            (_, Some(_)) => Err(Error::ArgumentError("Only the signal action takes a SIGNAL")),
+            _ => {
+                Err(Error::ArgumentError("ACTION must be one of start, stop, restart, down, \
+                                          signal or unload"))
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::path_for;
+
+        #[test]
+        fn path_for_actions() {
+            assert_eq!(path_for("redis", "restart", None).unwrap(),
+                       "/services/redis/restart");
+            assert_eq!(path_for("redis", "signal", Some("HUP")).unwrap(),
+                       "/services/redis/signal/HUP");
+            assert!(path_for("redis", "signal", None).is_err());
+            assert!(path_for("redis", "stop", Some("HUP")).is_err());
+            assert!(path_for("redis", "reload", None).is_err());
+        }
+    }
+}
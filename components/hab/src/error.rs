@@ -30,6 +30,7 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     ArgumentError(&'static str),
     CommandNotFoundInPkg((String, String)),
+    ControlRequest(String),
     CryptoCLI(String),
     DepotClient(depot_client::Error),
     DockerDaemonDown,
@@ -57,6 +58,7 @@ impl fmt::Display for Error {
                         c,
                         p)
             }
+            Error::ControlRequest(ref e) => format!("Control request failed: {}", e),
             Error::CryptoCLI(ref e) => format!("{}", e),
             Error::DepotClient(ref err) => format!("{}", err),
             Error::DockerDaemonDown => {
@@ -105,6 +107,7 @@ impl error::Error for Error {
             Error::CommandNotFoundInPkg(_) => {
                 "Command was not found under any 'PATH' directories in the package"
             }
+            Error::ControlRequest(_) => "The Supervisor refused or failed a control request",
             Error::CryptoCLI(_) => "A cryptographic error has occurred",
             Error::DepotClient(ref err) => err.description(),
             Error::DockerDaemonDown => "The Docker daemon could not be found.",
@@ -81,6 +81,7 @@ pub mod hab_gossip {
     use std::result;
 
     use common;
+    use common::event::Event;
     use common::gossip_file::GossipFile;
     use common::wire_message::WireMessage;
     use hcore::crypto::SymKey;
@@ -176,10 +177,10 @@ pub mod hab_gossip {
                     Some(key) => try!(WireMessage::encrypted(&msg, &key)),
                     None => try!(WireMessage::plain(&msg)),
                 };
-                try!(json::encode(&wire_msg))
+                try!(wire_msg.to_bytes())
             };
-            debug!("Encoded message {:#?}", encoded);
-            try!(self.socket.send_to(encoded.as_bytes()));
+            debug!("Encoded message as {} bytes", encoded.len());
+            try!(self.socket.send_to(&encoded));
             debug!("Sent protocol: {:?}", msg);
             Ok(())
         }
@@ -209,6 +210,33 @@ pub mod hab_gossip {
     #[derive(Debug, RustcEncodable, Clone, PartialEq, Eq)]
     pub enum Message {
         GossipFile(GossipFile),
+        RingKeyRotation(RingKeyRotation),
+        Event(Event),
+        Departure(Departure),
+    }
+
+    /// The departure of a member from the ring.
+    #[derive(Clone, Debug, RustcEncodable, PartialEq, Eq)]
+    pub struct Departure {
+        pub id: Uuid,
+        pub member_id: MemberId,
+    }
+
+    /// How far a rotation of the ring key has gone.
+    #[derive(Clone, Copy, Debug, RustcEncodable, PartialEq, Eq)]
+    pub enum RotationStage {
+        Distribute,
+        Activate,
+        Retire,
+    }
+
+    /// A rotation to a new revision of the ring key.
+    #[derive(Clone, Debug, RustcEncodable, PartialEq, Eq)]
+    pub struct RingKeyRotation {
+        pub id: Uuid,
+        pub name_with_rev: String,
+        pub key: String,
+        pub stage: RotationStage,
     }
 
     /// A UUID for Rumors. In practice, always matches the UUID of a message payload.
@@ -229,6 +257,36 @@ pub mod hab_gossip {
                 payload: Message::GossipFile(cf),
             }
         }
+
+        /// Create a new rumor with a `Message::Event` payload.
+        pub fn event(event: Event) -> Rumor {
+            Rumor {
+                id: event.id.clone(),
+                payload: Message::Event(event),
+            }
+        }
+
+        /// Create a new rumor with a `Message::Departure` payload, for a new departure of the
+        /// member.
+        pub fn departure(member_id: MemberId) -> Rumor {
+            let departure = Departure {
+                id: Uuid::new_v4(),
+                member_id: member_id,
+            };
+            Rumor {
+                id: departure.id,
+                payload: Message::Departure(departure),
+            }
+        }
+
+        /// Create a new rumor with a `Message::RingKeyRotation` payload. Every stage of a rotation
+        /// shares its id, so each stage replaces the previous one.
+        pub fn ring_key_rotation(rotation: RingKeyRotation) -> Rumor {
+            Rumor {
+                id: rotation.id,
+                payload: Message::RingKeyRotation(rotation),
+            }
+        }
     }
 
     /// A list of rumors, and their corresponding heat. Heat determines whether we need to share the
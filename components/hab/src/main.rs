@@ -30,6 +30,7 @@ use std::thread;
 
 use clap::ArgMatches;
 
+use common::event::DEFAULT_EVENT_TTL_SECS;
 use common::ui::UI;
 use hcore::env as henv;
 use hcore::crypto::{init, default_cache_key_path, BoxKeyPair, SigKeyPair, SymKey};
@@ -58,6 +59,9 @@ const DEFAULT_BINLINK_DIR: &'static str = "/bin";
 
 const MAX_FILE_UPLOAD_SIZE_BYTES: u64 = 4096;
 
+/// How long `hab ring key rotate` waits for each stage of a rotation to reach the ring
+const RING_KEY_ROTATE_WAIT_SECS: u64 = 30;
+
 fn main() {
     env_logger::init().unwrap();
     let mut ui = UI::default();
@@ -90,6 +94,13 @@ fn start(ui: &mut UI) -> Result<()> {
         ("config", Some(matches)) => {
             match matches.subcommand() {
                 ("apply", Some(m)) => try!(sub_config_apply(ui, m)),
+                ("rollback", Some(m)) => try!(sub_config_rollback(ui, m)),
+                _ => unreachable!(),
+            }
+        }
+        ("event", Some(matches)) => {
+            match matches.subcommand() {
+                ("publish", Some(m)) => try!(sub_event_publish(ui, m)),
                 _ => unreachable!(),
             }
         }
@@ -134,11 +145,13 @@ fn start(ui: &mut UI) -> Result<()> {
         }
         ("ring", Some(matches)) => {
             match matches.subcommand() {
+                ("evict", Some(m)) => try!(sub_ring_evict(ui, m)),
                 ("key", Some(m)) => {
                     match m.subcommand() {
                         ("export", Some(sc)) => try!(sub_ring_key_export(sc)),
                         ("import", Some(_)) => try!(sub_ring_key_import(ui)),
                         ("generate", Some(sc)) => try!(sub_ring_key_generate(ui, sc)),
+                        ("rotate", Some(sc)) => try!(sub_ring_key_rotate(ui, sc)),
                         _ => unreachable!(),
                     }
                 }
@@ -147,6 +160,7 @@ fn start(ui: &mut UI) -> Result<()> {
         }
         ("service", Some(matches)) => {
             match matches.subcommand() {
+                ("control", Some(m)) => try!(sub_service_control(ui, m)),
                 ("key", Some(m)) => {
                     match m.subcommand() {
                         ("generate", Some(sc)) => try!(sub_service_key_generate(ui, sc)),
@@ -186,14 +200,7 @@ fn sub_cli_setup(ui: &mut UI) -> Result<()> {
 fn sub_config_apply(ui: &mut UI, m: &ArgMatches) -> Result<()> {
     let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
     let fs_root_path = Some(Path::new(&fs_root));
-    let peers_str = m.value_of("PEER").unwrap_or("127.0.0.1");
-    let mut peers: Vec<String> = peers_str.split(",").map(|p| p.into()).collect();
-    for p in peers.iter_mut() {
-        if p.find(':').is_none() {
-            p.push(':');
-            p.push_str(&hab_gossip::GOSSIP_DEFAULT_PORT.to_string());
-        }
-    }
+    let peers = peers_from_args(m);
     let number = value_t!(m, "VERSION_NUMBER", u64).unwrap_or_else(|e| e.exit());
     let file_path = match m.value_of("FILE") {
         Some("-") | None => None,
@@ -202,10 +209,7 @@ fn sub_config_apply(ui: &mut UI, m: &ArgMatches) -> Result<()> {
 
     init();
     let cache = default_cache_key_path(fs_root_path);
-    let ring_key = match m.value_of("RING") {
-        Some(name) => Some(try!(SymKey::get_latest_pair_for(&name, &cache))),
-        None => None,
-    };
+    let ring_key = try!(ring_key_from_args(m, &cache));
 
     let mut sg = try!(ServiceGroup::from_str(m.value_of("SERVICE_GROUP").unwrap()));
 
@@ -219,18 +223,69 @@ fn sub_config_apply(ui: &mut UI, m: &ArgMatches) -> Result<()> {
     command::config::apply::start(ui, &peers, ring_key.as_ref(), &sg, number, file_path)
 }
 
-fn sub_file_upload(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+fn sub_config_rollback(ui: &mut UI, m: &ArgMatches) -> Result<()> {
     let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
     let fs_root_path = Some(Path::new(&fs_root));
+    let peers = peers_from_args(m);
+    let number = value_t!(m, "VERSION_NUMBER", u64).unwrap_or_else(|e| e.exit());
+    let new_number = value_t!(m, "NEW_VERSION_NUMBER", u64).unwrap_or_else(|e| e.exit());
+
+    init();
+    let cache = default_cache_key_path(fs_root_path);
+    let ring_key = try!(ring_key_from_args(m, &cache));
+
+    let mut sg = try!(ServiceGroup::from_str(m.value_of("SERVICE_GROUP").unwrap()));
+
+    // use the org if it's passed in on the CLI or set in an env var
+    let org = match org_param_or_env(&m) {
+        Ok(org) => Some(org.to_string()),
+        Err(_e) => None,
+    };
+    sg.organization = org;
 
-    let peers_str = m.value_of("PEER").unwrap_or("127.0.0.1");
-    let mut peers: Vec<String> = peers_str.split(",").map(|p| p.into()).collect();
-    for p in peers.iter_mut() {
-        if p.find(':').is_none() {
-            p.push(':');
-            p.push_str(&hab_gossip::GOSSIP_DEFAULT_PORT.to_string());
+    command::config::rollback::start(ui, &peers, ring_key.as_ref(), &sg, number, new_number)
+}
+
+fn sub_event_publish(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+    let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
+    let fs_root_path = Some(Path::new(&fs_root));
+    let peers = peers_from_args(m);
+    let topic = m.value_of("TOPIC").unwrap();
+    let ttl = if m.is_present("TTL") {
+        value_t!(m, "TTL", u64).unwrap_or_else(|e| e.exit())
+    } else {
+        DEFAULT_EVENT_TTL_SECS
+    };
+    let body = match m.value_of("BODY") {
+        Some("-") | None => {
+            let mut body = String::new();
+            try!(io::stdin().read_to_string(&mut body));
+            body
         }
-    }
+        Some(body) => body.to_string(),
+    };
+
+    init();
+    let cache = default_cache_key_path(fs_root_path);
+    let ring_key = try!(ring_key_from_args(m, &cache));
+
+    let mut sg = try!(ServiceGroup::from_str(m.value_of("SERVICE_GROUP").unwrap()));
+
+    // use the org if it's passed in on the CLI or set in an env var
+    let org = match org_param_or_env(&m) {
+        Ok(org) => Some(org.to_string()),
+        Err(_e) => None,
+    };
+    sg.organization = org;
+
+    command::event::publish::start(ui, &peers, ring_key.as_ref(), &sg, topic, body, ttl)
+}
+
+fn sub_file_upload(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+    let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
+    let fs_root_path = Some(Path::new(&fs_root));
+
+    let peers = peers_from_args(m);
     let number = value_t!(m, "VERSION_NUMBER", u64).unwrap_or_else(|e| e.exit());
     let file_path = Path::new(m.value_of("FILE").unwrap());
     match file_path.metadata() {
@@ -248,10 +303,7 @@ fn sub_file_upload(ui: &mut UI, m: &ArgMatches) -> Result<()> {
 
     init();
     let cache = default_cache_key_path(fs_root_path);
-    let ring_key = match m.value_of("RING") {
-        Some(name) => Some(try!(SymKey::get_latest_pair_for(&name, &cache))),
-        None => None,
-    };
+    let ring_key = try!(ring_key_from_args(m, &cache));
 
     let mut sg = try!(ServiceGroup::from_str(m.value_of("SERVICE_GROUP").unwrap()));
     // apply the organization name to the service group, either
@@ -508,6 +560,35 @@ fn sub_ring_key_generate(ui: &mut UI, m: &ArgMatches) -> Result<()> {
     command::ring::key::generate::start(ui, ring, &default_cache_key_path(fs_root_path))
 }
 
+fn sub_ring_evict(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+    let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
+    let fs_root_path = Some(Path::new(&fs_root));
+    let peers = peers_from_args(m);
+    init();
+    let ring_key = try!(ring_key_from_args(m, &default_cache_key_path(fs_root_path)));
+
+    command::ring::evict::start(ui, &peers, ring_key.as_ref(), m.value_of("MEMBER_ID").unwrap())
+}
+
+fn sub_ring_key_rotate(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+    let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
+    let fs_root_path = Some(Path::new(&fs_root));
+    let ring = m.value_of("RING").unwrap();
+    let peers = peers_from_args(m);
+    let wait = if m.is_present("WAIT") {
+        value_t!(m, "WAIT", u64).unwrap_or_else(|e| e.exit())
+    } else {
+        RING_KEY_ROTATE_WAIT_SECS
+    };
+    init();
+
+    command::ring::key::rotate::start(ui,
+                                      &peers,
+                                      ring,
+                                      wait,
+                                      &default_cache_key_path(fs_root_path))
+}
+
 fn sub_ring_key_import(ui: &mut UI) -> Result<()> {
     let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
     let fs_root_path = Some(Path::new(&fs_root));
@@ -518,6 +599,44 @@ fn sub_ring_key_import(ui: &mut UI) -> Result<()> {
     command::ring::key::import::start(ui, &content, &default_cache_key_path(fs_root_path))
 }
 
+fn sub_service_control(ui: &mut UI, m: &ArgMatches) -> Result<()> {
+    let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
+    let fs_root_path = Some(Path::new(&fs_root));
+    let sidecar = match m.value_of("SIDECAR").unwrap_or("127.0.0.1") {
+        s if s.find(':').is_none() => {
+            format!("{}:{}", s, command::service::control::DEFAULT_SIDECAR_PORT)
+        }
+        s => s.to_string(),
+    };
+    let mut sg = try!(ServiceGroup::from_str(m.value_of("SERVICE_GROUP").unwrap()));
+    let action = m.value_of("ACTION").unwrap();
+    init();
+    let cache = default_cache_key_path(fs_root_path);
+
+    // a ring key is trusted for every service; otherwise seal the request with a user key for the
+    // service key of the service group
+    if let Some(ring_key) = try!(ring_key_from_args(m, &cache)) {
+        return command::service::control::start(ui,
+                                                &sidecar,
+                                                &sg,
+                                                action,
+                                                m.value_of("SIGNAL"),
+                                                command::service::control::Seal::Ring(&ring_key));
+    }
+    sg.organization = Some(try!(org_param_or_env(&m)));
+    let service_pair = try!(BoxKeyPair::get_latest_pair_for(&sg.to_string(), &cache));
+    let user = try!(user_param_or_env(&m));
+    let user_pair = try!(BoxKeyPair::get_latest_pair_for(&user, &cache));
+
+    command::service::control::start(ui,
+                                     &sidecar,
+                                     &sg,
+                                     action,
+                                     m.value_of("SIGNAL"),
+                                     command::service::control::Seal::User(&user_pair,
+                                                                           &service_pair))
+}
+
 fn sub_service_key_generate(ui: &mut UI, m: &ArgMatches) -> Result<()> {
     let fs_root = henv::var(FS_ROOT_ENVVAR).unwrap_or(FS_ROOT_PATH.to_string());
     let fs_root_path = Some(Path::new(&fs_root));
@@ -612,6 +731,31 @@ fn origin_param_or_env(m: &ArgMatches) -> Result<String> {
     }
 }
 
+/// The supervisors given with the PEER param, or the local one if there are none. Peers without a
+/// port get the default gossip port.
+fn peers_from_args(m: &ArgMatches) -> Vec<String> {
+    m.value_of("PEER")
+        .unwrap_or("127.0.0.1")
+        .split(",")
+        .map(|p| {
+            if p.find(':').is_none() {
+                format!("{}:{}", p, hab_gossip::GOSSIP_DEFAULT_PORT)
+            } else {
+                p.to_string()
+            }
+        })
+        .collect()
+}
+
+/// The latest revision of the ring key named with the RING param, from the key cache at
+/// `cache`; `None` if no ring key is named.
+fn ring_key_from_args(m: &ArgMatches, cache: &Path) -> Result<Option<SymKey>> {
+    match m.value_of("RING") {
+        Some(name) => Ok(Some(try!(SymKey::get_latest_pair_for(name, cache)))),
+        None => Ok(None),
+    }
+}
+
 /// Check to see if the user has passed in an ORG param.
 /// If not, check the HABITAT_ORG env var. If that's
 /// empty too, then error.
@@ -177,6 +177,49 @@
 # pkg_expose=(80 443)
 # ```
 #
+# ### pkg_exports
+# An associative array of the configuration keys this service exports to the
+# services bound to it, by the name they are exported as. The supervisor
+# gossips their values, and keeps them up to date as the configuration changes.
+# ```
+# pkg_exports=(
+#   [port]=port
+#   [password]=auth.password
+# )
+# ```
+#
+# ### pkg_binds
+# An associative array of the service groups this service must be bound to
+# before it starts, with the configuration keys it needs each of them to
+# export. The supervisor waits until every one of them has a live member
+# exporting those keys.
+# ```
+# pkg_binds=(
+#   [database]="port username password"
+# )
+# ```
+#
+# ### pkg_binds_optional
+# Like `pkg_binds`, but for service groups this service can run without.
+# ```
+# pkg_binds_optional=(
+#   [cache]="port"
+# )
+# ```
+#
+# ### pkg_shutdown_signal
+# The signal the supervisor sends to stop the service. Defaults to `TERM`.
+# ```
+# pkg_shutdown_signal=QUIT
+# ```
+#
+# ### pkg_shutdown_timeout
+# How long, in seconds, the supervisor waits for the service to stop after
+# sending it `pkg_shutdown_signal`, before it kills it. Defaults to 8.
+# ```
+# pkg_shutdown_timeout=30
+# ```
+#
 # ### pkg_origin
 # A string to use for the origin. The origin is used to denote a particular upstream of a
 # package; when we resolve dependencies, we consider a version of a package to be equal
@@ -358,6 +401,16 @@ pkg_pconfig_dirs=()
 pkg_svc_run=''
 # An array of ports to expose.
 pkg_expose=()
+# The configuration keys the service exports, by the name they are exported as
+declare -A pkg_exports
+# The binds the service requires, and the keys each must export
+declare -A pkg_binds
+# The binds the service can use, and the keys each must export
+declare -A pkg_binds_optional
+# The signal to send the service to stop it
+pkg_shutdown_signal=''
+# How long, in seconds, to wait for the service to stop before killing it
+pkg_shutdown_timeout=''
 # The user to run the service as
 pkg_svc_user=hab
 # The group to run the service as
@@ -1821,10 +1874,15 @@ do_default_install() {
 # * `$pkg_prefix/PKG_CONFIG_PATH` - Any PKG_CONFIG_PATH entries for things that depend on us
 # * `$pkg_prefix/DEPS` - Any dependencies we need to use the package at runtime
 # * `$pkg_prefix/EXPOSES` - Any ports we expose
+# * `$pkg_prefix/EXPORTS` - The configuration keys we export, one `name=key` per line
+# * `$pkg_prefix/BINDS` - The binds we require, one `name=key key` per line
+# * `$pkg_prefix/BINDS_OPTIONAL` - The binds we can use, in the same format
 # * `$pkg_prefix/FILES` - blake2b checksums of all files in the package
 # * `$pkg_prefix/LDFLAGS` - Any LDFLAGS for things that link against us
 # * `$pkg_prefix/LD_RUN_PATH` - The LD_RUN_PATH for things that link against us
 # * `$pkg_prefix/PATH` - Any PATH entries for things that link against us
+# * `$pkg_prefix/SHUTDOWN_SIGNAL` - The signal that stops the service
+# * `$pkg_prefix/SHUTDOWN_TIMEOUT` - How long the service has to stop, in seconds
 _build_metadata() {
   build_line "Building package metadata"
   local ld_run_path_part=""
@@ -1935,6 +1993,19 @@ _build_metadata() {
     echo $port_part > $pkg_prefix/EXPOSES
   fi
 
+  local export
+  for export in "${!pkg_exports[@]}"; do
+    echo "${export}=${pkg_exports[$export]}" >> $pkg_prefix/EXPORTS
+  done
+
+  local bind
+  for bind in "${!pkg_binds[@]}"; do
+    echo "${bind}=${pkg_binds[$bind]}" >> $pkg_prefix/BINDS
+  done
+  for bind in "${!pkg_binds_optional[@]}"; do
+    echo "${bind}=${pkg_binds_optional[$bind]}" >> $pkg_prefix/BINDS_OPTIONAL
+  done
+
   if [[ ${#pkg_interpreters[@]} -gt 0 ]]; then
     local interpreters="$(printf "${pkg_prefix}/%s\n" ${pkg_interpreters[@]})"
     printf "%s\n" ${pkg_interpreters[@]} \
@@ -1971,6 +2042,13 @@ _build_metadata() {
   echo "$pkg_svc_user" > $pkg_prefix/SVC_USER
   echo "$pkg_svc_group" > $pkg_prefix/SVC_GROUP
 
+  if [[ -n "${pkg_shutdown_signal}" ]]; then
+    echo "$pkg_shutdown_signal" > $pkg_prefix/SHUTDOWN_SIGNAL
+  fi
+  if [[ -n "${pkg_shutdown_timeout}" ]]; then
+    echo "$pkg_shutdown_timeout" > $pkg_prefix/SHUTDOWN_TIMEOUT
+  fi
+
   # Generate the blake2b hashes of all the files in the package. This
   # is not in the resulting MANIFEST because MANIFEST is included!
   pushd "$HAB_CACHE_SRC_PATH/$pkg_dirname" > /dev/null
@@ -1986,7 +2064,7 @@ _build_metadata() {
 }
 
 # Copy the `./config` directory, relative to the Plan, to `$pkg_prefix/config`.
-# Do the same with `default.toml`. Delegates most of the implementation to the
+# Do the same with `default.toml` and `schema.toml`. Delegates most of the implementation to the
 # `do_default_build_config()` function.
 do_build_config() {
   do_default_build_config
@@ -2007,6 +2085,9 @@ do_default_build_config() {
   if [[ -f "$PLAN_CONTEXT/default.toml" ]]; then
     cp "$PLAN_CONTEXT/default.toml" $pkg_prefix
   fi
+  if [[ -f "$PLAN_CONTEXT/schema.toml" ]]; then
+    cp "$PLAN_CONTEXT/schema.toml" $pkg_prefix
+  fi
   return 0
 }
 
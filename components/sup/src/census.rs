@@ -30,6 +30,7 @@
 //! for selecting a leader deterministically for the group. We rely on the eventual consistency of
 //! every supervisors CensusEntry to elect a new leader in a reasonable amount of time.
 
+use std::cmp;
 use std::collections::{HashMap, BTreeMap};
 use std::fmt;
 use std::ops::{Deref, DerefMut};
@@ -37,12 +38,16 @@ use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::Duration;
 
+use common;
+use common::message::swim;
+use common::wire_message::{ProtoConvert, uuid_from_proto};
 use toml;
 use uuid::Uuid;
 
 use gossip::member::{MemberId, MemberList, Health};
 use gossip::lamport_clock::LamportClock;
 use error::{Error, Result};
+use health_check;
 use util;
 
 static LOGKEY: &'static str = "CN";
@@ -74,6 +79,18 @@ pub struct CensusEntry {
     pub suspect: bool,
     pub confirmed: bool,
     pub detached: bool,
+    pub package: Option<String>,
+    pub health: Option<health_check::Status>,
+    /// How many times our process has been restarted after it died; `None` if the member
+    /// predates it
+    pub restarts: Option<u64>,
+    /// How our process last ended, if it has
+    pub last_exit: Option<String>,
+    /// The package releases which failed as canaries; `None` if there are none, or the member
+    /// predates them
+    pub bad_releases: Option<Vec<String>>,
+    /// The configuration we export to the services bound to us, as TOML
+    pub cfg: Option<String>,
     pub incarnation: LamportClock,
 }
 
@@ -102,6 +119,12 @@ impl CensusEntry {
             suspect: false,
             confirmed: false,
             detached: false,
+            package: None,
+            health: None,
+            restarts: None,
+            last_exit: None,
+            bad_releases: None,
+            cfg: None,
             service: service.into(),
             group: group.into(),
             incarnation: LamportClock::new(),
@@ -182,6 +205,103 @@ impl CensusEntry {
         self.needs_write = Some(true);
     }
 
+    /// Set the identifier of the package we are running.
+    pub fn package(&mut self, package: Option<String>) {
+        if self.package != package {
+            self.package = package;
+            self.incarnation.increment();
+            self.needs_write = Some(true);
+        }
+    }
+
+    /// Set the configuration we export, as TOML.
+    pub fn cfg(&mut self, cfg: Option<String>) {
+        if self.cfg != cfg {
+            self.cfg = cfg;
+            self.incarnation.increment();
+            self.needs_write = Some(true);
+        }
+    }
+
+    /// Set the status of our latest periodic health check.
+    pub fn health(&mut self, health: Option<health_check::Status>) {
+        if self.health != health {
+            self.health = health;
+            self.incarnation.increment();
+            self.needs_write = Some(true);
+        }
+    }
+
+    /// Set how many times our process has been restarted after it died, and how it last ended.
+    pub fn process_status(&mut self, restarts: u64, last_exit: Option<String>) {
+        if self.restarts != Some(restarts) || self.last_exit != last_exit {
+            self.restarts = Some(restarts);
+            self.last_exit = last_exit;
+            self.incarnation.increment();
+            self.needs_write = Some(true);
+        }
+    }
+
+    /// Whether our latest health check passed; `None` if it was inconclusive, or we have not
+    /// checked since our last package update.
+    pub fn healthy(&self) -> Option<bool> {
+        match self.health {
+            Some(health_check::Status::Ok) => Some(true),
+            Some(health_check::Status::Critical) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns true if our latest health check was critical.
+    pub fn is_critical(&self) -> bool {
+        self.health == Some(health_check::Status::Critical)
+    }
+
+    /// Returns true if this member is alive and its latest health check was not critical, so it
+    /// can be sent traffic.
+    pub fn is_available(&self) -> bool {
+        self.alive && !self.is_critical()
+    }
+
+    /// The configuration this member exports; empty if it exports none, or we can't parse it.
+    pub fn exported_cfg(&self) -> toml::Table {
+        self.cfg
+            .as_ref()
+            .and_then(|cfg| toml::Parser::new(cfg).parse())
+            .unwrap_or(toml::Table::new())
+    }
+
+    /// Returns true if this member exports a value for a configuration key.
+    pub fn exports(&self, key: &str) -> bool {
+        toml::Value::Table(self.exported_cfg()).lookup(key).is_some()
+    }
+
+    /// This member as TOML, the way templates see it, with its exported configuration as the
+    /// `cfg` table.
+    pub fn to_toml(&self) -> toml::Value {
+        let mut value = toml::encode(self);
+        if let toml::Value::Table(ref mut table) = value {
+            table.insert(String::from("cfg"), toml::Value::Table(self.exported_cfg()));
+        }
+        value
+    }
+
+    /// Record a package release that failed as a canary, so nobody in the group retries it.
+    pub fn bad_release(&mut self, release: String) {
+        if !self.is_bad_release(&release) {
+            let mut bad_releases = self.bad_releases.take().unwrap_or(Vec::new());
+            bad_releases.push(release);
+            self.bad_releases = Some(bad_releases);
+            self.incarnation.increment();
+            self.needs_write = Some(true);
+        }
+    }
+
+    /// Returns true if this member recorded the package release as bad.
+    pub fn is_bad_release(&self, release: &str) -> bool {
+        self.bad_releases.as_ref().map_or(false, |releases| releases.iter().any(|r| r == release))
+    }
+
     /// Set us to alive.
     pub fn set_alive(&mut self) {
         self.alive = true;
@@ -222,6 +342,25 @@ impl CensusEntry {
         self.needs_write = Some(true);
     }
 
+    /// Take back our entry from before the supervisor restarted. We keep our id and whether we
+    /// have initialized data, so the group doesn't see a new member; but we are no longer the
+    /// leader or a follower, nor part of an election, until the group decides again. Our health
+    /// and package are unknown until our service reports them again, so a health check that
+    /// failed before the restart doesn't make us unavailable.
+    pub fn rejoin(&mut self) {
+        self.hostname = util::sys::hostname().unwrap_or(String::from("unknown"));
+        self.ip = util::sys::ip().map(|s| s.to_string()).unwrap_or("127.0.0.1".to_string());
+        self.leader = false;
+        self.follower = false;
+        self.vote = None;
+        self.election = None;
+        self.initialized = false;
+        self.health = None;
+        self.package = None;
+        self.keep_me = true;
+        self.set_alive();
+    }
+
     /// Return the string we use for this CensusEntry when it is a candidate in an election.
     pub fn candidate_string(&self) -> String {
         format!("{}", self.id)
@@ -279,6 +418,18 @@ impl PartialEq for CensusEntry {
             false
         } else if self.group != other.group {
             false
+        } else if self.package != other.package {
+            false
+        } else if self.health != other.health {
+            false
+        } else if self.restarts != other.restarts {
+            false
+        } else if self.last_exit != other.last_exit {
+            false
+        } else if self.bad_releases != other.bad_releases {
+            false
+        } else if self.cfg != other.cfg {
+            false
         } else {
             true
         }
@@ -291,6 +442,165 @@ impl fmt::Display for CensusEntry {
     }
 }
 
+impl ProtoConvert for CensusEntry {
+    type Proto = swim::CensusEntry;
+
+    fn to_proto(&self) -> swim::CensusEntry {
+        let mut proto = swim::CensusEntry::new();
+        proto.set_id(self.id.to_string());
+        proto.set_member_id(self.member_id.to_string());
+        proto.set_hostname(self.hostname.clone());
+        proto.set_ip(self.ip.clone());
+        proto.set_suitability(self.suitability);
+        if let Some(ref port) = self.port {
+            proto.set_port(port.clone());
+        }
+        if let Some(ref exposes) = self.exposes {
+            proto.mut_exposes().set_ports(exposes.clone().into());
+        }
+        proto.set_leader(self.leader);
+        proto.set_follower(self.follower);
+        proto.set_data_init(self.data_init);
+        if let Some(ref vote) = self.vote {
+            proto.set_vote(vote.clone());
+        }
+        if let Some(election) = self.election {
+            proto.set_election(election);
+        }
+        if let Some(needs_write) = self.needs_write {
+            proto.set_needs_write(needs_write);
+        }
+        proto.set_initialized(self.initialized);
+        proto.set_keep_me(self.keep_me);
+        proto.set_service(self.service.clone());
+        proto.set_group(self.group.clone());
+        proto.set_alive(self.alive);
+        proto.set_suspect(self.suspect);
+        proto.set_confirmed(self.confirmed);
+        proto.set_detached(self.detached);
+        if let Some(ref package) = self.package {
+            proto.set_package(package.clone());
+        }
+        if let Some(ref health) = self.health {
+            proto.set_health(match *health {
+                health_check::Status::Ok => swim::HealthStatus::OK,
+                health_check::Status::Warning => swim::HealthStatus::WARNING,
+                health_check::Status::Critical => swim::HealthStatus::CRITICAL,
+                health_check::Status::Unknown => swim::HealthStatus::UNKNOWN,
+            });
+        }
+        if let Some(restarts) = self.restarts {
+            proto.set_restarts(restarts);
+        }
+        if let Some(ref last_exit) = self.last_exit {
+            proto.set_last_exit(last_exit.clone());
+        }
+        if let Some(ref bad_releases) = self.bad_releases {
+            proto.set_bad_releases(bad_releases.clone().into());
+        }
+        if let Some(ref cfg) = self.cfg {
+            proto.set_cfg(cfg.clone());
+        }
+        proto.set_incarnation(self.incarnation.counter);
+        proto
+    }
+
+    fn from_proto(mut proto: swim::CensusEntry) -> common::Result<CensusEntry> {
+        Ok(CensusEntry {
+            id: try!(uuid_from_proto(proto.get_id())),
+            member_id: try!(uuid_from_proto(proto.get_member_id())),
+            hostname: proto.take_hostname(),
+            ip: proto.take_ip(),
+            suitability: proto.get_suitability(),
+            port: if proto.has_port() {
+                Some(proto.take_port())
+            } else {
+                None
+            },
+            exposes: if proto.has_exposes() {
+                Some(proto.take_exposes().take_ports().into_vec())
+            } else {
+                None
+            },
+            leader: proto.get_leader(),
+            follower: proto.get_follower(),
+            data_init: proto.get_data_init(),
+            vote: if proto.has_vote() {
+                Some(proto.take_vote())
+            } else {
+                None
+            },
+            election: if proto.has_election() {
+                Some(proto.get_election())
+            } else {
+                None
+            },
+            needs_write: if proto.has_needs_write() {
+                Some(proto.get_needs_write())
+            } else {
+                None
+            },
+            initialized: proto.get_initialized(),
+            keep_me: proto.get_keep_me(),
+            service: proto.take_service(),
+            group: proto.take_group(),
+            alive: proto.get_alive(),
+            suspect: proto.get_suspect(),
+            confirmed: proto.get_confirmed(),
+            detached: proto.get_detached(),
+            package: if proto.has_package() {
+                Some(proto.take_package())
+            } else {
+                None
+            },
+            health: if proto.has_health() {
+                Some(match proto.get_health() {
+                    swim::HealthStatus::OK => health_check::Status::Ok,
+                    swim::HealthStatus::WARNING => health_check::Status::Warning,
+                    swim::HealthStatus::CRITICAL => health_check::Status::Critical,
+                    swim::HealthStatus::UNKNOWN => health_check::Status::Unknown,
+                })
+            } else {
+                None
+            },
+            restarts: if proto.has_restarts() {
+                Some(proto.get_restarts())
+            } else {
+                None
+            },
+            last_exit: if proto.has_last_exit() {
+                Some(proto.take_last_exit())
+            } else {
+                None
+            },
+            bad_releases: if proto.get_bad_releases().is_empty() {
+                None
+            } else {
+                Some(proto.take_bad_releases().into_vec())
+            },
+            cfg: if proto.has_cfg() {
+                Some(proto.take_cfg())
+            } else {
+                None
+            },
+            incarnation: LamportClock { counter: proto.get_incarnation() },
+        })
+    }
+}
+
+/// Where we stand in a rolling or canary update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateTurn {
+    /// A member ahead of us has not finished updating yet
+    Wait,
+    /// Everyone ahead of us is running the new package and is healthy
+    Go,
+    /// A member running the new package failed its health check
+    Halted(CensusEntryId),
+    /// The new package has been recorded as a bad release by the group
+    Rejected,
+}
+
 /// A simple map of Census Entries; used for decoding toml data
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct CensusMap {
@@ -378,6 +688,21 @@ impl Census {
         self.population.insert(ce.id, ce);
     }
 
+    /// Remove an entry from the census. If it was the entry the census was created with, another
+    /// entry takes its place. The remaining entries need to be written again.
+    pub fn remove(&mut self, id: &CensusEntryId) -> Option<CensusEntry> {
+        let removed = self.population.remove(id);
+        if *id == self.me {
+            if let Some(next) = self.population.keys().next() {
+                self.me = next.clone();
+            }
+        }
+        for (_id, mut ce) in self.population.iter_mut() {
+            ce.needs_write = Some(true);
+        }
+        removed
+    }
+
     /// Set whether we are in an event
     pub fn in_event(&mut self, status: bool) {
         self.in_event = status;
@@ -518,6 +843,17 @@ impl Census {
         }
     }
 
+    /// Is the leader suspected of being dead? We give it the chance to come back before we
+    /// replace it.
+    pub fn leader_suspected(&self) -> bool {
+        self.population.values().any(|ce| ce.leader && ce.suspect)
+    }
+
+    /// The leader, if it has been confirmed dead, or has left the member list.
+    pub fn dead_leader(&self) -> Option<&CensusEntry> {
+        self.population.values().find(|ce| ce.leader && (ce.confirmed || ce.detached))
+    }
+
     /// Is there one leader, and everyone alive is a follower?
     pub fn has_all_followers(&self) -> bool {
         let size = self.population.values().filter(|&ce| ce.alive).count() - 1;
@@ -541,13 +877,18 @@ impl Census {
 
     /// Decide who we should vote for, and return their CensusEntry.
     ///
+    /// * Skip nodes whose health check is critical, unless every node is critical
     /// * Choose the node with the highest `suitability` number
     /// * If all those are equal, choose the node whose `id` field sorts first lexicographically
     pub fn determine_vote(&self) -> &CensusEntry {
         let acc: Option<&CensusEntry> = None;
-        let vote: &CensusEntry = self.population
+        let all_critical = self.population
             .values()
             .filter(|ce| ce.alive)
+            .all(|ce| ce.is_critical());
+        let vote: &CensusEntry = self.population
+            .values()
+            .filter(|ce| ce.alive && (all_critical || !ce.is_critical()))
             .fold(acc, |acc, ref rce| {
                 match acc {
                     Some(lce) => {
@@ -626,6 +967,88 @@ impl Census {
         self.population.get(&Uuid::parse_str(&my_vote).unwrap())
     }
 
+    /// Decide whether it is our turn to update to the `target` package in a rolling update.
+    ///
+    /// * Living members take their turn in the order their `id` sorts lexicographically
+    /// * We go once every member ahead of us runs `target` and reports healthy
+    /// * If any living member running `target` reports unhealthy, the update is halted
+    pub fn rolling_update_turn(&self, target: &str) -> UpdateTurn {
+        let alive = self.alive_by_id();
+        if let Some(turn) = self.update_blocked(&alive, target) {
+            return turn;
+        }
+
+        for ce in alive.iter().take_while(|ce| ce.id != self.me) {
+            if ce.package.as_ref().map_or(true, |p| p != target) {
+                debug!("Waiting for {} to update to {}", ce.id, target);
+                return UpdateTurn::Wait;
+            }
+            if ce.healthy() != Some(true) {
+                debug!("Waiting for {} to report healthy", ce.id);
+                return UpdateTurn::Wait;
+            }
+        }
+        UpdateTurn::Go
+    }
+
+    /// Decide whether it is our turn to update to the `target` package in a canary update.
+    ///
+    /// * The first `percent` of living members, ordered by `id`, are canaries; there is always at
+    ///   least one
+    /// * Canaries go right away
+    /// * Everyone else goes once every canary runs `target` and reports healthy
+    pub fn canary_update_turn(&self, target: &str, percent: u8) -> UpdateTurn {
+        let alive = self.alive_by_id();
+        if let Some(turn) = self.update_blocked(&alive, target) {
+            return turn;
+        }
+
+        let canaries = canary_count(alive.len(), percent);
+        if alive.iter().take(canaries).any(|ce| ce.id == self.me) {
+            return UpdateTurn::Go;
+        }
+        for ce in alive.iter().take(canaries) {
+            if ce.package.as_ref().map_or(true, |p| p != target) || ce.healthy() != Some(true) {
+                debug!("Waiting for canary {} to report healthy on {}", ce.id, target);
+                return UpdateTurn::Wait;
+            }
+        }
+        UpdateTurn::Go
+    }
+
+    /// Returns true if a member of the census we can send traffic to exports every one of the
+    /// keys, so a service bound to this census has what it needs.
+    pub fn satisfies_bind(&self, keys: &[String]) -> bool {
+        self.population
+            .values()
+            .any(|ce| ce.is_available() && keys.iter().all(|key| ce.exports(key)))
+    }
+
+    /// Has any member of the census recorded this package release as bad?
+    pub fn is_bad_release(&self, release: &str) -> bool {
+        self.population.values().any(|ce| ce.is_bad_release(release))
+    }
+
+    fn alive_by_id(&self) -> Vec<&CensusEntry> {
+        let mut alive: Vec<&CensusEntry> = self.population
+            .values()
+            .filter(|ce| ce.alive)
+            .collect();
+        alive.sort_by_key(|ce| ce.id.simple().to_string());
+        alive
+    }
+
+    fn update_blocked(&self, alive: &[&CensusEntry], target: &str) -> Option<UpdateTurn> {
+        if self.is_bad_release(target) {
+            return Some(UpdateTurn::Rejected);
+        }
+        alive.iter()
+            .find(|ce| {
+                ce.package.as_ref().map_or(false, |p| p == target) && ce.healthy() == Some(false)
+            })
+            .map(|failed| UpdateTurn::Halted(failed.id.clone()))
+    }
+
     pub fn total_population(&self) -> usize {
         self.population.len()
     }
@@ -667,6 +1090,12 @@ impl Census {
     }
 }
 
+/// How many of the `alive` members are canaries, given a `percent` of the group.
+fn canary_count(alive: usize, percent: u8) -> usize {
+    let count = ((alive * cmp::min(percent, 100) as usize) as f32 / 100.0).ceil() as usize;
+    cmp::min(cmp::max(count, 1), alive)
+}
+
 impl Deref for Census {
     type Target = HashMap<Uuid, CensusEntry>;
 
@@ -740,6 +1169,32 @@ impl CensusList {
         }
     }
 
+    /// Removes the entries of a member which has left the ring, and the censuses left empty.
+    /// Returns true if we removed any.
+    pub fn remove_member(&mut self, member_id: &MemberId) -> bool {
+        let mut removed = false;
+        for (sg, mut census) in self.censuses.iter_mut() {
+            let local = *sg == self.local_census;
+            let ids: Vec<CensusEntryId> = census.iter()
+                .filter(|&(id, ce)| ce.member_id == *member_id && !(local && *id == census.me))
+                .map(|(id, _ce)| id.clone())
+                .collect();
+            for id in ids.iter() {
+                census.remove(id);
+                removed = true;
+            }
+        }
+        let empty: Vec<String> = self.censuses
+            .iter()
+            .filter(|&(sg, census)| *sg != self.local_census && census.total_population() == 0)
+            .map(|(sg, _census)| sg.clone())
+            .collect();
+        for sg in empty.iter() {
+            self.censuses.remove(sg);
+        }
+        removed
+    }
+
     pub fn process(&mut self, mut remote_ce: CensusEntry) -> bool {
         remote_ce.needs_write = Some(true);
         if let Some(mut current_ce) = self.get_mut(&remote_ce.id, &remote_ce.service_group()) {
@@ -803,7 +1258,7 @@ pub fn start_health_adjuster(census_list: Arc<RwLock<CensusList>>,
                                         census_entry.set_suspect();
                                     }
                                 }
-                                Health::Confirmed => {
+                                Health::Confirmed | Health::Departed => {
                                     if census_entry.confirmed == false {
                                         census_entry.set_confirmed();
                                     }
@@ -817,6 +1272,24 @@ pub fn start_health_adjuster(census_list: Arc<RwLock<CensusList>>,
                     }
                 }
             }
+            // Members which have left the ring leave the census too
+            let left: Vec<MemberId> = {
+                let cl = cl1.read().unwrap();
+                let ml = ml1.read().unwrap();
+                cl.iter()
+                    .flat_map(|(_sg, census)| census.values())
+                    .filter(|ce| ce.member_id != ml.my_id && ml.has_left(&ce.member_id))
+                    .map(|ce| ce.member_id.clone())
+                    .collect()
+            };
+            if !left.is_empty() {
+                let mut cl = cl1.write().unwrap();
+                for member_id in left.iter() {
+                    if cl.remove_member(member_id) {
+                        outputln!("Removed member {} from the census", member_id);
+                    }
+                }
+            }
             thread::sleep(Duration::from_millis(1000));
         }
     });
@@ -825,8 +1298,12 @@ pub fn start_health_adjuster(census_list: Arc<RwLock<CensusList>>,
 #[cfg(test)]
 mod test {
     mod census {
+        use rustc_serialize::json::{self, Json};
+        use uuid::Uuid;
+
         use gossip::member::MemberId;
-        use census::{Census, CensusEntry};
+        use census::{Census, CensusEntry, UpdateTurn};
+        use health_check;
 
         fn generate_ce() -> CensusEntry {
             CensusEntry::new("soup", "unit", MemberId::new_v4())
@@ -865,6 +1342,29 @@ mod test {
             leader.set_confirmed();
         }
 
+        #[test]
+        fn rejoin_keeps_our_id() {
+            let mut ce = generate_ce();
+            ce.data_init(true);
+            ce.leader(true);
+            ce.vote(Some(ce.candidate_string()));
+            ce.set_confirmed();
+            ce.health(Some(health_check::Status::Critical));
+            ce.package(Some(String::from("core/redis/3.2.4/20160920131015")));
+            let before = ce.clone();
+
+            ce.rejoin();
+            assert_eq!(ce.id, before.id);
+            assert!(ce.data_init);
+            assert!(!ce.leader);
+            assert!(ce.vote.is_none());
+            assert!(ce.alive);
+            assert!(ce.incarnation > before.incarnation);
+            assert!(ce.health.is_none());
+            assert!(ce.package.is_none());
+            assert!(ce.is_available());
+        }
+
         #[test]
         fn has_quorum() {
             let mut census = generate_census();
@@ -906,5 +1406,233 @@ mod test {
             fail_the_leader(&mut census);
             assert_eq!(census.has_leader(), false);
         }
+
+        #[test]
+        fn dead_leader() {
+            let mut census = generate_census();
+            add_entries(&mut census, 2);
+            elect_an_entry(&mut census);
+            assert!(census.dead_leader().is_none());
+
+            // A suspect leader may still come back
+            {
+                let (_id, mut leader) = census.population
+                    .iter_mut()
+                    .find(|&(_id, ref ce)| ce.leader)
+                    .unwrap();
+                leader.set_suspect();
+            }
+            assert_eq!(census.has_leader(), false);
+            assert_eq!(census.leader_suspected(), true);
+            assert!(census.dead_leader().is_none());
+
+            fail_the_leader(&mut census);
+            assert_eq!(census.leader_suspected(), false);
+            assert!(census.dead_leader().is_some());
+        }
+
+        fn generate_ce_with_id(id: &str,
+                               package: &str,
+                               health: Option<health_check::Status>)
+                               -> CensusEntry {
+            let mut ce = generate_ce();
+            ce.id = Uuid::parse_str(id).unwrap();
+            ce.package(Some(package.to_string()));
+            ce.health(health);
+            ce
+        }
+
+        fn generate_rolling_census() -> Census {
+            let mut census = Census::new(generate_ce_with_id("22222222222222222222222222222222",
+                                                             "core/soup/1.0.0/1",
+                                                             Some(health_check::Status::Ok)));
+            census.add(generate_ce_with_id("11111111111111111111111111111111",
+                                           "core/soup/1.0.0/1",
+                                           Some(health_check::Status::Ok)));
+            census.add(generate_ce_with_id("33333333333333333333333333333333",
+                                           "core/soup/1.0.0/1",
+                                           Some(health_check::Status::Ok)));
+            census
+        }
+
+        #[test]
+        fn determine_vote_skips_critical_members() {
+            let mut census = generate_rolling_census();
+            let third = Uuid::parse_str("33333333333333333333333333333333").unwrap();
+            assert_eq!(census.determine_vote().id, third);
+            census.get_mut(&third).unwrap().health(Some(health_check::Status::Critical));
+            assert_eq!(census.determine_vote().id,
+                       Uuid::parse_str("22222222222222222222222222222222").unwrap());
+        }
+
+        #[test]
+        fn determine_vote_when_every_member_is_critical() {
+            let mut census = generate_rolling_census();
+            for (_id, ce) in census.iter_mut() {
+                ce.health(Some(health_check::Status::Critical));
+            }
+            assert_eq!(census.determine_vote().id,
+                       Uuid::parse_str("33333333333333333333333333333333").unwrap());
+        }
+
+        #[test]
+        fn rolling_update_waits_for_earlier_members() {
+            let census = generate_rolling_census();
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Wait);
+        }
+
+        #[test]
+        fn rolling_update_waits_for_earlier_members_to_be_healthy() {
+            let mut census = generate_rolling_census();
+            {
+                let first = census.get_mut(&Uuid::parse_str("11111111111111111111111111111111")
+                        .unwrap())
+                    .unwrap();
+                first.package(Some("core/soup/1.0.1/1".to_string()));
+                first.health(None);
+            }
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Wait);
+        }
+
+        #[test]
+        fn rolling_update_goes_when_earlier_members_are_healthy() {
+            let mut census = generate_rolling_census();
+            census.get_mut(&Uuid::parse_str("11111111111111111111111111111111").unwrap())
+                .unwrap()
+                .package(Some("core/soup/1.0.1/1".to_string()));
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Go);
+        }
+
+        #[test]
+        fn rolling_update_skips_dead_members() {
+            let mut census = generate_rolling_census();
+            census.get_mut(&Uuid::parse_str("11111111111111111111111111111111").unwrap())
+                .unwrap()
+                .set_confirmed();
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Go);
+        }
+
+        #[test]
+        fn rolling_update_halts_on_failed_health_check() {
+            let mut census = generate_rolling_census();
+            let failed_id = Uuid::parse_str("11111111111111111111111111111111").unwrap();
+            {
+                let first = census.get_mut(&failed_id).unwrap();
+                first.package(Some("core/soup/1.0.1/1".to_string()));
+                first.health(Some(health_check::Status::Critical));
+            }
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Halted(failed_id));
+        }
+
+        #[test]
+        fn canary_update_goes_for_canaries() {
+            let mut census = generate_rolling_census();
+            census.add(generate_ce_with_id("00000000000000000000000000000000",
+                                           "core/soup/1.0.0/1",
+                                           Some(health_check::Status::Ok)));
+            // Three of four members are canaries; we sort third
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 75),
+                       UpdateTurn::Go);
+            // A quarter of four members is just the first one
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 25),
+                       UpdateTurn::Wait);
+        }
+
+        #[test]
+        fn canary_update_waits_for_canaries_to_be_healthy() {
+            let mut census = generate_rolling_census();
+            let canary_id = Uuid::parse_str("11111111111111111111111111111111").unwrap();
+            {
+                let canary = census.get_mut(&canary_id).unwrap();
+                canary.package(Some("core/soup/1.0.1/1".to_string()));
+                canary.health(None);
+            }
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 10),
+                       UpdateTurn::Wait);
+            census.get_mut(&canary_id).unwrap().health(Some(health_check::Status::Ok));
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 10),
+                       UpdateTurn::Go);
+        }
+
+        #[test]
+        fn canary_update_always_has_a_canary() {
+            let census = generate_census();
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 0),
+                       UpdateTurn::Go);
+        }
+
+        #[test]
+        fn bad_releases_are_rejected() {
+            let mut census = generate_rolling_census();
+            census.get_mut(&Uuid::parse_str("33333333333333333333333333333333").unwrap())
+                .unwrap()
+                .bad_release("core/soup/1.0.1/1".to_string());
+            assert_eq!(census.is_bad_release("core/soup/1.0.1/1"), true);
+            assert_eq!(census.rolling_update_turn("core/soup/1.0.1/1"),
+                       UpdateTurn::Rejected);
+            assert_eq!(census.canary_update_turn("core/soup/1.0.1/1", 50),
+                       UpdateTurn::Rejected);
+        }
+
+        #[test]
+        fn decodes_entries_from_older_members() {
+            let mut ce = generate_ce();
+            ce.process_status(2, None);
+            ce.bad_release("core/soup/1.0.1/1".to_string());
+            // A member which predates restarts and bad releases sends neither
+            let mut old = match Json::from_str(&json::encode(&ce).unwrap()).unwrap() {
+                Json::Object(object) => object,
+                _ => panic!("A census entry is encoded as an object"),
+            };
+            old.remove("restarts");
+            old.remove("bad_releases");
+            let decoded: CensusEntry = json::decode(&Json::Object(old).to_string()).unwrap();
+            assert_eq!(decoded.id, ce.id);
+            assert_eq!(decoded.restarts, None);
+            assert_eq!(decoded.bad_releases, None);
+            assert_eq!(decoded.is_bad_release("core/soup/1.0.1/1"), false);
+        }
+
+        #[test]
+        fn binds_need_an_available_member_exporting_every_key() {
+            let mut census = generate_census();
+            let keys = vec![String::from("port"), String::from("auth.password")];
+            assert_eq!(census.satisfies_bind(&keys), false);
+            census.me_mut().cfg(Some(String::from("port = 5432\n")));
+            assert_eq!(census.satisfies_bind(&keys), false);
+            census.me_mut().cfg(Some(String::from("port = 5432\n[auth]\npassword = \"x\"\n")));
+            assert_eq!(census.satisfies_bind(&keys), true);
+            census.me_mut().health(Some(health_check::Status::Critical));
+            assert_eq!(census.satisfies_bind(&keys), false);
+        }
+    }
+
+    mod census_list {
+        use gossip::member::MemberId;
+        use census::{Census, CensusEntry, CensusList};
+
+        #[test]
+        fn remove_member() {
+            let departed = MemberId::new_v4();
+            let mut cl = CensusList::new(Census::new(CensusEntry::new("soup",
+                                                                      "unit",
+                                                                      MemberId::new_v4())));
+            let other = CensusEntry::new("soup", "unit", MemberId::new_v4());
+            cl.process(other.clone());
+            cl.process(CensusEntry::new("soup", "unit", departed.clone()));
+            cl.process(CensusEntry::new("salad", "unit", departed.clone()));
+            assert_eq!(cl.len(), 2);
+
+            assert!(cl.remove_member(&departed));
+            assert!(!cl.remove_member(&departed));
+            assert_eq!(cl.len(), 1);
+            assert_eq!(cl.local_census().total_population(), 2);
+            assert!(cl.local_census().get(&other.id).is_some());
+        }
     }
 }
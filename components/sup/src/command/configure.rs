@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Prints the configuration options for a service. Actually the `config` command.
+//! Prints the configuration of a service. Actually the `config` command.
 //!
 //! # Examples
 //!
@@ -20,27 +20,38 @@
 //! $ hab-sup config redis
 //! ```
 //!
-//! Will show the `default.toml`.
+//! Will show the configuration of `redis`, merged from its `default.toml`, its `user.toml`, the
+//! configuration gossiped to it and the `HAB_REDIS` environment variable, and which of them each
+//! key comes from.
 
-use std::io::prelude::*;
-use std::fs::File;
+use toml;
 
-use error::Result;
 use config::gconfig;
+use error::Result;
 use package::Package;
+use service_config::{env_var_name, Cfg, CfgSource};
 
-/// Print the default.toml for a given package.
+/// Print the merged configuration for a given package, preceded by where each key comes from.
 ///
 /// # Failures
 ///
 /// * If the package cannot be found
-/// * If the default.toml does not exist, or cannot be read
-/// * If we can't read the file into a string
+/// * If a layer of the configuration cannot be read, or is not valid
 pub fn display() -> Result<()> {
     let package = try!(Package::load(gconfig().package(), None));
-    let mut file = try!(File::open(package.path().join("default.toml")));
-    let mut s = String::new();
-    try!(file.read_to_string(&mut s));
-    println!("{}", s);
+    let cfg = try!(Cfg::new(&package));
+    let env_var = env_var_name(&package);
+    println!("# Each key comes from the last of these that sets it: default.toml, user.toml, \
+              gossip.toml, {}",
+             env_var);
+    println!("#");
+    for (key, source) in cfg.sources() {
+        match source {
+            CfgSource::Environment => println!("#   {}: {}", key, env_var),
+            source => println!("#   {}: {}", key, source),
+        }
+    }
+    println!("");
+    println!("{}", toml::encode_str(&cfg.to_toml()));
     Ok(())
 }
@@ -0,0 +1,216 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Renders the configuration templates and hooks of a package, without starting the service.
+//! Actually the `render` command.
+//!
+//! # Examples
+//!
+//! ```bash
+//! $ hab-sup render acme/redis
+//! ```
+//!
+//! Will print every file under `config/` and every hook of the `redis` package, as the supervisor
+//! would render them for a service alone in its census.
+//!
+//! ```bash
+//! $ hab-sup render acme/redis --user-config user.toml --census census.toml --output /tmp/redis
+//! ```
+//!
+//! Will render them with `user.toml` in place of the service's `user.toml`, with the `svc` and
+//! `bind` tables of `census.toml` in place of those built from the census, and write them to
+//! `/tmp/redis/config` and `/tmp/redis/hooks`.
+
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use toml;
+
+use census::{Census, CensusEntry, CensusList};
+use config::gconfig;
+use error::{Error, Result};
+use gossip::member::MemberId;
+use package::Package;
+use package::hooks::HookType;
+use service_config::ServiceConfig;
+
+static LOGKEY: &'static str = "RN";
+
+/// Render the templates of the configured package, printing them or writing them to the output
+/// directory.
+///
+/// # Failures
+///
+/// * If the package cannot be found
+/// * If the user config or census data cannot be read, or is not valid TOML
+/// * If the configuration does not match the package's schema
+/// * If a template cannot be rendered
+pub fn package() -> Result<()> {
+    let package = try!(Package::load(gconfig().package(), None));
+    let ce = CensusEntry::new(package.name.clone(),
+                              gconfig().group().to_string(),
+                              MemberId::new_v4());
+    let census_list = CensusList::new(Census::new(ce));
+    let mut service_config = try!(ServiceConfig::new(&package, &census_list, gconfig().bind()));
+    if let Some(path) = gconfig().user_config() {
+        let user = try!(read_toml(Path::new(path)));
+        try!(service_config.user_cfg(&package, toml::Value::Table(user)));
+    }
+    if let Some(path) = gconfig().census_data() {
+        service_config.census_data(&try!(read_toml(Path::new(path))));
+    }
+
+    let output_dir = gconfig().output_dir().map(Path::new);
+    for (dir, name, data) in try!(render(&package, &service_config)) {
+        try!(emit(output_dir, dir, &name, &data));
+    }
+    Ok(())
+}
+
+/// Renders the configuration templates and hooks of a package. Returns the directory each file
+/// belongs in, its name, and what it rendered to.
+fn render(package: &Package,
+          service_config: &ServiceConfig)
+          -> Result<Vec<(&'static str, String, String)>> {
+    let mut rendered = Vec::new();
+    for (name, data) in try!(service_config.render(package)) {
+        rendered.push(("config", name, data));
+    }
+    for hook_type in &[HookType::Init,
+                       HookType::HealthCheck,
+                       HookType::FileUpdated,
+                       HookType::Reconfigure,
+                       HookType::Run,
+                       HookType::PostRun,
+                       HookType::PreStop,
+                       HookType::PostStop,
+                       HookType::Suitability,
+                       HookType::SmokeTest,
+                       HookType::Event] {
+        let template = package.hook_template_path(hook_type);
+        if !template.is_file() {
+            continue;
+        }
+        let data = try!(service_config.render_template(&template));
+        rendered.push(("hooks", hook_type.to_string(), data));
+    }
+    Ok(rendered)
+}
+
+/// Writes a rendered template to the output directory, or prints it if there is none. What was
+/// rendered is reported on standard error, so printed templates can be piped on their own.
+fn emit(output_dir: Option<&Path>, dir: &str, name: &str, data: &str) -> Result<()> {
+    match output_dir {
+        Some(output_dir) => {
+            let dir = output_dir.join(dir);
+            try!(fs::create_dir_all(&dir));
+            let path = dir.join(name);
+            let mut file = try!(File::create(&path));
+            try!(file.write_all(data.as_bytes()));
+            try!(writeln!(&mut io::stderr(), "{}", output_format!("Rendered {}", path.display())));
+        }
+        None => {
+            try!(writeln!(&mut io::stderr(), "{}", output_format!("Rendered {}/{}", dir, name)));
+            println!("{}", data);
+        }
+    }
+    Ok(())
+}
+
+fn read_toml(path: &Path) -> Result<toml::Table> {
+    let mut file = try!(File::open(path));
+    let mut contents = String::new();
+    try!(file.read_to_string(&mut contents));
+    let mut toml_parser = toml::Parser::new(&contents);
+    toml_parser.parse().ok_or(sup_error!(Error::TomlParser(toml_parser.errors)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use hcore::package::{PackageIdent, PackageInstall};
+    use tempdir::TempDir;
+
+    use census::{Census, CensusEntry, CensusList};
+    use config::{gcache, Config};
+    use gossip::member::MemberId;
+    use package::Package;
+    use service_config::ServiceConfig;
+    use super::{emit, render};
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn read_file(path: &Path) -> String {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    fn gen_pkg(installed_path: &Path) -> Package {
+        let pkg_install = PackageInstall::new_from_parts(
+            PackageIdent::from_str("neurosis/sovereign/2000/20160222201258").unwrap(),
+            PathBuf::from("/"),
+            PathBuf::from("/fakeo"),
+            installed_path.to_path_buf());
+        Package {
+            origin: String::from("neurosis"),
+            name: String::from("sovereign"),
+            version: String::from("2000"),
+            release: String::from("20160222201258"),
+            deps: Vec::new(),
+            tdeps: Vec::new(),
+            pkg_install: pkg_install,
+        }
+    }
+
+    #[test]
+    fn renders_config_and_hooks() {
+        gcache(Config::new());
+        let pkg_dir = TempDir::new("render_pkg").unwrap();
+        write_file(&pkg_dir.path().join("default.toml"), "port = 6379\n");
+        write_file(&pkg_dir.path().join("config").join("sovereign.conf"),
+                   "port {{cfg.port}}\n");
+        write_file(&pkg_dir.path().join("hooks").join("run"),
+                   "exec {{pkg.name}} --port {{cfg.port}}\n");
+        let package = gen_pkg(pkg_dir.path());
+        let ce = CensusEntry::new("sovereign", "default", MemberId::new_v4());
+        let census_list = CensusList::new(Census::new(ce));
+        let service_config = ServiceConfig::new(&package, &census_list, Vec::new()).unwrap();
+
+        let rendered = render(&package, &service_config).unwrap();
+        assert_eq!(rendered,
+                   vec![("config", String::from("sovereign.conf"), String::from("port 6379\n")),
+                        ("hooks",
+                         String::from("run"),
+                         String::from("exec sovereign --port 6379\n"))]);
+
+        let output_dir = TempDir::new("render_output").unwrap();
+        for &(dir, ref name, ref data) in &rendered {
+            emit(Some(output_dir.path()), dir, name, data).unwrap();
+        }
+        assert_eq!(read_file(&output_dir.path().join("config").join("sovereign.conf")),
+                   "port 6379\n");
+        assert_eq!(read_file(&output_dir.path().join("hooks").join("run")),
+                   "exec sovereign --port 6379\n");
+    }
+}
@@ -53,10 +53,16 @@
 //! Will start the `redis` service using the `leader` topology in the `production` group, while
 //! watching the `haproxy` `default` group's configuration.
 //!
+//! ```bash
+//! $ hab-sup start acme/redis acme/haproxy
+//! ```
+//!
+//! Will start both the `redis` and `haproxy` services in one supervisor, sharing its gossip
+//! server and sidecar.
+//!
 //! See the [documentation on topologies](../topology) for a deeper discussion of how they function.
 //!
 
-use std::env;
 use std::path::Path;
 
 use ansi_term::Colour::Yellow;
@@ -70,13 +76,14 @@ use hcore::package::PackageIdent;
 use {PRODUCT, VERSION};
 use error::{Error, Result};
 use config::{gconfig, UpdateStrategy};
+use manager::Manager;
 use package::Package;
-use topology::{self, Topology};
 
 static LOGKEY: &'static str = "CS";
 
-/// Creates a [Package](../../pkg/struct.Package.html), then passes it to the run method of the
-/// selected [topology](../../topology).
+/// Creates a [Package](../../pkg/struct.Package.html) for each package given on the command line,
+/// then hands them to a [Manager](../../manager) which runs each with the selected
+/// [topology](../../topology).
 ///
 /// # Failures
 ///
@@ -93,7 +100,26 @@ pub fn package() -> Result<()> {
         return Err(sup_error!(Error::RootRequired));
     }
 
-    match Package::load(gconfig().package(), None) {
+    let mut packages = vec![try!(load(&mut ui, gconfig().package(), gconfig().local_artifact()))];
+    for &(ref ident, ref artifact) in gconfig().additional_packages() {
+        packages.push(try!(load(&mut ui, ident, artifact.as_ref().map(String::as_ref))));
+    }
+
+    let mut manager = try!(Manager::new());
+    for package in packages.into_iter() {
+        try!(manager.add_service(package));
+    }
+    manager.run()
+}
+
+/// Loads an installed package, installing it from the local artifact or the Depot if it is
+/// missing. When an update strategy is set, the latest release in the Depot is installed first.
+///
+/// # Failures
+///
+/// * Fails if the package is not installed and cannot be installed
+pub fn load(ui: &mut UI, ident: &PackageIdent, local_artifact: Option<&str>) -> Result<Package> {
+    match Package::load(ident, None) {
         Ok(mut package) => {
             let update_strategy = gconfig().update_strategy();
             match update_strategy {
@@ -101,21 +127,21 @@ pub fn package() -> Result<()> {
                 _ => {
                     let url = gconfig().url();
                     outputln!("Checking Depot for newer versions...");
-                    // It is important to pass `gconfig().package()` to `show_package()` instead
-                    // of the package identifier of the loaded package. This will ensure that
-                    // if the operator starts a package while specifying a version number, they
-                    // will only automaticaly receive release updates for the started package.
+                    // It is important to pass the requested package identifier to
+                    // `show_package()` instead of the package identifier of the loaded package.
+                    // This will ensure that if the operator starts a package while specifying a
+                    // version number, they will only automaticaly receive release updates for
+                    // the started package.
                     //
                     // If the operator does not specify a version number they will
                     // automatically receive updates for any releases, regardless of version
                     // number, for the started  package.
                     let depot_client = try!(Client::new(url, PRODUCT, VERSION, None));
-                    let latest_pkg_data =
-                        try!(depot_client.show_package((*gconfig().package()).clone()));
+                    let latest_pkg_data = try!(depot_client.show_package(ident.clone()));
                     let latest_ident: PackageIdent = latest_pkg_data.get_ident().clone().into();
                     if &latest_ident > package.ident() {
                         outputln!("Downloading latest version from Depot: {}", latest_ident);
-                        let new_pkg_data = try!(install::start(&mut ui,
+                        let new_pkg_data = try!(install::start(ui,
                                                                url,
                                                                &latest_ident.to_string(),
                                                                PRODUCT,
@@ -129,15 +155,14 @@ pub fn package() -> Result<()> {
                     };
                 }
             }
-            start_package(package)
+            Ok(package)
         }
         Err(_) => {
-            outputln!("{} is not installed",
-                      Yellow.bold().paint(gconfig().package().to_string()));
+            outputln!("{} is not installed", Yellow.bold().paint(ident.to_string()));
             let url = gconfig().url();
-            let new_pkg_data = match gconfig().local_artifact() {
+            let new_pkg_data = match local_artifact {
                 Some(artifact) => {
-                    try!(install::start(&mut ui,
+                    try!(install::start(ui,
                                         url,
                                         &artifact,
                                         PRODUCT,
@@ -148,11 +173,11 @@ pub fn package() -> Result<()> {
                 }
                 None => {
                     outputln!("Searching for {} in remote {}",
-                              Yellow.bold().paint(gconfig().package().to_string()),
+                              Yellow.bold().paint(ident.to_string()),
                               url);
-                    try!(install::start(&mut ui,
+                    try!(install::start(ui,
                                         url,
-                                        &gconfig().package().to_string(),
+                                        &ident.to_string(),
                                         PRODUCT,
                                         VERSION,
                                         Path::new(FS_ROOT_PATH),
@@ -160,19 +185,7 @@ pub fn package() -> Result<()> {
                                         &default_cache_key_path(None)))
                 }
             };
-            let package = try!(Package::load(&new_pkg_data, None));
-            start_package(package)
+            Package::load(&new_pkg_data, None)
         }
     }
 }
-
-fn start_package(package: Package) -> Result<()> {
-    let run_path = try!(package.run_path());
-    debug!("Setting the PATH to {}", run_path);
-    env::set_var("PATH", &run_path);
-    match *gconfig().topology() {
-        Topology::Standalone => topology::standalone::run(package),
-        Topology::Leader => topology::leader::run(package),
-        Topology::Initializer => topology::initializer::run(package),
-    }
-}
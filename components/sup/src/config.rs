@@ -20,6 +20,7 @@
 //!
 //! See the [Config](struct.Config.html) struct for the specific options available.
 
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::{Once, ONCE_INIT};
 use std::mem;
@@ -62,6 +63,7 @@ pub fn gconfig() -> &'static Config {
 /// An enum with the various CLI commands. Used to keep track of what command was called.
 pub enum Command {
     Config,
+    Render,
     Start,
     ShellBash,
     ShellSh,
@@ -71,6 +73,8 @@ pub enum Command {
 pub enum UpdateStrategy {
     None,
     AtOnce,
+    Rolling,
+    Canary,
 }
 
 impl UpdateStrategy {
@@ -78,6 +82,8 @@ impl UpdateStrategy {
         match strategy {
             "none" => UpdateStrategy::None,
             "at-once" => UpdateStrategy::AtOnce,
+            "rolling" => UpdateStrategy::Rolling,
+            "canary" => UpdateStrategy::Canary,
             s => panic!("Invalid update strategy {}", s),
         }
     }
@@ -93,6 +99,7 @@ impl FromStr for Command {
     fn from_str(s: &str) -> Result<Command, SupError> {
         match s {
             "config" => Ok(Command::Config),
+            "render" => Ok(Command::Render),
             "bash" => Ok(Command::ShellBash),
             "sh" => Ok(Command::ShellSh),
             "start" => Ok(Command::Start),
@@ -114,12 +121,14 @@ pub struct Config {
     command: Command,
     package: PackageIdent,
     local_artifact: Option<String>,
+    additional_packages: Vec<(PackageIdent, Option<String>)>,
     url: String,
     topology: Topology,
     group: String,
     path: String,
     archive: String,
     bind: Vec<String>,
+    bind_wait_secs: Option<u64>,
     key: String,
     email: Option<String>,
     expire_days: Option<u16>,
@@ -133,13 +142,28 @@ pub struct Config {
     outfile: Option<String>,
     gossip_peer: Vec<String>,
     gossip_permanent: bool,
+    reap_after_secs: u64,
     update_strategy: UpdateStrategy,
+    canary_percent: u8,
+    canary_soak_secs: u64,
+    health_check_interval_secs: u64,
+    health_check_timeout_secs: u64,
+    hook_timeout_secs: u64,
+    hook_timeouts: HashMap<String, u64>,
+    restart_backoff_max_secs: u64,
+    crash_loop_restarts: u32,
+    crash_loop_window_secs: u64,
+    log_max_bytes: u64,
+    log_retain: usize,
     service_group: String,
     file_path: String,
     version_number: u64,
     organization: Option<String>,
     ring: Option<String>,
     config_from: Option<String>,
+    user_config: Option<String>,
+    census_data: Option<String>,
+    output_dir: Option<String>,
 }
 
 impl Config {
@@ -159,6 +183,39 @@ impl Config {
         self.config_from.as_ref()
     }
 
+    /// Set the TOML file rendered templates use in place of the service's `user.toml`
+    pub fn set_user_config(&mut self, user_config: Option<String>) -> &mut Config {
+        self.user_config = user_config;
+        self
+    }
+
+    /// Return the TOML file rendered templates use in place of the service's `user.toml`
+    pub fn user_config(&self) -> Option<&String> {
+        self.user_config.as_ref()
+    }
+
+    /// Set the TOML file with the `svc` and `bind` data rendered templates use
+    pub fn set_census_data(&mut self, census_data: Option<String>) -> &mut Config {
+        self.census_data = census_data;
+        self
+    }
+
+    /// Return the TOML file with the `svc` and `bind` data rendered templates use
+    pub fn census_data(&self) -> Option<&String> {
+        self.census_data.as_ref()
+    }
+
+    /// Set the directory rendered templates are written to
+    pub fn set_output_dir(&mut self, output_dir: Option<String>) -> &mut Config {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Return the directory rendered templates are written to
+    pub fn output_dir(&self) -> Option<&String> {
+        self.output_dir.as_ref()
+    }
+
     /// Set the archive
     pub fn set_archive(&mut self, archive: String) -> &mut Config {
         self.archive = archive;
@@ -180,6 +237,131 @@ impl Config {
         self.update_strategy.clone()
     }
 
+    /// Set the percentage of the service group that takes a canary update first
+    pub fn set_canary_percent(&mut self, percent: u8) -> &mut Config {
+        self.canary_percent = percent;
+        self
+    }
+
+    /// Return the percentage of the service group that takes a canary update first
+    pub fn canary_percent(&self) -> u8 {
+        self.canary_percent
+    }
+
+    /// Set how long, in seconds, a canary must stay healthy before the rest of the group updates
+    pub fn set_canary_soak_secs(&mut self, secs: u64) -> &mut Config {
+        self.canary_soak_secs = secs;
+        self
+    }
+
+    /// Return how long, in seconds, a canary must stay healthy before the rest of the group updates
+    pub fn canary_soak_secs(&self) -> u64 {
+        self.canary_soak_secs
+    }
+
+    /// Set how often, in seconds, the health check runs; 0 never runs it
+    pub fn set_health_check_interval_secs(&mut self, secs: u64) -> &mut Config {
+        self.health_check_interval_secs = secs;
+        self
+    }
+
+    /// Return how often, in seconds, the health check runs; 0 never runs it
+    pub fn health_check_interval_secs(&self) -> u64 {
+        self.health_check_interval_secs
+    }
+
+    /// Set how long, in seconds, a health check may run before it is considered critical
+    pub fn set_health_check_timeout_secs(&mut self, secs: u64) -> &mut Config {
+        self.health_check_timeout_secs = secs;
+        self
+    }
+
+    /// Return how long, in seconds, a health check may run before it is considered critical
+    pub fn health_check_timeout_secs(&self) -> u64 {
+        self.health_check_timeout_secs
+    }
+
+    /// Set how long, in seconds, a hook may run before it is killed; 0 lets hooks run forever
+    pub fn set_hook_timeout_secs(&mut self, secs: u64) -> &mut Config {
+        self.hook_timeout_secs = secs;
+        self
+    }
+
+    /// Return how long, in seconds, a hook may run before it is killed; 0 lets hooks run forever
+    pub fn hook_timeout_secs(&self) -> u64 {
+        self.hook_timeout_secs
+    }
+
+    /// Set how long, in seconds, the named hook may run before it is killed, overriding the
+    /// timeout for every other hook
+    pub fn set_timeout_for_hook(&mut self, hook: String, secs: u64) -> &mut Config {
+        self.hook_timeouts.insert(hook, secs);
+        self
+    }
+
+    /// Return how long, in seconds, the named hook may run before it is killed; 0 lets it run
+    /// forever
+    pub fn timeout_for_hook(&self, hook: &str) -> u64 {
+        self.hook_timeouts.get(hook).map_or(self.hook_timeout_secs, |secs| *secs)
+    }
+
+    /// Set the longest, in seconds, we wait before restarting a process that died
+    pub fn set_restart_backoff_max_secs(&mut self, secs: u64) -> &mut Config {
+        self.restart_backoff_max_secs = secs;
+        self
+    }
+
+    /// Return the longest, in seconds, we wait before restarting a process that died
+    pub fn restart_backoff_max_secs(&self) -> u64 {
+        self.restart_backoff_max_secs
+    }
+
+    /// Set how many times a process may die within the crash loop window before we stop
+    /// restarting it; 0 always restarts it
+    pub fn set_crash_loop_restarts(&mut self, restarts: u32) -> &mut Config {
+        self.crash_loop_restarts = restarts;
+        self
+    }
+
+    /// Return how many times a process may die within the crash loop window before we stop
+    /// restarting it; 0 always restarts it
+    pub fn crash_loop_restarts(&self) -> u32 {
+        self.crash_loop_restarts
+    }
+
+    /// Set how far back, in seconds, we count the failures of a process
+    pub fn set_crash_loop_window_secs(&mut self, secs: u64) -> &mut Config {
+        self.crash_loop_window_secs = secs;
+        self
+    }
+
+    /// Return how far back, in seconds, we count the failures of a process
+    pub fn crash_loop_window_secs(&self) -> u64 {
+        self.crash_loop_window_secs
+    }
+
+    /// Set how large, in bytes, a service log may grow before it is rotated; 0 never rotates it
+    pub fn set_log_max_bytes(&mut self, bytes: u64) -> &mut Config {
+        self.log_max_bytes = bytes;
+        self
+    }
+
+    /// Return how large, in bytes, a service log may grow before it is rotated; 0 never rotates it
+    pub fn log_max_bytes(&self) -> u64 {
+        self.log_max_bytes
+    }
+
+    /// Set how many rotated service logs we keep
+    pub fn set_log_retain(&mut self, retain: usize) -> &mut Config {
+        self.log_retain = retain;
+        self
+    }
+
+    /// Return how many rotated service logs we keep
+    pub fn log_retain(&self) -> usize {
+        self.log_retain
+    }
+
     /// Set the `Command` we used
     pub fn set_command(&mut self, command: Command) -> &mut Config {
         self.command = command;
@@ -300,6 +482,17 @@ impl Config {
         self.bind.clone()
     }
 
+    /// Set how long to wait for required binds; `None` waits forever
+    pub fn set_bind_wait_secs(&mut self, bind_wait_secs: Option<u64>) -> &mut Config {
+        self.bind_wait_secs = bind_wait_secs;
+        self
+    }
+
+    /// Return how long to wait for required binds; `None` waits forever
+    pub fn bind_wait_secs(&self) -> Option<u64> {
+        self.bind_wait_secs
+    }
+
     /// Set the url
     pub fn set_url(&mut self, url: String) -> &mut Config {
         self.url = url;
@@ -367,6 +560,19 @@ impl Config {
         self
     }
 
+    /// Set how long, in seconds, a member stays confirmed dead or departed before we remove it
+    /// from the ring; 0 never removes it
+    pub fn set_reap_after_secs(&mut self, secs: u64) -> &mut Config {
+        self.reap_after_secs = secs;
+        self
+    }
+
+    /// Return how long, in seconds, a member stays confirmed dead or departed before we remove it
+    /// from the ring; 0 never removes it
+    pub fn reap_after_secs(&self) -> u64 {
+        self.reap_after_secs
+    }
+
     pub fn gossip_peer(&self) -> &[String] {
         &self.gossip_peer
     }
@@ -432,6 +638,16 @@ impl Config {
         self.local_artifact.as_ref().map(String::as_ref)
     }
 
+    /// Add a package to run beside the first one, optionally from a local artifact.
+    pub fn add_package(&mut self, ident: PackageIdent, artifact: Option<String>) -> &mut Config {
+        self.additional_packages.push((ident, artifact));
+        self
+    }
+
+    pub fn additional_packages(&self) -> &[(PackageIdent, Option<String>)] {
+        &self.additional_packages
+    }
+
     pub fn set_organization(&mut self, org: String) -> &mut Config {
         self.organization = Some(org);
         self
@@ -455,7 +671,11 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
-    use super::{Config, Command};
+    use std::str::FromStr;
+
+    use hcore::package::PackageIdent;
+
+    use super::{Config, Command, UpdateStrategy};
     use topology::Topology;
 
     #[test]
@@ -471,6 +691,26 @@ mod tests {
         assert_eq!(c.command(), Command::Start);
     }
 
+    #[test]
+    fn render_files() {
+        let mut c = Config::new();
+        assert_eq!(c.user_config(), None);
+        c.set_user_config(Some(String::from("user.toml")))
+            .set_census_data(Some(String::from("census.toml")))
+            .set_output_dir(Some(String::from("/tmp/rendered")));
+        assert_eq!(c.user_config(), Some(&String::from("user.toml")));
+        assert_eq!(c.census_data(), Some(&String::from("census.toml")));
+        assert_eq!(c.output_dir(), Some(&String::from("/tmp/rendered")));
+    }
+
+    #[test]
+    fn bind_wait() {
+        let mut c = Config::new();
+        assert_eq!(c.bind_wait_secs(), None);
+        c.set_bind_wait_secs(Some(120));
+        assert_eq!(c.bind_wait_secs(), Some(120));
+    }
+
     #[test]
     fn key() {
         let mut c = Config::new();
@@ -478,6 +718,18 @@ mod tests {
         assert_eq!(c.key(), "foolio");
     }
 
+    #[test]
+    fn additional_packages() {
+        let mut c = Config::new();
+        assert!(c.additional_packages().is_empty());
+        c.add_package(PackageIdent::from_str("core/redis").unwrap(), None);
+        c.add_package(PackageIdent::from_str("core/haproxy").unwrap(),
+                      Some(String::from("/tmp/core-haproxy.hart")));
+        assert_eq!(c.additional_packages().len(), 2);
+        assert_eq!(c.additional_packages()[1].1,
+                   Some(String::from("/tmp/core-haproxy.hart")));
+    }
+
     #[test]
     fn path() {
         let mut c = Config::new();
@@ -498,4 +750,66 @@ mod tests {
         c.set_topology(Topology::Leader);
         assert_eq!(*c.topology(), Topology::Leader);
     }
+
+    #[test]
+    fn update_strategy() {
+        let mut c = Config::new();
+        assert_eq!(c.update_strategy(), UpdateStrategy::None);
+        c.set_update_strategy(UpdateStrategy::from_str("rolling"));
+        assert_eq!(c.update_strategy(), UpdateStrategy::Rolling);
+        c.set_update_strategy(UpdateStrategy::from_str("canary"));
+        assert_eq!(c.update_strategy(), UpdateStrategy::Canary);
+    }
+
+    #[test]
+    fn health_check() {
+        let mut c = Config::new();
+        c.set_health_check_interval_secs(30).set_health_check_timeout_secs(5);
+        assert_eq!(c.health_check_interval_secs(), 30);
+        assert_eq!(c.health_check_timeout_secs(), 5);
+    }
+
+    #[test]
+    fn hook_timeouts() {
+        let mut c = Config::new();
+        assert_eq!(c.timeout_for_hook("init"), 0);
+        c.set_hook_timeout_secs(60).set_timeout_for_hook(String::from("init"), 600);
+        assert_eq!(c.timeout_for_hook("init"), 600);
+        assert_eq!(c.timeout_for_hook("reconfigure"), 60);
+    }
+
+    #[test]
+    fn restart_policy() {
+        let mut c = Config::new();
+        c.set_restart_backoff_max_secs(120)
+            .set_crash_loop_restarts(3)
+            .set_crash_loop_window_secs(600);
+        assert_eq!(c.restart_backoff_max_secs(), 120);
+        assert_eq!(c.crash_loop_restarts(), 3);
+        assert_eq!(c.crash_loop_window_secs(), 600);
+    }
+
+    #[test]
+    fn reap_after() {
+        let mut c = Config::new();
+        assert_eq!(c.reap_after_secs(), 0);
+        c.set_reap_after_secs(3600);
+        assert_eq!(c.reap_after_secs(), 3600);
+    }
+
+    #[test]
+    fn log_rotation() {
+        let mut c = Config::new();
+        c.set_log_max_bytes(1024).set_log_retain(2);
+        assert_eq!(c.log_max_bytes(), 1024);
+        assert_eq!(c.log_retain(), 2);
+    }
+
+    #[test]
+    fn canary() {
+        let mut c = Config::new();
+        c.set_canary_percent(25).set_canary_soak_secs(600);
+        assert_eq!(c.canary_percent(), 25);
+        assert_eq!(c.canary_soak_secs(), 600);
+    }
 }
@@ -0,0 +1,304 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Schemas for the configuration of a package.
+//!
+//! A package can ship a `schema.toml` next to its `default.toml`. Every table in the schema
+//! describes one configuration key; nested keys are named with dots:
+//!
+//! ```toml
+//! [port]
+//! type = "integer"
+//! required = true
+//! min = 1
+//! max = 65535
+//!
+//! [loglevel]
+//! type = "string"
+//! enum = ["debug", "info", "warning"]
+//!
+//! ["tls.enabled"]
+//! type = "boolean"
+//! ```
+//!
+//! `type` is one of `string`, `integer`, `float`, `boolean`, `array` or `table`; an integer is
+//! also a valid float. `min` and `max` bound numbers. Every field of a rule is optional, and keys
+//! the schema does not mention are not checked.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use toml;
+
+use error::{Error, Result, SupError};
+
+static LOGKEY: &'static str = "SM";
+/// The file name of the schema, next to `default.toml`
+pub const SCHEMA_FILE: &'static str = "schema.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    Table,
+}
+
+impl Kind {
+    fn from_str(kind: &str) -> Option<Kind> {
+        match kind {
+            "string" => Some(Kind::String),
+            "integer" => Some(Kind::Integer),
+            "float" => Some(Kind::Float),
+            "boolean" => Some(Kind::Boolean),
+            "array" => Some(Kind::Array),
+            "table" => Some(Kind::Table),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Kind::String => "a string",
+            Kind::Integer => "an integer",
+            Kind::Float => "a float",
+            Kind::Boolean => "a boolean",
+            Kind::Array => "an array",
+            Kind::Table => "a table",
+        }
+    }
+
+    fn matches(&self, value: &toml::Value) -> bool {
+        match (*self, value) {
+            (Kind::String, &toml::Value::String(_)) |
+            (Kind::Integer, &toml::Value::Integer(_)) |
+            (Kind::Float, &toml::Value::Float(_)) |
+            (Kind::Float, &toml::Value::Integer(_)) |
+            (Kind::Boolean, &toml::Value::Boolean(_)) |
+            (Kind::Array, &toml::Value::Array(_)) |
+            (Kind::Table, &toml::Value::Table(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// What we check about a single configuration key.
+#[derive(Debug, Default)]
+struct Rule {
+    kind: Option<Kind>,
+    required: bool,
+    one_of: Option<Vec<toml::Value>>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Rule {
+    fn from_toml(key: &str, table: &toml::Table) -> Result<Rule> {
+        let mut rule = Rule::default();
+        for (field, value) in table.iter() {
+            match (field.as_str(), value) {
+                ("type", &toml::Value::String(ref kind)) => {
+                    match Kind::from_str(kind) {
+                        Some(kind) => rule.kind = Some(kind),
+                        None => {
+                            return Err(invalid_schema(key, &format!("unknown type {}", kind)));
+                        }
+                    }
+                }
+                ("required", &toml::Value::Boolean(required)) => rule.required = required,
+                ("enum", &toml::Value::Array(ref values)) => rule.one_of = Some(values.clone()),
+                ("min", value) if as_number(value).is_some() => rule.min = as_number(value),
+                ("max", value) if as_number(value).is_some() => rule.max = as_number(value),
+                (field, _) => {
+                    return Err(invalid_schema(key, &format!("can't understand {}", field)));
+                }
+            }
+        }
+        Ok(rule)
+    }
+
+    /// Checks a value against this rule, adding any problems with it to `errors`.
+    fn check(&self, key: &str, value: Option<&toml::Value>, errors: &mut Vec<String>) {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                if self.required {
+                    errors.push(format!("{}: is required", key));
+                }
+                return;
+            }
+        };
+        if let Some(kind) = self.kind {
+            if !kind.matches(value) {
+                errors.push(format!("{}: expected {}, found {}",
+                                    key,
+                                    kind.name(),
+                                    value.type_str()));
+                return;
+            }
+        }
+        if let Some(ref one_of) = self.one_of {
+            if !one_of.iter().any(|v| v == value) {
+                let allowed: Vec<String> = one_of.iter().map(|v| v.to_string()).collect();
+                errors.push(format!("{}: must be one of {}, found {}",
+                                    key,
+                                    allowed.join(", "),
+                                    value));
+            }
+        }
+        if let Some(number) = as_number(value) {
+            if let Some(min) = self.min {
+                if number < min {
+                    errors.push(format!("{}: must be at least {}, found {}", key, min, value));
+                }
+            }
+            if let Some(max) = self.max {
+                if number > max {
+                    errors.push(format!("{}: must be at most {}, found {}", key, max, value));
+                }
+            }
+        }
+    }
+}
+
+/// The schema of a package's configuration.
+#[derive(Debug)]
+pub struct Schema {
+    rules: BTreeMap<String, Rule>,
+}
+
+impl Schema {
+    /// Loads the schema at `path`. Returns `None` if there is no schema.
+    ///
+    /// # Failures
+    ///
+    /// * The schema cannot be read, or is not valid
+    pub fn load(path: &Path) -> Result<Option<Schema>> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("Failed to open {}: {}", path.display(), e);
+                return Ok(None);
+            }
+        };
+        let mut schema = String::new();
+        try!(file.read_to_string(&mut schema));
+        Ok(Some(try!(Schema::from_str(&schema))))
+    }
+
+    /// Parses a schema.
+    ///
+    /// # Failures
+    ///
+    /// * The schema is not valid TOML, or a rule in it cannot be understood
+    pub fn from_str(schema: &str) -> Result<Schema> {
+        let mut toml_parser = toml::Parser::new(schema);
+        let toml = try!(toml_parser.parse()
+            .ok_or(sup_error!(Error::TomlParser(toml_parser.errors))));
+        let mut rules = BTreeMap::new();
+        for (key, value) in toml.iter() {
+            match *value {
+                toml::Value::Table(ref table) => {
+                    rules.insert(key.clone(), try!(Rule::from_toml(key, table)));
+                }
+                _ => return Err(invalid_schema(key, "must be a table")),
+            }
+        }
+        Ok(Schema { rules: rules })
+    }
+
+    /// Checks the `cfg` tree of a service's configuration against the schema. Returns every
+    /// problem found; if there are none, the configuration is valid.
+    pub fn validate(&self, cfg: &toml::Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (key, rule) in self.rules.iter() {
+            rule.check(key, cfg.lookup(key), &mut errors);
+        }
+        errors
+    }
+}
+
+fn as_number(value: &toml::Value) -> Option<f64> {
+    match *value {
+        toml::Value::Integer(i) => Some(i as f64),
+        toml::Value::Float(f) => Some(f),
+        _ => None,
+    }
+}
+
+fn invalid_schema(key: &str, reason: &str) -> SupError {
+    sup_error!(Error::InvalidConfigSchema(format!("{}: {}", key, reason)))
+}
+
+#[cfg(test)]
+mod test {
+    use toml;
+
+    use super::Schema;
+
+    static SCHEMA: &'static str = r#"
+        [port]
+        type = "integer"
+        required = true
+        min = 1
+        max = 65535
+
+        [loglevel]
+        type = "string"
+        enum = ["debug", "info"]
+
+        ["tls.enabled"]
+        type = "boolean"
+    "#;
+
+    fn cfg(toml: &str) -> toml::Value {
+        toml::Value::Table(toml::Parser::new(toml).parse().unwrap())
+    }
+
+    #[test]
+    fn accepts_valid_config() {
+        let schema = Schema::from_str(SCHEMA).unwrap();
+        let cfg = cfg("port = 6379\nloglevel = \"info\"\n[tls]\nenabled = true\n");
+        assert!(schema.validate(&cfg).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let schema = Schema::from_str(SCHEMA).unwrap();
+        let cfg = cfg("loglevel = \"verbose\"\n[tls]\nenabled = \"yes\"\n");
+        assert_eq!(schema.validate(&cfg),
+                   vec![String::from("loglevel: must be one of \"debug\", \"info\", found \
+                                      \"verbose\""),
+                        String::from("port: is required"),
+                        String::from("tls.enabled: expected a boolean, found string")]);
+    }
+
+    #[test]
+    fn checks_ranges() {
+        let schema = Schema::from_str(SCHEMA).unwrap();
+        let errors = schema.validate(&cfg("port = 0\n"));
+        assert_eq!(errors, vec![String::from("port: must be at least 1, found 0")]);
+    }
+
+    #[test]
+    fn rejects_invalid_schemas() {
+        assert!(Schema::from_str("[port]\ntype = \"number\"\n").is_err());
+        assert!(Schema::from_str("[port]\nrequired = \"yes\"\n").is_err());
+        assert!(Schema::from_str("port = 1\n").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authenticated control requests.
+//!
+//! The sidecar's POST routes change the state of a running service, so the body of every control
+//! request must be sealed, as described in `habitat_common::control`, with a key the supervisor
+//! trusts: either the ring key, or a user key for the service key of the service being
+//! controlled.
+//! `hab service control` seals and sends them.
+//!
+//! A request is only accepted for the path it was sealed for, only for a short time after it was
+//! sealed, and only once; we remember the id of every request we accepted until it expires.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use common::control::{ControlRequest, BOX_FORMAT_VERSION, RING_FORMAT_VERSION};
+use hcore::crypto::{default_cache_key_path, BoxKeyPair, SymKey};
+use rustc_serialize::base64::FromBase64;
+use time;
+
+use error::{Error, Result, SupError};
+
+static LOGKEY: &'static str = "CT";
+/// How long, in seconds, a sealed control request is accepted for
+pub const MAX_REQUEST_AGE_SECS: i64 = 60;
+
+lazy_static! {
+    /// The ids of the requests we accepted, with when they were sealed
+    static ref SEEN_REQUESTS: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
+/// Opens a sealed control request for `path`.
+///
+/// Requests sealed with any of `ring_keys` are accepted, as are requests sealed for the service key
+/// named `service_key_name` (for example `redis.default@acme`), if one is given.
+///
+/// # Failures
+///
+/// * The request was sealed with a key we do not trust, or could not be opened
+/// * The request was sealed for another path
+/// * The request is older than `MAX_REQUEST_AGE_SECS`
+/// * We already accepted the request
+pub fn open(sealed: &str,
+            path: &str,
+            ring_keys: &[SymKey],
+            service_key_name: Option<&str>)
+            -> Result<ControlRequest> {
+    let mut lines = sealed.lines();
+    let plaintext = match lines.next() {
+        Some(version) if version == RING_FORMAT_VERSION => {
+            if ring_keys.is_empty() {
+                return Err(unauthorized("this supervisor has no ring key"));
+            }
+            let ring_key = match lines.next()
+                .and_then(|name| ring_keys.iter().find(|k| k.name_with_rev() == name)) {
+                Some(ring_key) => ring_key,
+                None => return Err(unauthorized("sealed with an unknown ring key")),
+            };
+            let nonce = try!(decode_line(lines.next(), "nonce"));
+            let ciphertext = try!(decode_line(lines.next(), "ciphertext"));
+            match ring_key.decrypt(&nonce, &ciphertext) {
+                Ok(plaintext) => plaintext,
+                Err(_) => return Err(unauthorized("could not be opened with the ring key")),
+            }
+        }
+        Some(version) if version == BOX_FORMAT_VERSION => {
+            let service_key_name = match service_key_name {
+                Some(name) => name,
+                None => return Err(unauthorized("service keys are not accepted here")),
+            };
+            let _sender = lines.next();
+            match lines.next() {
+                Some(receiver) if is_revision_of(receiver, service_key_name) => {}
+                _ => return Err(unauthorized("sealed for another service key")),
+            }
+            match BoxKeyPair::decrypt(sealed.as_bytes(), &default_cache_key_path(None)) {
+                Ok(plaintext) => plaintext,
+                Err(_) => return Err(unauthorized("could not be opened with the service key")),
+            }
+        }
+        _ => return Err(unauthorized("not a sealed control request")),
+    };
+
+    let request = match ControlRequest::from_plaintext(&plaintext) {
+        Some(request) => request,
+        None => return Err(unauthorized("malformed")),
+    };
+    if request.path != path {
+        return Err(unauthorized("sealed for another path"));
+    }
+    let now = time::get_time().sec;
+    if (now - request.timestamp).abs() > MAX_REQUEST_AGE_SECS {
+        return Err(unauthorized("expired"));
+    }
+    if !first_sight(&request, now) {
+        return Err(unauthorized("already accepted"));
+    }
+    Ok(request)
+}
+
+/// Remembers the id of a request we are about to accept. Returns false if we already accepted
+/// it. Requests that have expired by now are forgotten, as they are refused anyway.
+fn first_sight(request: &ControlRequest, now: i64) -> bool {
+    let mut seen = SEEN_REQUESTS.lock().unwrap();
+    let expired: Vec<String> = seen.iter()
+        .filter(|&(_, timestamp)| (now - *timestamp).abs() > MAX_REQUEST_AGE_SECS)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in expired {
+        seen.remove(&id);
+    }
+    if seen.contains_key(&request.id) {
+        return false;
+    }
+    seen.insert(request.id.clone(), request.timestamp);
+    true
+}
+
+fn decode_line(line: Option<&str>, what: &str) -> Result<Vec<u8>> {
+    match line.map(|l| l.from_base64()) {
+        Some(Ok(bytes)) => Ok(bytes),
+        _ => Err(unauthorized(&format!("can't decode {}", what))),
+    }
+}
+
+/// Returns true if `name_with_rev` is a revision of the key named `name`.
+fn is_revision_of(name_with_rev: &str, name: &str) -> bool {
+    let prefix = format!("{}-", name);
+    name_with_rev.starts_with(&prefix) &&
+    name_with_rev[prefix.len()..].chars().all(|c| c.is_digit(10))
+}
+
+fn unauthorized(reason: &str) -> SupError {
+    sup_error!(Error::Unauthorized(reason.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use hcore::crypto::SymKey;
+    use tempdir::TempDir;
+
+    use common::control::ControlRequest;
+
+    use super::{open, MAX_REQUEST_AGE_SECS};
+
+    fn ring_key(name: &str) -> (TempDir, SymKey) {
+        let cache = TempDir::new("key_cache").unwrap();
+        let key = SymKey::generate_pair_for_ring(name, cache.path()).unwrap();
+        (cache, key)
+    }
+
+    #[test]
+    fn open_ring_key_request() {
+        let (_cache, key) = ring_key("beyonce");
+        let request = ControlRequest::new("/services/redis/restart", "");
+        let sealed = request.seal_with_ring_key(&key).unwrap();
+        let opened = open(&sealed, "/services/redis/restart", &[key.clone()], None).unwrap();
+        assert_eq!(opened, request);
+    }
+
+    #[test]
+    fn keeps_the_body() {
+        let (_cache, key) = ring_key("beyonce");
+        let request = ControlRequest::new("/services", "core/redis\nwith a newline");
+        let sealed = request.seal_with_ring_key(&key).unwrap();
+        let opened = open(&sealed, "/services", &[key.clone()], None).unwrap();
+        assert_eq!(opened.body, "core/redis\nwith a newline");
+    }
+
+    #[test]
+    fn opens_with_any_ring_key() {
+        let (_cache, key) = ring_key("beyonce");
+        let (_other_cache, other_key) = ring_key("jayz");
+        let request = ControlRequest::new("/start", "");
+        let sealed = request.seal_with_ring_key(&key).unwrap();
+        let opened = open(&sealed, "/start", &[other_key, key], None).unwrap();
+        assert_eq!(opened, request);
+    }
+
+    #[test]
+    fn rejects_another_path() {
+        let (_cache, key) = ring_key("beyonce");
+        let sealed = ControlRequest::new("/services/redis/restart", "")
+            .seal_with_ring_key(&key)
+            .unwrap();
+        assert!(open(&sealed, "/services/redis/down", &[key.clone()], None).is_err());
+    }
+
+    #[test]
+    fn rejects_another_ring_key() {
+        let (_cache, key) = ring_key("beyonce");
+        let (_other_cache, other_key) = ring_key("jayz");
+        let sealed = ControlRequest::new("/start", "").seal_with_ring_key(&other_key).unwrap();
+        assert!(open(&sealed, "/start", &[key.clone()], None).is_err());
+        assert!(open(&sealed, "/start", &[], None).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_requests() {
+        let (_cache, key) = ring_key("beyonce");
+        let mut request = ControlRequest::new("/start", "");
+        request.timestamp -= MAX_REQUEST_AGE_SECS + 1;
+        let sealed = request.seal_with_ring_key(&key).unwrap();
+        assert!(open(&sealed, "/start", &[key.clone()], None).is_err());
+    }
+
+    #[test]
+    fn rejects_replayed_requests() {
+        let (_cache, key) = ring_key("beyonce");
+        let sealed = ControlRequest::new("/start", "").seal_with_ring_key(&key).unwrap();
+        assert!(open(&sealed, "/start", &[key.clone()], None).is_ok());
+        assert!(open(&sealed, "/start", &[key.clone()], None).is_err());
+
+        // A request sealed again is a new request
+        let sealed = ControlRequest::new("/start", "").seal_with_ring_key(&key).unwrap();
+        assert!(open(&sealed, "/start", &[key.clone()], None).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsealed_requests() {
+        let (_cache, key) = ring_key("beyonce");
+        assert!(open("/start\n0\n", "/start", &[key.clone()], None).is_err());
+        assert!(open("", "/start", &[key.clone()], None).is_err());
+    }
+}
@@ -20,8 +20,8 @@
 //!
 //! The way an Election works is this: every supervisor creates a new Election rumor, and sends it
 //! out. It then recieves every other Election rumor, and overrides its own rumor with any inbound
-//! rumor that is more 'suitable', where 'suitable' == "either has a higher suitability or sorts
-//! its supervisor id first".
+//! rumor that is more 'suitable', where 'suitable' == "is not failing its health check, has a
+//! higher suitability, or sorts its supervisor id first".
 //!
 //! The `leader` topology then evaluates these rumors.
 
@@ -31,6 +31,9 @@ use std::cmp::PartialEq;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
 
+use common;
+use common::message::swim;
+use common::wire_message::{ProtoConvert, uuid_from_proto};
 use uuid::Uuid;
 
 use gossip::member::MemberId;
@@ -63,6 +66,9 @@ pub struct Election {
     pub group: String,
     pub leader_id: MemberId,
     pub suitability: u32,
+    /// True if the candidate's health check was critical when it entered the election; `None` if
+    /// the candidate predates it
+    pub critical: Option<bool>,
     pub votes: HashSet<MemberId>,
     pub status: ElectionStatus,
     pub term: u32,
@@ -87,7 +93,58 @@ impl PartialEq for Election {
     fn eq(&self, other: &Election) -> bool {
         self.service == other.service && self.group == other.group &&
         self.leader_id == other.leader_id && self.suitability == other.suitability &&
-        self.votes == other.votes && self.status == other.status && self.term == other.term
+        self.critical == other.critical && self.votes == other.votes &&
+        self.status == other.status && self.term == other.term
+    }
+}
+
+impl ProtoConvert for Election {
+    type Proto = swim::Election;
+
+    fn to_proto(&self) -> swim::Election {
+        let mut proto = swim::Election::new();
+        proto.set_id(self.id.to_string());
+        proto.set_service(self.service.clone());
+        proto.set_group(self.group.clone());
+        proto.set_leader_id(self.leader_id.to_string());
+        proto.set_suitability(self.suitability);
+        if let Some(critical) = self.critical {
+            proto.set_critical(critical);
+        }
+        for vote in self.votes.iter() {
+            proto.mut_votes().push(vote.to_string());
+        }
+        proto.set_status(match self.status {
+            ElectionStatus::Running => swim::ElectionStatus::RUNNING,
+            ElectionStatus::Finished => swim::ElectionStatus::FINISHED,
+        });
+        proto.set_term(self.term);
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Election) -> common::Result<Election> {
+        let mut votes = HashSet::new();
+        for vote in proto.get_votes() {
+            votes.insert(try!(uuid_from_proto(vote)));
+        }
+        Ok(Election {
+            id: try!(uuid_from_proto(proto.get_id())),
+            service: proto.take_service(),
+            group: proto.take_group(),
+            leader_id: try!(uuid_from_proto(proto.get_leader_id())),
+            suitability: proto.get_suitability(),
+            critical: if proto.has_critical() {
+                Some(proto.get_critical())
+            } else {
+                None
+            },
+            votes: votes,
+            status: match proto.get_status() {
+                swim::ElectionStatus::RUNNING => ElectionStatus::Running,
+                swim::ElectionStatus::FINISHED => ElectionStatus::Finished,
+            },
+            term: proto.get_term(),
+        })
     }
 }
 
@@ -108,10 +165,16 @@ impl Election {
             votes: votes,
             term: term,
             suitability: suitability,
+            critical: None,
             status: ElectionStatus::Running,
         }
     }
 
+    /// How suitable the candidate is; a healthy candidate always beats a critical one.
+    fn rank(&self) -> (bool, u32) {
+        (!self.critical.unwrap_or(false), self.suitability)
+    }
+
     /// Returns true if the election is finished.
     pub fn finished(&self) -> bool {
         self.status == ElectionStatus::Finished
@@ -133,8 +196,10 @@ impl Election {
     /// * If we are the same, return false
     /// * If we are running and the inbound is finsihed, and our term is the same, take the remote
     /// and return true
-    /// * If our suitability is higher then theirs, vote for them, and return true
-    /// * If their suitability is higher than ours, vote for them, and return true
+    /// * If we are critical and they are not, or their suitability is higher than ours, vote for
+    /// them, and return true
+    /// * If they are critical and we are not, or our suitability is higher than theirs, add their
+    /// votes to ours, and return true
     /// * If we are suitablely equal, but our string is higher, vote for ourselves with the remote
     /// and return true
     /// * Otherwise, vote for them, and take theirs - return true
@@ -148,13 +213,13 @@ impl Election {
            self.term == remote_election.term {
             *self = remote_election;
             true
-        } else if self.suitability > remote_election.suitability {
+        } else if self.rank() > remote_election.rank() {
             for x in remote_election.votes.iter() {
                 self.votes.insert(*x);
             }
             self.votes.insert(remote_election.leader_id);
             true
-        } else if remote_election.suitability > self.suitability {
+        } else if remote_election.rank() > self.rank() {
             let old_votes = self.votes.clone();
             let old_id = self.leader_id.clone();
             *self = remote_election;
@@ -211,6 +276,8 @@ pub struct ElectionList {
     pub elections: HashMap<String, Election>,
     my_service_group: String,
     pub member_id: MemberId,
+    critical: bool,
+    suitability: u32,
 }
 
 impl ElectionList {
@@ -219,9 +286,23 @@ impl ElectionList {
             elections: HashMap::new(),
             my_service_group: service_group,
             member_id: member_id,
+            critical: false,
+            suitability: 0,
         }
     }
 
+    /// Set whether our health check is critical. Elections we start from now on carry it, so
+    /// other members prefer a healthy candidate over us.
+    pub fn set_critical(&mut self, critical: bool) {
+        self.critical = critical;
+    }
+
+    /// Set our suitability, from the package's suitability hook. Elections we start from now on
+    /// carry it.
+    pub fn set_suitability(&mut self, suitability: u32) {
+        self.suitability = suitability;
+    }
+
     /// Returns this supervisors election
     pub fn election(&self) -> Option<&Election> {
         self.elections.get(&self.my_service_group)
@@ -287,8 +368,13 @@ impl ElectionList {
             .get(&format!("{}.{}", service, group))
             .map_or(0, |e| e.term.clone());
 
-        // Eventually, you need to actually get the suitability here, or in election::new
-        Election::new(service, group, self.member_id.clone(), 0, current_term)
+        let mut election = Election::new(service,
+                                         group,
+                                         self.member_id.clone(),
+                                         self.suitability,
+                                         current_term);
+        election.critical = Some(self.critical);
+        election
     }
 
     /// Finish the election
@@ -352,10 +438,27 @@ mod test {
     }
 
     mod election {
+        use rustc_serialize::json::{self, Json};
+
         use gossip::member::MemberId;
         use election::Election;
         use super::generate_election;
 
+        #[test]
+        fn decodes_elections_from_older_members() {
+            let (_id, mut election) = generate_election();
+            election.critical = Some(true);
+            // A member which predates critical candidates does not send it
+            let mut old = match Json::from_str(&json::encode(&election).unwrap()).unwrap() {
+                Json::Object(object) => object,
+                _ => panic!("An election is encoded as an object"),
+            };
+            old.remove("critical");
+            let decoded: Election = json::decode(&Json::Object(old).to_string()).unwrap();
+            assert_eq!(decoded.critical, None);
+            assert_eq!(decoded.leader_id, election.leader_id);
+        }
+
         #[test]
         fn new() {
             let member_id = MemberId::new_v4();
@@ -404,6 +507,24 @@ mod test {
             assert!(local_election.votes.contains(&remote_id));
         }
 
+        #[test]
+        fn update_via_prefers_healthy_candidates() {
+            let (local_id, mut local_election) = generate_election();
+            let (remote_id, mut remote_election) = generate_election();
+
+            local_election.suitability = 100;
+            local_election.critical = Some(true);
+            assert!(local_election.update_via(remote_election.clone()));
+            assert_eq!(local_election.leader_id, remote_id);
+            assert!(local_election.votes.contains(&local_id));
+
+            let (_other_id, mut other_election) = generate_election();
+            other_election.suitability = 100;
+            other_election.critical = Some(true);
+            remote_election.update_via(other_election);
+            assert_eq!(remote_election.leader_id, remote_id);
+        }
+
         #[test]
         fn update_via_when_both_sides_equal() {
             let (local_id, mut local_election) = generate_election();
@@ -483,4 +604,18 @@ mod test {
         }
     }
 
+    mod election_list {
+        use gossip::member::MemberId;
+        use election::ElectionList;
+
+        #[test]
+        fn generate_election_for_uses_our_suitability() {
+            let member_id = MemberId::new_v4();
+            let mut el = ElectionList::new("handy.manny".to_string(), member_id);
+            el.set_suitability(42);
+            let e = el.generate_election_for("handy".to_string(), "manny".to_string());
+            assert_eq!(e.suitability, 42);
+            assert_eq!(e.leader_id, member_id);
+        }
+    }
 }
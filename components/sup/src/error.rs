@@ -98,6 +98,7 @@ impl SupError {
 #[derive(Debug)]
 pub enum Error {
     ActorError(actor::ActorError),
+    BindsUnsatisfied(Vec<String>),
     CommandNotImplemented,
     DbInvalidPath,
     DepotClient(depot_client::Error),
@@ -113,15 +114,22 @@ pub enum Error {
     /// TODO: once discovery/etcd.rs is purged, this error can be removed
     HyperError(hyper::error::Error),
     InvalidBinding(String),
+    InvalidConfig(Vec<String>),
+    InvalidConfigSchema(String),
+    InvalidHookTimeout(String),
     InvalidKeyParameter(String),
     InvalidPidFile,
     InvalidServiceGroupString(String),
+    InvalidSignal(String),
+    InvalidSuitability(String),
     Io(io::Error),
     IPFailed,
     JsonDecode(json::DecoderError),
     JsonEncode(json::EncoderError),
+    JsonParser(json::ParserError),
     KeyNotFound(String),
     MetaFileIO(io::Error),
+    MissingBinds(Vec<String>),
     NetParseError(net::AddrParseError),
     NoRunFile,
     NulError(ffi::NulError),
@@ -130,6 +138,8 @@ pub enum Error {
     Permissions(String),
     RemotePackageNotFound(package::PackageIdent),
     RootRequired,
+    ServiceLoaded(String),
+    ServicePanicked(String),
     SignalFailed,
     SignalNotifierStarted,
     StrFromUtf8Error(str::Utf8Error),
@@ -137,6 +147,7 @@ pub enum Error {
     TomlEncode(toml::Error),
     TomlParser(Vec<toml::ParserError>),
     TryRecvError(mpsc::TryRecvError),
+    Unauthorized(String),
     UnknownTopology(String),
     UnpackFailed,
     UuidParseError(uuid::ParseError),
@@ -159,6 +170,9 @@ impl fmt::Display for SupError {
             Error::HabitatCore(ref err) => format!("{}", err),
             Error::HandlebarsTemplateFileError(ref err) => format!("{:?}", err),
             Error::HandlebarsRenderError(ref err) => format!("{}", err),
+            Error::BindsUnsatisfied(ref binds) => {
+                format!("Timed out waiting for binds: {}", binds.join(", "))
+            }
             Error::CommandNotImplemented => format!("Command is not yet implemented!"),
             Error::DbInvalidPath => format!("Invalid filepath to internal datastore"),
             Error::DepotClient(ref err) => format!("{}", err),
@@ -172,6 +186,13 @@ impl fmt::Display for SupError {
             Error::InvalidBinding(ref binding) => {
                 format!("Invalid binding - must be ':' delimited: {}", binding)
             }
+            Error::InvalidConfig(ref errors) => {
+                format!("Invalid configuration: {}", errors.join("; "))
+            }
+            Error::InvalidConfigSchema(ref e) => format!("Invalid configuration schema: {}", e),
+            Error::InvalidHookTimeout(ref timeout) => {
+                format!("Invalid hook timeout - must be seconds or hook=seconds: {}", timeout)
+            }
             Error::InvalidKeyParameter(ref e) => {
                 format!("Invalid parameter for key generation: {:?}", e)
             }
@@ -179,12 +200,20 @@ impl fmt::Display for SupError {
             Error::InvalidServiceGroupString(ref e) => {
                 format!("Invalid service group string: {}", e)
             }
+            Error::InvalidSignal(ref s) => format!("Invalid signal: {}", s),
+            Error::InvalidSuitability(ref s) => {
+                format!("The suitability hook must print a number, not: {}", s)
+            }
             Error::Io(ref err) => format!("{}", err),
             Error::IPFailed => format!("Failed to discover this hosts outbound IP address"),
             Error::JsonDecode(ref e) => format!("JSON decoding error: {}", e),
             Error::JsonEncode(ref e) => format!("JSON encoding error: {}", e),
+            Error::JsonParser(ref e) => format!("JSON parse error: {}", e),
             Error::KeyNotFound(ref e) => format!("Key not found in key cache: {}", e),
             Error::MetaFileIO(ref e) => format!("IO error while accessing MetaFile: {:?}", e),
+            Error::MissingBinds(ref binds) => {
+                format!("Missing required binds, set them with --bind: {}", binds.join(", "))
+            }
             Error::NetParseError(ref e) => format!("Can't parse ip:port: {}", e),
             Error::NoRunFile => {
                 format!("No run file is present for this package; specify a run hook or \
@@ -212,6 +241,10 @@ impl fmt::Display for SupError {
             Error::RootRequired => {
                 "Root or administrator permissions required to complete operation".to_string()
             }
+            Error::ServiceLoaded(ref name) => {
+                format!("The {} service is already running in this supervisor", name)
+            }
+            Error::ServicePanicked(ref name) => format!("The {} service thread panicked", name),
             Error::SignalFailed => format!("Failed to send a signal to the child process"),
             Error::SignalNotifierStarted => {
                 format!("Only one instance of a Signal Notifier may be running")
@@ -223,6 +256,7 @@ impl fmt::Display for SupError {
                 format!("Failed to parse toml:\n{}", toml_parser_string(errs))
             }
             Error::TryRecvError(ref err) => format!("{}", err),
+            Error::Unauthorized(ref e) => format!("Unauthorized control request: {}", e),
             Error::UnknownTopology(ref t) => format!("Unknown topology {}!", t),
             Error::UnpackFailed => format!("Failed to unpack a package"),
             Error::UuidParseError(ref e) => format!("Uuid Parse Error: {:?}", e),
@@ -249,6 +283,7 @@ impl error::Error for SupError {
             Error::HandlebarsTemplateFileError(ref err) => err.description(),
             Error::HabitatCommon(ref err) => err.description(),
             Error::HabitatCore(ref err) => err.description(),
+            Error::BindsUnsatisfied(_) => "Required binds were not satisfied in time",
             Error::CommandNotImplemented => "Command is not yet implemented!",
             Error::DbInvalidPath => "A bad filepath was provided for an internal datastore",
             Error::DepotClient(ref err) => err.description(),
@@ -258,17 +293,24 @@ impl error::Error for SupError {
             Error::HTTP(_) => "Received an HTTP error",
             Error::HyperError(ref err) => err.description(),
             Error::InvalidBinding(_) => "Invalid binding parameter",
+            Error::InvalidConfig(_) => "The configuration does not match its schema",
+            Error::InvalidConfigSchema(_) => "The configuration schema cannot be understood",
+            Error::InvalidHookTimeout(_) => "Invalid hook timeout parameter",
             Error::InvalidKeyParameter(_) => "Key parameter error",
             Error::InvalidPidFile => "Invalid child process PID file",
             Error::InvalidServiceGroupString(_) => {
                 "Service group strings must be in service.group format (example: redis.default)"
             }
+            Error::InvalidSignal(_) => "Unknown or unsupported Unix signal",
+            Error::InvalidSuitability(_) => "The suitability hook did not print a number",
             Error::Io(ref err) => err.description(),
             Error::IPFailed => "Failed to discover the outbound IP address",
             Error::JsonDecode(_) => "JSON decoding error: {:?}",
             Error::JsonEncode(_) => "JSON encoding error",
+            Error::JsonParser(_) => "Failed to parse JSON",
             Error::KeyNotFound(_) => "Key not found in key cache",
             Error::MetaFileIO(_) => "MetaFile could not be read or written to",
+            Error::MissingBinds(_) => "Required binds were not given",
             Error::NetParseError(_) => "Can't parse IP:port",
             Error::NoRunFile => {
                 "No run file is present for this package; specify a run hook or $pkg_svc_run \
@@ -286,6 +328,8 @@ impl error::Error for SupError {
             Error::RootRequired => {
                 "Root or administrator permissions required to complete operation"
             }
+            Error::ServiceLoaded(_) => "The service is already running in this supervisor",
+            Error::ServicePanicked(_) => "The service thread panicked",
             Error::SignalFailed => "Failed to send a signal to the child process",
             Error::SignalNotifierStarted => "Only one instance of a Signal Notifier may be running",
             Error::StrFromUtf8Error(_) => "Failed to convert a str from a &[u8] as UTF-8",
@@ -293,6 +337,7 @@ impl error::Error for SupError {
             Error::TomlEncode(_) => "Failed to encode toml!",
             Error::TomlParser(_) => "Failed to parse toml!",
             Error::TryRecvError(_) => "A channel failed to recieve a response",
+            Error::Unauthorized(_) => "Control request was not sealed with a trusted key",
             Error::UnknownTopology(_) => "Unknown topology",
             Error::UnpackFailed => "Failed to unpack a package",
             Error::UuidParseError(_) => "Uuid Parse Error",
@@ -15,50 +15,69 @@
 //! The Gossip Client.
 //!
 //! This module takes a `UtpSocket`, and lets you send and receive messages with it. Messages are
-//! encoded with json.
+//! encoded with json, unless the client is told the remote understands protocol buffers; we
+//! receive messages in either.
 //!
 
 use std::net::ToSocketAddrs;
-use std::str;
 
-use common::wire_message::WireMessage;
-use hcore::crypto::SymKey;
-use rustc_serialize::json;
+use common::wire_message::{Encoding, WireMessage};
 use utp::UtpSocket;
 
 use error::Result;
+use gossip::ring_keys::RingKeys;
 use gossip::rumor::{Protocol, Peer, RumorList};
 
 pub const BUFFER_SIZE: usize = 10000;
 
 /// A Gossip Client.
-pub struct Client<'a> {
+pub struct Client {
     pub socket: UtpSocket,
-    ring_key: Option<&'a SymKey>,
+    ring_keys: RingKeys,
+    encoding: Encoding,
+    protocol_version: Option<u32>,
 }
 
-impl<'a> Client<'a> {
+impl Client {
     /// Create a new client from anything that can become a `SocketAddr`.
     ///
     /// # Errors
     ///
     /// * If we cannot connect the UTP socket
-    pub fn new<A: ToSocketAddrs>(dst: A, ring_key: Option<&'a SymKey>) -> Result<Client> {
+    pub fn new<A: ToSocketAddrs>(dst: A, ring_keys: RingKeys) -> Result<Client> {
         let socket = try!(UtpSocket::connect(dst));
-        Ok(Client {
-            socket: socket,
-            ring_key: ring_key,
-        })
+        Ok(Client::from_socket(socket, ring_keys))
     }
 
     /// Create a new client from a `UtpSocket`
-    pub fn from_socket(socket: UtpSocket, ring_key: Option<&'a SymKey>) -> Client {
+    pub fn from_socket(socket: UtpSocket, ring_keys: RingKeys) -> Client {
         Client {
             socket: socket,
-            ring_key: ring_key,
+            ring_keys: ring_keys,
+            encoding: Encoding::Json,
+            protocol_version: None,
         }
     }
 
+    /// The encoding we send messages with. Once we receive a message, it is the encoding the
+    /// remote sent it with.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Send messages with the given encoding; only use the binary encoding with a remote which
+    /// understands it.
+    pub fn set_encoding(&mut self, encoding: Encoding) -> &mut Client {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The protocol version the remote speaks, once we receive a message from it; `None` if it
+    /// predates versioning.
+    pub fn protocol_version(&self) -> Option<u32> {
+        self.protocol_version
+    }
+
     /// Send a ping.
     ///
     /// # Errors
@@ -102,26 +121,26 @@ impl<'a> Client<'a> {
     /// * We cannot decode the data into a `gossip::message::Protocol`
     pub fn recv_message(&mut self) -> Result<Protocol> {
         let mut buf = [0u8; BUFFER_SIZE];
-        let mut json_str = String::new();
+        let mut bytes = Vec::new();
         let mut keep_reading_buffer = true;
 
         while keep_reading_buffer {
             let (amt, _src) = try!(self.socket.recv_from(&mut buf));
             match amt {
                 0 => keep_reading_buffer = false,
-                amt => {
-                    let partial_str = try!(str::from_utf8(&buf[..amt]));
-                    json_str.push_str(partial_str);
-                }
+                amt => bytes.extend_from_slice(&buf[..amt]),
             }
         }
 
-        debug!("Received protocol ({:?}): {}",
+        let wire_msg = try!(WireMessage::from_bytes(&bytes));
+        debug!("Received protocol ({:?}): {} bytes of {:?}, protocol version {:?}",
                self.socket.peer_addr(),
-               json_str);
-
-        let wire_msg: WireMessage = try!(json::decode(&json_str));
-        Ok(try!(wire_msg.msg(self.ring_key)))
+               bytes.len(),
+               wire_msg.encoding(),
+               wire_msg.protocol_version);
+        self.encoding = wire_msg.encoding();
+        self.protocol_version = wire_msg.protocol_version;
+        Ok(try!(wire_msg.proto_msg_with_keys(self.ring_keys.keys())))
     }
 
     /// Send a message.
@@ -132,14 +151,16 @@ impl<'a> Client<'a> {
     /// * We fail to send the encoded buffer to the remote
     pub fn send_message(&mut self, msg: Protocol) -> Result<()> {
         let encoded = {
-            let wire_msg = match self.ring_key.as_ref() {
-                Some(key) => try!(WireMessage::encrypted(&msg, &key)),
-                None => try!(WireMessage::plain(&msg)),
+            let wire_msg = match self.ring_keys.active() {
+                Some(key) => try!(WireMessage::encrypted_with_encoding(&msg, key, self.encoding)),
+                None => try!(WireMessage::plain_with_encoding(&msg, self.encoding)),
             };
-            try!(json::encode(&wire_msg))
+            try!(wire_msg.to_bytes())
         };
-        debug!("Encoded message {:#?}", encoded);
-        try!(self.socket.send_to(encoded.as_bytes()));
+        debug!("Encoded message as {} bytes of {:?}",
+               encoded.len(),
+               self.encoding);
+        try!(self.socket.send_to(&encoded));
         debug!("Sent protocol: {:?}", msg);
         Ok(())
     }
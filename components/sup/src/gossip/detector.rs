@@ -119,6 +119,11 @@ impl Detector {
         self.open_requests.remove(member_id);
     }
 
+    /// Stops tracking a member that has been reaped
+    pub fn forget(&mut self, member_id: &MemberId) {
+        self.open_requests.remove(member_id);
+    }
+
     /// Checks the timeout of connections. Returns a tuple of (suspect, confirmed) members.
     pub fn expire(&mut self) -> (Vec<MemberId>, Vec<MemberId>, Vec<MemberId>) {
         debug!("Detector State: {:#?}", self);
@@ -16,19 +16,31 @@
 //!
 //! This module tracks membership in the gossip ring. It consists of `Members` and a collection of
 //! them in a `MemberList`.
+//!
+//! Members leave the ring either by being confirmed dead by the failure detector, or by departing:
+//! a supervisor announces its departure when it shuts down cleanly, and `hab ring evict` announces
+//! the departure of a dead member on its behalf. Once a member has been confirmed dead or departed
+//! for long enough, it is reaped - removed from the `MemberList` for good, unless it rejoins with
+//! a higher incarnation.
 
 use std::mem;
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::Deref;
 
+use common;
+use common::message::swim;
+use common::wire_message::{PROTOCOL_VERSION, ProtoConvert, uuid_from_proto};
 use rand::{thread_rng, Rng};
+use time;
 use uuid::Uuid;
 
 use gossip::lamport_clock::LamportClock;
 
 // How many members do we send a PingReq to for a failed node?
 static PINGREQ_MEMBERS: usize = 5;
+/// How long a member stays confirmed dead or departed before it is reaped, by default
+pub const DEFAULT_REAP_AFTER_SECS: u64 = 86400;
 
 /// Every time we receive a Suspect or Confirmed message about our own entry in the MemberList, we
 /// update our Incarnation.
@@ -40,6 +52,8 @@ pub enum Health {
     Alive,
     Suspect,
     Confirmed,
+    /// The member left the ring on purpose, or was evicted from it
+    Departed,
 }
 
 /// Each member has a Uuid.
@@ -56,6 +70,8 @@ pub struct Member {
     pub incarnation: Incarnation,
     pub health: Health,
     pub permanent: bool,
+    /// The version of the gossip protocol the member speaks; `None` if it predates versioning.
+    pub protocol_version: Option<u32>,
 }
 
 impl Member {
@@ -69,6 +85,7 @@ impl Member {
             incarnation: Incarnation::new(),
             health: Health::Alive,
             permanent: permanent,
+            protocol_version: Some(PROTOCOL_VERSION),
         }
     }
 
@@ -82,6 +99,11 @@ impl Member {
     /// * If the incarnation is equal, and the rhs health is confirmed, used rhs
     /// * If the incarnation is equal, and the lhs is alive, and the rhs is suspect, use rhs
     /// * If the incarnation is equal, and the lhs is confirmed, and the rhs is suspect or alive, use the lhs
+    /// * If the incarnation is equal, and the lhs is departed, use the lhs
+    /// * If the incarnation is equal, and the rhs is departed, use the rhs
+    ///
+    /// Whenever the rhs says we are confirmed, suspect or departed, we increment our incarnation
+    /// instead, so the rest of the ring learns we are still here.
     pub fn update_via(&mut self, my_id: &MemberId, rhs: Member) -> bool {
         if self.incarnation > rhs.incarnation {
             return false;
@@ -92,7 +114,10 @@ impl Member {
             if self.health == rhs.health {
                 return false;
             }
-            if rhs.health == Health::Confirmed {
+            if self.health == Health::Departed {
+                return false;
+            }
+            if rhs.health == Health::Confirmed || rhs.health == Health::Departed {
                 if self.id == *my_id {
                     self.incarnation.increment();
                     self.health = Health::Alive;
@@ -128,6 +153,110 @@ impl fmt::Display for Member {
     }
 }
 
+impl ProtoConvert for Member {
+    type Proto = swim::Member;
+
+    fn to_proto(&self) -> swim::Member {
+        let mut proto = swim::Member::new();
+        proto.set_id(self.id.to_string());
+        proto.set_hostname(self.hostname.clone());
+        proto.set_ip(self.ip.clone());
+        proto.set_gossip_listener(self.gossip_listener.clone());
+        proto.set_incarnation(self.incarnation.counter);
+        proto.set_health(match self.health {
+            Health::Alive => swim::Health::ALIVE,
+            Health::Suspect => swim::Health::SUSPECT,
+            Health::Confirmed => swim::Health::CONFIRMED,
+            Health::Departed => swim::Health::DEPARTED,
+        });
+        proto.set_permanent(self.permanent);
+        if let Some(protocol_version) = self.protocol_version {
+            proto.set_protocol_version(protocol_version);
+        }
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Member) -> common::Result<Member> {
+        Ok(Member {
+            id: try!(uuid_from_proto(proto.get_id())),
+            hostname: proto.take_hostname(),
+            ip: proto.take_ip(),
+            gossip_listener: proto.take_gossip_listener(),
+            incarnation: LamportClock { counter: proto.get_incarnation() },
+            health: match proto.get_health() {
+                swim::Health::ALIVE => Health::Alive,
+                swim::Health::SUSPECT => Health::Suspect,
+                swim::Health::CONFIRMED => Health::Confirmed,
+                swim::Health::DEPARTED => Health::Departed,
+            },
+            permanent: proto.get_permanent(),
+            protocol_version: if proto.has_protocol_version() {
+                Some(proto.get_protocol_version())
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// The departure of a member from the ring. Sent by a supervisor as it shuts down cleanly, or by
+/// `hab ring evict` for a member that is never coming back.
+#[derive(Clone, Debug, PartialEq, Eq, RustcDecodable, RustcEncodable)]
+pub struct Departure {
+    pub id: Uuid,
+    pub member_id: MemberId,
+    /// The incarnation of the member which departed; a member which rejoins with a higher one
+    /// outranks the departure. `None` if the member departs whatever its incarnation, as when it
+    /// is evicted.
+    pub incarnation: Option<Incarnation>,
+}
+
+impl Departure {
+    /// The departure of a member, whatever its incarnation.
+    pub fn new(member_id: MemberId) -> Departure {
+        Departure {
+            id: Uuid::new_v4(),
+            member_id: member_id,
+            incarnation: None,
+        }
+    }
+
+    /// The departure of a member at its current incarnation.
+    pub fn of(member: &Member) -> Departure {
+        Departure {
+            id: Uuid::new_v4(),
+            member_id: member.id.clone(),
+            incarnation: Some(member.incarnation.clone()),
+        }
+    }
+}
+
+impl ProtoConvert for Departure {
+    type Proto = swim::Departure;
+
+    fn to_proto(&self) -> swim::Departure {
+        let mut proto = swim::Departure::new();
+        proto.set_id(self.id.to_string());
+        proto.set_member_id(self.member_id.to_string());
+        if let Some(ref incarnation) = self.incarnation {
+            proto.set_incarnation(incarnation.counter);
+        }
+        proto
+    }
+
+    fn from_proto(proto: swim::Departure) -> common::Result<Departure> {
+        Ok(Departure {
+            id: try!(uuid_from_proto(proto.get_id())),
+            member_id: try!(uuid_from_proto(proto.get_member_id())),
+            incarnation: if proto.has_incarnation() {
+                Some(LamportClock { counter: proto.get_incarnation() })
+            } else {
+                None
+            },
+        })
+    }
+}
+
 /// A list of members. Keeps track of both the members themselves, and provides an order to iterate
 /// through them via the `next()` function.
 #[derive(Debug, RustcDecodable, RustcEncodable)]
@@ -136,6 +265,11 @@ pub struct MemberList {
     position: usize,
     order: Vec<Uuid>,
     pub my_id: MemberId,
+    /// When we first saw each confirmed or departed member in that state, in seconds since the
+    /// epoch
+    dead_since: HashMap<MemberId, i64>,
+    /// The incarnation each reaped member had; we ignore rumors of them from before it
+    reaped: HashMap<MemberId, Incarnation>,
 }
 
 impl MemberList {
@@ -146,6 +280,8 @@ impl MemberList {
             position: 0,
             order: Vec::new(),
             my_id: my_member.id.clone(),
+            dead_since: HashMap::new(),
+            reaped: HashMap::new(),
         };
         ml.insert(my_member);
         ml
@@ -185,16 +321,24 @@ impl MemberList {
     /// so we insert them.
     ///
     /// Either way, we return true if we added a new member or mutated an existing one; false if we
-    /// did nothing.
+    /// did nothing. Members we have reaped are ignored, unless they come back with a higher
+    /// incarnation.
     pub fn process(&mut self, remote_member: Member) -> bool {
         // This is strange - rust won't let this be an else.
         if let Some(mut current_member) = self.members.get_mut(&remote_member.id) {
             return current_member.update_via(&self.my_id, remote_member);
         }
+        if let Some(incarnation) = self.reaped.get(&remote_member.id) {
+            if remote_member.incarnation <= *incarnation {
+                return false;
+            }
+        }
+        self.reaped.remove(&remote_member.id);
         match remote_member.health {
             Health::Alive => warn!("Member {} is alive", remote_member.id),
             Health::Suspect => warn!("Member {} is suspect", remote_member.id),
             Health::Confirmed => warn!("Member {} is confirmed dead", remote_member.id),
+            Health::Departed => warn!("Member {} has departed", remote_member.id),
         }
         self.insert(remote_member);
         return true;
@@ -220,7 +364,7 @@ impl MemberList {
     /// Set a members health to Suspect.
     pub fn suspect(&mut self, member_id: &MemberId) {
         if let Some(mut member) = self.members.get_mut(member_id) {
-            if member.health != Health::Confirmed {
+            if member.health != Health::Confirmed && member.health != Health::Departed {
                 warn!("Member {} is suspect", member_id);
                 member.health = Health::Suspect;
             }
@@ -230,20 +374,100 @@ impl MemberList {
     /// Set a members health to Confirmed.
     pub fn confirm(&mut self, member_id: &MemberId) {
         if let Some(mut member) = self.members.get_mut(member_id) {
-            if member.health != Health::Confirmed {
+            if member.health != Health::Confirmed && member.health != Health::Departed {
                 warn!("Member {} is confirmed dead", member_id);
                 member.health = Health::Confirmed;
             }
         }
     }
 
+    /// Set a members health to Departed. Returns true if we changed the member.
+    ///
+    /// A departure from an earlier incarnation of the member is ignored; the member has come back
+    /// since. If we are the one said to have departed, we are evidently still here; we increment
+    /// our incarnation instead, so the rest of the ring learns we are alive.
+    pub fn depart(&mut self, departure: &Departure) -> bool {
+        let my_id = self.my_id.clone();
+        let member_id = &departure.member_id;
+        if let Some(mut member) = self.members.get_mut(member_id) {
+            if let Some(ref incarnation) = departure.incarnation {
+                if member.incarnation > *incarnation {
+                    return false;
+                }
+            }
+            if *member_id == my_id {
+                if member.health != Health::Departed {
+                    member.incarnation.increment();
+                    return true;
+                }
+            } else if member.health != Health::Departed {
+                warn!("Member {} has departed", member_id);
+                member.health = Health::Departed;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Marks ourselves as departed, as we leave the ring. Returns our member entry, to be shared
+    /// with our peers.
+    pub fn leave(&mut self) -> Member {
+        let my_id = self.my_id.clone();
+        let member = self.members.get_mut(&my_id).unwrap();
+        member.health = Health::Departed;
+        member.clone()
+    }
+
+    /// Return true if the member has departed, or been reaped.
+    pub fn has_left(&self, member_id: &MemberId) -> bool {
+        if self.reaped.contains_key(member_id) {
+            return true;
+        }
+        match self.members.get(member_id) {
+            Some(member) => member.health == Health::Departed,
+            None => false,
+        }
+    }
+
+    /// Removes the members which have been confirmed dead or departed for at least
+    /// `reap_after_secs`, and returns their ids. We never reap ourselves, nor permanent members
+    /// unless they departed.
+    pub fn reap(&mut self, reap_after_secs: u64) -> Vec<MemberId> {
+        let now = time::get_time().sec;
+        let mut reaped = Vec::new();
+        for (id, member) in self.members.iter() {
+            let dead = member.health == Health::Departed ||
+                       (member.health == Health::Confirmed && !member.permanent);
+            if !dead || *id == self.my_id {
+                self.dead_since.remove(id);
+                continue;
+            }
+            let since = *self.dead_since.entry(id.clone()).or_insert(now);
+            if now - since >= reap_after_secs as i64 {
+                reaped.push(id.clone());
+            }
+        }
+        for id in reaped.iter() {
+            warn!("Member {} has been reaped", id);
+            if let Some(member) = self.members.remove(id) {
+                self.reaped.insert(id.clone(), member.incarnation);
+            }
+            self.dead_since.remove(id);
+            self.order.retain(|oid| oid != id);
+        }
+        if self.position > self.order.len() {
+            self.position = self.order.len();
+        }
+        reaped
+    }
+
     /// Selects PINGREQ_MEMBERS number of members to use as targets for a PingReq. The members are
     /// chosen completely randomly.
     pub fn pingreq_targets(&self, myself: &MemberId, target: &MemberId) -> Vec<Member> {
         let mut rng = thread_rng();
         let mut values: Vec<&Member> = self.members
             .values()
-            .filter(|m| &m.id != myself && &m.id != target)
+            .filter(|m| &m.id != myself && &m.id != target && m.health != Health::Departed)
             .collect();
         rng.shuffle(&mut values);
         let mut results: Vec<Member> = Vec::new();
@@ -269,12 +493,13 @@ impl MemberList {
         usual_suspects
     }
 
-    /// Return true if all members other than the provided id are Confirmed.
+    /// Return true if all members other than the provided id are Confirmed or Departed.
     pub fn isolated(&self, myself: &MemberId) -> bool {
         self.members.iter().fold(true, |acc, (id, m)| {
             if id == myself {
                 acc
-            } else if m.health == Health::Confirmed && acc != false {
+            } else if (m.health == Health::Confirmed || m.health == Health::Departed) &&
+                      acc != false {
                 true
             } else {
                 false
@@ -405,11 +630,46 @@ mod test {
             assert_eq!(bobo.health, Health::Confirmed);
             assert_eq!(r, false);
         }
+
+        #[test]
+        fn update_via_equal_and_departed() {
+            let my_id = MemberId::new_v4();
+            let mut bobo = bobo();
+            let mut other_bobo = bobo.clone();
+            other_bobo.health = Health::Departed;
+            let r = bobo.update_via(&my_id, other_bobo);
+            assert_eq!(bobo.health, Health::Departed);
+            assert_eq!(r, true);
+
+            // Once departed, only a higher incarnation brings a member back
+            let mut tclown = bobo.clone();
+            tclown.health = Health::Alive;
+            let r = bobo.update_via(&my_id, tclown.clone());
+            assert_eq!(bobo.health, Health::Departed);
+            assert_eq!(r, false);
+            tclown.incarnation.increment();
+            let r = bobo.update_via(&my_id, tclown);
+            assert_eq!(bobo.health, Health::Alive);
+            assert_eq!(r, true);
+        }
+
+        #[test]
+        fn update_via_equal_while_rhs_departed_and_lhs_is_me() {
+            let my_id = MemberId::new_v4();
+            let mut bobo = bobo();
+            bobo.id = my_id.clone();
+            let mut other_bobo = bobo.clone();
+            other_bobo.health = Health::Departed;
+            let r = bobo.update_via(&my_id, other_bobo);
+            assert_eq!(bobo.health, Health::Alive);
+            assert_eq!(*bobo.incarnation, 1);
+            assert_eq!(r, true);
+        }
     }
 
     mod member_list {
         use uuid::Uuid;
-        use gossip::member::{Member, MemberList, Health};
+        use gossip::member::{Departure, Member, MemberList, Health};
 
         fn new_member_list() -> MemberList {
             let james = Member::new(String::from("james"),
@@ -552,5 +812,85 @@ mod test {
             // One member who is not us is alive, so we are not isolated
             assert_eq!(ml.isolated(&my_id), false);
         }
+
+        #[test]
+        fn depart() {
+            let mut ml = new_member_list();
+            let member = Member::new(String::from("a.foo.com"),
+                                     String::from("192.168.1.1"),
+                                     String::from("192.168.1.1:4312"),
+                                     false);
+            let member_id = member.id.clone();
+            ml.insert(member);
+            assert!(ml.depart(&Departure::new(member_id.clone())));
+            assert!(!ml.depart(&Departure::new(member_id.clone())));
+            assert_eq!(ml.get(&member_id).unwrap().health, Health::Departed);
+            assert!(ml.has_left(&member_id));
+
+            // We refute our own departure
+            let my_id = ml.my_id.clone();
+            let departure = Departure::of(ml.get(&my_id).unwrap());
+            assert!(ml.depart(&departure));
+            assert_eq!(ml.get(&my_id).unwrap().health, Health::Alive);
+            assert_eq!(*ml.get(&my_id).unwrap().incarnation, 1);
+
+            // Once, as we have come back since
+            assert!(!ml.depart(&departure));
+            assert_eq!(*ml.get(&my_id).unwrap().incarnation, 1);
+        }
+
+        #[test]
+        fn depart_ignores_earlier_incarnations() {
+            let mut ml = new_member_list();
+            let mut member = Member::new(String::from("a.foo.com"),
+                                         String::from("192.168.1.1"),
+                                         String::from("192.168.1.1:4312"),
+                                         false);
+            let departure = Departure::of(&member);
+            member.incarnation.increment();
+            let member_id = member.id.clone();
+            ml.insert(member);
+            assert!(!ml.depart(&departure));
+            assert_eq!(ml.get(&member_id).unwrap().health, Health::Alive);
+        }
+
+        #[test]
+        fn reap() {
+            let mut ml = new_member_list();
+            let alive = Member::new(String::from("a.foo.com"),
+                                    String::from("192.168.1.1"),
+                                    String::from("192.168.1.1:4312"),
+                                    false);
+            let mut dead = Member::new(String::from("b.foo.com"),
+                                       String::from("192.168.1.2"),
+                                       String::from("192.168.1.2:4312"),
+                                       false);
+            dead.health = Health::Confirmed;
+            let mut permanent = Member::new(String::from("c.foo.com"),
+                                            String::from("192.168.1.3"),
+                                            String::from("192.168.1.3:4312"),
+                                            true);
+            permanent.health = Health::Confirmed;
+            let alive_id = alive.id.clone();
+            let dead_id = dead.id.clone();
+            let permanent_id = permanent.id.clone();
+            ml.insert(alive);
+            ml.insert(dead.clone());
+            ml.insert(permanent);
+
+            assert!(ml.reap(60).is_empty());
+            assert_eq!(ml.reap(0), vec![dead_id.clone()]);
+            assert!(ml.get(&dead_id).is_none());
+            assert!(ml.get(&alive_id).is_some());
+            assert!(ml.get(&permanent_id).is_some());
+            assert!(ml.has_left(&dead_id));
+
+            // Rumors of the reaped member don't bring it back, unless it has rejoined since
+            assert!(!ml.process(dead.clone()));
+            dead.health = Health::Alive;
+            dead.incarnation.increment();
+            assert!(ml.process(dead));
+            assert!(!ml.has_left(&dead_id));
+        }
     }
 }
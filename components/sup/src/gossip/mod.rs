@@ -19,6 +19,8 @@
 pub mod rumor;
 pub mod lamport_clock;
 pub mod member;
+pub mod ring_keys;
+pub mod ring_state;
 pub mod server;
 pub mod client;
 pub mod detector;
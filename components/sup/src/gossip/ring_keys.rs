@@ -0,0 +1,305 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The ring keys of a supervisor, and their rotation.
+//!
+//! A supervisor accepts messages encrypted with any revision of its ring key it knows about, and
+//! sends messages encrypted with the active one. At startup, it knows about every revision of the
+//! ring key in its key cache.
+//!
+//! `hab ring key rotate` moves a ring to a new revision of its key without splitting it, by
+//! gossiping a `RingKeyRotation` through three stages:
+//!
+//! * `Distribute`: every supervisor installs the new revision, and accepts messages encrypted
+//!   with it
+//! * `Activate`: every supervisor sends messages encrypted with the new revision
+//! * `Retire`: every supervisor forgets the older revisions, and removes them from its key cache
+//!
+//! Each stage is only gossiped once the previous one has had time to reach the whole ring.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use common;
+use common::message::swim;
+use common::wire_message::{ProtoConvert, uuid_from_proto};
+use hcore::crypto::SymKey;
+use hcore::crypto::keys::parse_name_with_rev;
+use uuid::Uuid;
+
+use error::Result;
+
+static LOGKEY: &'static str = "RK";
+
+/// How far a rotation of the ring key has gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, RustcEncodable, RustcDecodable)]
+pub enum RotationStage {
+    Distribute,
+    Activate,
+    Retire,
+}
+
+/// A rotation to a new revision of the ring key. It is only ever gossiped encrypted with a ring
+/// key, as it carries the new one.
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct RingKeyRotation {
+    /// Shared by every stage of the rotation, so each stage replaces the previous one
+    pub id: Uuid,
+    /// The name with revision of the new key
+    pub name_with_rev: String,
+    /// The contents of the new key's file
+    pub key: String,
+    pub stage: RotationStage,
+}
+
+impl ProtoConvert for RingKeyRotation {
+    type Proto = swim::RingKeyRotation;
+
+    fn to_proto(&self) -> swim::RingKeyRotation {
+        let mut proto = swim::RingKeyRotation::new();
+        proto.set_id(self.id.to_string());
+        proto.set_name_with_rev(self.name_with_rev.clone());
+        proto.set_key(self.key.clone());
+        proto.set_stage(match self.stage {
+            RotationStage::Distribute => swim::RotationStage::DISTRIBUTE,
+            RotationStage::Activate => swim::RotationStage::ACTIVATE,
+            RotationStage::Retire => swim::RotationStage::RETIRE,
+        });
+        proto
+    }
+
+    fn from_proto(mut proto: swim::RingKeyRotation) -> common::Result<RingKeyRotation> {
+        Ok(RingKeyRotation {
+            id: try!(uuid_from_proto(proto.get_id())),
+            name_with_rev: proto.take_name_with_rev(),
+            key: proto.take_key(),
+            stage: match proto.get_stage() {
+                swim::RotationStage::DISTRIBUTE => RotationStage::Distribute,
+                swim::RotationStage::ACTIVATE => RotationStage::Activate,
+                swim::RotationStage::RETIRE => RotationStage::Retire,
+            },
+        })
+    }
+}
+
+/// The revisions of the ring key a supervisor knows about.
+#[derive(Clone)]
+pub struct RingKeys {
+    /// Every revision we accept messages encrypted with, newest first
+    keys: Vec<SymKey>,
+    /// The name with revision of the key we send messages encrypted with
+    active: Option<String>,
+    /// How far each rotation we have processed has gone
+    rotations: HashMap<String, RotationStage>,
+    cache_key_path: PathBuf,
+}
+
+impl RingKeys {
+    /// Ring keys for a ring without a key; messages are sent in plaintext.
+    pub fn new<P: Into<PathBuf>>(cache_key_path: P) -> RingKeys {
+        RingKeys {
+            keys: Vec::new(),
+            active: None,
+            rotations: HashMap::new(),
+            cache_key_path: cache_key_path.into(),
+        }
+    }
+
+    /// Loads the ring key we send messages with, and every revision of it in the key cache.
+    ///
+    /// # Failures
+    ///
+    /// * The key, or one of its revisions, cannot be read
+    pub fn load<P: Into<PathBuf>>(name_with_rev: &str, cache_key_path: P) -> Result<RingKeys> {
+        let mut ring_keys = RingKeys::new(cache_key_path);
+        let active = try!(SymKey::get_pair_for(name_with_rev, &ring_keys.cache_key_path));
+        ring_keys.keys = try!(SymKey::get_pairs_for(&active.name, &ring_keys.cache_key_path));
+        ring_keys.active = Some(active.name_with_rev());
+        if ring_keys.keys.len() > 1 {
+            let accepted: Vec<String> = ring_keys.keys.iter().map(|k| k.name_with_rev()).collect();
+            outputln!("Accepting messages encrypted with ring keys {}",
+                      accepted.join(", "));
+        }
+        Ok(ring_keys)
+    }
+
+    /// The key we send messages encrypted with, if the ring has one.
+    pub fn active(&self) -> Option<&SymKey> {
+        match self.active {
+            Some(ref active) => self.keys.iter().find(|k| k.name_with_rev() == *active),
+            None => None,
+        }
+    }
+
+    /// Every key we accept messages encrypted with.
+    pub fn keys(&self) -> &[SymKey] {
+        &self.keys
+    }
+
+    /// Applies a stage of a rotation. Returns true if we had not applied it before, and it should
+    /// be shared.
+    ///
+    /// Rotations of another ring, or to a revision older than the one we send with, are ignored.
+    ///
+    /// # Failures
+    ///
+    /// * The new key cannot be written to the key cache
+    /// * A retired key cannot be removed from the key cache
+    pub fn process(&mut self, rotation: &RingKeyRotation) -> Result<bool> {
+        let (name, rev) = match self.active() {
+            Some(active) => (active.name.clone(), active.rev.clone()),
+            None => {
+                debug!("Ignoring rotation to {}; we have no ring key",
+                       rotation.name_with_rev);
+                return Ok(false);
+            }
+        };
+        let (rotation_name, rotation_rev) = try!(parse_name_with_rev(&rotation.name_with_rev));
+        if rotation_name != name || rotation_rev < rev {
+            debug!("Ignoring rotation to {}; we send with {}-{}",
+                   rotation.name_with_rev,
+                   name,
+                   rev);
+            return Ok(false);
+        }
+        if let Some(stage) = self.rotations.get(&rotation.name_with_rev) {
+            if *stage >= rotation.stage {
+                return Ok(false);
+            }
+        }
+
+        if !self.keys.iter().any(|k| k.name_with_rev() == rotation.name_with_rev) {
+            let (key, _) = try!(SymKey::write_file_from_str(&rotation.key, &self.cache_key_path));
+            if key.name_with_rev() != rotation.name_with_rev {
+                outputln!("Ignoring rotation to {}; it carries the key {}",
+                          rotation.name_with_rev,
+                          key.name_with_rev());
+                return Ok(false);
+            }
+            outputln!("Installed ring key {}", rotation.name_with_rev);
+            self.keys.push(key);
+            self.keys.sort_by(|a, b| b.rev.cmp(&a.rev));
+        }
+        if rotation.stage >= RotationStage::Activate &&
+           self.active.as_ref() != Some(&rotation.name_with_rev) {
+            outputln!("Sending messages encrypted with ring key {}",
+                      rotation.name_with_rev);
+            self.active = Some(rotation.name_with_rev.clone());
+        }
+        if rotation.stage == RotationStage::Retire {
+            try!(self.retire_all_but(&rotation.name_with_rev));
+        }
+        self.rotations.insert(rotation.name_with_rev.clone(), rotation.stage);
+        Ok(true)
+    }
+
+    /// Forgets every revision but one, and removes them from the key cache.
+    fn retire_all_but(&mut self, name_with_rev: &str) -> Result<()> {
+        let (keep, retired): (Vec<SymKey>, Vec<SymKey>) =
+            self.keys.drain(..).partition(|k| k.name_with_rev() == name_with_rev);
+        self.keys = keep;
+        for key in retired.iter() {
+            if let Ok(path) = SymKey::get_secret_key_path(&key.name_with_rev(),
+                                                          &self.cache_key_path) {
+                try!(fs::remove_file(&path));
+            }
+            outputln!("Retired ring key {}", key.name_with_rev());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Read;
+
+    use hcore::crypto::SymKey;
+    use tempdir::TempDir;
+    use uuid::Uuid;
+
+    use super::{RingKeyRotation, RingKeys, RotationStage};
+
+    fn key_contents(key: &SymKey, cache: &TempDir) -> String {
+        let path = SymKey::get_secret_key_path(&key.name_with_rev(), cache.path()).unwrap();
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn rotates_through_every_stage() {
+        let ours = TempDir::new("ring_keys").unwrap();
+        let theirs = TempDir::new("ring_keys").unwrap();
+        let old = SymKey::generate_pair_for_ring("beyonce", ours.path()).unwrap();
+        let old_contents = key_contents(&old, &ours);
+        SymKey::write_file_from_str(&old_contents, theirs.path()).unwrap();
+        // Revisions are timestamps to the second
+        ::std::thread::sleep(::std::time::Duration::from_millis(1100));
+        let new = SymKey::generate_pair_for_ring("beyonce", theirs.path()).unwrap();
+        let new_contents = key_contents(&new, &theirs);
+        let old_path = SymKey::get_secret_key_path(&old.name_with_rev(), ours.path()).unwrap();
+
+        let mut ring_keys = RingKeys::load(&old.name_with_rev(), ours.path()).unwrap();
+        assert_eq!(ring_keys.keys().len(), 1);
+        let mut rotation = RingKeyRotation {
+            id: Uuid::new_v4(),
+            name_with_rev: new.name_with_rev(),
+            key: new_contents,
+            stage: RotationStage::Distribute,
+        };
+
+        assert!(ring_keys.process(&rotation).unwrap());
+        assert!(!ring_keys.process(&rotation).unwrap());
+        assert_eq!(ring_keys.keys().len(), 2);
+        assert_eq!(ring_keys.active().unwrap().name_with_rev(), old.name_with_rev());
+
+        rotation.stage = RotationStage::Activate;
+        assert!(ring_keys.process(&rotation).unwrap());
+        assert_eq!(ring_keys.active().unwrap().name_with_rev(), new.name_with_rev());
+        assert_eq!(ring_keys.keys().len(), 2);
+
+        rotation.stage = RotationStage::Retire;
+        assert!(ring_keys.process(&rotation).unwrap());
+        assert_eq!(ring_keys.keys().len(), 1);
+        assert_eq!(ring_keys.active().unwrap().name_with_rev(), new.name_with_rev());
+        assert!(!old_path.exists());
+
+        // A late rotation to the old revision does not bring it back
+        let stale = RingKeyRotation {
+            id: Uuid::new_v4(),
+            name_with_rev: old.name_with_rev(),
+            key: old_contents,
+            stage: RotationStage::Distribute,
+        };
+        assert!(!ring_keys.process(&stale).unwrap());
+        assert_eq!(ring_keys.keys().len(), 1);
+    }
+
+    #[test]
+    fn ignores_rotations_without_a_ring_key() {
+        let cache = TempDir::new("ring_keys").unwrap();
+        let key = SymKey::generate_pair_for_ring("beyonce", cache.path()).unwrap();
+        let mut ring_keys = RingKeys::new(cache.path());
+        let rotation = RingKeyRotation {
+            id: Uuid::new_v4(),
+            name_with_rev: key.name_with_rev(),
+            key: key_contents(&key, &cache),
+            stage: RotationStage::Activate,
+        };
+        assert!(!ring_keys.process(&rotation).unwrap());
+        assert!(ring_keys.active().is_none());
+    }
+}
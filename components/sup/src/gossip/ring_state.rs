@@ -0,0 +1,221 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The ring state a supervisor keeps on disk, so it can rejoin the ring as itself after a
+//! restart.
+//!
+//! A supervisor saves its own member entry, the members it knows about and the rumors worth
+//! keeping to `/hab/sup/ring-<gossip port>.json` every few seconds, and once more as it leaves
+//! the ring. When it starts again, it takes back its member id with a higher incarnation, and
+//! gossips with the members it knew even if its `--peer`s are gone.
+//!
+//! Only census entries, gossip files and the departures of other members are kept: elections
+//! only matter while they run, events would be delivered to the `event` hook twice, ring key
+//! rotations carry the ring key, and our own departures would have the ring believe we left
+//! again.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::json;
+
+use error::Result;
+use gossip::member::{Member, MemberId, MemberList};
+use gossip::rumor::{Message, Rumor, RumorList};
+
+/// The directory the ring state is kept in
+pub const RING_STATE_DIR: &'static str = "/hab/sup";
+/// The permissions of the ring state, which only the supervisor's user can read
+const RING_STATE_PERMISSIONS: u32 = 0o600;
+
+/// What a supervisor knows of the ring, as saved to disk.
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct RingState {
+    /// Our own member entry
+    pub member: Member,
+    /// Every other member we know about
+    pub members: Vec<Member>,
+    /// The rumors worth keeping across a restart
+    pub rumors: Vec<Rumor>,
+}
+
+impl RingState {
+    /// The path of the ring state of the supervisor gossiping on `listen_port`; each supervisor
+    /// on a host has its own.
+    pub fn path(listen_port: u16) -> PathBuf {
+        Path::new(RING_STATE_DIR).join(format!("ring-{}.json", listen_port))
+    }
+
+    /// Takes a snapshot of our member list and rumors.
+    pub fn capture(member_list: &MemberList, rumor_list: &RumorList) -> RingState {
+        RingState {
+            member: member_list.get(&member_list.my_id).unwrap().clone(),
+            members: member_list.values()
+                .filter(|m| m.id != member_list.my_id)
+                .cloned()
+                .collect(),
+            rumors: rumor_list.values()
+                .filter(|r| is_kept(&member_list.my_id, &r.payload))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Loads the ring state saved at `path`; `None` if there is none.
+    ///
+    /// # Failures
+    ///
+    /// * The ring state cannot be read or decoded
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<RingState>> {
+        if !path.as_ref().exists() {
+            return Ok(None);
+        }
+        let mut file = try!(File::open(path.as_ref()));
+        let mut buf = String::new();
+        try!(file.read_to_string(&mut buf));
+        Ok(Some(try!(json::decode(&buf))))
+    }
+
+    /// Saves the ring state to `path`. It is written to a temporary file first, so a crash never
+    /// leaves half of it behind. Only the supervisor's user can read it, as census entries carry
+    /// the configuration members export.
+    ///
+    /// # Failures
+    ///
+    /// * The ring state cannot be written
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let tmp_path = path.with_extension("json.write");
+        {
+            let mut file = try!(OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(RING_STATE_PERMISSIONS)
+                .open(&tmp_path));
+            try!(fs::set_permissions(&tmp_path,
+                                     fs::Permissions::from_mode(RING_STATE_PERMISSIONS)));
+            try!(file.write_all(try!(json::encode(self)).as_bytes()));
+        }
+        try!(fs::rename(&tmp_path, path));
+        Ok(())
+    }
+
+    /// Our member entry as we rejoin the ring: `fresh`, with its current addresses, but with our
+    /// previous id and an incarnation that trumps any rumor of our death or departure.
+    pub fn rejoin_as(&self, mut fresh: Member) -> Member {
+        fresh.id = self.member.id.clone();
+        fresh.incarnation = self.member.incarnation.clone();
+        fresh.incarnation.increment();
+        fresh
+    }
+}
+
+fn is_kept(my_id: &MemberId, message: &Message) -> bool {
+    match *message {
+        Message::CensusEntry(_) |
+        Message::GossipFile(_) => true,
+        Message::Departure(ref departure) => departure.member_id != *my_id,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::str::FromStr;
+
+    use common::event::Event;
+    use hcore::service::ServiceGroup;
+    use tempdir::TempDir;
+
+    use census::CensusEntry;
+    use gossip::member::{Departure, Health, Member, MemberList};
+    use gossip::rumor::{Message, Rumor, RumorList};
+    use super::RingState;
+
+    fn member() -> Member {
+        Member::new(String::from("hostname"),
+                    String::from("127.0.0.1"),
+                    String::from("127.0.0.1:9634"),
+                    false)
+    }
+
+    fn ring() -> (MemberList, RumorList) {
+        let me = member();
+        let other = member();
+        let mut ml = MemberList::new(me.clone());
+        ml.insert(other.clone());
+        let mut rl = RumorList::new();
+        rl.add_rumor(Rumor::member(me.clone()));
+        rl.add_rumor(Rumor::member(other));
+        rl.add_rumor(Rumor::census_entry(CensusEntry::new("soup", "unit", me.id)));
+        let event = Event::new(ServiceGroup::from_str("soup.unit").unwrap(),
+                               "cache",
+                               String::from("flush"),
+                               60)
+            .unwrap();
+        rl.add_rumor(Rumor::event(event));
+        rl.add_rumor(Rumor::departure(Departure::of(&me)));
+        (ml, rl)
+    }
+
+    #[test]
+    fn capture_keeps_members_and_lasting_rumors() {
+        let (ml, rl) = ring();
+        let state = RingState::capture(&ml, &rl);
+        assert_eq!(state.member.id, ml.my_id);
+        assert_eq!(state.members.len(), 1);
+        assert!(state.members[0].id != ml.my_id);
+        assert_eq!(state.rumors.len(), 1);
+        match state.rumors[0].payload {
+            Message::CensusEntry(_) => {}
+            _ => panic!("Only the census entry should be kept, not our own departure"),
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new("ring_state").unwrap();
+        let path = dir.path().join("sup").join("ring-9634.json");
+        assert!(RingState::load(&path).unwrap().is_none());
+
+        let (ml, rl) = ring();
+        let state = RingState::capture(&ml, &rl);
+        state.save(&path).unwrap();
+        assert_eq!(RingState::load(&path).unwrap(), Some(state));
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn rejoin_as_keeps_our_id() {
+        let (mut ml, rl) = ring();
+        let my_id = ml.my_id.clone();
+        ml.leave();
+        let state = RingState::capture(&ml, &rl);
+        let fresh = member();
+        let member = state.rejoin_as(fresh.clone());
+        assert_eq!(member.id, my_id);
+        assert!(member.incarnation > state.member.incarnation);
+        assert_eq!(member.health, Health::Alive);
+        assert_eq!(member.gossip_listener, fresh.gossip_listener);
+    }
+}
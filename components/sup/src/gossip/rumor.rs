@@ -20,7 +20,11 @@
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
+use common;
+use common::event::Event;
 use common::gossip_file::GossipFile;
+use common::message::swim;
+use common::wire_message::{ProtoConvert, uuid_from_proto};
 use uuid::Uuid;
 
 use census::CensusEntry;
@@ -29,7 +33,8 @@ use election::Election;
 /// How many times does a rumor get shared with a member before we stop sharing it?
 pub const COLD_AFTER: usize = 3;
 
-use gossip::member::{Member, MemberId};
+use gossip::member::{Departure, Health, Member, MemberId};
+use gossip::ring_keys::RingKeyRotation;
 
 /// A Peer is a representation of a member; it tracks how to contact the member, and whether this
 /// request is actually being proxied during a PingReq operation.
@@ -53,6 +58,40 @@ impl Peer {
     }
 }
 
+impl ProtoConvert for Peer {
+    type Proto = swim::Peer;
+
+    fn to_proto(&self) -> swim::Peer {
+        let mut proto = swim::Peer::new();
+        proto.set_member_id(self.member_id.to_string());
+        proto.set_listening_on(self.listening_on.clone());
+        if let Some(ref proxy_through) = self.proxy_through {
+            proto.set_proxy_through(proxy_through.clone());
+        }
+        if let Some(ref proxy_to) = self.proxy_to {
+            proto.set_proxy_to(proxy_to.clone());
+        }
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Peer) -> common::Result<Peer> {
+        Ok(Peer {
+            member_id: try!(uuid_from_proto(proto.get_member_id())),
+            listening_on: proto.take_listening_on(),
+            proxy_through: if proto.has_proxy_through() {
+                Some(proto.take_proxy_through())
+            } else {
+                None
+            },
+            proxy_to: if proto.has_proxy_to() {
+                Some(proto.take_proxy_to())
+            } else {
+                None
+            },
+        })
+    }
+}
+
 /// The SWIM Protocol.
 #[derive(Debug, RustcDecodable, RustcEncodable)]
 pub enum Protocol {
@@ -62,6 +101,46 @@ pub enum Protocol {
     Inject(RumorList),
 }
 
+impl ProtoConvert for Protocol {
+    type Proto = swim::Swim;
+
+    fn to_proto(&self) -> swim::Swim {
+        let mut proto = swim::Swim::new();
+        let (swim_type, peer, rumors) = match *self {
+            Protocol::Ping(ref peer, ref rumors) => (swim::SwimType::PING, Some(peer), rumors),
+            Protocol::Ack(ref peer, ref rumors) => (swim::SwimType::ACK, Some(peer), rumors),
+            Protocol::PingReq(ref peer, ref rumors) => {
+                (swim::SwimType::PINGREQ, Some(peer), rumors)
+            }
+            Protocol::Inject(ref rumors) => (swim::SwimType::INJECT, None, rumors),
+        };
+        proto.set_field_type(swim_type);
+        if let Some(peer) = peer {
+            proto.set_peer(peer.to_proto());
+        }
+        proto.set_rumors(rumors.to_proto());
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Swim) -> common::Result<Protocol> {
+        let rumors = try!(RumorList::from_proto(proto.take_rumors()));
+        if proto.get_field_type() == swim::SwimType::INJECT {
+            return Ok(Protocol::Inject(rumors));
+        }
+        if !proto.has_peer() {
+            return Err(common::Error::WireDecode(format!("{:?} without a peer",
+                                                         proto.get_field_type())));
+        }
+        let peer = try!(Peer::from_proto(proto.take_peer()));
+        Ok(match proto.get_field_type() {
+            swim::SwimType::PING => Protocol::Ping(peer, rumors),
+            swim::SwimType::ACK => Protocol::Ack(peer, rumors),
+            swim::SwimType::PINGREQ => Protocol::PingReq(peer, rumors),
+            swim::SwimType::INJECT => Protocol::Inject(rumors),
+        })
+    }
+}
+
 /// Rumors contain Messages as their payload, which are then processed by the correct internal
 /// sub-system.
 #[derive(Debug, RustcDecodable, RustcEncodable, Clone, PartialEq, Eq)]
@@ -71,6 +150,23 @@ pub enum Message {
     Election(Election),
     GossipFile(GossipFile),
     Blank,
+    RingKeyRotation(RingKeyRotation),
+    Event(Event),
+    Departure(Departure),
+}
+
+impl Message {
+    /// The first protocol version which understands this message. A supervisor speaking an older
+    /// version can't decode a rumor list carrying it, so we don't send it to them.
+    pub fn protocol_version(&self) -> u32 {
+        match *self {
+            Message::RingKeyRotation(_) => 3,
+            Message::Event(_) => 4,
+            Message::Departure(_) => 5,
+            Message::Member(ref member) if member.health == Health::Departed => 5,
+            _ => 1,
+        }
+    }
 }
 
 /// A UUID for Rumors. In practice, always matches the UUID of a message payload.
@@ -116,6 +212,30 @@ impl Rumor {
         }
     }
 
+    /// Create a new rumor with a `Message::RingKeyRotation` payload.
+    pub fn ring_key_rotation(rotation: RingKeyRotation) -> Rumor {
+        Rumor {
+            id: rotation.id.clone(),
+            payload: Message::RingKeyRotation(rotation),
+        }
+    }
+
+    /// Create a new rumor with a `Message::Event` payload.
+    pub fn event(event: Event) -> Rumor {
+        Rumor {
+            id: event.id.clone(),
+            payload: Message::Event(event),
+        }
+    }
+
+    /// Create a new rumor with a `Message::Departure` payload.
+    pub fn departure(departure: Departure) -> Rumor {
+        Rumor {
+            id: departure.id.clone(),
+            payload: Message::Departure(departure),
+        }
+    }
+
     /// Create a new rumor with a 'Blank' payload.
     pub fn blank() -> Rumor {
         Rumor {
@@ -125,6 +245,93 @@ impl Rumor {
     }
 }
 
+impl ProtoConvert for Rumor {
+    type Proto = swim::Rumor;
+
+    fn to_proto(&self) -> swim::Rumor {
+        let mut proto = swim::Rumor::new();
+        proto.set_id(self.id.to_string());
+        let rumor_type = match self.payload {
+            Message::Member(ref member) => {
+                proto.set_member(member.to_proto());
+                swim::RumorType::MEMBER
+            }
+            Message::CensusEntry(ref ce) => {
+                proto.set_census_entry(ce.to_proto());
+                swim::RumorType::CENSUS_ENTRY
+            }
+            Message::Election(ref election) => {
+                proto.set_election(election.to_proto());
+                swim::RumorType::ELECTION
+            }
+            Message::GossipFile(ref gossip_file) => {
+                proto.set_gossip_file(gossip_file.to_proto());
+                swim::RumorType::GOSSIP_FILE
+            }
+            Message::Blank => swim::RumorType::BLANK,
+            Message::RingKeyRotation(ref rotation) => {
+                proto.set_ring_key_rotation(rotation.to_proto());
+                swim::RumorType::RING_KEY_ROTATION
+            }
+            Message::Event(ref event) => {
+                proto.set_event(event.to_proto());
+                swim::RumorType::EVENT
+            }
+            Message::Departure(ref departure) => {
+                proto.set_departure(departure.to_proto());
+                swim::RumorType::DEPARTURE
+            }
+        };
+        proto.set_field_type(rumor_type);
+        proto
+    }
+
+    fn from_proto(mut proto: swim::Rumor) -> common::Result<Rumor> {
+        let rumor_type = proto.get_field_type();
+        let has_payload = match rumor_type {
+            swim::RumorType::MEMBER => proto.has_member(),
+            swim::RumorType::CENSUS_ENTRY => proto.has_census_entry(),
+            swim::RumorType::ELECTION => proto.has_election(),
+            swim::RumorType::GOSSIP_FILE => proto.has_gossip_file(),
+            swim::RumorType::BLANK => true,
+            swim::RumorType::RING_KEY_ROTATION => proto.has_ring_key_rotation(),
+            swim::RumorType::EVENT => proto.has_event(),
+            swim::RumorType::DEPARTURE => proto.has_departure(),
+        };
+        if !has_payload {
+            return Err(common::Error::WireDecode(format!("{:?} rumor without its message",
+                                                         rumor_type)));
+        }
+        let payload = match rumor_type {
+            swim::RumorType::MEMBER => {
+                Message::Member(try!(Member::from_proto(proto.take_member())))
+            }
+            swim::RumorType::CENSUS_ENTRY => {
+                Message::CensusEntry(try!(CensusEntry::from_proto(proto.take_census_entry())))
+            }
+            swim::RumorType::ELECTION => {
+                Message::Election(try!(Election::from_proto(proto.take_election())))
+            }
+            swim::RumorType::GOSSIP_FILE => {
+                Message::GossipFile(try!(GossipFile::from_proto(proto.take_gossip_file())))
+            }
+            swim::RumorType::BLANK => Message::Blank,
+            swim::RumorType::RING_KEY_ROTATION => {
+                let rotation = proto.take_ring_key_rotation();
+                Message::RingKeyRotation(try!(RingKeyRotation::from_proto(rotation)))
+            }
+            swim::RumorType::EVENT => Message::Event(try!(Event::from_proto(proto.take_event()))),
+            swim::RumorType::DEPARTURE => {
+                Message::Departure(try!(Departure::from_proto(proto.take_departure())))
+            }
+        };
+        Ok(Rumor {
+            id: try!(uuid_from_proto(proto.get_id())),
+            payload: payload,
+        })
+    }
+}
+
 /// A list of rumors, and their corresponding heat. Heat determines whether we need to share the
 /// rumor with a given Member
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -238,6 +445,19 @@ impl RumorList {
         hot_rumors
     }
 
+    /// Get a RumorList with only the rumors a member speaking `protocol_version` understands; a
+    /// member which didn't tell us its version is treated as speaking version 1.
+    pub fn understood_by(&self, protocol_version: Option<u32>) -> RumorList {
+        let protocol_version = protocol_version.unwrap_or(1);
+        let mut understood = RumorList::new();
+        for (rumor_id, rumor) in self.rumors.iter() {
+            if rumor.payload.protocol_version() <= protocol_version {
+                understood.rumors.insert(rumor_id.clone(), rumor.clone());
+            }
+        }
+        understood
+    }
+
     /// Return the heat for a given member and rumor.
     pub fn heat_for(&self, member_id: &MemberId, rumor_id: &RumorId) -> usize {
         if self.heat.contains_key(member_id) {
@@ -288,6 +508,56 @@ impl RumorList {
         }
     }
 
+    /// Removes the events which have expired; nobody would take them anymore.
+    pub fn prune_expired_events(&mut self) {
+        let mut prune_list: Vec<RumorId> = Vec::new();
+        for (rid, rumor) in self.rumors.iter() {
+            if let Message::Event(ref event) = rumor.payload {
+                if event.is_expired() {
+                    prune_list.push(rid.clone());
+                }
+            }
+        }
+        for rid in prune_list.iter() {
+            self.remove_rumor(&rid);
+        }
+    }
+
+    /// Removes the census entries of a member which has left the ring.
+    pub fn prune_census_entries_for(&mut self, member_id: &MemberId) {
+        let mut prune_list: Vec<RumorId> = Vec::new();
+        for (rid, rumor) in self.rumors.iter() {
+            if let Message::CensusEntry(ref ce) = rumor.payload {
+                if ce.member_id == *member_id {
+                    prune_list.push(rid.clone());
+                }
+            }
+        }
+        for rid in prune_list.iter() {
+            self.remove_rumor(&rid);
+        }
+    }
+
+    /// Removes every rumor about a member we have reaped: the member itself, its departures and
+    /// its census entries.
+    pub fn prune_member(&mut self, member_id: &MemberId) {
+        self.prune_census_entries_for(member_id);
+        let mut prune_list: Vec<RumorId> = Vec::new();
+        for (rid, rumor) in self.rumors.iter() {
+            match rumor.payload {
+                Message::Member(ref member) if member.id == *member_id => {
+                    prune_list.push(rid.clone())
+                }
+                Message::Departure(ref departure) if departure.member_id == *member_id => {
+                    prune_list.push(rid.clone())
+                }
+                _ => {}
+            }
+        }
+        for rid in prune_list.iter() {
+            self.remove_rumor(&rid);
+        }
+    }
 
     pub fn remove_rumor(&mut self, rumor_id: &RumorId) {
         self.rumors.remove(rumor_id);
@@ -297,6 +567,31 @@ impl RumorList {
     }
 }
 
+/// Only the rumors are sent; the heat is ours alone, and a decoded list has none.
+impl ProtoConvert for RumorList {
+    type Proto = swim::RumorList;
+
+    fn to_proto(&self) -> swim::RumorList {
+        let mut proto = swim::RumorList::new();
+        for rumor in self.rumors.values() {
+            proto.mut_rumors().push(rumor.to_proto());
+        }
+        proto
+    }
+
+    fn from_proto(mut proto: swim::RumorList) -> common::Result<RumorList> {
+        let mut rumors = HashMap::new();
+        for rumor in proto.take_rumors().into_iter() {
+            let rumor = try!(Rumor::from_proto(rumor));
+            rumors.insert(rumor.id.clone(), rumor);
+        }
+        Ok(RumorList {
+            rumors: rumors,
+            heat: HashMap::new(),
+        })
+    }
+}
+
 impl Deref for RumorList {
     type Target = HashMap<RumorId, Rumor>;
 
@@ -314,8 +609,15 @@ impl DerefMut for RumorList {
 #[cfg(test)]
 mod test {
     mod rumor_list {
+        use std::str::FromStr;
+
+        use common::event::Event;
+        use hcore::service::ServiceGroup;
+
         use gossip::rumor::{Rumor, RumorId, RumorList};
-        use gossip::member::MemberId;
+        use census::CensusEntry;
+        use gossip::member::{Departure, Health, Member, MemberId};
+        use gossip::ring_keys::{RingKeyRotation, RotationStage};
 
         #[test]
         fn add_rumor() {
@@ -342,5 +644,154 @@ mod test {
             let fake_member = MemberId::new_v4();
             assert_eq!(0, rl.heat_for(&fake_member, &rumor_id));
         }
+
+        #[test]
+        fn understood_by() {
+            let blank = Rumor::blank();
+            let rotation = Rumor::ring_key_rotation(RingKeyRotation {
+                id: RumorId::new_v4(),
+                name_with_rev: String::from("beyonce-20160101000000"),
+                key: String::new(),
+                stage: RotationStage::Distribute,
+            });
+            let mut rl = RumorList::new();
+            rl.add_rumor(blank.clone());
+            rl.add_rumor(rotation);
+            assert_eq!(rl.understood_by(Some(3)).len(), 2);
+            let old = rl.understood_by(None);
+            assert_eq!(old.len(), 1);
+            assert!(old.contains_key(&blank.id));
+        }
+
+        #[test]
+        fn departures_need_protocol_5() {
+            let mut member = Member::new(String::from("bobo"),
+                                         String::from("192.168.1.1"),
+                                         String::from("192.168.1.1:9634"),
+                                         false);
+            let mut rl = RumorList::new();
+            rl.add_rumor(Rumor::member(member.clone()));
+            assert_eq!(rl.understood_by(Some(4)).len(), 1);
+            member.health = Health::Departed;
+            rl.add_rumor(Rumor::member(member.clone()));
+            rl.add_rumor(Rumor::departure(Departure::new(member.id.clone())));
+            assert_eq!(rl.understood_by(Some(4)).len(), 0);
+            assert_eq!(rl.understood_by(Some(5)).len(), 2);
+        }
+
+        #[test]
+        fn prune_member() {
+            let member = Member::new(String::from("bobo"),
+                                     String::from("192.168.1.1"),
+                                     String::from("192.168.1.1:9634"),
+                                     false);
+            let ce = CensusEntry::new("redis", "default", member.id.clone());
+            let mut rl = RumorList::new();
+            rl.add_rumor(Rumor::member(member.clone()));
+            rl.add_rumor(Rumor::census_entry(ce));
+            rl.add_rumor(Rumor::departure(Departure::new(member.id.clone())));
+            rl.add_rumor(Rumor::blank());
+            rl.prune_census_entries_for(&member.id);
+            assert_eq!(rl.len(), 3);
+            rl.prune_member(&member.id);
+            assert_eq!(rl.len(), 1);
+        }
+
+        #[test]
+        fn prune_expired_events() {
+            let group = ServiceGroup::from_str("redis.default").unwrap();
+            let fresh = Event::new(group.clone(), "cache", String::new(), 60).unwrap();
+            let mut expired = Event::new(group, "cache", String::new(), 60).unwrap();
+            expired.published_at -= 61;
+            let mut rl = RumorList::new();
+            rl.add_rumor(Rumor::event(fresh.clone()));
+            rl.add_rumor(Rumor::event(expired));
+            rl.add_rumor(Rumor::blank());
+            rl.prune_expired_events();
+            assert_eq!(rl.len(), 2);
+            assert!(rl.contains_key(&fresh.id));
+        }
+    }
+
+    mod protocol {
+        use std::str::FromStr;
+
+        use common::event::Event;
+        use common::wire_message::{Encoding, WireMessage};
+        use hcore::service::ServiceGroup;
+
+        use census::CensusEntry;
+        use election::Election;
+        use gossip::member::{Departure, Member};
+        use gossip::ring_keys::{RingKeyRotation, RotationStage};
+        use gossip::rumor::{Peer, Protocol, Rumor, RumorId, RumorList};
+
+        fn rumor_list() -> RumorList {
+            let member = Member::new(String::from("bobo"),
+                                     String::from("192.168.1.1"),
+                                     String::from("192.168.1.1:9634"),
+                                     false);
+            let mut ce = CensusEntry::new("redis", "default", member.id.clone());
+            ce.exposes = Some(vec![String::from("6379")]);
+            ce.bad_release(String::from("core/redis/3.2.1/20160101000000"));
+            let election = Election::new(String::from("redis"),
+                                         String::from("default"),
+                                         member.id.clone(),
+                                         10,
+                                         1);
+            let group = ServiceGroup::from_str("redis.default").unwrap();
+            let mut rl = RumorList::new();
+            rl.add_rumor(Rumor::member(member.clone()));
+            rl.add_rumor(Rumor::census_entry(ce));
+            rl.add_rumor(Rumor::election(election));
+            rl.add_rumor(Rumor::blank());
+            rl.add_rumor(Rumor::ring_key_rotation(RingKeyRotation {
+                id: RumorId::new_v4(),
+                name_with_rev: String::from("beyonce-20160101000000"),
+                key: String::from("SYM-SEC-1"),
+                stage: RotationStage::Activate,
+            }));
+            rl.add_rumor(Rumor::event(Event::new(group, "cache", String::from("flush"), 60)
+                .unwrap()));
+            rl.add_rumor(Rumor::departure(Departure::new(member.id.clone())));
+            rl
+        }
+
+        #[test]
+        fn round_trips_in_every_encoding() {
+            let rl = rumor_list();
+            let mut peer = Peer::new(RumorId::new_v4(), String::from("192.168.1.1:9634"));
+            peer.proxy_to = Some(String::from("192.168.1.2:9634"));
+            for encoding in &[Encoding::Json, Encoding::Binary] {
+                let bytes = WireMessage::plain_with_encoding(&Protocol::Ping(peer.clone(),
+                                                                             rl.clone()),
+                                                             *encoding)
+                    .unwrap()
+                    .to_bytes()
+                    .unwrap();
+                let wire_msg = WireMessage::from_bytes(&bytes).unwrap();
+                assert_eq!(wire_msg.encoding(), *encoding);
+                match wire_msg.proto_msg_with_keys(None).unwrap() {
+                    Protocol::Ping(decoded_peer, decoded_rl) => {
+                        assert_eq!(decoded_peer.member_id, peer.member_id);
+                        assert_eq!(decoded_peer.proxy_to, peer.proxy_to);
+                        assert_eq!(decoded_peer.proxy_through, None);
+                        assert_eq!(decoded_rl.rumors, rl.rumors);
+                    }
+                    protocol => panic!("Decoded {:?} instead of a ping", protocol),
+                }
+            }
+        }
+
+        #[test]
+        fn binary_messages_need_a_protocol_buffer_type() {
+            let bytes = WireMessage::plain_with_encoding(&Protocol::Inject(rumor_list()),
+                                                         Encoding::Binary)
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+            let wire_msg = WireMessage::from_bytes(&bytes).unwrap();
+            assert!(wire_msg.msg::<RumorList>(None).is_err());
+        }
     }
 }
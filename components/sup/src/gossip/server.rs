@@ -20,22 +20,30 @@
 //! * An inbound listener, which receives SWIM Protocol messages.
 //! * An outbound distributor, which initiates outbound pings to members
 //! * A failure detector, which tracks outbound connections and times them out
+//!
+//! A reaper can also be started, which removes members from the ring once they have been
+//! confirmed dead or departed for long enough.
 
 use threadpool::ThreadPool;
 
 use std::thread;
-use std::ops::Deref;
 use std::time::Duration;
 use std::sync::{Arc, RwLock};
 use std::net;
+use std::path::PathBuf;
 
+use common::event::EventList;
 use common::gossip_file::GossipFileList;
-use hcore::crypto::{default_cache_key_path, SymKey};
+use common::wire_message::Encoding;
+use hcore::crypto::default_cache_key_path;
 use hcore::service::ServiceGroup;
+use rand::{thread_rng, Rng};
 use utp::{UtpListener, UtpSocket};
 
 use gossip::client::Client;
-use gossip::member::{Member, MemberList, Health};
+use gossip::member::{Departure, Member, MemberList, Health};
+use gossip::ring_keys::RingKeys;
+use gossip::ring_state::RingState;
 use gossip::rumor::{Peer, Protocol, Rumor, RumorList, Message};
 use gossip::detector::Detector;
 use election::ElectionList;
@@ -50,9 +58,31 @@ static OUTBOUND_INTERVAL: u64 = 200;
 static OUTBOUND_MAX_THREADS: usize = 5;
 /// How many inbound threads do we allow?
 static INBOUND_MAX_THREADS: usize = 5;
+/// How often the reaper looks for members to remove, in milliseconds
+static REAP_INTERVAL: u64 = 1000;
+/// How many members we tell directly that we are leaving the ring
+static LEAVE_MEMBERS: usize = 5;
+/// How often we save our ring state, in milliseconds
+static RING_STATE_INTERVAL: u64 = 10000;
 /// The default port for the Gossip protocol
 pub static GOSSIP_DEFAULT_PORT: usize = 9634;
 
+/// The gossip lists kept for each service this supervisor runs. Every service tracks the whole
+/// ring, but elects and writes files for its own service group.
+#[derive(Clone)]
+pub struct ServiceGossip {
+    /// The service group these lists belong to
+    pub service_group: String,
+    /// Our list of censuses
+    pub census_list: Arc<RwLock<CensusList>>,
+    /// The list of elections
+    pub election_list: Arc<RwLock<ElectionList>>,
+    /// The list of gossip files
+    pub gossip_file_list: Arc<RwLock<GossipFileList>>,
+    /// The events gossiped to the service group
+    pub event_list: Arc<RwLock<EventList>>,
+}
+
 /// A gossip server
 pub struct Server {
     /// The port to listen for inbound connections on
@@ -61,32 +91,31 @@ pub struct Server {
     pub member_list: Arc<RwLock<MemberList>>,
     /// Our list of rumors to share
     pub rumor_list: Arc<RwLock<RumorList>>,
-    /// Our list of censuses
-    pub census_list: Arc<RwLock<CensusList>>,
     /// The failure detector
     pub detector: Arc<RwLock<Detector>>,
-    /// The list of elections
-    pub election_list: Arc<RwLock<ElectionList>>,
-    /// The list of gossip files
-    pub gossip_file_list: Arc<RwLock<GossipFileList>>,
+    /// The gossip lists for each service we run
+    pub services: Arc<RwLock<Vec<ServiceGossip>>>,
     /// Our 'peer' entry, used to generate SWIM protocol messages.
     pub peer: Peer,
-    /// An optional ring key used to encrypt messages with peers
-    ring_key: Arc<Option<SymKey>>,
+    /// The revisions of the ring key used to encrypt messages with peers, if we have one
+    ring_keys: Arc<RwLock<RingKeys>>,
+    /// Where we save our ring state, if we keep one
+    ring_state_path: Option<PathBuf>,
+    /// Our census entries from before we restarted, waiting for their services to be added
+    previous_census: RwLock<Vec<CensusEntry>>,
 }
 
 impl Server {
-    /// Creates a new Server. Creates our own entry in the census and membership lists, and writes
-    /// a rumor that this server is alive.
+    /// Creates a new Server. Creates our own entry in the membership list, and writes a rumor
+    /// that this server is alive.
+    ///
+    /// If we saved our ring state at `ring_state_path` before we restarted, we rejoin the ring as
+    /// the member we were, and start out knowing the members and rumors we knew then.
     pub fn new(listen_ip: String,
                listen_port: u16,
                permanent: bool,
                ring_name_with_rev: Option<String>,
-               service: String,
-               group: String,
-               organization: Option<String>,
-               exposes: Option<Vec<String>>,
-               port: Option<String>)
+               ring_state_path: Option<PathBuf>)
                -> Server {
 
         let hostname = util::sys::hostname().unwrap_or(String::from("unknown"));
@@ -94,37 +123,43 @@ impl Server {
         let peer_listen = listen.clone();
         let peer_listen2 = peer_listen.clone();
 
-        let member = Member::new(hostname, listen_ip, peer_listen2, permanent);
+        let ring_state = match ring_state_path {
+            Some(ref path) => {
+                match RingState::load(path) {
+                    Ok(ring_state) => ring_state,
+                    Err(e) => {
+                        outputln!("Ignoring the ring state in {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
 
-        let service_group = format!("{}.{}", service, group);
-        let mut ce = CensusEntry::new(service.clone(), group.clone(), member.id.clone());
-        ce.exposes = exposes;
-        ce.port = port;
+        let mut member = Member::new(hostname, listen_ip, peer_listen2, permanent);
+        if let Some(ref ring_state) = ring_state {
+            member = ring_state.rejoin_as(member);
+            outputln!("Rejoining the ring with {} members we knew",
+                      ring_state.members.len());
+        }
         let my_id = member.id.clone();
-        let leader_id = member.id.clone();
         outputln!("Supervisor {}", member);
-        outputln!("Census {}", ce);
 
-        let census_list = CensusList::new(Census::new(ce.clone()));
-
-        let ring_key = match ring_name_with_rev {
-            Some(rnwr) => Some(SymKey::get_pair_for(&rnwr, &default_cache_key_path(None)).unwrap()),
-            None => None,
+        let ring_keys = match ring_name_with_rev {
+            Some(rnwr) => RingKeys::load(&rnwr, default_cache_key_path(None)).unwrap(),
+            None => RingKeys::new(default_cache_key_path(None)),
         };
 
         let server = Server {
             listen: listen,
             member_list: Arc::new(RwLock::new(MemberList::new(member.clone()))),
             rumor_list: Arc::new(RwLock::new(RumorList::new())),
-            census_list: Arc::new(RwLock::new(census_list)),
             peer: Peer::new(my_id, peer_listen),
             detector: Arc::new(RwLock::new(Detector::new())),
-            election_list: Arc::new(RwLock::new(ElectionList::new(service_group, leader_id))),
-            gossip_file_list:
-                Arc::new(RwLock::new(GossipFileList::new(ServiceGroup::new(service,
-                                                                           group,
-                                                                           organization)))),
-            ring_key: Arc::new(ring_key),
+            services: Arc::new(RwLock::new(Vec::new())),
+            ring_keys: Arc::new(RwLock::new(ring_keys)),
+            ring_state_path: ring_state_path,
+            previous_census: RwLock::new(Vec::new()),
         };
 
         // Write our Alive Rumor
@@ -134,14 +169,132 @@ impl Server {
             rl.add_rumor(rumor);
         }
 
+        if let Some(ring_state) = ring_state {
+            server.restore(ring_state);
+        }
+
+        server
+    }
+
+    /// Takes back the members and rumors of the ring state we saved before we restarted. Our own
+    /// census entries are set aside until their services are added again, and our own departures
+    /// are dropped; we are back.
+    fn restore(&self, ring_state: RingState) {
+        let my_id = self.peer.member_id.clone();
+        let mut ml = self.member_list.write().unwrap();
+        let mut rl = self.rumor_list.write().unwrap();
+        let mut previous_census = self.previous_census.write().unwrap();
+        for member in ring_state.members.into_iter() {
+            if member.id == my_id {
+                continue;
+            }
+            rl.add_rumor(Rumor::member(member.clone()));
+            ml.insert(member);
+        }
+        for rumor in ring_state.rumors.into_iter() {
+            match rumor.payload {
+                Message::CensusEntry(ref ce) if ce.member_id == my_id => {
+                    previous_census.push(ce.clone());
+                    continue;
+                }
+                Message::Departure(ref departure) if departure.member_id == my_id => continue,
+                _ => {}
+            }
+            rl.add_rumor(rumor);
+        }
+    }
+
+    /// Adds a service to the gossip server. Creates our own entry in the census for the service,
+    /// and writes a rumor about it.
+    ///
+    /// The new service starts out knowing every census entry the services we already run know
+    /// about, and they learn about the new service in turn.
+    pub fn add_service(&self,
+                       service: String,
+                       group: String,
+                       organization: Option<String>,
+                       exposes: Option<Vec<String>>,
+                       port: Option<String>)
+                       -> ServiceGossip {
+        let member_id = self.peer.member_id.clone();
+        let service_group = format!("{}.{}", service, group);
+        let previous = {
+            let mut previous_census = self.previous_census.write().unwrap();
+            let position = previous_census.iter()
+                .position(|ce| ce.service == service && ce.group == group);
+            position.map(|i| previous_census.remove(i))
+        };
+        let mut ce = match previous {
+            Some(mut ce) => {
+                ce.rejoin();
+                ce
+            }
+            None => CensusEntry::new(service.clone(), group.clone(), member_id.clone()),
+        };
+        ce.exposes = exposes;
+        ce.port = port;
+        outputln!("Census {}", ce);
+
+        let sg = ServiceGroup::new(service, group, organization);
+        let mut census_list = CensusList::new(Census::new(ce.clone()));
+        let mut gossip_file_list = GossipFileList::new(sg.clone());
+        // Take in the census entries and files we already have rumors of, which includes the
+        // ones we knew about before we restarted
+        {
+            let rl = self.rumor_list.read().unwrap();
+            for rumor in rl.values() {
+                match rumor.payload {
+                    Message::CensusEntry(ref other) if other.member_id != member_id => {
+                        census_list.process(other.clone());
+                    }
+                    Message::GossipFile(ref gf) => {
+                        gossip_file_list.process(gf.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut services = self.services.write().unwrap();
+        for existing in services.iter() {
+            {
+                let existing_cl = existing.census_list.read().unwrap();
+                for (_sg, census) in existing_cl.iter() {
+                    for (_id, existing_ce) in census.iter() {
+                        census_list.process(existing_ce.clone());
+                    }
+                }
+            }
+            existing.census_list.write().unwrap().process(ce.clone());
+        }
+
+        let service_gossip = ServiceGossip {
+            service_group: service_group.clone(),
+            census_list: Arc::new(RwLock::new(census_list)),
+            election_list: Arc::new(RwLock::new(ElectionList::new(service_group, member_id))),
+            gossip_file_list: Arc::new(RwLock::new(gossip_file_list)),
+            event_list: Arc::new(RwLock::new(EventList::new(sg))),
+        };
+        services.push(service_gossip.clone());
+
         // Write our Census Entry Rumor
         {
             let rumor = Rumor::census_entry(ce);
-            let mut rl = server.rumor_list.write().unwrap();
+            let mut rl = self.rumor_list.write().unwrap();
             rl.add_rumor(rumor);
         }
 
-        server
+        service_gossip
+    }
+
+    /// The revisions of the ring key shared with our peers.
+    pub fn ring_keys(&self) -> Arc<RwLock<RingKeys>> {
+        self.ring_keys.clone()
+    }
+
+    /// Stops gossiping on behalf of a service we no longer run.
+    pub fn remove_service(&self, service_group: &str) {
+        let mut services = self.services.write().unwrap();
+        services.retain(|sg| sg.service_group != service_group);
     }
 
     /// Starts the inbound gossip listener.
@@ -151,25 +304,23 @@ impl Server {
     /// * If we cannot bind to the listener
     pub fn start_inbound(&self) -> Result<()> {
         outputln!("Starting inbound gossip listener");
-        let key = self.ring_key.clone();
+        let key = self.ring_keys.clone();
         let ml = self.member_list.clone();
         let rl = self.rumor_list.clone();
-        let cl = self.census_list.clone();
+        let services = self.services.clone();
         let my_peer = self.peer.clone();
         let detector = self.detector.clone();
-        let el = self.election_list.clone();
-        let gfl = self.gossip_file_list.clone();
         let listener = try!(UtpListener::bind(&self.listen[..]));
         let _t = thread::Builder::new()
             .name("inbound".to_string())
-            .spawn(move || inbound(listener, key, my_peer, ml, rl, cl, detector, el, gfl));
+            .spawn(move || inbound(listener, key, my_peer, ml, rl, services, detector));
         Ok(())
     }
 
     /// Starts the outbound gossip distributor.
     pub fn start_outbound(&self) {
         outputln!("Starting outbound gossip distributor");
-        let key = self.ring_key.clone();
+        let key = self.ring_keys.clone();
         let ml = self.member_list.clone();
         let rl = self.rumor_list.clone();
         let my_peer = self.peer.clone();
@@ -182,7 +333,7 @@ impl Server {
     /// Starts the failure detector.
     pub fn start_failure_detector(&self) {
         outputln!("Starting gossip failure detector");
-        let key = self.ring_key.clone();
+        let key = self.ring_keys.clone();
         let my_peer = self.peer.clone();
         let ml = self.member_list.clone();
         let rl = self.rumor_list.clone();
@@ -192,6 +343,90 @@ impl Server {
             .spawn(move || failure_detector(key, my_peer, ml, rl, detector));
     }
 
+    /// Starts the reaper, which removes members that have been confirmed dead or departed for
+    /// `reap_after_secs`.
+    pub fn start_reaper(&self, reap_after_secs: u64) {
+        outputln!("Starting gossip reaper; removing members dead for {} seconds",
+                  reap_after_secs);
+        let ml = self.member_list.clone();
+        let rl = self.rumor_list.clone();
+        let detector = self.detector.clone();
+        let _t = thread::Builder::new()
+            .name("reaper".to_string())
+            .spawn(move || reaper(ml, rl, detector, reap_after_secs));
+    }
+
+    /// Starts saving our ring state every few seconds, if we keep one.
+    pub fn start_ring_state_writer(&self) {
+        if let Some(ref path) = self.ring_state_path {
+            outputln!("Saving the ring state to {}", path.display());
+            let path = path.clone();
+            let ml = self.member_list.clone();
+            let rl = self.rumor_list.clone();
+            let _t = thread::Builder::new()
+                .name("ring_state".to_string())
+                .spawn(move || ring_state_writer(path, ml, rl));
+        }
+    }
+
+    /// Saves our ring state now, if we keep one.
+    ///
+    /// # Failures
+    ///
+    /// * The ring state cannot be written
+    pub fn save_ring_state(&self) -> Result<()> {
+        if let Some(ref path) = self.ring_state_path {
+            try!(capture_ring_state(&self.member_list, &self.rumor_list).save(path));
+        }
+        Ok(())
+    }
+
+    /// Leaves the ring as we shut down. We mark ourselves as departed, and tell a few of the
+    /// members still alive directly; we won't be around to gossip it, and they shouldn't have to
+    /// wait for the failure detector to notice we are gone.
+    pub fn leave(&self) {
+        let (member, targets) = {
+            let mut ml = self.member_list.write().unwrap();
+            let member = ml.leave();
+            let mut targets: Vec<Member> = ml.values()
+                .filter(|m| m.id != member.id && m.health == Health::Alive)
+                .cloned()
+                .collect();
+            thread_rng().shuffle(&mut targets);
+            targets.truncate(LEAVE_MEMBERS);
+            (member, targets)
+        };
+        outputln!("Leaving the ring");
+        let mut departure_rumors = RumorList::new();
+        departure_rumors.add_rumor(Rumor::member(member.clone()));
+        departure_rumors.add_rumor(Rumor::departure(Departure::of(&member)));
+        {
+            let mut rl = self.rumor_list.write().unwrap();
+            for rumor in departure_rumors.values() {
+                rl.add_rumor(rumor.clone());
+            }
+        }
+
+        for target in targets.iter() {
+            let rumors = departure_rumors.understood_by(target.protocol_version);
+            if rumors.is_empty() {
+                continue;
+            }
+            let mut c = match Client::new(&target.gossip_listener[..],
+                                          self.ring_keys.read().unwrap().clone()) {
+                Ok(c) => c,
+                Err(e) => {
+                    debug!("Failed to create a gossip client for {}: {}", target.id, e);
+                    continue;
+                }
+            };
+            c.set_encoding(Encoding::for_protocol(target.protocol_version));
+            if let Err(e) = c.inject(rumors) {
+                debug!("Failed to tell {} we are leaving: {:?}", target.id, e);
+            }
+        }
+    }
+
     /// Sends blocking SWIM requests to our initial gossip peers.
     ///
     /// # Errors
@@ -211,6 +446,10 @@ impl Server {
                     outputln!("Could not connect to any initial peers; attempt {} of {}.",
                               count,
                               fail_after);
+                    if self.member_list.read().unwrap().len() > 1 {
+                        outputln!("Rejoining through the members we knew before we restarted");
+                        return Ok(());
+                    }
                 }
             }
         }
@@ -221,7 +460,7 @@ impl Server {
         let mut initialized = false;
         for to in peer_listeners {
             outputln!("Joining gossip peer at {}", to);
-            let mut c = match Client::new(&to[..], self.ring_key.deref().as_ref()) {
+            let mut c = match Client::new(&to[..], self.ring_keys.read().unwrap().clone()) {
                 Ok(c) => c,
                 Err(e) => {
                     debug!("Error creating gossip client - {:?}", e);
@@ -230,9 +469,10 @@ impl Server {
                 }
             };
 
+            // We don't know which protocol version an initial peer speaks yet
             let ping_rumors = {
                 let rl = self.rumor_list.read().unwrap();
-                rl.clone()
+                rl.understood_by(None)
             };
 
             match c.ping(self.peer.clone(), ping_rumors) {
@@ -249,19 +489,45 @@ impl Server {
     }
 }
 
+fn capture_ring_state(member_list: &Arc<RwLock<MemberList>>,
+                      rumor_list: &Arc<RwLock<RumorList>>)
+                      -> RingState {
+    let ml = member_list.read().unwrap();
+    let rl = rumor_list.read().unwrap();
+    RingState::capture(&ml, &rl)
+}
+
+/// Saves our ring state to `path` every RING_STATE_INTERVAL. A failure is only logged when it
+/// starts, so a read-only `/hab/sup` doesn't flood the log.
+pub fn ring_state_writer(path: PathBuf,
+                         member_list: Arc<RwLock<MemberList>>,
+                         rumor_list: Arc<RwLock<RumorList>>) {
+    let mut failing = false;
+    loop {
+        thread::sleep(Duration::from_millis(RING_STATE_INTERVAL));
+        match capture_ring_state(&member_list, &rumor_list).save(&path) {
+            Ok(()) => failing = false,
+            Err(e) => {
+                if !failing {
+                    outputln!("Failed to save the ring state to {}: {}", path.display(), e);
+                }
+                failing = true;
+            }
+        }
+    }
+}
+
 /// Listens for incoming UTP requests, and spawns a thread to handle each. Handles at most
 /// INBOUND_MAX_THREADS concurrent requests.
 ///
 /// New requests are handled by passing them to `receive`.
 pub fn inbound(listener: UtpListener,
-               ring_key: Arc<Option<SymKey>>,
+               ring_keys: Arc<RwLock<RingKeys>>,
                my_peer: Peer,
                member_list: Arc<RwLock<MemberList>>,
                rumor_list: Arc<RwLock<RumorList>>,
-               census_list: Arc<RwLock<CensusList>>,
-               detector: Arc<RwLock<Detector>>,
-               election_list: Arc<RwLock<ElectionList>>,
-               gossip_file_list: Arc<RwLock<GossipFileList>>) {
+               services: Arc<RwLock<Vec<ServiceGossip>>>,
+               detector: Arc<RwLock<Detector>>) {
     let pool = ThreadPool::new(INBOUND_MAX_THREADS);
     for connection in listener.incoming() {
         loop {
@@ -281,16 +547,14 @@ pub fn inbound(listener: UtpListener,
                        pool.active_count(),
                        pool.max_count());
 
-                let key = ring_key.clone();
+                let key = ring_keys.clone();
                 let my_peer = my_peer.clone();
                 let ml = member_list.clone();
                 let rl = rumor_list.clone();
-                let cl = census_list.clone();
+                let services = services.clone();
                 let d1 = detector.clone();
-                let el = election_list.clone();
-                let gfl = gossip_file_list.clone();
 
-                pool.execute(move || receive(socket, src, key, my_peer, ml, rl, cl, d1, el, gfl));
+                pool.execute(move || receive(socket, src, key, my_peer, ml, rl, services, d1));
             }
             _ => {}
         }
@@ -317,15 +581,13 @@ pub fn inbound(listener: UtpListener,
 /// * Forward along the RumorList to that Peer as a Proxy Ping.
 fn receive(socket: UtpSocket,
            src: net::SocketAddr,
-           ring_key: Arc<Option<SymKey>>,
+           ring_keys: Arc<RwLock<RingKeys>>,
            my_peer: Peer,
            member_list: Arc<RwLock<MemberList>>,
            rumor_list: Arc<RwLock<RumorList>>,
-           census_list: Arc<RwLock<CensusList>>,
-           detector: Arc<RwLock<Detector>>,
-           election_list: Arc<RwLock<ElectionList>>,
-           gossip_file_list: Arc<RwLock<GossipFileList>>) {
-    let mut client = Client::from_socket(socket, ring_key.deref().as_ref());
+           services: Arc<RwLock<Vec<ServiceGossip>>>,
+           detector: Arc<RwLock<Detector>>) {
+    let mut client = Client::from_socket(socket, ring_keys.read().unwrap().clone());
     let msg = match client.recv_message() {
         Ok(msg) => msg,
         Err(e) => {
@@ -353,7 +615,7 @@ fn receive(socket: UtpSocket,
             };
 
             // Create a client for that peer
-            let mut c = match Client::new(&respond_to[..], ring_key.deref().as_ref()) {
+            let mut c = match Client::new(&respond_to[..], ring_keys.read().unwrap().clone()) {
                 Ok(c) => c,
                 Err(e) => {
                     debug!("Failed to create a gossip client for {:?}; aborting: {}",
@@ -362,11 +624,13 @@ fn receive(socket: UtpSocket,
                     return;
                 }
             };
+            // Whoever sent us the ping understands the encoding they sent it with
+            c.set_encoding(client.encoding());
 
             // Generate our Ack rumors
             let ack_rumors = {
                 let rl = rumor_list.read().unwrap();
-                rl.hot_rumors_for(&from_peer.member_id)
+                rl.hot_rumors_for(&from_peer.member_id).understood_by(client.protocol_version())
             };
 
             // If we're Ack-ing through a proxy, make sure our reply is correct
@@ -393,19 +657,18 @@ fn receive(socket: UtpSocket,
 
             // Update our rumors
             debug!("Updating rumors from {:#?}", from_peer);
+            process_ring_key_rotations(&remote_rumor_list, &ring_keys, &rumor_list);
             process_rumors(remote_rumor_list,
                            rumor_list,
                            member_list,
-                           census_list,
-                           election_list,
-                           gossip_file_list);
+                           services);
         }
         Protocol::Ack(mut from_peer, remote_rumor_list) => {
             // If this is a proxy ack, forward the results on
             if from_peer.proxy_to.is_some() {
                 debug!("Proxy Ack for {:?}", from_peer);
                 let forward_to = from_peer.proxy_to.take().unwrap();
-                let mut c = match Client::new(&forward_to[..], ring_key.deref().as_ref()) {
+                let mut c = match Client::new(&forward_to[..], ring_keys.read().unwrap().clone()) {
                     Ok(c) => c,
                     Err(e) => {
                         debug!("Failed to create a gossip client to forward for {:?}; aborting: \
@@ -416,7 +679,8 @@ fn receive(socket: UtpSocket,
                     }
                 };
                 from_peer.proxy_through = None;
-                match c.ack(from_peer.clone(), remote_rumor_list) {
+                // We don't know which protocol version the member we forward to speaks
+                match c.ack(from_peer.clone(), remote_rumor_list.understood_by(None)) {
                     Ok(_) => {}
                     Err(e) => debug!("Failed to send Ack to {:?}: {:?}", from_peer, e),
                 }
@@ -427,12 +691,11 @@ fn receive(socket: UtpSocket,
                 }
                 // Update our rumors
                 debug!("Updating rumors via ack from {:#?} ", from_peer);
+                process_ring_key_rotations(&remote_rumor_list, &ring_keys, &rumor_list);
                 process_rumors(remote_rumor_list,
                                rumor_list,
                                member_list,
-                               census_list,
-                               election_list,
-                               gossip_file_list);
+                               services);
             }
         }
         Protocol::PingReq(from_peer, remote_rumor_list) => {
@@ -444,7 +707,7 @@ fn receive(socket: UtpSocket,
                     return;
                 }
             };
-            let mut c = match Client::new(&proxy_to[..], ring_key.deref().as_ref()) {
+            let mut c = match Client::new(&proxy_to[..], ring_keys.read().unwrap().clone()) {
                 Ok(c) => c,
                 Err(e) => {
                     debug!("Failed to create a gossip connection for sending ping-req to {} for \
@@ -465,22 +728,22 @@ fn receive(socket: UtpSocket,
         }
         Protocol::Inject(remote_rumor_list) => {
             debug!("Incoming rumor injection: {:?}", remote_rumor_list);
+            process_ring_key_rotations(&remote_rumor_list, &ring_keys, &rumor_list);
             process_rumors(remote_rumor_list,
                            rumor_list,
                            member_list,
-                           census_list,
-                           election_list,
-                           gossip_file_list);
+                           services);
         }
     }
 }
 
+/// Processes a list of rumors, updating our lists and sharing what we learned. Rumors about
+/// censuses, elections, gossip files and events are processed by every service we run.
 pub fn process_rumors(remote_rumors: RumorList,
                       rumor_list: Arc<RwLock<RumorList>>,
                       member_list: Arc<RwLock<MemberList>>,
-                      census_list: Arc<RwLock<CensusList>>,
-                      election_list: Arc<RwLock<ElectionList>>,
-                      gossip_file_list: Arc<RwLock<GossipFileList>>) {
+                      services: Arc<RwLock<Vec<ServiceGossip>>>) {
+    let services = services.read().unwrap().clone();
     for (id, remote_rumor) in remote_rumors.rumors.into_iter() {
         match remote_rumor.payload {
             Message::Member(m) => {
@@ -500,16 +763,25 @@ pub fn process_rumors(remote_rumors: RumorList,
                     // get the current member for the new rumor
                     {
                         let mut rl = rumor_list.write().unwrap();
+                        if member.health == Health::Departed {
+                            rl.prune_census_entries_for(&member.id);
+                        }
                         rl.add_rumor(Rumor::member(member));
                     }
                 }
             }
             Message::CensusEntry(ce) => {
                 debug!("Processing Census Entry {:#?}", ce);
-                let processed = {
-                    let mut cl = census_list.write().unwrap();
-                    cl.process(ce.clone())
-                };
+                if member_list.read().unwrap().has_left(&ce.member_id) {
+                    continue;
+                }
+                let mut processed = false;
+                for service in services.iter() {
+                    let mut cl = service.census_list.write().unwrap();
+                    if cl.process(ce.clone()) {
+                        processed = true;
+                    }
+                }
                 if processed {
                     let mut rl = rumor_list.write().unwrap();
                     // If we changed, by definition we took the other side.
@@ -520,18 +792,20 @@ pub fn process_rumors(remote_rumors: RumorList,
             // destroy the un-neccessary rumors
             Message::Election(election) => {
                 debug!("Processing Election {}", election);
-                let processed = {
-                    let mut el = election_list.write().unwrap();
-                    el.process(election.clone())
-                };
-                if processed {
-                    debug!("We processed Election {}", election);
-
-                    let elector = {
-                        let el = election_list.read().unwrap();
-                        el.get(&election.service_group()).unwrap().clone()
+                let mut elector = None;
+                for service in services.iter() {
+                    let processed = {
+                        let mut el = service.election_list.write().unwrap();
+                        el.process(election.clone())
                     };
+                    if processed && elector.is_none() {
+                        debug!("We processed Election {}", election);
+                        let el = service.election_list.read().unwrap();
+                        elector = Some(el.get(&election.service_group()).unwrap().clone());
+                    }
+                }
 
+                if let Some(elector) = elector {
                     let mut rl = rumor_list.write().unwrap();
                     rl.prune_elections_for(&elector.service_group());
                     rl.add_rumor(Rumor::election(elector));
@@ -539,28 +813,129 @@ pub fn process_rumors(remote_rumors: RumorList,
             }
             Message::GossipFile(gossip_file) => {
                 debug!("Processing Gossip File {}", gossip_file);
-                let processed = {
-                    let mut gf = gossip_file_list.write().unwrap();
-                    gf.process(gossip_file.clone())
-                };
+                let mut processed = false;
+                for service in services.iter() {
+                    let mut gf = service.gossip_file_list.write().unwrap();
+                    if gf.process(gossip_file.clone()) {
+                        processed = true;
+                    }
+                }
                 if processed {
                     let mut rl = rumor_list.write().unwrap();
                     rl.prune_gossip_files_for(&gossip_file);
                     rl.add_rumor(Rumor::gossip_file(gossip_file));
                 }
             }
+            // Every member shares an event until it expires, whether or not it runs a service in
+            // the event's service group
+            Message::Event(event) => {
+                debug!("Processing Event {}", event);
+                let known = rumor_list.read().unwrap().contains_key(&id);
+                if known || event.is_expired() {
+                    continue;
+                }
+                if let Err(e) = event.validate() {
+                    outputln!("Ignoring {}: {}", event, e);
+                    continue;
+                }
+                for service in services.iter() {
+                    let mut el = service.event_list.write().unwrap();
+                    el.process(event.clone());
+                }
+                let mut rl = rumor_list.write().unwrap();
+                rl.prune_expired_events();
+                rl.add_rumor(Rumor::event(event));
+            }
+            // Every member shares a departure, whether or not it knows the member who departed
+            Message::Departure(departure) => {
+                debug!("Processing departure of {}", departure.member_id);
+                let known = rumor_list.read().unwrap().contains_key(&id);
+                if known {
+                    continue;
+                }
+                let (departed, about_us) = {
+                    let mut ml = member_list.write().unwrap();
+                    let about_us = departure.member_id == ml.my_id;
+                    if ml.depart(&departure) {
+                        (ml.get(&departure.member_id).map(|m| m.clone()), about_us)
+                    } else {
+                        (None, about_us)
+                    }
+                };
+                let mut rl = rumor_list.write().unwrap();
+                if let Some(member) = departed {
+                    if member.health == Health::Departed {
+                        rl.prune_census_entries_for(&member.id);
+                    }
+                    rl.add_rumor(Rumor::member(member));
+                }
+                // We have refuted a departure of our own, rather than share it
+                if !about_us {
+                    rl.add_rumor(Rumor::departure(departure));
+                }
+            }
             Message::Blank => {}
+            // Applied by `process_ring_key_rotations` as they are received
+            Message::RingKeyRotation(_) => {}
+        }
+    }
+}
+
+/// Applies the ring key rotations in a list of rumors, and shares the stages we had not applied
+/// before.
+pub fn process_ring_key_rotations(remote_rumors: &RumorList,
+                                  ring_keys: &Arc<RwLock<RingKeys>>,
+                                  rumor_list: &Arc<RwLock<RumorList>>) {
+    for remote_rumor in remote_rumors.values() {
+        if let Message::RingKeyRotation(ref rotation) = remote_rumor.payload {
+            debug!("Processing ring key rotation to {}", rotation.name_with_rev);
+            let processed = ring_keys.write().unwrap().process(rotation);
+            match processed {
+                Ok(true) => {
+                    let mut rl = rumor_list.write().unwrap();
+                    rl.add_rumor(Rumor::ring_key_rotation(rotation.clone()));
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    outputln!("Failed to rotate the ring key to {}: {}",
+                              rotation.name_with_rev,
+                              e)
+                }
+            }
         }
     }
 }
 
 
+/// The reaper. Every REAP_INTERVAL in milliseconds, it removes the members which have been
+/// confirmed dead or departed for `reap_after_secs`, along with every rumor about them.
+pub fn reaper(member_list: Arc<RwLock<MemberList>>,
+              rumor_list: Arc<RwLock<RumorList>>,
+              detector: Arc<RwLock<Detector>>,
+              reap_after_secs: u64) {
+    loop {
+        let reaped = {
+            let mut ml = member_list.write().unwrap();
+            ml.reap(reap_after_secs)
+        };
+        for member_id in reaped.iter() {
+            outputln!("Removed member {} from the ring", member_id);
+            {
+                detector.write().unwrap().forget(member_id);
+            }
+            let mut rl = rumor_list.write().unwrap();
+            rl.prune_member(member_id);
+        }
+        thread::sleep(Duration::from_millis(REAP_INTERVAL));
+    }
+}
+
 /// The outbound distributor. Every OUTBOUND_INTERVAL in milliseconds, it spawns a new connection
 /// to the next member.
 ///
 /// Like inbound, it is backed by a thread pool - if we have more than OUTBOUND_MAX_THREADS running
 /// at once, we delay the next outbound message until a thread is free.
-pub fn outbound(ring_key: Arc<Option<SymKey>>,
+pub fn outbound(ring_keys: Arc<RwLock<RingKeys>>,
                 my_peer: Peer,
                 member_list: Arc<RwLock<MemberList>>,
                 rumor_list: Arc<RwLock<RumorList>>,
@@ -598,12 +973,17 @@ pub fn outbound(ring_key: Arc<Option<SymKey>>,
             continue;
         }
 
+        // Departed members are gone for good
+        if member.health == Health::Departed {
+            continue;
+        }
+
         let running_request = {
             detector.read().unwrap().exists(&member.id)
         };
 
         if !running_request {
-            let key1 = ring_key.clone();
+            let key1 = ring_keys.clone();
             let rl1 = rumor_list.clone();
             let ml1 = member_list.clone();
             let mp1 = my_peer.clone();
@@ -622,7 +1002,7 @@ pub fn outbound(ring_key: Arc<Option<SymKey>>,
 }
 
 /// Send an outbound Ping. If we fail to send, we initiate a PingReq.
-pub fn send_outbound(ring_key: Arc<Option<SymKey>>,
+pub fn send_outbound(ring_keys: Arc<RwLock<RingKeys>>,
                      my_peer: Peer,
                      member: Member,
                      rumor_list: Arc<RwLock<RumorList>>,
@@ -633,13 +1013,13 @@ pub fn send_outbound(ring_key: Arc<Option<SymKey>>,
         d.start(member.id.clone());
     }
 
-    let mut c = match Client::new(&member.gossip_listener[..], ring_key.deref().as_ref()) {
+    let mut c = match Client::new(&member.gossip_listener[..], ring_keys.read().unwrap().clone()) {
         Ok(c) => c,
         Err(e) => {
             debug!("Failed to create a gossip connection for {}; sending ping-req: {}",
                    member.id,
                    e);
-            send_pingreq(ring_key.clone(),
+            send_pingreq(ring_keys.clone(),
                          my_peer,
                          member,
                          rumor_list,
@@ -648,10 +1028,11 @@ pub fn send_outbound(ring_key: Arc<Option<SymKey>>,
             return;
         }
     };
+    c.set_encoding(Encoding::for_protocol(member.protocol_version));
 
     let ping_rumors = {
         let rl = rumor_list.read().unwrap();
-        rl.hot_rumors_for(&member.id)
+        rl.hot_rumors_for(&member.id).understood_by(member.protocol_version)
     };
 
     debug!("Preparing hot rumors for {:?}: {:#?}", member, ping_rumors);
@@ -664,7 +1045,7 @@ pub fn send_outbound(ring_key: Arc<Option<SymKey>>,
         Ok(_) => {}
         Err(e) => {
             debug!("Failed to ping {:?}: {:?}", my_peer, e);
-            send_pingreq(ring_key.clone(),
+            send_pingreq(ring_keys.clone(),
                          my_peer,
                          member,
                          rumor_list,
@@ -682,7 +1063,7 @@ pub fn send_outbound(ring_key: Arc<Option<SymKey>>,
 
 /// Send a PingReq for a failed Ping. We pick targets from the Member List, and then send a PingReq
 /// to each of them, with our information filled in.
-pub fn send_pingreq(ring_key: Arc<Option<SymKey>>,
+pub fn send_pingreq(ring_keys: Arc<RwLock<RingKeys>>,
                     my_peer: Peer,
                     member: Member,
                     rumor_list: Arc<RwLock<RumorList>>,
@@ -694,7 +1075,7 @@ pub fn send_pingreq(ring_key: Arc<Option<SymKey>>,
 
     let ping_rumors = {
         let rl = rumor_list.read().unwrap();
-        rl.hot_rumors_for(&member.id)
+        rl.hot_rumors_for(&member.id).understood_by(member.protocol_version)
     };
 
     let pingreq_targets = {
@@ -707,7 +1088,7 @@ pub fn send_pingreq(ring_key: Arc<Option<SymKey>>,
                member.id,
                pingreq_member.id);
         let mut c = match Client::new(&pingreq_member.gossip_listener[..],
-                                      ring_key.deref().as_ref()) {
+                                      ring_keys.read().unwrap().clone()) {
             Ok(c) => c,
             Err(e) => {
                 debug!("Failed to create a gossip connection for {}; aborting ping-req request: \
@@ -717,12 +1098,15 @@ pub fn send_pingreq(ring_key: Arc<Option<SymKey>>,
                 continue;
             }
         };
+        c.set_encoding(Encoding::for_protocol(pingreq_member.protocol_version));
 
         let mut proxy_peer = my_peer.clone();
         proxy_peer.proxy_through = Some(pingreq_member.gossip_listener.clone());
         proxy_peer.proxy_to = Some(member.gossip_listener.clone());
 
-        match c.pingreq(proxy_peer, ping_rumors.clone()) {
+        // The rumors must be understood by both the member and the one we proxy through
+        match c.pingreq(proxy_peer,
+                        ping_rumors.understood_by(pingreq_member.protocol_version)) {
             Ok(_) => {}
             Err(e) => {
                 debug!("Failed to pingreq {:?}: {:?}; aborting ping-req request",
@@ -736,7 +1120,7 @@ pub fn send_pingreq(ring_key: Arc<Option<SymKey>>,
 /// The failure detector. Every 100ms, we check for any failed for confirmed timeouts within the
 /// detector. If we find a timeout, we update our rumor and the members entry. Additionally, if we
 /// mark a member as Suspect through a rumor we were passed, we set up its entry in the detector.
-pub fn failure_detector(ring_key: Arc<Option<SymKey>>,
+pub fn failure_detector(ring_keys: Arc<RwLock<RingKeys>>,
                         my_peer: Peer,
                         member_list: Arc<RwLock<MemberList>>,
                         rumor_list: Arc<RwLock<RumorList>>,
@@ -754,9 +1138,10 @@ pub fn failure_detector(ring_key: Arc<Option<SymKey>>,
                 let mut ml = member_list.write().unwrap();
                 ml.suspect(member_id);
             }
-            let suspect_member = {
-                let ml = member_list.read().unwrap();
-                ml.get(&member_id).unwrap().clone()
+            // The member may have been reaped in the meantime
+            let suspect_member = match member_list.read().unwrap().get(&member_id) {
+                Some(member) => member.clone(),
+                None => continue,
             };
             {
                 let mut rl = rumor_list.write().unwrap();
@@ -770,9 +1155,9 @@ pub fn failure_detector(ring_key: Arc<Option<SymKey>>,
                 let mut ml = member_list.write().unwrap();
                 ml.confirm(member_id);
             }
-            let confirmed_member = {
-                let ml = member_list.read().unwrap();
-                ml.get(&member_id).unwrap().clone()
+            let confirmed_member = match member_list.read().unwrap().get(&member_id) {
+                Some(member) => member.clone(),
+                None => continue,
             };
             {
                 let mut rl = rumor_list.write().unwrap();
@@ -798,9 +1183,11 @@ pub fn failure_detector(ring_key: Arc<Option<SymKey>>,
 
         // For each pingreq target, send the pingreq!
         for member_id in pingreq.iter() {
-            let ml = member_list.read().unwrap();
-            let member = ml.get(&member_id).unwrap().clone();
-            send_pingreq(ring_key.clone(),
+            let member = match member_list.read().unwrap().get(&member_id) {
+                Some(member) => member.clone(),
+                None => continue,
+            };
+            send_pingreq(ring_keys.clone(),
                          my_peer.clone(),
                          member,
                          rumor_list.clone(),
@@ -811,3 +1198,62 @@ pub fn failure_detector(ring_key: Arc<Option<SymKey>>,
         thread::sleep(Duration::from_millis(100));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tempdir::TempDir;
+
+    use gossip::member::Health;
+    use gossip::rumor::{Message, RumorList};
+    use super::{Server, process_rumors};
+
+    fn new_server(dir: &TempDir) -> Server {
+        Server::new(String::from("127.0.0.1"),
+                    9634,
+                    false,
+                    None,
+                    Some(dir.path().join("ring-9634.json")))
+    }
+
+    #[test]
+    fn rejoins_after_leaving() {
+        let dir = TempDir::new("gossip_server").unwrap();
+        let (my_id, departures) = {
+            let server = new_server(&dir);
+            server.leave();
+            server.save_ring_state().unwrap();
+            let mut departures = RumorList::new();
+            for rumor in server.rumor_list.read().unwrap().values() {
+                match rumor.payload {
+                    Message::Member(_) |
+                    Message::Departure(_) => departures.add_rumor(rumor.clone()),
+                    _ => {}
+                }
+            }
+            (server.peer.member_id.clone(), departures)
+        };
+        assert_eq!(departures.len(), 2);
+
+        let server = new_server(&dir);
+        assert_eq!(server.peer.member_id, my_id);
+        let incarnation = {
+            let ml = server.member_list.read().unwrap();
+            ml.get(&my_id).unwrap().incarnation.clone()
+        };
+        // We don't gossip our own departure again
+        assert!(!server.rumor_list.read().unwrap().values().any(|rumor| match rumor.payload {
+            Message::Departure(_) => true,
+            _ => false,
+        }));
+
+        // Hearing of the departure from a peer neither has us leave, nor bumps our incarnation
+        process_rumors(departures,
+                       server.rumor_list.clone(),
+                       server.member_list.clone(),
+                       server.services.clone());
+        let ml = server.member_list.read().unwrap();
+        let me = ml.get(&my_id).unwrap();
+        assert_eq!(me.health, Health::Alive);
+        assert_eq!(me.incarnation, incarnation);
+    }
+}
@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Health checks, and the scheduler that runs them in the background.
+
 use std::fmt::{self, Display, Formatter};
+use std::result;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use time::{Duration, SteadyTime};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Status {
@@ -22,6 +30,44 @@ pub enum Status {
     Unknown,
 }
 
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Critical => "critical",
+            Status::Unknown => "unknown",
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Statuses are gossiped in census entries, and rendered into templates; we encode them as plain
+// strings so they read the same in both.
+impl Encodable for Status {
+    fn encode<S: Encoder>(&self, s: &mut S) -> result::Result<(), S::Error> {
+        s.emit_str(self.as_str())
+    }
+}
+
+impl Decodable for Status {
+    fn decode<D: Decoder>(d: &mut D) -> result::Result<Self, D::Error> {
+        let status = try!(d.read_str());
+        match &status[..] {
+            "ok" => Ok(Status::Ok),
+            "warning" => Ok(Status::Warning),
+            "critical" => Ok(Status::Critical),
+            "unknown" => Ok(Status::Unknown),
+            _ => Err(d.error(&format!("unknown health check status: {}", status))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CheckResult {
     pub status: Status,
@@ -64,3 +110,87 @@ impl Display for CheckResult {
         write!(f, "{} - {}", status_code, self.output)
     }
 }
+
+/// A health check running in a thread of its own, so a slow check never blocks the supervisor.
+pub struct PendingCheck {
+    receiver: Receiver<CheckResult>,
+    deadline: SteadyTime,
+}
+
+impl PendingCheck {
+    /// Starts running `check` in the background. It has `timeout` to finish before it is
+    /// considered critical.
+    pub fn start<F>(timeout: Duration, check: F) -> PendingCheck
+        where F: FnOnce() -> CheckResult + Send + 'static
+    {
+        let (tx, rx) = channel();
+        thread::Builder::new()
+            .name(String::from("health-check"))
+            .spawn(move || tx.send(check()).unwrap_or(()))
+            .ok();
+        PendingCheck {
+            receiver: rx,
+            deadline: SteadyTime::now() + timeout,
+        }
+    }
+
+    /// Returns the result of the check once it has finished or timed out.
+    pub fn poll(&self) -> Option<CheckResult> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => {
+                if SteadyTime::now() > self.deadline {
+                    Some(CheckResult::critical(String::from("health check timed out")))
+                } else {
+                    None
+                }
+            }
+            Err(TryRecvError::Disconnected) => {
+                Some(CheckResult::unknown(String::from("health check did not return a result")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration as StdDuration;
+
+    use rustc_serialize::json;
+    use time::Duration;
+
+    use super::{CheckResult, PendingCheck, Status};
+
+    #[test]
+    fn status_encodes_as_a_string() {
+        assert_eq!(json::encode(&Status::Critical).unwrap(), "\"critical\"");
+        let status: Status = json::decode("\"warning\"").unwrap();
+        assert_eq!(status, Status::Warning);
+    }
+
+    #[test]
+    fn pending_check_returns_the_result() {
+        let check = PendingCheck::start(Duration::seconds(10),
+                                        || CheckResult::ok(String::from("fine")));
+        let mut result = None;
+        for _ in 0..100 {
+            result = check.poll();
+            if result.is_some() {
+                break;
+            }
+            thread::sleep(StdDuration::from_millis(10));
+        }
+        assert_eq!(result.unwrap().status, Status::Ok);
+    }
+
+    #[test]
+    fn pending_check_times_out() {
+        let check = PendingCheck::start(Duration::milliseconds(10), || {
+            thread::sleep(StdDuration::from_millis(500));
+            CheckResult::ok(String::from("too late"))
+        });
+        thread::sleep(StdDuration::from_millis(50));
+        assert_eq!(check.poll().unwrap().status, Status::Critical);
+    }
+}
@@ -232,6 +232,18 @@ macro_rules! output_format {
             format!("{}", so)
         }
     };
+    (preamble $preamble:expr, logkey $logkey:expr, $content: expr) => {
+        {
+            use $crate::output::StructuredOutput;
+            let so = StructuredOutput::new($preamble,
+                                           $logkey,
+                                           line!(),
+                                           file!(),
+                                           column!(),
+                                           $content);
+            format!("{}", so)
+        }
+    };
 
     ($content: expr, $($arg:tt)*) => {
         {
@@ -268,10 +280,14 @@ pub mod command;
 pub mod util;
 pub mod package;
 pub mod topology;
+pub mod manager;
 pub mod state_machine;
 pub mod sidecar;
 pub mod health_check;
+pub mod logs;
 pub mod config;
+pub mod config_schema;
+pub mod control;
 pub mod service_config;
 pub mod census;
 pub mod gossip;
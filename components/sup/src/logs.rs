@@ -0,0 +1,299 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log files for supervised processes.
+//!
+//! The supervisor writes the standard output and standard error of each service to `stdout.log`
+//! and `stderr.log` in the `logs` directory of the service's var path. Once a log would grow past
+//! its maximum size it is rotated: `stdout.log` becomes `stdout.log.1`, `stdout.log.1` becomes
+//! `stdout.log.2`, and so on, keeping only the configured number of old logs.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hcore;
+
+use error::Result;
+use output;
+
+/// How large, in bytes, a log may grow before it is rotated
+pub const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// How many rotated logs we keep
+pub const DEFAULT_LOG_RETAIN: usize = 5;
+/// How many lines of a log we return, unless asked for another number
+pub const DEFAULT_TAIL_LINES: usize = 100;
+/// How often, in milliseconds, we check whether a followed log has grown
+const FOLLOW_POLL_MS: u64 = 250;
+/// How long, in seconds, a log is followed before the response ends; a client that wants more
+/// asks again
+pub const MAX_FOLLOW_SECS: u64 = 300;
+
+/// Which output of a process a log holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn file_name(&self) -> &'static str {
+        match *self {
+            Stream::Stdout => "stdout.log",
+            Stream::Stderr => "stderr.log",
+        }
+    }
+
+    /// The logkey we print the output with
+    pub fn logkey(&self) -> &'static str {
+        match *self {
+            Stream::Stdout => "O",
+            Stream::Stderr => output::STDERR_LOGKEY,
+        }
+    }
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stream::Stdout => write!(f, "stdout"),
+            Stream::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+/// When logs are rotated, and how many old logs are kept.
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    /// How large, in bytes, a log may grow before it is rotated; 0 never rotates it
+    pub max_bytes: u64,
+    /// How many rotated logs we keep; 0 truncates the log instead
+    pub retain: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation {
+            max_bytes: DEFAULT_LOG_MAX_BYTES,
+            retain: DEFAULT_LOG_RETAIN,
+        }
+    }
+}
+
+/// The directory a service's logs are written to.
+pub fn log_dir(service: &str) -> PathBuf {
+    hcore::fs::svc_var_path(service).join("logs")
+}
+
+/// The path of one of a service's logs.
+pub fn log_path(service: &str, stream: Stream) -> PathBuf {
+    log_dir(service).join(stream.file_name())
+}
+
+/// The path of the `n`th most recently rotated log.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_os_string();
+    rotated.push(format!(".{}", n));
+    PathBuf::from(rotated)
+}
+
+/// A log file we append to, rotating it as it grows.
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    rotation: Rotation,
+}
+
+impl LogFile {
+    /// Opens a log for appending, creating it and its directory if needed.
+    pub fn open<P: Into<PathBuf>>(path: P, rotation: Rotation) -> Result<LogFile> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let file = try!(OpenOptions::new().create(true).append(true).open(&path));
+        let size = try!(file.metadata()).len();
+        Ok(LogFile {
+            path: path,
+            file: file,
+            size: size,
+            rotation: rotation,
+        })
+    }
+
+    /// Appends a line to the log, rotating it first if the line would take it past its maximum
+    /// size.
+    pub fn write_line(&mut self, line: &[u8]) -> Result<()> {
+        let len = line.len() as u64;
+        if self.rotation.max_bytes > 0 && self.size > 0 &&
+           self.size + len > self.rotation.max_bytes {
+            try!(self.rotate());
+        }
+        try!(self.file.write_all(line));
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        if self.rotation.retain > 0 {
+            for n in (1..self.rotation.retain).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    try!(fs::rename(&from, rotated_path(&self.path, n + 1)));
+                }
+            }
+            try!(fs::rename(&self.path, rotated_path(&self.path, 1)));
+        }
+        self.file = try!(OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path));
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Returns the last `lines` lines of a log, reaching back into the most recently rotated log if
+/// the current one is shorter.
+pub fn tail(path: &Path, lines: usize) -> Result<Vec<String>> {
+    let mut tail = try!(read_tail(path, lines));
+    if tail.len() < lines {
+        let older = try!(read_tail(&rotated_path(path, 1), lines - tail.len()));
+        for line in older.into_iter().rev() {
+            tail.push_front(line);
+        }
+    }
+    Ok(tail.into_iter().collect())
+}
+
+fn read_tail(path: &Path, lines: usize) -> Result<VecDeque<String>> {
+    let mut tail = VecDeque::new();
+    if lines == 0 || !path.exists() {
+        return Ok(tail);
+    }
+    let mut reader = BufReader::new(try!(File::open(path)));
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if try!(reader.read_until(b'\n', &mut buf)) == 0 {
+            break;
+        }
+        if tail.len() == lines {
+            tail.pop_front();
+        }
+        tail.push_back(String::from_utf8_lossy(&buf).trim_right_matches('\n').to_string());
+    }
+    Ok(tail)
+}
+
+/// Writes the last `lines` lines of a log, then follows the log as it grows for `max_time`, or
+/// until writing fails; usually because the reader went away. A reader that went away is only
+/// noticed once the log grows, so `max_time` bounds how long a quiet log is followed for nobody.
+pub fn follow<W: Write>(path: &Path, lines: usize, max_time: Duration, out: &mut W) -> Result<()> {
+    let started = Instant::now();
+    let mut position = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    for line in try!(tail(path, lines)) {
+        try!(writeln!(out, "{}", line));
+    }
+    try!(out.flush());
+    while started.elapsed() < max_time {
+        thread::sleep(Duration::from_millis(FOLLOW_POLL_MS));
+        let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        // The log was rotated; start again from the top of the new one
+        if len < position {
+            position = 0;
+        }
+        if len > position {
+            let mut file = try!(File::open(path));
+            try!(file.seek(SeekFrom::Start(position)));
+            let mut buf = Vec::new();
+            try!(file.take(len - position).read_to_end(&mut buf));
+            try!(out.write_all(&buf));
+            try!(out.flush());
+            position = len;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use super::{follow, rotated_path, tail, LogFile, Rotation};
+
+    #[test]
+    fn rotates_and_retains() {
+        let dir = TempDir::new("logs").unwrap();
+        let path = dir.path().join("stdout.log");
+        let rotation = Rotation {
+            max_bytes: 10,
+            retain: 2,
+        };
+        let mut log = LogFile::open(&path, rotation).unwrap();
+        for line in &["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            log.write_line(line.as_bytes()).unwrap();
+        }
+        assert_eq!(tail(&path, 10).unwrap(), vec!["four", "five"]);
+        assert_eq!(tail(&rotated_path(&path, 1), 10).unwrap(), vec!["three"]);
+        assert_eq!(tail(&rotated_path(&path, 2), 10).unwrap(), vec!["one", "two"]);
+        assert!(!rotated_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn tail_reaches_into_the_rotated_log() {
+        let dir = TempDir::new("logs").unwrap();
+        let path = dir.path().join("stdout.log");
+        let rotation = Rotation {
+            max_bytes: 12,
+            retain: 1,
+        };
+        let mut log = LogFile::open(&path, rotation).unwrap();
+        for line in &["one\n", "two\n", "three\n", "four\n"] {
+            log.write_line(line.as_bytes()).unwrap();
+        }
+        assert_eq!(tail(&path, 3).unwrap(), vec!["two", "three", "four"]);
+        assert_eq!(tail(&path, 1).unwrap(), vec!["four"]);
+    }
+
+    #[test]
+    fn follow_ends_after_max_time() {
+        let dir = TempDir::new("logs").unwrap();
+        let path = dir.path().join("stdout.log");
+        let mut log = LogFile::open(&path, Rotation::default()).unwrap();
+        log.write_line(b"one\n").unwrap();
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            let mut file = OpenOptions::new().append(true).open(writer_path).unwrap();
+            file.write_all(b"two\n").unwrap();
+        });
+        let mut out = Vec::new();
+        follow(&path, 10, Duration::from_millis(1000), &mut out).unwrap();
+        writer.join().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "one\ntwo\n");
+    }
+}
@@ -40,7 +40,11 @@ use hcore::url::{DEFAULT_DEPOT_URL, DEPOT_URL_ENVVAR};
 
 use sup::config::{gcache, gconfig, Command, Config, UpdateStrategy};
 use sup::error::{Error, Result, SupError};
+use sup::gossip::member::DEFAULT_REAP_AFTER_SECS;
 use sup::command::*;
+use sup::logs::{DEFAULT_LOG_MAX_BYTES, DEFAULT_LOG_RETAIN};
+use sup::supervisor::{DEFAULT_CRASH_LOOP_RESTARTS, DEFAULT_CRASH_LOOP_WINDOW_SECS,
+                      DEFAULT_RESTART_BACKOFF_MAX_SECS};
 use sup::topology::Topology;
 use sup::util::parse_ip_port_with_defaults;
 use sup::util::path::busybox_paths;
@@ -58,9 +62,14 @@ static DEFAULT_GROUP: &'static str = "default";
 static DEFAULT_HTTP_LISTEN_IP: &'static str = "0.0.0.0";
 static DEFAULT_HTTP_LISTEN_PORT: u16 = 9631;
 const DEFAULT_GOSSIP_LISTEN_PORT: u16 = 9634;
+const DEFAULT_CANARY_PERCENT: u8 = 10;
+const DEFAULT_CANARY_SOAK_SECS: u64 = 300;
+const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+const DEFAULT_HEALTH_CHECK_TIMEOUT_SECS: u64 = 10;
 
 static RING_ENVVAR: &'static str = "HAB_RING";
 static RING_KEY_ENVVAR: &'static str = "HAB_RING_KEY";
+static JSON_OUTPUT_ENVVAR: &'static str = "HAB_SUP_JSON_OUTPUT";
 
 /// Creates a [Config](config/struct.Config.html) from global args
 /// and subcommand args.
@@ -71,20 +80,68 @@ fn config_from_args(subcommand: &str, sub_args: &ArgMatches) -> Result<()> {
     if let Some(ref config_from) = sub_args.value_of("config-from") {
         config.set_config_from(Some(config_from.to_string()));
     }
+    config.set_user_config(sub_args.value_of("user-config").map(|s| s.to_string()));
+    config.set_census_data(sub_args.value_of("census").map(|s| s.to_string()));
+    config.set_output_dir(sub_args.value_of("output").map(|s| s.to_string()));
     if let Some(ref strategy) = sub_args.value_of("strategy") {
         config.set_update_strategy(UpdateStrategy::from_str(strategy));
     }
+    config.set_canary_percent(value_or_default(sub_args, "canary-percent", DEFAULT_CANARY_PERCENT));
+    config.set_canary_soak_secs(value_or_default(sub_args,
+                                                 "canary-soak",
+                                                 DEFAULT_CANARY_SOAK_SECS));
+    config.set_health_check_interval_secs(value_or_default(sub_args,
+                                                           "health-check-interval",
+                                                           DEFAULT_HEALTH_CHECK_INTERVAL_SECS));
+    config.set_health_check_timeout_secs(value_or_default(sub_args,
+                                                          "health-check-timeout",
+                                                          DEFAULT_HEALTH_CHECK_TIMEOUT_SECS));
+    if let Some(timeouts) = sub_args.values_of("hook-timeout") {
+        for timeout in timeouts {
+            let (hook, secs) = match timeout.find('=') {
+                Some(i) => (Some(&timeout[..i]), &timeout[i + 1..]),
+                None => (None, timeout),
+            };
+            let secs = match secs.parse::<u64>() {
+                Ok(secs) => secs,
+                Err(_) => return Err(sup_error!(Error::InvalidHookTimeout(timeout.to_string()))),
+            };
+            match hook {
+                Some(hook) => config.set_timeout_for_hook(hook.to_string(), secs),
+                None => config.set_hook_timeout_secs(secs),
+            };
+        }
+    }
+    config.set_restart_backoff_max_secs(value_or_default(sub_args,
+                                                         "restart-backoff-max",
+                                                         DEFAULT_RESTART_BACKOFF_MAX_SECS));
+    config.set_crash_loop_restarts(value_or_default(sub_args,
+                                                    "crash-loop-restarts",
+                                                    DEFAULT_CRASH_LOOP_RESTARTS));
+    config.set_crash_loop_window_secs(value_or_default(sub_args,
+                                                       "crash-loop-window",
+                                                       DEFAULT_CRASH_LOOP_WINDOW_SECS));
+    config.set_log_max_bytes(value_or_default(sub_args, "log-max-bytes", DEFAULT_LOG_MAX_BYTES));
+    config.set_log_retain(value_or_default(sub_args, "log-retain", DEFAULT_LOG_RETAIN));
     if let Some(ref archive) = sub_args.value_of("archive") {
         config.set_archive(archive.to_string());
     }
-    if let Some(ref ident_or_artifact) = sub_args.value_of("pkg_ident_or_artifact") {
-        if Path::new(ident_or_artifact).is_file() {
-            let ident = try!(PackageArchive::new(Path::new(ident_or_artifact)).ident());
-            config.set_package(ident);
-            config.set_local_artifact(ident_or_artifact.to_string());
-        } else {
-            let ident = try!(PackageIdent::from_str(ident_or_artifact));
-            config.set_package(ident);
+    if let Some(idents_or_artifacts) = sub_args.values_of("pkg_ident_or_artifact") {
+        for (i, ident_or_artifact) in idents_or_artifacts.enumerate() {
+            let (ident, artifact) = if Path::new(ident_or_artifact).is_file() {
+                let ident = try!(PackageArchive::new(Path::new(ident_or_artifact)).ident());
+                (ident, Some(ident_or_artifact.to_string()))
+            } else {
+                (try!(PackageIdent::from_str(ident_or_artifact)), None)
+            };
+            if i == 0 {
+                config.set_package(ident);
+                if let Some(artifact) = artifact {
+                    config.set_local_artifact(artifact);
+                }
+            } else {
+                config.add_package(ident, artifact);
+            }
         }
     }
     if let Some(key) = sub_args.value_of("key") {
@@ -132,6 +189,10 @@ fn config_from_args(subcommand: &str, sub_args: &ArgMatches) -> Result<()> {
         None => vec![],
     };
     config.set_bind(bindings);
+    if sub_args.is_present("bind-wait") {
+        let secs = value_t!(sub_args, "bind-wait", u64).unwrap_or_else(|e| e.exit());
+        config.set_bind_wait_secs(Some(secs));
+    }
     config.set_path(sub_args.value_of("path")
         .unwrap_or(fs::svc_path(sup::PROGRAM_NAME.as_str())
             .join("data")
@@ -179,6 +240,7 @@ fn config_from_args(subcommand: &str, sub_args: &ArgMatches) -> Result<()> {
     if sub_args.is_present("permanent-peer") {
         config.set_gossip_permanent(true);
     }
+    config.set_reap_after_secs(value_or_default(sub_args, "reap-after", DEFAULT_REAP_AFTER_SECS));
     if let Some(sg) = sub_args.value_of("service-group") {
         config.set_service_group(sg.to_string());
     }
@@ -216,6 +278,10 @@ fn config_from_args(subcommand: &str, sub_args: &ArgMatches) -> Result<()> {
     if sub_args.is_present("no-color") {
         sup::output::set_no_color(true);
     }
+    let json_env = henv::var(JSON_OUTPUT_ENVVAR).map(|v| v == "true" || v == "1").unwrap_or(false);
+    if sub_args.is_present("json") || json_env {
+        sup::output::set_json(true);
+    }
     if let Some(org) = sub_args.value_of("organization") {
         config.set_organization(org.to_string());
     }
@@ -263,7 +329,7 @@ fn main() {
             .long("strategy")
             .short("s")
             .takes_value(true)
-            .possible_values(&["none", "at-once"])
+            .possible_values(&["none", "at-once", "rolling", "canary"])
             .help("The update strategy; [default: none].")
     };
 
@@ -273,12 +339,63 @@ fn main() {
         .arg(Arg::with_name("pkg_ident_or_artifact")
             .index(1)
             .required(true)
-            .help("A Habitat package identifier (ex: acme/redis) or a filepath to a Habitat \
-                   Artifact (ex: /home/acme-redis-3.0.7-21120102031201-x86_64-linux.hart)"))
+            .multiple(true)
+            .help("One or more Habitat package identifiers (ex: acme/redis) or filepaths to \
+                   Habitat Artifacts \
+                   (ex: /home/acme-redis-3.0.7-21120102031201-x86_64-linux.hart)"))
         .arg(arg_url())
         .arg(arg_group())
         .arg(arg_org())
         .arg(arg_strategy())
+        .arg(Arg::with_name("canary-percent")
+            .long("canary-percent")
+            .value_name("percent")
+            .help("The percentage of the service group that updates first with the canary \
+                   strategy [default: 10]"))
+        .arg(Arg::with_name("canary-soak")
+            .long("canary-soak")
+            .value_name("seconds")
+            .help("How long canaries must stay healthy before the rest of the service group \
+                   updates [default: 300]"))
+        .arg(Arg::with_name("health-check-interval")
+            .long("health-check-interval")
+            .value_name("seconds")
+            .help("How often to run the health check; 0 disables it [default: 30]"))
+        .arg(Arg::with_name("health-check-timeout")
+            .long("health-check-timeout")
+            .value_name("seconds")
+            .help("How long a health check may run before it is considered critical \
+                   [default: 10]"))
+        .arg(Arg::with_name("hook-timeout")
+            .long("hook-timeout")
+            .value_name("[hook=]seconds")
+            .multiple(true)
+            .number_of_values(1)
+            .help("How long hooks may run before they are killed; prefix a hook name to set it \
+                   for that hook only (ex: init=600). 0 lets hooks run forever [default: 0]"))
+        .arg(Arg::with_name("restart-backoff-max")
+            .long("restart-backoff-max")
+            .value_name("seconds")
+            .help("The longest to wait before restarting a service that died; the wait doubles \
+                   each time it dies [default: 60]"))
+        .arg(Arg::with_name("crash-loop-restarts")
+            .long("crash-loop-restarts")
+            .value_name("restarts")
+            .help("Stop restarting a service that dies this many times within the crash loop \
+                   window; 0 always restarts it [default: 5]"))
+        .arg(Arg::with_name("crash-loop-window")
+            .long("crash-loop-window")
+            .value_name("seconds")
+            .help("How far back to count the failures of a service [default: 300]"))
+        .arg(Arg::with_name("log-max-bytes")
+            .long("log-max-bytes")
+            .value_name("bytes")
+            .help("Rotate a service's stdout and stderr logs once they would grow past this \
+                   size; 0 never rotates them [default: 10485760]"))
+        .arg(Arg::with_name("log-retain")
+            .long("log-retain")
+            .value_name("logs")
+            .help("How many rotated logs to keep [default: 5]"))
         .arg(Arg::with_name("config-from")
             .short("C")
             .long("config-from")
@@ -294,6 +411,12 @@ fn main() {
             .value_name("bind")
             .multiple(true)
             .help("One or more service groups to bind to a configuration"))
+        .arg(Arg::with_name("bind-wait")
+            .long("bind-wait")
+            .value_name("seconds")
+            .help("How long to wait for the package's required binds to have live members \
+                   before giving up; 0 refuses to start unless they already do [default: \
+                   wait forever]"))
         .arg(Arg::with_name("ring")
             .short("r")
             .long("ring")
@@ -315,18 +438,57 @@ fn main() {
         .arg(Arg::with_name("permanent-peer")
             .short("I")
             .long("permanent-peer")
-            .help("If this service is a permanent peer"));
+            .help("If this service is a permanent peer"))
+        .arg(Arg::with_name("reap-after")
+            .long("reap-after")
+            .value_name("seconds")
+            .help("How long a member stays confirmed dead or departed before it is removed from \
+                   the ring; 0 never removes them [default: 86400]"));
     let sub_bash = SubCommand::with_name("bash")
         .about("Start an interactive shell (bash)")
         .aliases(&["b", "ba", "bas"]);
     let sub_sh = SubCommand::with_name("sh").about("Start an interactive shell (sh)");
     let sub_config = SubCommand::with_name("config")
-        .about("Print the default.toml for a given package")
+        .about("Print the configuration for a given package, and where each key comes from")
         .aliases(&["c", "co", "con", "conf", "confi"])
         .arg(Arg::with_name("pkg_ident_or_artifact")
             .index(1)
             .required(true)
             .help("Name of package"));
+    let sub_render = SubCommand::with_name("render")
+        .about("Render the configuration templates and hooks of a package, without starting it")
+        .aliases(&["r", "re", "ren", "rend", "rende"])
+        .arg(Arg::with_name("pkg_ident_or_artifact")
+            .index(1)
+            .required(true)
+            .help("Name of package"))
+        .arg(arg_group())
+        .arg(arg_org())
+        .arg(Arg::with_name("config-from")
+            .short("C")
+            .long("config-from")
+            .value_name("config-from")
+            .help("Use package config from this path, rather than the package itself"))
+        .arg(Arg::with_name("bind")
+            .long("bind")
+            .value_name("bind")
+            .multiple(true)
+            .help("One or more service groups to bind to a configuration"))
+        .arg(Arg::with_name("user-config")
+            .long("user-config")
+            .value_name("file")
+            .help("Render with this TOML file in place of the service's user.toml"))
+        .arg(Arg::with_name("census")
+            .long("census")
+            .value_name("file")
+            .help("Render with the svc and bind tables of this TOML file, rather than a census \
+                   of just this service"))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("dir")
+            .help("Write the rendered files to config/ and hooks/ in this directory, rather than \
+                   printing them"));
     let args = App::new(sup::PROGRAM_NAME.as_str())
         .version(VERSION)
         .setting(AppSettings::VersionlessSubcommands)
@@ -339,10 +501,16 @@ fn main() {
             .long("no-color")
             .global(true)
             .help("Turn ANSI color off :("))
+        .arg(Arg::with_name("json")
+            .long("json")
+            .global(true)
+            .help("Print output as JSON, one object per line; the same as setting \
+                   HAB_SUP_JSON_OUTPUT=true"))
         .subcommand(sub_start)
         .subcommand(sub_bash)
         .subcommand(sub_sh)
-        .subcommand(sub_config);
+        .subcommand(sub_config)
+        .subcommand(sub_render);
     let matches = args.get_matches();
 
     debug!("clap matches {:?}", matches);
@@ -360,6 +528,7 @@ fn main() {
         Command::ShellBash => shell_bash(),
         Command::ShellSh => shell_sh(),
         Command::Config => configure(),
+        Command::Render => render(),
         Command::Start => start(),
     };
 
@@ -369,6 +538,16 @@ fn main() {
     }
 }
 
+/// The value of the `name` argument, or `default` if it was not given. Exits with an error if it
+/// was given but is not a valid `T`.
+fn value_or_default<T: FromStr>(args: &ArgMatches, name: &str, default: T) -> T {
+    if args.is_present(name) {
+        value_t!(args, name, T).unwrap_or_else(|e| e.exit())
+    } else {
+        default
+    }
+}
+
 /// Exit with an error message and the right status code
 #[allow(dead_code)]
 fn exit_with(e: SupError, code: i32) {
@@ -395,6 +574,12 @@ fn configure() -> Result<()> {
     Ok(())
 }
 
+/// Render the templates of a package
+#[allow(dead_code)]
+fn render() -> Result<()> {
+    render::package()
+}
+
 /// Start a service
 #[allow(dead_code)]
 fn start() -> Result<()> {
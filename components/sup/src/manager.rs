@@ -0,0 +1,381 @@
+// Copyright (c) 2016 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The service manager.
+//!
+//! A single supervisor process can run many services. The manager owns the pieces they share -
+//! the gossip server, the member list and the sidecar - and runs each service's
+//! [topology](../topology) in a thread of its own. Every service keeps its own `Supervisor`,
+//! `ServiceConfig`, census and state machine.
+//!
+//! The manager also owns the signal handler: `SIGINT` and `SIGTERM` stop every service, leave the
+//! gossip ring and save the ring state, and any other signal is forwarded on to each supervised
+//! process.
+
+use std::net::SocketAddrV4;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use common::ui::UI;
+use hcore::package::PackageIdent;
+use time::SteadyTime;
+use wonder;
+
+use command;
+use config::gconfig;
+use error::{Error, Result};
+use gossip::member::MemberList;
+use gossip::ring_state::RingState;
+use gossip::rumor::RumorList;
+use gossip::server::{self, ServiceGossip};
+use output;
+use package::Package;
+use sidecar::{self, SidecarActor, SidecarServices};
+use topology::{self, Topology};
+use util::signals::{self, SignalNotifier};
+
+static LOGKEY: &'static str = "MR";
+static MINIMUM_LOOP_TIME_MS: i64 = 200;
+
+/// Messages the manager sends to a running service.
+#[derive(Debug)]
+pub enum ServiceMessage {
+    /// Take the service down and return from its topology
+    Stop,
+}
+
+/// Messages other threads send to the manager.
+#[derive(Debug)]
+pub enum ManagerMessage {
+    /// Load a package, installing it if needed, and start it as a new service
+    Load(PackageIdent, Option<String>),
+    /// Stop the named service and stop gossiping about it
+    Unload(String),
+}
+
+/// Everything a topology needs from the manager to run a service.
+pub struct ServiceContext {
+    pub member_list: Arc<RwLock<MemberList>>,
+    pub rumor_list: Arc<RwLock<RumorList>>,
+    /// The gossip lists of every service in this supervisor
+    pub gossip_services: Arc<RwLock<Vec<ServiceGossip>>>,
+    /// The gossip lists of this service
+    pub gossip: ServiceGossip,
+    /// The services the sidecar reports on; the worker registers itself here
+    pub sidecar_services: SidecarServices,
+    /// Messages from the manager
+    pub control: Receiver<ServiceMessage>,
+}
+
+/// A service running in its own thread.
+struct ServiceThread {
+    name: String,
+    service_group: String,
+    control: Sender<ServiceMessage>,
+    handle: thread::JoinHandle<Result<()>>,
+}
+
+/// Tells the manager a service thread has exited when dropped, whether its topology returned or
+/// panicked.
+struct ServiceExit {
+    name: String,
+    exits: Sender<String>,
+}
+
+impl Drop for ServiceExit {
+    fn drop(&mut self) {
+        self.exits.send(self.name.clone()).unwrap_or(());
+    }
+}
+
+pub struct Manager {
+    gossip_server: server::Server,
+    sidecar_services: SidecarServices,
+    #[allow(dead_code)]
+    sidecar_actor: SidecarActor,
+    services: Vec<ServiceThread>,
+    exits: (Sender<String>, Receiver<String>),
+    control: (Sender<ManagerMessage>, Receiver<ManagerMessage>),
+}
+
+impl Manager {
+    /// Creates a new manager. Starts the gossip server, joins the initial peers and starts the
+    /// sidecar.
+    ///
+    /// # Failures
+    ///
+    /// * The gossip server cannot listen or reach its initial peers
+    /// * The sidecar listen address cannot be parsed
+    pub fn new() -> Result<Manager> {
+        let ring_state_path = RingState::path(gconfig().gossip_listen_port());
+        let gossip_server = server::Server::new(String::from(gconfig().gossip_listen_ip()),
+                                                gconfig().gossip_listen_port(),
+                                                gconfig().gossip_permanent(),
+                                                gconfig().ring().clone(),
+                                                Some(ring_state_path));
+        try!(gossip_server.start_inbound());
+        try!(gossip_server.initial_peers(gconfig().gossip_peer()));
+        gossip_server.start_outbound();
+        gossip_server.start_failure_detector();
+        gossip_server.start_ring_state_writer();
+        if gconfig().reap_after_secs() > 0 {
+            gossip_server.start_reaper(gconfig().reap_after_secs());
+        }
+        output::set_member_id(gossip_server.member_list.read().unwrap().my_id.to_string());
+
+        let sidecar_services: SidecarServices = Arc::new(RwLock::new(Vec::new()));
+        let control = channel();
+        let sidecar_listen = try!(SocketAddrV4::from_str(&format!("{}:{}",
+                                                                  &gconfig().http_listen_ip(),
+                                                                  gconfig().http_listen_port())));
+        let sidecar_actor = sidecar::Sidecar::start(sidecar_listen,
+                                                    sidecar_services.clone(),
+                                                    gossip_server.member_list.clone(),
+                                                    gossip_server.rumor_list.clone(),
+                                                    gossip_server.detector.clone(),
+                                                    gossip_server.ring_keys(),
+                                                    control.0.clone());
+        Ok(Manager {
+            gossip_server: gossip_server,
+            sidecar_services: sidecar_services,
+            sidecar_actor: sidecar_actor,
+            services: Vec::new(),
+            exits: channel(),
+            control: control,
+        })
+    }
+
+    /// A handle other threads can use to load and unload services while the manager runs.
+    pub fn sender(&self) -> Sender<ManagerMessage> {
+        self.control.0.clone()
+    }
+
+    /// Starts a package as a new service, running the topology from the configuration in a
+    /// thread of its own.
+    ///
+    /// # Failures
+    ///
+    /// * A service with the same name is already running
+    pub fn add_service(&mut self, package: Package) -> Result<()> {
+        if self.services.iter().any(|s| s.name == package.name) {
+            return Err(sup_error!(Error::ServiceLoaded(package.name.clone())));
+        }
+        let exposes = package.exposes().clone();
+        let port = exposes.first().map(|e| e.clone());
+        let gossip = self.gossip_server.add_service(package.name.clone(),
+                                                    gconfig().group().to_string(),
+                                                    gconfig().organization().clone(),
+                                                    Some(exposes),
+                                                    port);
+        let (tx, rx) = channel();
+        let ctx = ServiceContext {
+            member_list: self.gossip_server.member_list.clone(),
+            rumor_list: self.gossip_server.rumor_list.clone(),
+            gossip_services: self.gossip_server.services.clone(),
+            gossip: gossip.clone(),
+            sidecar_services: self.sidecar_services.clone(),
+            control: rx,
+        };
+        let name = package.name.clone();
+        output::set_service_group(name.clone(), gossip.service_group.clone());
+        let exit = ServiceExit {
+            name: name.clone(),
+            exits: self.exits.0.clone(),
+        };
+        let handle = try!(thread::Builder::new().name(format!("service-{}", name)).spawn(move || {
+            // Dropped once the topology returns or panics
+            let _exit = exit;
+            run_topology(package, ctx)
+        }));
+        outputln!("Started the {} service", name);
+        self.services.push(ServiceThread {
+            name: name,
+            service_group: gossip.service_group,
+            control: tx,
+            handle: handle,
+        });
+        Ok(())
+    }
+
+    /// Stops a running service and waits for its topology to return.
+    pub fn stop_service(&mut self, name: &str) -> Result<()> {
+        match self.services.iter().position(|s| s.name == name) {
+            Some(index) => {
+                let service = self.services.remove(index);
+                service.control.send(ServiceMessage::Stop).unwrap_or(());
+                self.reap(service)
+            }
+            None => {
+                outputln!("The {} service is not running", name);
+                Ok(())
+            }
+        }
+    }
+
+    /// The main loop of the manager. Handles signals, loads and unloads services on request, and
+    /// returns once no services are left running.
+    ///
+    /// # Failures
+    ///
+    /// * The last service to exit returned an error
+    pub fn run(&mut self) -> Result<()> {
+        let handler = wonder::actor::Builder::new(SignalNotifier)
+            .name("signal-handler".to_string())
+            .start(())
+            .unwrap();
+        let mut last_result = Ok(());
+        loop {
+            let start_time = SteadyTime::now();
+            match handler.receiver.try_recv() {
+                Ok(wonder::actor::Message::Cast(signals::Message::Signal(sig))) => {
+                    debug!("SIG = {:?}", sig);
+                    match sig {
+                        signals::Signal::SIGINT | signals::Signal::SIGTERM => {
+                            let names: Vec<String> =
+                                self.services.iter().map(|s| s.name.clone()).collect();
+                            for name in names.iter() {
+                                if let Err(e) = self.stop_service(name) {
+                                    last_result = Err(e);
+                                }
+                            }
+                            self.gossip_server.leave();
+                            if let Err(e) = self.gossip_server.save_ring_state() {
+                                outputln!("Failed to save the ring state: {}", e);
+                            }
+                            break;
+                        }
+                        _ => {
+                            outputln!("Forwarding {:?} on to the supervised processes", sig);
+                            let sidecar_services = self.sidecar_services.read().unwrap();
+                            for service in sidecar_services.iter() {
+                                let supervisor = service.supervisor.read().unwrap();
+                                try!(supervisor.send_unix_signal(sig.clone()));
+                            }
+                        }
+                    };
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    panic!("signal handler crashed!");
+                }
+            }
+
+            loop {
+                match self.control.1.try_recv() {
+                    Ok(ManagerMessage::Load(ident, artifact)) => {
+                        let mut ui = UI::default();
+                        match command::start::load(&mut ui,
+                                                   &ident,
+                                                   artifact.as_ref().map(String::as_ref)) {
+                            Ok(package) => {
+                                if let Err(e) = self.add_service(package) {
+                                    outputln!("Failed to start {}: {}", ident, e);
+                                }
+                            }
+                            Err(e) => outputln!("Failed to load {}: {}", ident, e),
+                        }
+                    }
+                    Ok(ManagerMessage::Unload(name)) => {
+                        if let Err(e) = self.stop_service(&name) {
+                            outputln!("The {} service exited with an error: {}", name, e);
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            loop {
+                match self.exits.1.try_recv() {
+                    Ok(name) => {
+                        if let Some(index) = self.services.iter().position(|s| s.name == name) {
+                            let service = self.services.remove(index);
+                            last_result = self.reap(service);
+                            if let Err(ref e) = last_result {
+                                outputln!("The {} service exited with an error: {}", name, e);
+                            }
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            if self.services.is_empty() {
+                break;
+            }
+
+            // Slow down our loop
+            let elapsed_time = SteadyTime::now() - start_time;
+            let elapsed_millis = elapsed_time.num_milliseconds();
+
+            if elapsed_millis < MINIMUM_LOOP_TIME_MS {
+                thread::sleep(Duration::from_millis((MINIMUM_LOOP_TIME_MS - elapsed_millis) as
+                                                    u64));
+            }
+        }
+        last_result
+    }
+
+    /// Joins the thread of a service that has been removed from the manager, and forgets about it
+    /// in the gossip server and sidecar.
+    fn reap(&self, service: ServiceThread) -> Result<()> {
+        let result = match service.handle.join() {
+            Ok(result) => result,
+            Err(_) => Err(sup_error!(Error::ServicePanicked(service.name.clone()))),
+        };
+        self.gossip_server.remove_service(&service.service_group);
+        output::remove_service_group(&service.name);
+        {
+            let mut sidecar_services = self.sidecar_services.write().unwrap();
+            sidecar_services.retain(|s| s.name != service.name);
+        }
+        outputln!("Stopped the {} service", service.name);
+        result
+    }
+}
+
+/// Runs a service with the topology from the configuration.
+fn run_topology(package: Package, ctx: ServiceContext) -> Result<()> {
+    match *gconfig().topology() {
+        Topology::Standalone => topology::standalone::run(package, ctx),
+        Topology::Leader => topology::leader::run(package, ctx),
+        Topology::Initializer => topology::initializer::run(package, ctx),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    use super::ServiceExit;
+
+    #[test]
+    fn service_exit_is_sent_when_a_service_panics() {
+        let (tx, rx) = channel();
+        let exit = ServiceExit {
+            name: String::from("soup"),
+            exits: tx,
+        };
+        let handle = thread::spawn(move || {
+            let _exit = exit;
+            panic!("the soup boiled over");
+        });
+        assert!(handle.join().is_err());
+        assert_eq!(rx.recv().unwrap(), "soup");
+    }
+}
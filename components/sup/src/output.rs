@@ -24,18 +24,43 @@
 //! is turned on, then every line printed is annotated with its preamble, logkey, and precise
 //! location. Without verbose, it prints simply the preamble and logkey. Coloring does what it says
 //! on the tin :)
+//!
+//! For log pipelines, output can instead be printed as JSON, one object per line:
+//!
+//! ```json
+//! {"timestamp":"2016-09-01T16:20:00Z","level":"info","logkey":"TP",
+//!  "service_group":"redis.default","member_id":"c8d1...","message":"Starting","location":null}
+//! ```
+//!
+//! The service group is known for output from a running service, whose preamble is the service's
+//! name, and for output from its hooks, which name their service. The level is `error` for
+//! anything a service or hook wrote to its standard error, and `info` otherwise. The location is
+//! only included in verbose mode.
 
+use std::collections::HashMap;
+use std::sync::RwLock;
 use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
 use std::fmt;
 
 use ansi_term::Colour::{White, Cyan, Green};
+use rustc_serialize::json;
+use time;
 
 use PROGRAM_NAME;
 
+/// The logkey of output a service or hook wrote to its standard error
+pub const STDERR_LOGKEY: &'static str = "E";
+
 static mut VERBOSE: AtomicBool = ATOMIC_BOOL_INIT;
 // I am sorry this isn't named the other way; I can't get an atomic initializer that defaults to
 // true. Them's the breaks.
 static mut NO_COLOR: AtomicBool = ATOMIC_BOOL_INIT;
+static mut JSON: AtomicBool = ATOMIC_BOOL_INIT;
+
+lazy_static! {
+    static ref MEMBER_ID: RwLock<Option<String>> = RwLock::new(None);
+    static ref SERVICE_GROUPS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
 
 /// True if verbose output is on.
 pub fn is_verbose() -> bool {
@@ -67,6 +92,45 @@ pub fn set_no_color(booly: bool) {
     }
 }
 
+/// True if output is printed as JSON.
+pub fn is_json() -> bool {
+    unsafe { JSON.load(Ordering::Relaxed) }
+}
+
+/// Turn JSON output on or off.
+pub fn set_json(booly: bool) {
+    unsafe {
+        JSON.store(booly, Ordering::Relaxed);
+    }
+}
+
+/// Set the gossip member id of this supervisor, which JSON output includes.
+pub fn set_member_id(member_id: String) {
+    *MEMBER_ID.write().unwrap() = Some(member_id);
+}
+
+/// Set the service group of a running service, which JSON output from that service includes.
+pub fn set_service_group(service: String, service_group: String) {
+    SERVICE_GROUPS.write().unwrap().insert(service, service_group);
+}
+
+/// Forget the service group of a service that has stopped.
+pub fn remove_service_group(service: &str) {
+    SERVICE_GROUPS.write().unwrap().remove(service);
+}
+
+/// A line of output, as it is printed in JSON.
+#[derive(RustcEncodable)]
+struct JsonOutput<'a> {
+    timestamp: String,
+    level: &'static str,
+    logkey: &'a str,
+    service_group: Option<String>,
+    member_id: Option<String>,
+    message: &'a str,
+    location: Option<String>,
+}
+
 /// Adds structure to printed output. Stores a preamble, a logkey, line, file, column, and content
 /// to print.
 pub struct StructuredOutput<'a> {
@@ -76,8 +140,12 @@ pub struct StructuredOutput<'a> {
     file: &'static str,
     column: u32,
     content: &'a str,
+    /// The service the output comes from, when the preamble is not its name; hooks print with
+    /// their own name as the preamble
+    pub service: Option<&'a str>,
     pub verbose: Option<bool>,
     pub color: Option<bool>,
+    pub json: Option<bool>,
 }
 
 impl<'a> StructuredOutput<'a> {
@@ -96,18 +164,47 @@ impl<'a> StructuredOutput<'a> {
             file: file,
             column: column,
             content: content,
+            service: None,
             verbose: None,
             color: None,
+            json: None,
+        }
+    }
+
+    fn fmt_json(&self, f: &mut fmt::Formatter, verbose: bool) -> fmt::Result {
+        let output = JsonOutput {
+            timestamp: format!("{}", time::now_utc().rfc3339()),
+            level: if self.logkey == STDERR_LOGKEY {
+                "error"
+            } else {
+                "info"
+            },
+            logkey: self.logkey,
+            service_group: SERVICE_GROUPS.read()
+                .unwrap()
+                .get(self.service.unwrap_or(self.preamble))
+                .cloned(),
+            member_id: MEMBER_ID.read().unwrap().clone(),
+            message: self.content.trim_right_matches('\n'),
+            location: if verbose {
+                Some(format!("{}:{}:{}", self.file, self.line, self.column))
+            } else {
+                None
+            },
+        };
+        match json::encode(&output) {
+            Ok(encoded) => write!(f, "{}", encoded),
+            Err(_) => Err(fmt::Error),
         }
     }
 }
 
-// If we ever want to create multiple output formats in the future, we would do it here -
-// essentially create a flag we check to see what output you want, then call a different formatting
-// function. Viola!
 impl<'a> fmt::Display for StructuredOutput<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let verbose = self.verbose.unwrap_or(is_verbose());
+        if self.json.unwrap_or(is_json()) {
+            return self.fmt_json(f, verbose);
+        }
         let color = self.color.unwrap_or(is_color());
         let preamble_color = if self.preamble == PROGRAM_NAME.as_str() {
             Cyan
@@ -149,8 +246,9 @@ impl<'a> fmt::Display for StructuredOutput<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::StructuredOutput;
+    use super::{set_service_group, StructuredOutput};
     use ansi_term::Colour::{White, Cyan};
+    use rustc_serialize::json::Json;
 
     use PROGRAM_NAME;
 
@@ -187,4 +285,35 @@ mod tests {
                            Cyan.paint(progname),
                            White.bold().paint("SOT")));
     }
+
+    #[test]
+    fn format_json() {
+        let mut so = so("soup", "opeth is amazing\n");
+        so.verbose = Some(false);
+        so.json = Some(true);
+        let output = Json::from_str(&format!("{}", so)).unwrap();
+        assert_eq!(output.find("logkey").and_then(|k| k.as_string()), Some("SOT"));
+        assert_eq!(output.find("level").and_then(|l| l.as_string()), Some("info"));
+        assert_eq!(output.find("message").and_then(|m| m.as_string()),
+                   Some("opeth is amazing"));
+        assert!(output.find("location").unwrap().is_null());
+    }
+
+    #[test]
+    fn format_json_service_group() {
+        set_service_group(String::from("opeth"), String::from("opeth.default"));
+        let mut output = so("opeth", "blackwater park");
+        output.json = Some(true);
+        let json = Json::from_str(&format!("{}", output)).unwrap();
+        assert_eq!(json.find("service_group").and_then(|sg| sg.as_string()),
+                   Some("opeth.default"));
+
+        // A hook prints with its own name, but the service group of its service
+        let mut hook = so("run", "blackwater park");
+        hook.service = Some("opeth");
+        hook.json = Some(true);
+        let json = Json::from_str(&format!("{}", hook)).unwrap();
+        assert_eq!(json.find("service_group").and_then(|sg| sg.as_string()),
+                   Some("opeth.default"));
+    }
 }
@@ -12,25 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
 use std::fs::{self, OpenOptions};
+use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration as StdDuration;
 
-use handlebars::Handlebars;
+use libc::{self, c_int, pid_t};
+use time::{self, Duration, SteadyTime};
 
+use config::gconfig;
 use error::{Error, Result};
 use hcore::util;
+use output::{self, StructuredOutput};
 use package::Package;
-use service_config::{ServiceConfig, never_escape_fn};
-use util::convert;
-use util::handlebars_helpers;
+use service_config::ServiceConfig;
+use supervisor::{WEXITSTATUS, WIFEXITED};
+use util::signals;
 use util::users as hab_users;
 
 pub const HOOK_PERMISSIONS: u32 = 0o755;
 static LOGKEY: &'static str = "PH";
+/// How many of the last lines a hook wrote we keep in its result
+const OUTPUT_TAIL_LINES: usize = 20;
+/// How often, in milliseconds, we check whether a hook has exited
+const HOOK_POLL_MS: u64 = 50;
+
+lazy_static! {
+    /// The result of the last run of each hook, by service and then by hook
+    static ref LAST_RESULTS: RwLock<HashMap<String, BTreeMap<String, HookResult>>> =
+        RwLock::new(HashMap::new());
+}
 
 #[derive(Debug, Clone)]
 pub enum HookType {
@@ -39,6 +57,12 @@ pub enum HookType {
     FileUpdated,
     Run,
     Init,
+    PostRun,
+    PreStop,
+    PostStop,
+    Suitability,
+    SmokeTest,
+    Event,
 }
 
 impl fmt::Display for HookType {
@@ -49,17 +73,52 @@ impl fmt::Display for HookType {
             &HookType::FileUpdated => write!(f, "file_updated"),
             &HookType::Reconfigure => write!(f, "reconfigure"),
             &HookType::Run => write!(f, "run"),
+            &HookType::PostRun => write!(f, "post_run"),
+            &HookType::PreStop => write!(f, "pre_stop"),
+            &HookType::PostStop => write!(f, "post_stop"),
+            &HookType::Suitability => write!(f, "suitability"),
+            &HookType::SmokeTest => write!(f, "smoke_test"),
+            &HookType::Event => write!(f, "event"),
         }
     }
 }
 
-#[derive(Debug)]
+/// The result of a run of a hook.
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct HookResult {
+    /// The name of the hook
+    pub hook: String,
+    /// The code the hook exited with; `None` if it was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Whether the hook ran longer than its timeout, and was killed
+    pub timed_out: bool,
+    /// How long the hook ran, in milliseconds
+    pub duration_ms: i64,
+    /// When the hook finished, in seconds since the epoch
+    pub finished_at: i64,
+    /// The last lines the hook wrote to stdout and stderr
+    pub output: Vec<String>,
+}
+
+/// The results of the last run of each hook of a service, ordered by hook name.
+pub fn last_results(service: &str) -> Vec<HookResult> {
+    let results = LAST_RESULTS.read().unwrap();
+    results.get(service).map_or(Vec::new(), |r| r.values().cloned().collect())
+}
+
+#[derive(Debug, Clone)]
 pub struct Hook {
     pub htype: HookType,
     pub template: PathBuf,
     pub path: PathBuf,
     pub user: String,
     pub group: String,
+    /// The `PATH` the hook runs with; the runtime path of its package
+    pub run_path: Option<String>,
+    /// The service the hook belongs to
+    pub service: String,
+    /// How long, in seconds, the hook may run before it is killed; 0 lets it run forever
+    pub timeout_secs: u64,
 }
 
 impl Hook {
@@ -75,55 +134,152 @@ impl Hook {
             path: path,
             user: user,
             group: group,
+            run_path: None,
+            service: String::new(),
+            timeout_secs: 0,
         }
     }
 
     pub fn run(&self, context: Option<&ServiceConfig>) -> Result<String> {
+        self.run_with_env(context, &[])
+    }
+
+    /// Runs the hook like `run`, with extra variables in its environment.
+    pub fn run_with_env(&self,
+                        context: Option<&ServiceConfig>,
+                        env: &[(&str, String)])
+                        -> Result<String> {
         try!(self.compile(context));
+        self.exec_with_env(env)
+    }
+
+    /// Runs a hook that has already been compiled. What the hook writes to stdout and stderr is
+    /// printed with the name of the hook as the preamble, and the hook is killed, along with
+    /// anything it started, if it runs longer than its timeout. The result is kept for the
+    /// sidecar. Returns what the hook wrote to stdout.
+    pub fn exec(&self) -> Result<String> {
+        self.exec_with_env(&[])
+    }
+
+    /// Runs a hook that has already been compiled, like `exec`, with extra variables in its
+    /// environment.
+    pub fn exec_with_env(&self, env: &[(&str, String)]) -> Result<String> {
         let mut cmd = Command::new(&self.path);
         try!(self.run_platform(&mut cmd));
+        for &(key, ref value) in env {
+            cmd.env(key, value);
+        }
+        let started = SteadyTime::now();
         let mut child = try!(cmd.spawn());
-        {
-            let mut c_stdout = match child.stdout {
-                Some(ref mut s) => s,
-                None => {
-                    return Err(sup_error!(Error::HookFailed(self.htype.clone(),
-                                                            -1,
-                                                            String::from("Failed"))));
-                }
-            };
-            let preamble_str = format!("{}", &self.htype);
-            let mut line = output_format!(preamble & preamble_str, "");
-            loop {
-                let mut buf = [0u8; 1]; // Our byte buffer
-                let len = try!(c_stdout.read(&mut buf));
-                match len {
-                    0 => {
-                        // 0 == EOF, so stop writing and finish progress
-                        break;
-                    }
-                    _ => {
-                        // Write the buffer to the BufWriter on the Heap
-                        let buf_string = String::from_utf8_lossy(&buf[0..len]);
-                        line.push_str(&buf_string);
-                        if line.contains("\n") {
-                            print!("{}", line);
-                            line = output_format!(preamble & preamble_str, "");
-                        }
-                    }
-                }
+        let name = format!("{}", &self.htype);
+        let tail = Arc::new(Mutex::new(VecDeque::new()));
+        let stdout = match child.stdout.take() {
+            Some(stdout) => {
+                let name = name.clone();
+                let service = self.service.clone();
+                let tail = tail.clone();
+                try!(thread::Builder::new()
+                    .name(format!("hook-{}-stdout", name))
+                    .spawn(move || read_output(stdout, &name, &service, "O", &tail)))
+            }
+            None => {
+                return Err(sup_error!(Error::HookFailed(self.htype.clone(),
+                                                        -1,
+                                                        String::from("Failed"))));
             }
+        };
+        if let Some(stderr) = child.stderr.take() {
+            let name = name.clone();
+            let service = self.service.clone();
+            let tail = tail.clone();
+            try!(thread::Builder::new()
+                .name(format!("hook-{}-stderr", name))
+                .spawn(move || {
+                    read_output(stderr, &name, &service, output::STDERR_LOGKEY, &tail)
+                }));
         }
-        let exit_status = try!(child.wait());
-        if exit_status.success() {
-            Ok(String::from("Finished"))
+        let (exit_code, timed_out) = try!(self.wait(child.id()));
+        // Once a hook times out, something it started may still hold its stdout open; we only
+        // wait for the output of hooks that exited on their own.
+        let output = if timed_out {
+            String::new()
         } else {
+            stdout.join().unwrap_or(String::new())
+        };
+        let result = HookResult {
+            hook: name,
+            exit_code: exit_code,
+            timed_out: timed_out,
+            duration_ms: (SteadyTime::now() - started).num_milliseconds(),
+            finished_at: time::get_time().sec,
+            output: tail.lock().unwrap().iter().cloned().collect(),
+        };
+        self.record(result.clone());
+        if timed_out {
             Err(sup_error!(Error::HookFailed(self.htype.clone(),
-                                             exit_status.code().unwrap_or(-1),
-                                             String::from("Failed"))))
+                                             -1,
+                                             format!("Timed out after {} seconds",
+                                                     self.timeout_secs))))
+        } else if exit_code == Some(0) {
+            Ok(output)
+        } else {
+            Err(sup_error!(Error::HookFailed(self.htype.clone(), exit_code.unwrap_or(-1), output)))
+        }
+    }
+
+    /// Waits for the hook to exit, killing its process group if it runs longer than its timeout.
+    /// Returns the code it exited with, if it exited normally, and whether it timed out.
+    fn wait(&self, pid: u32) -> Result<(Option<i32>, bool)> {
+        let deadline = if self.timeout_secs > 0 {
+            Some(SteadyTime::now() + Duration::seconds(self.timeout_secs as i64))
+        } else {
+            None
+        };
+        let mut timed_out = false;
+        loop {
+            let mut status: c_int = 0;
+            let options = if timed_out { 0 } else { libc::WNOHANG };
+            match unsafe { libc::waitpid(pid as pid_t, &mut status, options) } {
+                0 => {}
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(sup_error!(Error::Io(err)));
+                    }
+                }
+                _ => {
+                    if WIFEXITED(status) {
+                        return Ok((Some(WEXITSTATUS(status)), timed_out));
+                    } else {
+                        return Ok((None, timed_out));
+                    }
+                }
+            }
+            if timed_out {
+                continue;
+            }
+            if let Some(deadline) = deadline {
+                if SteadyTime::now() > deadline {
+                    outputln!(preamble & format!("{}", self.htype),
+                              "Timed out after {} seconds; killing it",
+                              self.timeout_secs);
+                    try!(signals::send_signal_to_process_group(pid, signals::Signal::SIGKILL));
+                    timed_out = true;
+                    continue;
+                }
+            }
+            thread::sleep(StdDuration::from_millis(HOOK_POLL_MS));
         }
     }
 
+    /// Keeps the result of a run of this hook, replacing the last one.
+    fn record(&self, result: HookResult) {
+        let mut results = LAST_RESULTS.write().unwrap();
+        results.entry(self.service.clone())
+            .or_insert(BTreeMap::new())
+            .insert(result.hook.clone(), result);
+    }
+
     #[cfg(any(target_os="linux", target_os="macos"))]
     fn run_platform(&self, cmd: &mut Command) -> Result<()> {
         use std::os::unix::process::CommandExt;
@@ -139,6 +295,17 @@ impl Hook {
 
         let uid = uid.unwrap();
         let gid = gid.unwrap();
+        if let Some(ref run_path) = self.run_path {
+            cmd.env("PATH", run_path);
+        }
+        // Run the hook in a process group of its own, so we can kill everything it started if
+        // it times out
+        cmd.before_exec(|| {
+            unsafe {
+                libc::setpgid(0, 0);
+            }
+            Ok(())
+        });
         cmd.stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -155,14 +322,7 @@ impl Hook {
     pub fn compile(&self, context: Option<&ServiceConfig>) -> Result<()> {
         if let Some(ctx) = context {
             debug!("Rendering hook {:?}", self);
-            let mut handlebars = Handlebars::new();
-            handlebars.register_helper("json", Box::new(handlebars_helpers::json_helper));
-            handlebars.register_helper("toml", Box::new(handlebars_helpers::toml_helper));
-            handlebars.register_escape_fn(never_escape_fn);
-            try!(handlebars.register_template_file("hook", &self.template));
-            let toml = try!(ctx.to_toml());
-            let svc_data = convert::toml_to_json(toml);
-            let data = try!(handlebars.render("hook", &svc_data));
+            let data = try!(ctx.render_template(&self.template));
             let mut file = try!(OpenOptions::new()
                 .write(true)
                 .truncate(true)
@@ -190,6 +350,12 @@ pub struct HookTable<'a> {
     pub reconfigure_hook: Option<Hook>,
     pub file_updated_hook: Option<Hook>,
     pub run_hook: Option<Hook>,
+    pub post_run_hook: Option<Hook>,
+    pub pre_stop_hook: Option<Hook>,
+    pub post_stop_hook: Option<Hook>,
+    pub suitability_hook: Option<Hook>,
+    pub smoke_test_hook: Option<Hook>,
+    pub event_hook: Option<Hook>,
 }
 
 impl<'a> HookTable<'a> {
@@ -201,6 +367,12 @@ impl<'a> HookTable<'a> {
             reconfigure_hook: None,
             file_updated_hook: None,
             run_hook: None,
+            post_run_hook: None,
+            pre_stop_hook: None,
+            post_stop_hook: None,
+            suitability_hook: None,
+            smoke_test_hook: None,
+            event_hook: None,
         }
     }
 
@@ -214,6 +386,12 @@ impl<'a> HookTable<'a> {
                     self.reconfigure_hook = self.load_hook(HookType::Reconfigure);
                     self.health_check_hook = self.load_hook(HookType::HealthCheck);
                     self.run_hook = self.load_hook(HookType::Run);
+                    self.post_run_hook = self.load_hook(HookType::PostRun);
+                    self.pre_stop_hook = self.load_hook(HookType::PreStop);
+                    self.post_stop_hook = self.load_hook(HookType::PostStop);
+                    self.suitability_hook = self.load_hook(HookType::Suitability);
+                    self.smoke_test_hook = self.load_hook(HookType::SmokeTest);
+                    self.event_hook = self.load_hook(HookType::Event);
                 }
             }
             Err(_) => {}
@@ -228,8 +406,53 @@ impl<'a> HookTable<'a> {
             .expect("Can't determine user:group");
 
         match fs::metadata(&template) {
-            Ok(_) => Some(Hook::new(hook_type, template, concrete, user, group)),
+            Ok(_) => {
+                let mut hook = Hook::new(hook_type, template, concrete, user, group);
+                hook.run_path = self.package.run_path().ok();
+                hook.service = self.package.name.clone();
+                hook.timeout_secs = gconfig().timeout_for_hook(&hook.htype.to_string());
+                Some(hook)
+            }
             Err(_) => None,
         }
     }
 }
+
+/// Prints each line a hook writes to one of its outputs, under the hook's `name` and with the
+/// service group of its `service`, and keeps the last lines in `tail`. Returns everything the
+/// hook wrote.
+fn read_output<R: Read>(reader: R,
+                        name: &str,
+                        service: &str,
+                        logkey: &'static str,
+                        tail: &Mutex<VecDeque<String>>)
+                        -> String {
+    let mut reader = BufReader::new(reader);
+    let mut output = String::new();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = String::from_utf8_lossy(&buf).into_owned();
+        let mut so = StructuredOutput::new(name,
+                                           logkey,
+                                           line!(),
+                                           file!(),
+                                           column!(),
+                                           line.trim_right_matches('\n'));
+        so.service = Some(service);
+        println!("{}", so);
+        {
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == OUTPUT_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line.trim_right_matches('\n').to_string());
+        }
+        output.push_str(&line);
+    }
+    output
+}
@@ -23,18 +23,22 @@ use std::env;
 use std::fmt;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::string::ToString;
 use std::io::prelude::*;
 
+use common::event::Event;
 use hcore::package::{PackageIdent, PackageInstall};
 use hcore::util;
+use toml;
 
-use self::hooks::{HookTable, HOOK_PERMISSIONS};
+use self::hooks::{Hook, HookTable, HOOK_PERMISSIONS};
 use config::gconfig;
 use error::{Error, Result, SupError};
 use health_check::{self, CheckResult};
 use service_config::ServiceConfig;
-use supervisor::Supervisor;
+use supervisor::{ProcessHooks, Shutdown, Supervisor};
+use util::signals::Signal;
 use util::path::busybox_paths;
 use util::users as hab_users;
 
@@ -44,6 +48,12 @@ const HEALTHCHECK_FILENAME: &'static str = "health_check";
 const FILEUPDATED_FILENAME: &'static str = "file_updated";
 const RECONFIGURE_FILENAME: &'static str = "reconfigure";
 const RUN_FILENAME: &'static str = "run";
+const POSTRUN_FILENAME: &'static str = "post_run";
+const PRESTOP_FILENAME: &'static str = "pre_stop";
+const POSTSTOP_FILENAME: &'static str = "post_stop";
+const SUITABILITY_FILENAME: &'static str = "suitability";
+const SMOKETEST_FILENAME: &'static str = "smoke_test";
+const EVENT_FILENAME: &'static str = "event";
 
 #[derive(Debug, Clone)]
 pub struct Package {
@@ -125,6 +135,12 @@ impl Package {
             HookType::FileUpdated => base.join(FILEUPDATED_FILENAME),
             HookType::Reconfigure => base.join(RECONFIGURE_FILENAME),
             HookType::Run => base.join(RUN_FILENAME),
+            HookType::PostRun => base.join(POSTRUN_FILENAME),
+            HookType::PreStop => base.join(PRESTOP_FILENAME),
+            HookType::PostStop => base.join(POSTSTOP_FILENAME),
+            HookType::Suitability => base.join(SUITABILITY_FILENAME),
+            HookType::SmokeTest => base.join(SMOKETEST_FILENAME),
+            HookType::Event => base.join(EVENT_FILENAME),
         }
     }
 
@@ -136,6 +152,12 @@ impl Package {
             HookType::FileUpdated => base.join(FILEUPDATED_FILENAME),
             HookType::Reconfigure => base.join(RECONFIGURE_FILENAME),
             HookType::Run => base.join(RUN_FILENAME),
+            HookType::PostRun => base.join(POSTRUN_FILENAME),
+            HookType::PreStop => base.join(PRESTOP_FILENAME),
+            HookType::PostStop => base.join(POSTSTOP_FILENAME),
+            HookType::Suitability => base.join(SUITABILITY_FILENAME),
+            HookType::SmokeTest => base.join(SMOKETEST_FILENAME),
+            HookType::Event => base.join(EVENT_FILENAME),
         }
     }
 
@@ -244,6 +266,61 @@ impl Package {
         Ok(())
     }
 
+    /// Compile the hooks the supervisor runs around the lifecycle of the process.
+    pub fn process_hooks(&self, context: &ServiceConfig) -> Result<ProcessHooks> {
+        let hooks = self.hooks();
+        for hook in vec![&hooks.post_run_hook, &hooks.pre_stop_hook, &hooks.post_stop_hook] {
+            if let Some(ref hook) = *hook {
+                try!(hook.compile(Some(context)));
+            }
+        }
+        Ok(ProcessHooks {
+            post_run: hooks.post_run_hook,
+            pre_stop: hooks.pre_stop_hook,
+            post_stop: hooks.post_stop_hook,
+        })
+    }
+
+    /// How the supervisor should stop the package's service. The `shutdown_signal` and
+    /// `shutdown_timeout` keys of the `[hab]` table in its configuration win over the
+    /// SHUTDOWN_SIGNAL and SHUTDOWN_TIMEOUT metadata from its plan; without either, we send
+    /// `TERM` and wait 8 seconds.
+    ///
+    /// # Failures
+    ///
+    /// * The stop signal is not one we support
+    /// * `shutdown_signal` is not a string, or `shutdown_timeout` is not a number of seconds
+    /// * The package metadata cannot be read
+    pub fn shutdown(&self, context: &ServiceConfig) -> Result<Shutdown> {
+        let mut shutdown = Shutdown::default();
+        if let Some(signal) = try!(self.pkg_install.shutdown_signal()) {
+            shutdown.signal = try!(Signal::from_str(&signal));
+        }
+        if let Some(secs) = try!(self.pkg_install.shutdown_timeout()) {
+            shutdown.timeout_secs = secs;
+        }
+        let toml = try!(context.to_toml());
+        if let Some(signal) = toml.lookup("cfg.hab.shutdown_signal") {
+            match signal.as_str() {
+                Some(signal) => shutdown.signal = try!(Signal::from_str(signal)),
+                None => {
+                    return Err(invalid_shutdown("hab.shutdown_signal must be a signal name",
+                                                signal))
+                }
+            }
+        }
+        if let Some(secs) = toml.lookup("cfg.hab.shutdown_timeout") {
+            match secs.as_integer() {
+                Some(secs) if secs >= 0 => shutdown.timeout_secs = secs as u64,
+                _ => {
+                    return Err(invalid_shutdown("hab.shutdown_timeout must be a number of seconds",
+                                                secs))
+                }
+            }
+        }
+        Ok(shutdown)
+    }
+
     pub fn topology_leader() -> Result<()> {
         Ok(())
     }
@@ -324,38 +401,73 @@ impl Package {
         }
     }
 
-    pub fn health_check(&self,
-                        config: &ServiceConfig,
-                        supervisor: &Supervisor)
-                        -> Result<CheckResult> {
-        if let Some(hook) = self.hooks().health_check_hook {
-            match hook.run(Some(config)) {
+    /// Run the suitability hook if present, and return the number it printed last; higher
+    /// numbers make us a better candidate in leader elections.
+    ///
+    /// # Failures
+    ///
+    /// * The hook fails, or its last line of output is not a number
+    pub fn suitability(&self, context: &ServiceConfig) -> Result<Option<u64>> {
+        if let Some(hook) = self.hooks().suitability_hook {
+            let output = try!(hook.run(Some(context)));
+            let last_line = output.lines().last().unwrap_or("").trim().to_string();
+            match last_line.parse::<u64>() {
+                Ok(suitability) => Ok(Some(suitability)),
+                Err(_) => Err(sup_error!(Error::InvalidSuitability(last_line))),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Run the smoke_test hook if present, once an updated package passes its health checks. Any
+    /// exit code but 0 fails the update.
+    pub fn smoke_test(&self, context: &ServiceConfig) -> Result<CheckResult> {
+        if let Some(hook) = self.hooks().smoke_test_hook {
+            match hook.run(Some(context)) {
                 Ok(output) => Ok(health_check::CheckResult::ok(output)),
-                Err(SupError { err: Error::HookFailed(_, 1, output), .. }) => {
-                    Ok(health_check::CheckResult::warning(output))
-                }
-                Err(SupError { err: Error::HookFailed(_, 2, output), .. }) => {
+                Err(SupError { err: Error::HookFailed(_, _, output), .. }) => {
                     Ok(health_check::CheckResult::critical(output))
                 }
-                Err(SupError { err: Error::HookFailed(_, 3, output), .. }) => {
-                    Ok(health_check::CheckResult::unknown(output))
-                }
-                Err(SupError { err: Error::HookFailed(_, code, output), .. }) => {
-                    Err(sup_error!(Error::HealthCheck(format!("hook exited code={}, \
-                                                                    output={}",
-                                                              code,
-                                                              output))))
-                }
-                Err(e) => Err(SupError::from(e)),
+                Err(e) => Err(e),
             }
         } else {
-            let (health, status) = supervisor.status();
-            let last_config = try!(self.last_config());
-            if health {
-                Ok(health_check::CheckResult::ok(format!("{}\n{}", status, last_config)))
-            } else {
-                Ok(health_check::CheckResult::critical(format!("{}\n{}", status, last_config)))
+            Ok(health_check::CheckResult::ok(String::from("No smoke_test hook")))
+        }
+    }
+
+    /// The event hook, compiled with `context`, ready to be run with the `event_env` of each
+    /// event; `None` if the package has none.
+    pub fn event_hook(&self, context: &ServiceConfig) -> Result<Option<Hook>> {
+        match self.hooks().event_hook {
+            Some(hook) => {
+                try!(hook.compile(Some(context)));
+                Ok(Some(hook))
             }
+            None => Ok(None),
+        }
+    }
+
+    pub fn health_check(&self,
+                        config: &ServiceConfig,
+                        supervisor: &Supervisor)
+                        -> Result<CheckResult> {
+        if let Some(hook) = self.hooks().health_check_hook {
+            health_check_result(hook.run(Some(config)))
+        } else {
+            self.supervisor_health(supervisor)
+        }
+    }
+
+    /// The result of a health check for a package without a health_check hook, which is healthy
+    /// as long as the supervisor is.
+    pub fn supervisor_health(&self, supervisor: &Supervisor) -> Result<CheckResult> {
+        let (health, status) = supervisor.status();
+        let last_config = try!(self.last_config());
+        if health {
+            Ok(health_check::CheckResult::ok(format!("{}\n{}", status, last_config)))
+        } else {
+            Ok(health_check::CheckResult::critical(format!("{}\n{}", status, last_config)))
         }
     }
 
@@ -373,6 +485,46 @@ impl Package {
     }
 }
 
+/// The environment the event hook runs with for an event.
+pub fn event_env(event: &Event) -> Vec<(&'static str, String)> {
+    vec![("HAB_EVENT_ID", event.id.to_string()),
+         ("HAB_EVENT_SERVICE_GROUP", event.service_group.to_string()),
+         ("HAB_EVENT_TOPIC", event.topic.clone()),
+         ("HAB_EVENT_BODY", event.body.clone()),
+         ("HAB_EVENT_PUBLISHED_AT", event.published_at.to_string())]
+}
+
+/// Turns the result of running a health_check hook into a `CheckResult`, from its exit code.
+///
+/// # Failures
+///
+/// * The hook exited with a code that is not a health check status
+/// * The hook could not be run
+pub fn health_check_result(result: Result<String>) -> Result<CheckResult> {
+    match result {
+        Ok(output) => Ok(health_check::CheckResult::ok(output)),
+        Err(SupError { err: Error::HookFailed(_, 1, output), .. }) => {
+            Ok(health_check::CheckResult::warning(output))
+        }
+        Err(SupError { err: Error::HookFailed(_, 2, output), .. }) => {
+            Ok(health_check::CheckResult::critical(output))
+        }
+        Err(SupError { err: Error::HookFailed(_, 3, output), .. }) => {
+            Ok(health_check::CheckResult::unknown(output))
+        }
+        Err(SupError { err: Error::HookFailed(_, code, output), .. }) => {
+            Err(sup_error!(Error::HealthCheck(format!("hook exited code={}, output={}",
+                                                      code,
+                                                      output))))
+        }
+        Err(e) => Err(SupError::from(e)),
+    }
+}
+
+fn invalid_shutdown(problem: &str, value: &toml::Value) -> SupError {
+    sup_error!(Error::InvalidConfig(vec![format!("{}, not {}", problem, value)]))
+}
+
 impl Into<PackageIdent> for Package {
     fn into(self) -> PackageIdent {
         PackageIdent::new(self.origin,
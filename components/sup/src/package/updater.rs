@@ -48,12 +48,19 @@ impl PackageUpdater {
     pub fn run(actor: &PackageUpdaterActor) -> ActorResult<()> {
         actor.cast(UpdaterMessage::Run)
     }
+
+    /// Tell a package updater to never offer the given release again; for example, because it
+    /// failed as a canary.
+    pub fn reject(actor: &PackageUpdaterActor, ident: PackageIdent) -> ActorResult<()> {
+        actor.cast(UpdaterMessage::Reject(ident))
+    }
 }
 
 pub struct UpdaterState {
     pub depot: String,
     pub package: Arc<RwLock<Package>>,
     pub status: UpdaterStatus,
+    pub rejected: Vec<PackageIdent>,
 }
 
 impl UpdaterState {
@@ -62,6 +69,7 @@ impl UpdaterState {
             depot: depot,
             package: package,
             status: UpdaterStatus::Stopped,
+            rejected: Vec::new(),
         }
     }
 }
@@ -70,6 +78,7 @@ impl UpdaterState {
 pub enum UpdaterMessage {
     Ok,
     Run,
+    Reject(PackageIdent),
     Stop,
     Update(Package),
 }
@@ -109,7 +118,10 @@ impl GenServer for PackageUpdater {
         match depot_client.show_package(ident) {
             Ok(remote) => {
                 let latest_ident: PackageIdent = remote.get_ident().clone().into();
-                if &latest_ident > package.ident() {
+                if state.rejected.contains(&latest_ident) {
+                    debug!("Package found was rejected as a bad release: {}", latest_ident);
+                    HandleResult::NoReply(Some(TIMEOUT_MS))
+                } else if &latest_ident > package.ident() {
                     let mut ui = UI::default();
                     match depot_client.fetch_package(latest_ident.clone(),
                                                      &Path::new(FS_ROOT_PATH)
@@ -151,6 +163,15 @@ impl GenServer for PackageUpdater {
                    -> HandleResult<Self::T> {
         match msg {
             UpdaterMessage::Run => HandleResult::NoReply(Some(TIMEOUT_MS)),
+            UpdaterMessage::Reject(ident) => {
+                if !state.rejected.contains(&ident) {
+                    state.rejected.push(ident);
+                }
+                match state.status {
+                    UpdaterStatus::Running => HandleResult::NoReply(Some(TIMEOUT_MS)),
+                    UpdaterStatus::Stopped => HandleResult::NoReply(None),
+                }
+            }
             _ => {
                 match state.status {
                     UpdaterStatus::Running => HandleResult::NoReply(Some(TIMEOUT_MS)),
@@ -15,19 +15,23 @@
 /// Collect all the configuration data that is exposed to users, and render it.
 
 use std::ascii::AsciiExt;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 
 use ansi_term::Colour::Purple;
 use rustc_serialize::Encodable;
+use rustc_serialize::json::Json;
 use toml;
 use handlebars::Handlebars;
 
 use common::gossip_file::GOSSIP_TOML;
 use census::{Census, CensusList};
 use config::gconfig;
+use config_schema::{Schema, SCHEMA_FILE};
 use error::{Error, Result};
 use hcore::package::PackageInstall;
 use hcore::crypto;
@@ -57,6 +61,8 @@ pub struct ServiceConfig {
     config_hash: HashMap<String, String>,
     // Set to 'true' if we have data that needs to be sent to a configuration file
     pub needs_write: bool,
+    // Why the last configuration we were given was rejected; empty if it was applied
+    cfg_errors: Vec<String>,
 }
 
 pub fn never_escape_fn(data: &str) -> String {
@@ -67,8 +73,26 @@ impl ServiceConfig {
     /// Takes a new package and a new census list, and returns a ServiceConfig. This function can
     /// fail, and indeed, we want it to - it causes the program to crash if we can not render the
     /// first pass of the configuration file.
+    ///
+    /// If the configuration does not match the package's schema, we start without the gossiped
+    /// configuration; if it still does not match, we fail.
     pub fn new(package: &Package, cl: &CensusList, bindings: Vec<String>) -> Result<ServiceConfig> {
-        let cfg = try!(Cfg::new(package));
+        let mut cfg = try!(Cfg::new(package));
+        let cfg_errors = try!(cfg.validate(package));
+        if !cfg_errors.is_empty() {
+            if cfg.gossip.is_none() {
+                return Err(sup_error!(Error::InvalidConfig(cfg_errors)));
+            }
+            outputln!("Ignoring the gossiped configuration, which does not match the schema:");
+            for error in cfg_errors.iter() {
+                outputln!("    {}", error);
+            }
+            cfg.gossip = None;
+            let errors = try!(cfg.validate(package));
+            if !errors.is_empty() {
+                return Err(sup_error!(Error::InvalidConfig(errors)));
+            }
+        }
         let bind = try!(Bind::new(bindings, &cl));
         Ok(ServiceConfig {
             pkg: Pkg::new(&package.pkg_install),
@@ -79,6 +103,7 @@ impl ServiceConfig {
             bind: bind,
             config_hash: HashMap::new(),
             needs_write: true,
+            cfg_errors: cfg_errors,
         })
     }
 
@@ -127,38 +152,90 @@ impl ServiceConfig {
         self.needs_write = true
     }
 
-    /// Replace the `cfg` data.
-    pub fn cfg(&mut self, package: &Package) {
-        match Cfg::new(package) {
-            Ok(cfg) => {
-                self.cfg = cfg;
-                self.needs_write = true;
+    /// Replace the `cfg` data. If the new data does not match the package's schema, it is
+    /// rejected, and we keep the last good `cfg` data. Returns true if the new data was applied.
+    pub fn cfg(&mut self, package: &Package) -> bool {
+        let cfg = match Cfg::new(package) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                outputln!("Failed to write new cfg tree: {}", e);
+                return false;
+            }
+        };
+        match cfg.validate(package) {
+            Ok(errors) => {
+                let applied = errors.is_empty();
+                if applied {
+                    self.cfg = cfg;
+                    self.needs_write = true;
+                } else {
+                    outputln!("Rejected the new configuration, which does not match the schema; \
+                               keeping the last good configuration:");
+                    for error in errors.iter() {
+                        outputln!("    {}", error);
+                    }
+                }
+                self.cfg_errors = errors;
+                applied
+            }
+            Err(e) => {
+                outputln!("Failed to validate new cfg tree: {}", e);
+                false
             }
-            Err(e) => outputln!("Failed to write new cfg tree: {}", e),
         }
     }
 
-    /// Write the configuration to `config.toml`, and render the templated configuration files.
-    pub fn write(&mut self, pkg: &Package) -> Result<bool> {
-        let pi = &pkg.pkg_install;
-        let final_toml = try!(self.to_toml());
-        {
-            let mut last_toml = try!(File::create(pi.svc_path().join("config.toml")));
-            try!(write!(&mut last_toml, "{}", toml::encode_str(&final_toml)));
+    /// The configuration the service exports, as TOML; `None` if the package exports nothing.
+    pub fn exported_cfg(&self, exports: &BTreeMap<String, String>) -> Option<String> {
+        if exports.is_empty() {
+            None
+        } else {
+            Some(toml::encode_str(&self.cfg.exported(exports)))
         }
-        let mut handlebars = Handlebars::new();
+    }
 
-        debug!("Registering handlebars helpers");
-        handlebars.register_helper("json", Box::new(handlebars_helpers::json_helper));
-        handlebars.register_helper("toml", Box::new(handlebars_helpers::toml_helper));
+    /// Why the last configuration we were given was rejected; empty if it was applied.
+    pub fn cfg_errors(&self) -> &[String] {
+        &self.cfg_errors
+    }
 
-        debug!("Registering configuration templates");
-        // By default, handlebars escapes HTML. We don't want that.
-        handlebars.register_escape_fn(never_escape_fn);
+    /// Replace the `cfg` data from `user.toml` with `user`, as if it were the service's
+    /// `user.toml`.
+    ///
+    /// # Failures
+    ///
+    /// * The configuration does not match the package's schema
+    pub fn user_cfg(&mut self, package: &Package, user: toml::Value) -> Result<()> {
+        self.cfg.user = Some(user);
+        let errors = try!(self.cfg.validate(package));
+        if !errors.is_empty() {
+            return Err(sup_error!(Error::InvalidConfig(errors)));
+        }
+        self.needs_write = true;
+        Ok(())
+    }
+
+    /// Replace the `svc` and `bind` data with the `svc` and `bind` tables of `data`, rather than
+    /// building them from the census. Tables `data` does not have are left alone.
+    pub fn census_data(&mut self, data: &toml::Table) {
+        if let Some(&toml::Value::Table(ref svc)) = data.get("svc") {
+            self.svc = Svc { toml: svc.clone() };
+        }
+        if let Some(&toml::Value::Table(ref bind)) = data.get("bind") {
+            self.bind = Bind { toml: bind.clone() };
+        }
+        self.needs_write = true
+    }
+
+    /// Render the templated configuration files of a package, returning the name of each file
+    /// and what it rendered to.
+    pub fn render(&self, pkg: &Package) -> Result<Vec<(String, String)>> {
+        let mut handlebars = handlebars();
 
         // Register all the templates; this makes them available as partials!
         // I suspect this will be useful, but I think we'll want to make this
         // more explicit... in a minute, we render all the config files anyway.
+        debug!("Registering configuration templates");
         let config_files = try!(pkg.config_files());
         for config in config_files.iter() {
             let path = pkg.config_from().join("config").join(config);
@@ -171,11 +248,35 @@ impl ServiceConfig {
             }
         }
 
-        let final_data = convert::toml_to_json(final_toml);
-        let mut should_restart = false;
+        let final_data = convert::toml_to_json(try!(self.to_toml()));
+        let mut rendered = Vec::new();
         for config in config_files {
             debug!("Rendering template {}", &config);
             let template_data = try!(handlebars.render(&config, &final_data));
+            rendered.push((config, template_data));
+        }
+        Ok(rendered)
+    }
+
+    /// Render a single template, such as a hook, with this configuration.
+    pub fn render_template(&self, template: &Path) -> Result<String> {
+        let mut handlebars = handlebars();
+        try!(handlebars.register_template_file("template", template));
+        let data = convert::toml_to_json(try!(self.to_toml()));
+        Ok(try!(handlebars.render("template", &data)))
+    }
+
+    /// Write the configuration to `config.toml`, and render the templated configuration files.
+    pub fn write(&mut self, pkg: &Package) -> Result<bool> {
+        let pi = &pkg.pkg_install;
+        let final_toml = try!(self.to_toml());
+        {
+            let mut last_toml = try!(File::create(pi.svc_path().join("config.toml")));
+            try!(write!(&mut last_toml, "{}", toml::encode_str(&final_toml)));
+        }
+
+        let mut should_restart = false;
+        for (config, template_data) in try!(self.render(pkg)) {
             let file_hash = try!(crypto::hash::hash_string(&template_data));
             let filename = pi.svc_config_path().join(&config).to_string_lossy().into_owned();
             if self.config_hash.contains_key(&filename) {
@@ -204,6 +305,26 @@ impl ServiceConfig {
     }
 }
 
+/// A handlebars registry with the helpers every template is rendered with. By default,
+/// handlebars escapes HTML; we don't want that.
+pub fn handlebars() -> Handlebars {
+    let mut handlebars = Handlebars::new();
+    debug!("Registering handlebars helpers");
+    handlebars.register_helper("json", Box::new(handlebars_helpers::json_helper));
+    handlebars.register_helper("toml", Box::new(handlebars_helpers::toml_helper));
+    handlebars.register_helper("toUppercase",
+                               Box::new(handlebars_helpers::to_uppercase_helper));
+    handlebars.register_helper("toLowercase",
+                               Box::new(handlebars_helpers::to_lowercase_helper));
+    handlebars.register_helper("strReplace", Box::new(handlebars_helpers::str_replace_helper));
+    handlebars.register_helper("strJoin", Box::new(handlebars_helpers::str_join_helper));
+    handlebars.register_helper("eachAlive", Box::new(handlebars_helpers::each_alive_helper));
+    handlebars.register_helper("pkgPathFor",
+                               Box::new(handlebars_helpers::pkg_path_for_helper));
+    handlebars.register_escape_fn(never_escape_fn);
+    handlebars
+}
+
 #[derive(Debug, RustcEncodable)]
 struct Bind {
     toml: toml::Table,
@@ -217,7 +338,7 @@ impl Bind {
             match cl.get(&service_group) {
                 Some(census) => {
                     top.insert(format!("has_{}", bind), toml::Value::Boolean(true));
-                    top.insert(bind, toml::Value::Table(service_entry(census)));
+                    top.insert(bind, toml::Value::Table(bind_entry(census)));
                 }
                 None => {
                     top.insert(format!("has_{}", bind), toml::Value::Boolean(false));
@@ -279,13 +400,13 @@ fn service_entry(census: &Census) -> toml::Table {
     let service = toml::Value::String(census.service.clone());
     let group = toml::Value::String(census.group.clone());
     let ident = toml::Value::String(census.service_group());
-    let me = toml::encode(census.me());
-    let leader = census.get_leader().map(|ce| toml::encode(ce));
+    let me = census.me().to_toml();
+    let leader = census.get_leader().map(|ce| ce.to_toml());
     let mut members: Vec<toml::Value> = Vec::new();
     let mut member_id = toml::Table::new();
     for (sg, ce) in census.iter() {
-        members.push(toml::encode(ce));
-        member_id.insert(format!("{}", sg), toml::encode(ce));
+        members.push(ce.to_toml());
+        member_id.insert(format!("{}", sg), ce.to_toml());
     }
     let mut result = toml::Table::new();
     result.insert("service".to_string(), service);
@@ -300,8 +421,61 @@ fn service_entry(census: &Census) -> toml::Table {
     result
 }
 
+/// The service entry for a bound service group, with views of its members that only include
+/// those we can send traffic to:
+///
+/// * `alive_members` - every member that is alive and not critically unhealthy
+/// * `leader_members` - the leader, if it is alive and not critically unhealthy
+/// * `follower_members` - the followers that are alive and not critically unhealthy
+///
+/// `members` still lists every member of the census, whatever its state.
+fn bind_entry(census: &Census) -> toml::Table {
+    let mut result = service_entry(census);
+    let mut alive: Vec<toml::Value> = Vec::new();
+    let mut leaders: Vec<toml::Value> = Vec::new();
+    let mut followers: Vec<toml::Value> = Vec::new();
+    for (_id, ce) in census.iter().filter(|&(_id, ce)| ce.is_available()) {
+        alive.push(ce.to_toml());
+        if ce.leader {
+            leaders.push(ce.to_toml());
+        }
+        if ce.follower {
+            followers.push(ce.to_toml());
+        }
+    }
+    result.insert("alive_members".to_string(), toml::Value::Array(alive));
+    result.insert("leader_members".to_string(), toml::Value::Array(leaders));
+    result.insert("follower_members".to_string(), toml::Value::Array(followers));
+    result
+}
+
+/// The layers the `cfg` data is merged from, from the lowest precedence to the highest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfgSource {
+    /// The package's `default.toml`
+    Default,
+    /// The service's `user.toml`
+    User,
+    /// The configuration gossiped to the service group
+    Gossip,
+    /// The `HAB_<PKGNAME>` environment variable
+    Environment,
+}
+
+impl fmt::Display for CfgSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CfgSource::Default => write!(f, "default.toml"),
+            CfgSource::User => write!(f, "user.toml"),
+            CfgSource::Gossip => write!(f, "{}", GOSSIP_TOML),
+            CfgSource::Environment => write!(f, "environment"),
+        }
+    }
+}
+
+/// The `cfg` data of a service, merged from its layers.
 #[derive(Debug, RustcEncodable)]
-struct Cfg {
+pub struct Cfg {
     default: Option<toml::Value>,
     user: Option<toml::Value>,
     gossip: Option<toml::Value>,
@@ -346,7 +520,7 @@ fn toml_merge(existing: &toml::Table, ovr: &toml::Table) -> toml::Table {
 }
 
 impl Cfg {
-    fn new(pkg: &Package) -> Result<Cfg> {
+    pub fn new(pkg: &Package) -> Result<Cfg> {
         let mut cfg = Cfg {
             default: None,
             user: None,
@@ -360,7 +534,16 @@ impl Cfg {
         Ok(cfg)
     }
 
-    fn to_toml(&self) -> toml::Value {
+    /// Checks the merged configuration against the package's schema, if it has one. Returns every
+    /// problem found.
+    fn validate(&self, pkg: &Package) -> Result<Vec<String>> {
+        match try!(Schema::load(&pkg.config_from().join(SCHEMA_FILE))) {
+            Some(schema) => Ok(schema.validate(&self.to_toml())),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn to_toml(&self) -> toml::Value {
         let mut output_toml = toml::Table::new();
         if let Some(toml::Value::Table(ref default_cfg)) = self.default {
             output_toml = toml_merge(&output_toml, default_cfg);
@@ -377,6 +560,41 @@ impl Cfg {
         toml::Value::Table(output_toml)
     }
 
+    /// The configuration the service exports to the services bound to it: the value of each key
+    /// in `exports`, under the name it is exported as. Keys without a value are left out.
+    pub fn exported(&self, exports: &BTreeMap<String, String>) -> toml::Table {
+        let cfg = self.to_toml();
+        let mut exported = toml::Table::new();
+        for (name, key) in exports.iter() {
+            if let Some(value) = cfg.lookup(key) {
+                exported.insert(name.clone(), value.clone());
+            }
+        }
+        exported
+    }
+
+    /// Where each key of the merged configuration comes from: the layer with the highest
+    /// precedence that sets it to its merged value. Keys in tables are named with dots.
+    pub fn sources(&self) -> BTreeMap<String, CfgSource> {
+        let layers = [(CfgSource::Environment, &self.environment),
+                      (CfgSource::Gossip, &self.gossip),
+                      (CfgSource::User, &self.user),
+                      (CfgSource::Default, &self.default)];
+        let merged = self.to_toml();
+        let mut leaves = Vec::new();
+        toml_leaves(&merged, &mut Vec::new(), &mut leaves);
+        let mut sources = BTreeMap::new();
+        for (path, value) in leaves {
+            let source = layers.iter()
+                .find(|&&(_, layer)| {
+                    layer.as_ref().and_then(|l| toml_lookup(l, &path)) == Some(value)
+                })
+                .map_or(CfgSource::Default, |&(source, _)| source);
+            sources.insert(path.join("."), source);
+        }
+        sources
+    }
+
     fn load_default(&mut self, pkg: &Package) -> Result<()> {
         // Default
         let mut file = match File::open(pkg.config_from().join("default.toml")) {
@@ -454,16 +672,10 @@ impl Cfg {
     }
 
     fn load_environment(&mut self, pkg: &Package) -> Result<()> {
-        let var_name = format!("{}_{}", ENV_VAR_PREFIX, pkg.name)
-            .to_ascii_uppercase()
-            .replace("-", "_");
+        let var_name = env_var_name(pkg);
         match env::var(&var_name) {
             Ok(config) => {
-                let mut toml_parser = toml::Parser::new(&config);
-                let toml = try!(toml_parser.parse()
-                    .ok_or(sup_error!(Error::TomlParser(toml_parser.errors))));
-                self.environment = Some(toml::Value::Table(toml));
-
+                self.environment = Some(try!(parse_env_config(&config)));
             }
             Err(e) => {
                 debug!("Looking up environment variable {} failed: {:?}",
@@ -476,6 +688,51 @@ impl Cfg {
     }
 }
 
+/// The environment variable a package's configuration can be given in: `HAB_` and the name of the
+/// package, in upper case, with dashes replaced by underscores.
+pub fn env_var_name(pkg: &Package) -> String {
+    format!("{}_{}", ENV_VAR_PREFIX, pkg.name)
+        .to_ascii_uppercase()
+        .replace("-", "_")
+}
+
+/// Parses configuration given in an environment variable; a JSON object if it starts with `{`, and
+/// TOML otherwise.
+fn parse_env_config(config: &str) -> Result<toml::Value> {
+    if config.trim_left().starts_with('{') {
+        let json = try!(Json::from_str(config).map_err(|e| sup_error!(Error::JsonParser(e))));
+        // A JSON object is always a table
+        Ok(try!(convert::json_to_toml(json)).unwrap())
+    } else {
+        let mut toml_parser = toml::Parser::new(config);
+        let toml = try!(toml_parser.parse()
+            .ok_or(sup_error!(Error::TomlParser(toml_parser.errors))));
+        Ok(toml::Value::Table(toml))
+    }
+}
+
+/// Collects the path and value of every value in a TOML tree that is not a table.
+fn toml_leaves<'a>(value: &'a toml::Value,
+                   path: &mut Vec<String>,
+                   leaves: &mut Vec<(Vec<String>, &'a toml::Value)>) {
+    match *value {
+        toml::Value::Table(ref table) => {
+            for (key, value) in table.iter() {
+                path.push(key.clone());
+                toml_leaves(value, path, leaves);
+                path.pop();
+            }
+        }
+        ref leaf => leaves.push((path.clone(), leaf)),
+    }
+}
+
+fn toml_lookup<'a>(value: &'a toml::Value, path: &[String]) -> Option<&'a toml::Value> {
+    path.iter().fold(Some(value), |value, key| {
+        value.and_then(|v| v.as_table()).and_then(|t| t.get(key))
+    })
+}
+
 #[derive(Debug, RustcEncodable)]
 pub struct Pkg {
     pub origin: String,
@@ -644,6 +901,7 @@ impl Hab {
 
 #[cfg(test)]
 mod test {
+    use std::collections::BTreeMap;
     use std::path::PathBuf;
     use std::str::FromStr;
 
@@ -653,11 +911,12 @@ mod test {
     use census::{CensusEntry, Census, CensusList};
     use config::{gcache, Config};
     use gossip::member::MemberId;
+    use health_check;
     use hcore::package::{PackageIdent, PackageInstall};
     use package::Package;
     use service_config::ServiceConfig;
     use VERSION;
-    use super::toml_merge;
+    use super::{parse_env_config, toml_merge, Cfg, CfgSource};
 
     fn gen_pkg() -> Package {
         let pkg_install = PackageInstall::new_from_parts(
@@ -716,6 +975,57 @@ mod test {
         assert!(re.is_match(&ip));
     }
 
+    #[test]
+    fn to_toml_bind_views() {
+        gcache(Config::new());
+        let pkg = gen_pkg();
+        let mut cl = gen_census_list();
+        let mut leader = CensusEntry::new("postgresql", "production", MemberId::new_v4());
+        leader.leader = true;
+        leader.ip = String::from("10.0.0.1");
+        leader.cfg(Some(String::from("password = \"secret\"\n")));
+        let mut census = Census::new(leader);
+        let mut follower = CensusEntry::new("postgresql", "production", MemberId::new_v4());
+        follower.follower = true;
+        follower.ip = String::from("10.0.0.2");
+        census.add(follower);
+        let mut critical = CensusEntry::new("postgresql", "production", MemberId::new_v4());
+        critical.follower = true;
+        critical.ip = String::from("10.0.0.3");
+        critical.health(Some(health_check::Status::Critical));
+        census.add(critical);
+        let mut confirmed = CensusEntry::new("postgresql", "production", MemberId::new_v4());
+        confirmed.follower = true;
+        confirmed.ip = String::from("10.0.0.4");
+        confirmed.set_confirmed();
+        census.add(confirmed);
+        cl.insert(census);
+
+        let sc = ServiceConfig::new(&pkg,
+                                    &cl,
+                                    vec![String::from("database:postgresql.production")])
+            .unwrap();
+        let toml = sc.to_toml().unwrap();
+        let ips = |key: &str| -> Vec<String> {
+            let mut ips: Vec<String> = toml.lookup(key)
+                .unwrap()
+                .as_slice()
+                .unwrap()
+                .iter()
+                .map(|m| m.lookup("ip").unwrap().as_str().unwrap().to_string())
+                .collect();
+            ips.sort();
+            ips
+        };
+        assert_eq!(ips("bind.database.members").len(), 4);
+        assert_eq!(ips("bind.database.alive_members"), vec!["10.0.0.1", "10.0.0.2"]);
+        assert_eq!(ips("bind.database.leader_members"), vec!["10.0.0.1"]);
+        assert_eq!(ips("bind.database.follower_members"), vec!["10.0.0.2"]);
+        assert_eq!(toml.lookup("bind.database.leader_members.0.cfg.password")
+                       .and_then(|p| p.as_str()),
+                   Some("secret"));
+    }
+
     #[test]
     fn merge_into_an_empty_start() {
         let override_config = "rando_key = \"rando_override\"
@@ -799,6 +1109,54 @@ mod test {
         }
     }
 
+    #[test]
+    fn env_config_is_toml_or_json() {
+        let from_toml = parse_env_config("port = 6379\n[tls]\nenabled = true\n").unwrap();
+        let from_json = parse_env_config("{\"port\": 6379, \"tls\": {\"enabled\": true}, \
+                                          \"unset\": null}")
+            .unwrap();
+        assert_eq!(from_toml, from_json);
+        assert!(parse_env_config("{\"port\": ").is_err());
+        assert!(parse_env_config("{\"tls\": {\"port\": 9223372036854775808}}").is_err());
+        assert!(parse_env_config("{\"port\": 9223372036854775807}").is_ok());
+    }
+
+    #[test]
+    fn cfg_sources() {
+        let layer = |s: &str| Some(toml::Value::Table(toml::Parser::new(s).parse().unwrap()));
+        let cfg = Cfg {
+            default: layer("port = 6379\nloglevel = \"info\"\n[tls]\nenabled = false\n"),
+            user: layer("[tls]\nenabled = true\n"),
+            gossip: layer("loglevel = \"debug\"\n"),
+            environment: layer("loglevel = \"warning\"\nport = 6379\n"),
+        };
+        let sources = cfg.sources();
+        assert_eq!(sources.get("loglevel"), Some(&CfgSource::Environment));
+        assert_eq!(sources.get("port"), Some(&CfgSource::Environment));
+        assert_eq!(sources.get("tls.enabled"), Some(&CfgSource::User));
+        assert_eq!(sources.len(), 3);
+    }
+
+    #[test]
+    fn exported_cfg() {
+        let layer = |s: &str| Some(toml::Value::Table(toml::Parser::new(s).parse().unwrap()));
+        let cfg = Cfg {
+            default: layer("port = 5432\n[auth]\npassword = \"secret\"\n"),
+            user: layer("port = 5433\n"),
+            gossip: None,
+            environment: None,
+        };
+        let mut exports = BTreeMap::new();
+        exports.insert(String::from("port"), String::from("port"));
+        exports.insert(String::from("password"), String::from("auth.password"));
+        exports.insert(String::from("replication_port"), String::from("replication.port"));
+        let exported = cfg.exported(&exports);
+        assert_eq!(exported.get("port"), Some(&toml::Value::Integer(5433)));
+        assert_eq!(exported.get("password"),
+                   Some(&toml::Value::String(String::from("secret"))));
+        assert_eq!(exported.len(), 2);
+    }
+
     mod sys {
         use config::{gcache, Config};
         use service_config::Sys;
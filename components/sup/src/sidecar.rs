@@ -17,59 +17,166 @@
 //!
 //! Supports:
 //!
-//! * /config: Returns the current configuration of the service
+//! * /config: Returns the current configuration of the service, and why the last configuration
+//!   it was given was rejected, if it was
+//! * /config/history: Returns the versions of the gossiped configuration the service has applied
+//! * /events: Returns the events gossiped to the service's group (ex: `/events?since=3&wait=10`),
+//!   holding the request until there is one when asked to wait
 //! * /health: Returns the current health of the service
+//! * /hooks: Returns the result of the last run of each of the service's hooks
+//! * /logs: Returns the last lines of the service's log (ex: `/logs?stream=stderr&lines=50`), and
+//!   with `follow=true`, keeps sending its output as the service writes it, for a few minutes
 //! * /status: Returns the current status of the service, from the supervisors point of view
+//! * /services: Returns the services this supervisor runs
+//!
+//! The census and gossip the supervisor serves leave out the values of the configuration members
+//! export, which may hold secrets; only their keys are shown.
+//!
+//! A supervisor can run many services. The routes above report on the first service started;
+//! every service is also available under `/services/<name>`, for example
+//! `/services/redis/health`.
+//!
+//! The supervised process can be controlled with POST requests:
+//!
+//! * /start, /stop, /restart, /down: Start, stop, restart or take down the process. A stopped
+//!   process is started again right away, as one that exits is, but without backing off or
+//!   counting it as a failure; take it down to keep it down.
+//! * /signal/<signal>: Sends a Unix signal to the process (ex: `/signal/HUP`)
+//! * /services: Loads the package named in the body, and starts it as a new service
+//! * /services/<name>/unload: Stops a service and removes it from the supervisor
+//!
+//! As with the GET routes, `/services/<name>/restart` and friends control a specific service. The
+//! body of every POST must be [sealed](../control) with the ring key or with the service key of
+//! the service being controlled; loading a new service requires the ring key.
 
+use std::cmp;
 use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::io::{self, Read};
 use std::net::SocketAddrV4;
-use std::sync::{Arc, RwLock};
-
-use rustc_serialize::json;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::Sender;
+use std::time::Duration as StdDuration;
+
+use rustc_serialize::Encodable;
+use rustc_serialize::json::{self, Json};
 use iron::prelude::*;
+use iron::response::{ResponseBody, WriteBody};
 use iron::status;
 use router::Router;
+use time::{Duration, SteadyTime};
+use toml;
+use url::form_urlencoded;
 use wonder;
 use wonder::actor::{GenServer, InitResult, HandleResult, StopReason, ActorSender};
 
+use config::gconfig;
+use control;
 use error::{Error, SupError};
+use hcore::package::{PackageArchive, PackageIdent};
 use health_check;
-use package::Package;
+use logs;
+use manager::ManagerMessage;
+use package::{hooks, Package};
+use common::config_history::ConfigHistory;
+use common::event::EventList;
 use common::gossip_file::{GossipFileList, FileWriteRetry};
 use service_config::ServiceConfig;
 use gossip::member::{MemberList, MemberId};
 use gossip::rumor::RumorList;
 use gossip::detector::Detector;
+use gossip::ring_keys::RingKeys;
 use census::{CensusList, CensusEntry, CensusEntryId, Census};
 use election::{Election, ElectionList};
 use supervisor::Supervisor;
+use util::signals;
 
 static LOGKEY: &'static str = "SI";
 const GET_HEALTH: &'static str = "/health";
 const GET_CONFIG: &'static str = "/config";
+const GET_CONFIG_HISTORY: &'static str = "/config/history";
+const GET_EVENTS: &'static str = "/events";
 const GET_STATUS: &'static str = "/status";
 const GET_GOSSIP: &'static str = "/gossip";
 const GET_CENSUS: &'static str = "/census";
 const GET_ELECTION: &'static str = "/election";
+const GET_HOOKS: &'static str = "/hooks";
+const GET_LOGS: &'static str = "/logs";
+const GET_SERVICES: &'static str = "/services";
+const GET_SERVICE_HEALTH: &'static str = "/services/:service/health";
+const GET_SERVICE_CONFIG: &'static str = "/services/:service/config";
+const GET_SERVICE_CONFIG_HISTORY: &'static str = "/services/:service/config/history";
+const GET_SERVICE_EVENTS: &'static str = "/services/:service/events";
+const GET_SERVICE_STATUS: &'static str = "/services/:service/status";
+const GET_SERVICE_GOSSIP: &'static str = "/services/:service/gossip";
+const GET_SERVICE_CENSUS: &'static str = "/services/:service/census";
+const GET_SERVICE_ELECTION: &'static str = "/services/:service/election";
+const GET_SERVICE_HOOKS: &'static str = "/services/:service/hooks";
+const GET_SERVICE_LOGS: &'static str = "/services/:service/logs";
+const POST_START: &'static str = "/start";
+const POST_STOP: &'static str = "/stop";
+const POST_RESTART: &'static str = "/restart";
+const POST_DOWN: &'static str = "/down";
+const POST_SIGNAL: &'static str = "/signal/:signal";
+const POST_SERVICES: &'static str = "/services";
+const POST_SERVICE_START: &'static str = "/services/:service/start";
+const POST_SERVICE_STOP: &'static str = "/services/:service/stop";
+const POST_SERVICE_RESTART: &'static str = "/services/:service/restart";
+const POST_SERVICE_DOWN: &'static str = "/services/:service/down";
+const POST_SERVICE_SIGNAL: &'static str = "/services/:service/signal/:signal";
+const POST_SERVICE_UNLOAD: &'static str = "/services/:service/unload";
+/// What the values of exported configuration are replaced with in the census and gossip we serve
+const REDACTED: &'static str = "<redacted>";
+/// The longest an /events request can wait for an event, in seconds
+const MAX_EVENTS_WAIT_SECS: u64 = 10;
 
 pub type SidecarActor = wonder::actor::Actor<SidecarMessage>;
 
+/// The services a sidecar reports on, in the order they were started.
+pub type SidecarServices = Arc<RwLock<Vec<SidecarService>>>;
+
 pub struct Sidecar;
 
-pub struct SidecarState {
-    /// The IP:Port where the sidecar listens
-    pub listen: SocketAddrV4,
+/// Everything the sidecar needs to report on a single service.
+#[derive(Clone)]
+pub struct SidecarService {
+    /// The name of the service
+    pub name: String,
     /// The package this sidecar is helping out
     pub package: Arc<RwLock<Package>>,
     /// The configuration of the supervised service
     pub config: Arc<RwLock<ServiceConfig>>,
-    pub member_list: Arc<RwLock<MemberList>>,
-    pub rumor_list: Arc<RwLock<RumorList>>,
     pub census_list: Arc<RwLock<CensusList>>,
-    pub detector: Arc<RwLock<Detector>>,
     pub election_list: Arc<RwLock<ElectionList>>,
     pub supervisor: Arc<RwLock<Supervisor>>,
     pub gossip_file_list: Arc<RwLock<GossipFileList>>,
+    pub event_list: Arc<RwLock<EventList>>,
+}
+
+pub struct SidecarState {
+    /// The IP:Port where the sidecar listens
+    pub listen: SocketAddrV4,
+    /// The services this sidecar is helping out
+    pub services: SidecarServices,
+    pub member_list: Arc<RwLock<MemberList>>,
+    pub rumor_list: Arc<RwLock<RumorList>>,
+    pub detector: Arc<RwLock<Detector>>,
+    /// The ring keys; control requests sealed with any of them are trusted
+    pub ring_keys: Arc<RwLock<RingKeys>>,
+    /// Loads and unloads services in the supervisor
+    pub manager: Arc<Mutex<Sender<ManagerMessage>>>,
+}
+
+/// What a control request does to the supervised process.
+#[derive(Clone, Copy, Debug)]
+enum ControlAction {
+    Start,
+    Stop,
+    Restart,
+    Down,
+    Signal,
 }
 
 #[derive(Debug)]
@@ -80,27 +187,21 @@ pub enum SidecarMessage {
 
 impl SidecarState {
     pub fn new(listen: SocketAddrV4,
-               package: Arc<RwLock<Package>>,
-               config: Arc<RwLock<ServiceConfig>>,
+               services: SidecarServices,
                member_list: Arc<RwLock<MemberList>>,
                rumor_list: Arc<RwLock<RumorList>>,
-               census_list: Arc<RwLock<CensusList>>,
                detector: Arc<RwLock<Detector>>,
-               election_list: Arc<RwLock<ElectionList>>,
-               supervisor: Arc<RwLock<Supervisor>>,
-               gossip_file_list: Arc<RwLock<GossipFileList>>)
+               ring_keys: Arc<RwLock<RingKeys>>,
+               manager: Sender<ManagerMessage>)
                -> Self {
         SidecarState {
             listen: listen,
-            package: package,
-            config: config,
+            services: services,
             member_list: member_list,
             rumor_list: rumor_list,
-            census_list: census_list,
             detector: detector,
-            election_list: election_list,
-            supervisor: supervisor,
-            gossip_file_list: gossip_file_list,
+            ring_keys: ring_keys,
+            manager: Arc::new(Mutex::new(manager)),
         }
     }
 }
@@ -108,26 +209,20 @@ impl SidecarState {
 impl Sidecar {
     /// Start the sidecar.
     pub fn start(listen: SocketAddrV4,
-                 package: Arc<RwLock<Package>>,
-                 config: Arc<RwLock<ServiceConfig>>,
+                 services: SidecarServices,
                  member_list: Arc<RwLock<MemberList>>,
                  rumor_list: Arc<RwLock<RumorList>>,
-                 census_list: Arc<RwLock<CensusList>>,
                  detector: Arc<RwLock<Detector>>,
-                 election_list: Arc<RwLock<ElectionList>>,
-                 supervisor: Arc<RwLock<Supervisor>>,
-                 gossip_file_list: Arc<RwLock<GossipFileList>>)
+                 ring_keys: Arc<RwLock<RingKeys>>,
+                 manager: Sender<ManagerMessage>)
                  -> SidecarActor {
         let state = SidecarState::new(listen,
-                                      package,
-                                      config,
+                                      services,
                                       member_list,
                                       rumor_list,
-                                      census_list,
                                       detector,
-                                      election_list,
-                                      supervisor,
-                                      gossip_file_list);
+                                      ring_keys,
+                                      manager);
         wonder::actor::Builder::new(Sidecar).name("sidecar".to_string()).start(state).unwrap()
     }
 }
@@ -147,43 +242,144 @@ impl GenServer for Sidecar {
                       state: &mut Self::S)
                       -> HandleResult<Self::T> {
         let mut router = Router::new();
-        let package_1 = state.package.clone();
-        let package_2 = state.package.clone();
-        let config_1 = state.config.clone();
-
-        router.get(GET_CONFIG,
-                   move |r: &mut Request| config(&package_1, r),
-                   "config");
-
-        let supervisor_1 = state.supervisor.clone();
-        router.get(GET_STATUS,
-                   move |r: &mut Request| status(&supervisor_1, r),
-                   "status");
-
-        let supervisor_2 = state.supervisor.clone();
-        router.get(GET_HEALTH,
-                   move |r: &mut Request| health(&package_2, &config_1, &supervisor_2, r),
-                   "health");
-
-        let ml = state.member_list.clone();
-        let rl = state.rumor_list.clone();
-        let detector = state.detector.clone();
+
+        let services = state.services.clone();
+        router.get(GET_SERVICES,
+                   move |r: &mut Request| list_services(&services, r),
+                   "services");
+
+        for &(path, name) in &[(GET_CONFIG, "config"), (GET_SERVICE_CONFIG, "service_config")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, config),
+                       name);
+        }
+
+        for &(path, name) in &[(GET_CONFIG_HISTORY, "config_history"),
+                               (GET_SERVICE_CONFIG_HISTORY, "service_config_history")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, config_history),
+                       name);
+        }
+
+        for &(path, name) in &[(GET_EVENTS, "events"), (GET_SERVICE_EVENTS, "service_events")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| {
+                           with_service(&services, r, |svc, r| events(&svc.event_list, r))
+                       },
+                       name);
+        }
+
+        for &(path, name) in &[(GET_STATUS, "status"), (GET_SERVICE_STATUS, "service_status")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, status),
+                       name);
+        }
+
+        for &(path, name) in &[(GET_HEALTH, "health"), (GET_SERVICE_HEALTH, "service_health")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, health),
+                       name);
+        }
+
+        for &(path, name) in &[(GET_HOOKS, "hooks"), (GET_SERVICE_HOOKS, "service_hooks")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, hook_results),
+                       name);
+        }
+
+        for &(path, name) in &[(GET_LOGS, "logs"), (GET_SERVICE_LOGS, "service_logs")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| with_service(&services, r, service_logs),
+                       name);
+        }
+
         let id = {
-            Arc::new(ml.read().unwrap().my_id.clone())
+            Arc::new(state.member_list.read().unwrap().my_id.clone())
         };
-        let gfl = state.gossip_file_list.clone();
+        for &(path, name) in &[(GET_GOSSIP, "gossip"), (GET_SERVICE_GOSSIP, "service_gossip")] {
+            let services = state.services.clone();
+            let ml = state.member_list.clone();
+            let rl = state.rumor_list.clone();
+            let detector = state.detector.clone();
+            let id = id.clone();
+            router.get(path,
+                       move |r: &mut Request| {
+                           with_service(&services, r, |svc, r| {
+                               gossip(&ml, &rl, &svc.gossip_file_list, &detector, &id, r)
+                           })
+                       },
+                       name);
+        }
 
-        router.get(GET_GOSSIP,
-                   move |r: &mut Request| gossip(&ml, &rl, &gfl, &detector, &id, r),
-                   "gossip");
+        for &(path, name) in &[(GET_CENSUS, "census"), (GET_SERVICE_CENSUS, "service_census")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| {
+                           with_service(&services, r, |svc, r| census(&svc.census_list, r))
+                       },
+                       name);
+        }
 
-        let cl1 = state.census_list.clone();
-        router.get(GET_CENSUS, move |r: &mut Request| census(&cl1, r), "census");
+        for &(path, name) in &[(GET_ELECTION, "election"),
+                               (GET_SERVICE_ELECTION, "service_election")] {
+            let services = state.services.clone();
+            router.get(path,
+                       move |r: &mut Request| {
+                           with_service(&services, r, |svc, r| election(&svc.election_list, r))
+                       },
+                       name);
+        }
 
-        let el = state.election_list.clone();
-        router.get(GET_ELECTION,
-                   move |r: &mut Request| election(&el, r),
-                   "election");
+        for &(path, name, action) in &[(POST_START, "start", ControlAction::Start),
+                                       (POST_STOP, "stop", ControlAction::Stop),
+                                       (POST_RESTART, "restart", ControlAction::Restart),
+                                       (POST_DOWN, "down", ControlAction::Down),
+                                       (POST_SIGNAL, "signal", ControlAction::Signal),
+                                       (POST_SERVICE_START,
+                                        "service_start",
+                                        ControlAction::Start),
+                                       (POST_SERVICE_STOP, "service_stop", ControlAction::Stop),
+                                       (POST_SERVICE_RESTART,
+                                        "service_restart",
+                                        ControlAction::Restart),
+                                       (POST_SERVICE_DOWN, "service_down", ControlAction::Down),
+                                       (POST_SERVICE_SIGNAL,
+                                        "service_signal",
+                                        ControlAction::Signal)] {
+            let services = state.services.clone();
+            let ring_keys = state.ring_keys.clone();
+            router.post(path,
+                        move |r: &mut Request| {
+                            with_service(&services,
+                                         r,
+                                         |svc, r| control(&ring_keys, svc, r, action))
+                        },
+                        name);
+        }
+
+        let ring_keys = state.ring_keys.clone();
+        let manager = state.manager.clone();
+        router.post(POST_SERVICES,
+                    move |r: &mut Request| load_service(&ring_keys, &manager, r),
+                    "load_service");
+
+        let services = state.services.clone();
+        let ring_keys = state.ring_keys.clone();
+        let manager = state.manager.clone();
+        router.post(POST_SERVICE_UNLOAD,
+                    move |r: &mut Request| {
+                        with_service(&services,
+                                     r,
+                                     |svc, r| unload_service(&ring_keys, &manager, svc, r))
+                    },
+                    "service_unload");
 
         match Iron::new(router).http(state.listen) {
             Ok(_) => HandleResult::NoReply(None),
@@ -194,6 +390,63 @@ impl GenServer for Sidecar {
     }
 }
 
+/// Finds the service a request is for, and calls the handler with it.
+///
+/// Requests for `/services/:service/..` are for the named service; every other request is for the
+/// first service we started.
+fn with_service<F>(services: &SidecarServices,
+                   req: &mut Request,
+                   handler: F)
+                   -> IronResult<Response>
+    where F: Fn(&SidecarService, &mut Request) -> IronResult<Response>
+{
+    let service = {
+        let name = req.extensions
+            .get::<Router>()
+            .and_then(|params| params.find("service"))
+            .map(|name| name.to_string());
+        let services = services.read().unwrap();
+        match name {
+            Some(name) => services.iter().find(|svc| svc.name == name).cloned(),
+            None => services.first().cloned(),
+        }
+    };
+    match service {
+        Some(service) => handler(&service, req),
+        None => Ok(Response::with((status::NotFound, "No such service"))),
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+struct ServiceResponse {
+    name: String,
+    package: String,
+    status: String,
+}
+
+/// The /services callback.
+///
+/// Returns the services this supervisor runs, and their status.
+fn list_services(services: &SidecarServices, _req: &mut Request) -> IronResult<Response> {
+    let services = services.read().unwrap();
+    let response: Vec<ServiceResponse> = services.iter()
+        .map(|svc| {
+            ServiceResponse {
+                name: svc.name.clone(),
+                package: svc.package.read().unwrap().ident().to_string(),
+                status: svc.supervisor.read().unwrap().status().1,
+            }
+        })
+        .collect();
+
+    let json_response = match json::encode(&response) {
+        Ok(json_response) => json_response,
+        Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
+    };
+
+    Ok(Response::with((status::Ok, json_response)))
+}
+
 #[derive(Debug, RustcEncodable)]
 struct ElectionResponse<'a> {
     elections: &'a HashMap<String, Election>,
@@ -247,11 +500,7 @@ fn gossip(member_list: &Arc<RwLock<MemberList>>,
         file_write_retries: &gfl.file_write_retries,
     };
 
-    let json_response = match json::encode(&gossip_response) {
-        Ok(json_response) => json_response,
-        Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
-    };
-
+    let json_response = try!(encode_redacted(&gossip_response));
     Ok(Response::with((status::Ok, json_response)))
 }
 
@@ -281,25 +530,122 @@ fn census(census_list: &Arc<RwLock<CensusList>>, _req: &mut Request) -> IronResu
         leader: cl.local_census().get_leader(),
     };
 
-    let json_response = match json::encode(&response) {
-        Ok(json_response) => json_response,
+    let json_response = try!(encode_redacted(&response));
+    Ok(Response::with((status::Ok, json_response)))
+}
+
+/// Encodes census or gossip data as JSON, with the values of the configuration each census entry
+/// exports redacted.
+fn encode_redacted<T: Encodable>(data: &T) -> IronResult<String> {
+    let encoded = match json::encode(data) {
+        Ok(encoded) => encoded,
         Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
     };
+    let mut json = match Json::from_str(&encoded) {
+        Ok(json) => json,
+        Err(e) => return Err(IronError::from(sup_error!(Error::JsonParser(e)))),
+    };
+    redact_exported_cfg(&mut json);
+    Ok(json.to_string())
+}
 
-    Ok(Response::with((status::Ok, json_response)))
+/// Replaces the exported configuration of every census entry in `json`, its `cfg`, with the same
+/// keys holding redacted values.
+fn redact_exported_cfg(json: &mut Json) {
+    match *json {
+        Json::Object(ref mut object) => {
+            for (key, value) in object.iter_mut() {
+                let redacted = match *value {
+                    Json::String(ref cfg) if key == "cfg" => Some(redact_toml(cfg)),
+                    _ => None,
+                };
+                match redacted {
+                    Some(redacted) => *value = Json::String(redacted),
+                    None => redact_exported_cfg(value),
+                }
+            }
+        }
+        Json::Array(ref mut array) => {
+            for value in array.iter_mut() {
+                redact_exported_cfg(value);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn redact_toml(cfg: &str) -> String {
+    fn redact(value: toml::Value) -> toml::Value {
+        match value {
+            toml::Value::Table(table) => {
+                toml::Value::Table(table.into_iter().map(|(k, v)| (k, redact(v))).collect())
+            }
+            toml::Value::Array(array) => {
+                toml::Value::Array(array.into_iter().map(redact).collect())
+            }
+            _ => toml::Value::String(REDACTED.to_string()),
+        }
+    }
+    match toml::Parser::new(cfg).parse() {
+        Some(table) => format!("{}", redact(toml::Value::Table(table))),
+        None => REDACTED.to_string(),
+    }
 }
 
 /// The /config callback.
 ///
-/// Returns the current running configuration.
+/// Returns the current running configuration. If the last configuration we were given was
+/// rejected, it starts with comments saying why.
 ///
 /// # Failures
 ///
 /// * Fails if the configuration cannot be found.
-fn config(lock: &Arc<RwLock<Package>>, _req: &mut Request) -> IronResult<Response> {
-    let package = lock.read().unwrap();
+fn config(service: &SidecarService, _req: &mut Request) -> IronResult<Response> {
+    let package = service.package.read().unwrap();
     let last_config = try!(package.last_config());
-    Ok(Response::with((status::Ok, last_config)))
+    let service_config = service.config.read().unwrap();
+    let errors = service_config.cfg_errors();
+    if errors.is_empty() {
+        return Ok(Response::with((status::Ok, last_config)));
+    }
+    let mut output = String::from("# The last configuration was rejected, since it does not \
+                                   match the schema; running on the last good configuration.\n");
+    for error in errors {
+        output.push_str(&format!("#   {}\n", error));
+    }
+    output.push_str(&last_config);
+    Ok(Response::with((status::Ok, output)))
+}
+
+#[derive(Debug, RustcEncodable)]
+struct ConfigVersionResponse {
+    version_number: u64,
+    checksum: String,
+    applied_at: String,
+}
+
+/// The /config/history callback.
+///
+/// Returns the versions of the gossiped configuration the service has applied, oldest first.
+fn config_history(service: &SidecarService, _req: &mut Request) -> IronResult<Response> {
+    let versions = match ConfigHistory::for_service(&service.name).versions() {
+        Ok(versions) => versions,
+        Err(e) => return Err(IronError::from(SupError::from(e))),
+    };
+    let response: Vec<ConfigVersionResponse> = versions.into_iter()
+        .map(|v| {
+            ConfigVersionResponse {
+                version_number: v.version_number,
+                checksum: v.checksum,
+                applied_at: v.applied_at,
+            }
+        })
+        .collect();
+    let json_response = match json::encode(&response) {
+        Ok(json_response) => json_response,
+        Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
+    };
+    Ok(Response::with((status::Ok, json_response)))
 }
 
 /// The /status callback.
@@ -309,8 +655,8 @@ fn config(lock: &Arc<RwLock<Package>>, _req: &mut Request) -> IronResult<Respons
 /// # Failures
 ///
 /// * Fails if the supervisor cannot return the status.
-fn status(lock: &Arc<RwLock<Supervisor>>, _req: &mut Request) -> IronResult<Response> {
-    let supervisor = lock.read().unwrap();
+fn status(service: &SidecarService, _req: &mut Request) -> IronResult<Response> {
+    let supervisor = service.supervisor.read().unwrap();
     let (_health, output) = supervisor.status();
     Ok(Response::with((status::Ok, output)))
 }
@@ -323,15 +669,11 @@ fn status(lock: &Arc<RwLock<Supervisor>>, _req: &mut Request) -> IronResult<Resp
 /// # Failures
 ///
 /// * If the health_check cannot be run.
-fn health(package_lock: &Arc<RwLock<Package>>,
-          config_lock: &Arc<RwLock<ServiceConfig>>,
-          supervisor_lock: &Arc<RwLock<Supervisor>>,
-          _req: &mut Request)
-          -> IronResult<Response> {
+fn health(service: &SidecarService, _req: &mut Request) -> IronResult<Response> {
     let result = {
-        let package = package_lock.read().unwrap();
-        let config = config_lock.read().unwrap();
-        let supervisor = supervisor_lock.read().unwrap();
+        let package = service.package.read().unwrap();
+        let config = service.config.read().unwrap();
+        let supervisor = service.supervisor.read().unwrap();
         try!(package.health_check(&config, &supervisor))
     };
 
@@ -347,6 +689,272 @@ fn health(package_lock: &Arc<RwLock<Package>>,
     }
 }
 
+/// The /hooks callback.
+///
+/// Returns the result of the last run of each of the service's hooks: its exit code, how long it
+/// ran, and the last lines of its output.
+fn hook_results(service: &SidecarService, _req: &mut Request) -> IronResult<Response> {
+    let results = hooks::last_results(&service.name);
+    let json_response = match json::encode(&results) {
+        Ok(json_response) => json_response,
+        Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
+    };
+    Ok(Response::with((status::Ok, json_response)))
+}
+
+/// The /events callback.
+///
+/// Returns the unexpired events gossiped to the service's group, oldest first, each with the
+/// sequence number the service received it under. `since=N` returns only the events after
+/// sequence N, and `topic=T` only those with topic T. `wait=S` holds the request for up to S
+/// seconds, and at most `MAX_EVENTS_WAIT_SECS`, until the event list wakes it with an event to
+/// return.
+fn events(event_list: &Arc<RwLock<EventList>>, req: &mut Request) -> IronResult<Response> {
+    let mut since = 0;
+    let mut topic = None;
+    let mut wait = 0;
+    if let Some(ref query) = req.url.query {
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "since" => {
+                    since = match value.parse::<u64>() {
+                        Ok(since) => since,
+                        Err(_) => {
+                            return Ok(Response::with((status::BadRequest,
+                                                      format!("Invalid since: {}", value))))
+                        }
+                    }
+                }
+                "topic" => topic = Some(value.into_owned()),
+                "wait" => {
+                    wait = match value.parse::<u64>() {
+                        Ok(wait) => cmp::min(wait, MAX_EVENTS_WAIT_SECS),
+                        Err(_) => {
+                            return Ok(Response::with((status::BadRequest,
+                                                      format!("Invalid wait: {}", value))))
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let deadline = SteadyTime::now() + Duration::seconds(wait as i64);
+    let topic = topic.as_ref().map(String::as_ref);
+    let (notifier, mut last_sequence, mut events) = {
+        let el = event_list.read().unwrap();
+        (el.notifier(), el.last_sequence(), el.since(since, topic))
+    };
+    while events.is_empty() {
+        let remaining = (deadline - SteadyTime::now()).num_milliseconds();
+        if remaining <= 0 ||
+           !notifier.wait(last_sequence, StdDuration::from_millis(remaining as u64)) {
+            break;
+        }
+        let el = event_list.read().unwrap();
+        last_sequence = el.last_sequence();
+        events = el.since(since, topic);
+    }
+    let json_response = match json::encode(&events) {
+        Ok(json_response) => json_response,
+        Err(e) => return Err(IronError::from(sup_error!(Error::JsonEncode(e)))),
+    };
+    Ok(Response::with((status::Ok, json_response)))
+}
+
+/// The /logs callback.
+///
+/// Returns the last lines of the service's stdout log. `stream=stderr` returns its stderr log
+/// instead, `lines=N` returns the last N lines, and `follow=true` keeps the response open, sending
+/// the service's output as it writes it, for at most `logs::MAX_FOLLOW_SECS`.
+fn service_logs(service: &SidecarService, req: &mut Request) -> IronResult<Response> {
+    let mut stream = logs::Stream::Stdout;
+    let mut lines = logs::DEFAULT_TAIL_LINES;
+    let mut follow = false;
+    if let Some(ref query) = req.url.query {
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "stream" => {
+                    stream = match value.as_ref() {
+                        "stdout" => logs::Stream::Stdout,
+                        "stderr" => logs::Stream::Stderr,
+                        _ => {
+                            return Ok(Response::with((status::BadRequest,
+                                                      format!("Unknown log stream: {}", value))))
+                        }
+                    }
+                }
+                "lines" => {
+                    lines = match value.parse::<usize>() {
+                        Ok(lines) => lines,
+                        Err(_) => {
+                            return Ok(Response::with((status::BadRequest,
+                                                      format!("Invalid lines: {}", value))))
+                        }
+                    }
+                }
+                "follow" => follow = value == "true",
+                _ => {}
+            }
+        }
+    }
+    let path = logs::log_path(&service.name, stream);
+    if follow {
+        let body: Box<WriteBody + Send> = Box::new(FollowLog {
+            path: path,
+            lines: lines,
+        });
+        return Ok(Response::with((status::Ok, body)));
+    }
+    let mut output = match logs::tail(&path, lines) {
+        Ok(tail) => tail.join("\n"),
+        Err(e) => return Err(IronError::from(e)),
+    };
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(Response::with((status::Ok, output)))
+}
+
+/// The body of a `/logs?follow=true` response; follows the log until the client goes away, or
+/// for at most `logs::MAX_FOLLOW_SECS`, so a client that went away while the log was quiet doesn't
+/// hold on to a worker forever.
+struct FollowLog {
+    path: PathBuf,
+    lines: usize,
+}
+
+impl WriteBody for FollowLog {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        logs::follow(&self.path,
+                     self.lines,
+                     StdDuration::from_secs(logs::MAX_FOLLOW_SECS),
+                     res)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))
+    }
+}
+
+/// Opens the sealed body of a control request. Returns the body the request was sealed with, or a
+/// response refusing the request.
+fn authorize(ring_keys: &Arc<RwLock<RingKeys>>,
+             service: Option<&SidecarService>,
+             req: &mut Request)
+             -> IronResult<::std::result::Result<String, Response>> {
+    let mut sealed = String::new();
+    if let Err(e) = req.body.read_to_string(&mut sealed) {
+        return Err(IronError::from(sup_error!(Error::Io(e))));
+    }
+    let path = format!("/{}", req.url.path.join("/"));
+    let service_key_name = match (service, gconfig().organization().as_ref()) {
+        (Some(service), Some(org)) => {
+            Some(format!("{}.{}@{}", service.name, gconfig().group(), org))
+        }
+        _ => None,
+    };
+    let ring_keys = ring_keys.read().unwrap();
+    match control::open(&sealed,
+                        &path,
+                        ring_keys.keys(),
+                        service_key_name.as_ref().map(String::as_ref)) {
+        Ok(request) => Ok(Ok(request.body)),
+        Err(e) => {
+            let reason = match e.err {
+                Error::Unauthorized(ref reason) => reason.clone(),
+                _ => e.description().to_string(),
+            };
+            outputln!("Refusing control request for {}: {}", path, reason);
+            Ok(Err(Response::with((status::Unauthorized, reason))))
+        }
+    }
+}
+
+/// The /start, /stop, /restart, /down and /signal callbacks.
+///
+/// Changes the state of the supervised process, and returns its new status.
+///
+/// # Failures
+///
+/// * The supervisor fails to start, stop or signal the process
+fn control(ring_keys: &Arc<RwLock<RingKeys>>,
+           service: &SidecarService,
+           req: &mut Request,
+           action: ControlAction)
+           -> IronResult<Response> {
+    if let Err(response) = try!(authorize(ring_keys, Some(service), req)) {
+        return Ok(response);
+    }
+    outputln!("Control request: {:?} {}", action, service.name);
+    // Stopping can take as long as the stop hooks and the shutdown timeout, so the supervisor is
+    // only locked while its state changes
+    match action {
+        ControlAction::Start => try!(service.supervisor.write().unwrap().start()),
+        ControlAction::Stop => try!(Supervisor::stop_shared(&service.supervisor)),
+        ControlAction::Restart => try!(Supervisor::restart_shared(&service.supervisor)),
+        ControlAction::Down => try!(Supervisor::down_shared(&service.supervisor)),
+        ControlAction::Signal => {
+            let name = req.extensions
+                .get::<Router>()
+                .and_then(|params| params.find("signal"))
+                .unwrap_or("")
+                .to_string();
+            match signals::Signal::from_str(&name) {
+                Ok(signal) => try!(service.supervisor.read().unwrap().send_unix_signal(signal)),
+                Err(_) => {
+                    return Ok(Response::with((status::BadRequest,
+                                              format!("Invalid signal: {}", name))))
+                }
+            }
+        }
+    }
+    let (_health, output) = service.supervisor.read().unwrap().status();
+    Ok(Response::with((status::Ok, output)))
+}
+
+/// The POST /services callback.
+///
+/// Asks the supervisor to load the package identifier or artifact path in the body as a new
+/// service. Loading happens in the background; watch /services for the new service.
+fn load_service(ring_keys: &Arc<RwLock<RingKeys>>,
+                manager: &Arc<Mutex<Sender<ManagerMessage>>>,
+                req: &mut Request)
+                -> IronResult<Response> {
+    let body = match try!(authorize(ring_keys, None, req)) {
+        Ok(body) => body,
+        Err(response) => return Ok(response),
+    };
+    let ident_or_artifact = body.trim();
+    let message = if Path::new(ident_or_artifact).is_file() {
+        match PackageArchive::new(Path::new(ident_or_artifact)).ident() {
+            Ok(ident) => ManagerMessage::Load(ident, Some(ident_or_artifact.to_string())),
+            Err(e) => return Ok(Response::with((status::BadRequest, format!("{}", e)))),
+        }
+    } else {
+        match PackageIdent::from_str(ident_or_artifact) {
+            Ok(ident) => ManagerMessage::Load(ident, None),
+            Err(e) => return Ok(Response::with((status::BadRequest, format!("{}", e)))),
+        }
+    };
+    outputln!("Control request: load {}", ident_or_artifact);
+    manager.lock().unwrap().send(message).unwrap_or(());
+    Ok(Response::with((status::Accepted, format!("Loading {}", ident_or_artifact))))
+}
+
+/// The /services/<name>/unload callback.
+///
+/// Asks the supervisor to stop the service and forget about it.
+fn unload_service(ring_keys: &Arc<RwLock<RingKeys>>,
+                  manager: &Arc<Mutex<Sender<ManagerMessage>>>,
+                  service: &SidecarService,
+                  req: &mut Request)
+                  -> IronResult<Response> {
+    if let Err(response) = try!(authorize(ring_keys, Some(service), req)) {
+        return Ok(response);
+    }
+    outputln!("Control request: unload {}", service.name);
+    manager.lock().unwrap().send(ManagerMessage::Unload(service.name.clone())).unwrap_or(());
+    Ok(Response::with((status::Accepted, format!("Unloading {}", service.name))))
+}
+
 /// Translates SupErrors into IronErrors
 impl From<SupError> for IronError {
     fn from(err: SupError) -> IronError {
@@ -356,3 +964,30 @@ impl From<SupError> for IronError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rustc_serialize::json::Json;
+
+    use super::{redact_exported_cfg, REDACTED};
+
+    #[test]
+    fn redact_exported_cfg_keeps_keys() {
+        let mut json = Json::from_str(r#"{"rumors": [{"variant": "CensusEntry", "fields": [
+            {"service": "redis", "cfg": "port = 6379\n[auth]\npassword = \"hunter2\"\n"}]}]}"#)
+            .unwrap();
+        redact_exported_cfg(&mut json);
+        let cfg = json.find("rumors")
+            .and_then(|r| r.as_array())
+            .and_then(|r| r[0].find("fields"))
+            .and_then(|f| f.as_array())
+            .and_then(|f| f[0].find("cfg"))
+            .and_then(|c| c.as_string())
+            .unwrap();
+        assert!(cfg.contains("port"));
+        assert!(cfg.contains("password"));
+        assert!(cfg.contains(REDACTED));
+        assert!(!cfg.contains("6379"));
+        assert!(!cfg.contains("hunter2"));
+    }
+}
@@ -16,27 +16,49 @@
 ///
 /// The supervisor is responsible for running any services we are asked to start. It handles
 /// spawning the new process, watching for failure, and ensuring the service is either up or down.
-/// If the process dies, the supervisor will restart it.
+/// If the process dies, the supervisor will restart it, backing off exponentially while it keeps
+/// dying. A process that dies too often within the crash loop window is not restarted again until
+/// it is explicitly started, or its package or configuration changes.
 
+use std::cmp;
+use std::collections::VecDeque;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::BufReader;
 use std::io::prelude::*;
 use std::path::PathBuf;
-use std::process::{Command, Stdio, Child};
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
 use std::thread;
+use std::time::Duration as StdDuration;
 
 use hcore;
 use hcore::package::PackageIdent;
-use libc::{pid_t, c_int};
+use libc::{self, pid_t, c_int};
 use time::{Duration, SteadyTime};
 
 use error::{Result, Error};
+use logs::{self, LogFile};
+use package::hooks::Hook;
 use util::signals;
 use util::users as hab_users;
 
 const PIDFILE_NAME: &'static str = "PID";
 static LOGKEY: &'static str = "SV";
+/// How long, in seconds, a process has to stop before we kill it, unless its package says
+/// otherwise
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 8;
+/// How often, in milliseconds, we check whether a stopping process has exited
+const STOP_POLL_MS: u64 = 50;
+/// How long, in seconds, we wait before restarting a process that died for the first time; the
+/// wait doubles with each failure in the crash loop window
+const RESTART_BACKOFF_BASE_SECS: u64 = 1;
+/// The longest, in seconds, we wait before restarting a process that died
+pub const DEFAULT_RESTART_BACKOFF_MAX_SECS: u64 = 60;
+/// How many times a process may die within the crash loop window before we stop restarting it
+pub const DEFAULT_CRASH_LOOP_RESTARTS: u32 = 5;
+/// How far back, in seconds, we count the failures of a process
+pub const DEFAULT_CRASH_LOOP_WINDOW_SECS: u64 = 300;
 
 // Functions from POSIX libc.
 extern "C" {
@@ -99,26 +121,100 @@ pub fn WTERMSIG(status: c_int) -> c_int {
 
 pub type Pid = u32;
 
+/// How a process ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Termination {
+    /// It exited with this code
+    Exited(i32),
+    /// It was killed by this signal
+    Signaled(i32),
+    /// It did not stop within its shutdown timeout, and its process group was killed
+    Killed,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Termination::Exited(code) => write!(f, "exited with code {}", code),
+            &Termination::Signaled(signal) => write!(f, "killed by signal {}", signal),
+            &Termination::Killed => write!(f, "killed after the shutdown timeout"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ProcessState {
-    Down,
+    /// The process should be down; once it has stopped, how it ended
+    Down(Option<Termination>),
     Up,
     Start,
     Restart,
+    /// The process died too many times within the crash loop window, and is not restarted
+    CrashLoop,
 }
 
 impl fmt::Display for ProcessState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let state = match self {
-            &ProcessState::Down => "down",
-            &ProcessState::Up => "up",
-            &ProcessState::Start => "start",
-            &ProcessState::Restart => "restart",
-        };
-        write!(f, "{}", state)
+        match self {
+            &ProcessState::Down(Some(ref termination)) => write!(f, "down ({})", termination),
+            &ProcessState::Down(None) => write!(f, "down"),
+            &ProcessState::Up => write!(f, "up"),
+            &ProcessState::Start => write!(f, "start"),
+            &ProcessState::Restart => write!(f, "restart"),
+            &ProcessState::CrashLoop => write!(f, "crash loop"),
+        }
     }
 }
 
+/// How the supervisor stops a process: the signal it sends, and how long it waits for the
+/// process to stop before it kills its process group.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    pub signal: signals::Signal,
+    pub timeout_secs: u64,
+}
+
+impl Default for Shutdown {
+    fn default() -> Shutdown {
+        Shutdown {
+            signal: signals::Signal::SIGTERM,
+            timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// How the supervisor restarts a process that dies while it should be up.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// The longest, in seconds, we wait before restarting the process
+    pub backoff_max_secs: u64,
+    /// How many failures within the window put the process in a crash loop; 0 never does
+    pub crash_loop_restarts: u32,
+    /// How far back, in seconds, we count failures
+    pub crash_loop_window_secs: u64,
+}
+
+impl RestartPolicy {
+    /// How long, in seconds, to wait before restarting a process that has died `failures` times
+    /// within the crash loop window.
+    pub fn backoff_secs(&self, failures: u32) -> u64 {
+        if failures == 0 {
+            return 0;
+        }
+        let backoff = RESTART_BACKOFF_BASE_SECS << cmp::min(failures - 1, 32);
+        cmp::min(backoff, self.backoff_max_secs)
+    }
+}
+
+impl Default for RestartPolicy {
+    fn default() -> RestartPolicy {
+        RestartPolicy {
+            backoff_max_secs: DEFAULT_RESTART_BACKOFF_MAX_SECS,
+            crash_loop_restarts: DEFAULT_CRASH_LOOP_RESTARTS,
+            crash_loop_window_secs: DEFAULT_CRASH_LOOP_WINDOW_SECS,
+        }
+    }
+}
 
 /// Additional params used to start the Supervisor.
 /// These params are outside the scope of what is in
@@ -128,18 +224,32 @@ impl fmt::Display for ProcessState {
 pub struct RuntimeConfig {
     pub svc_user: String,
     pub svc_group: String,
+    pub run_path: String,
 }
 
 impl RuntimeConfig {
-    pub fn new(svc_user: String, svc_group: String) -> RuntimeConfig {
+    pub fn new(svc_user: String, svc_group: String, run_path: String) -> RuntimeConfig {
         RuntimeConfig {
             svc_user: svc_user,
             svc_group: svc_group,
+            run_path: run_path,
         }
     }
 }
 
 
+/// Hooks run around the lifecycle of the supervised process. The topology compiles them against
+/// the service config, so the supervisor only has to run them.
+#[derive(Debug, Default)]
+pub struct ProcessHooks {
+    /// Run once the process has started
+    pub post_run: Option<Hook>,
+    /// Run before the process is asked to stop
+    pub pre_stop: Option<Hook>,
+    /// Run once the process has stopped
+    pub post_stop: Option<Hook>,
+}
+
 #[derive(Debug)]
 pub struct Supervisor {
     pub pid: Option<Pid>,
@@ -148,6 +258,21 @@ pub struct Supervisor {
     pub state_entered: SteadyTime,
     pub has_started: bool,
     pub runtime_config: RuntimeConfig,
+    pub hooks: ProcessHooks,
+    pub shutdown: Shutdown,
+    pub restart_policy: RestartPolicy,
+    /// How the logs of the process are rotated
+    pub log_rotation: logs::Rotation,
+    /// How many times we have restarted the process after it died
+    pub restart_count: u64,
+    /// How the process ended the last time it stopped
+    pub last_termination: Option<Termination>,
+    /// When the process died while it should have been up, within the crash loop window
+    failures: VecDeque<SteadyTime>,
+    /// When we may restart the process after it died
+    next_start: Option<SteadyTime>,
+    /// The process we are stopping; it exiting is not a failure
+    stopping: Option<Pid>,
 }
 
 impl Supervisor {
@@ -155,10 +280,19 @@ impl Supervisor {
         Supervisor {
             pid: None,
             package_ident: package_ident,
-            state: ProcessState::Down,
+            state: ProcessState::Down(None),
             state_entered: SteadyTime::now(),
             has_started: false,
             runtime_config: runtime_config,
+            hooks: ProcessHooks::default(),
+            shutdown: Shutdown::default(),
+            restart_policy: RestartPolicy::default(),
+            log_rotation: logs::Rotation::default(),
+            restart_count: 0,
+            last_termination: None,
+            failures: VecDeque::new(),
+            next_start: None,
+            stopping: None,
         }
     }
 
@@ -168,13 +302,23 @@ impl Supervisor {
     }
 
     pub fn status(&self) -> (bool, String) {
-        let status = format!("{}: {} for {}",
-                             self.package_ident,
-                             self.state,
-                             SteadyTime::now() - self.state_entered);
+        let mut status = format!("{}: {} for {}",
+                                 self.package_ident,
+                                 self.state,
+                                 SteadyTime::now() - self.state_entered);
+        if let Some(next_start) = self.next_start {
+            let wait = cmp::max(next_start - SteadyTime::now(), Duration::zero());
+            status.push_str(&format!(", restarting in {}", wait));
+        }
+        if self.restart_count > 0 {
+            status.push_str(&format!(", restarted {} times", self.restart_count));
+        }
+        if let Some(ref termination) = self.last_termination {
+            status.push_str(&format!(", last {}", termination));
+        }
         let healthy = match self.state {
             ProcessState::Up | ProcessState::Start | ProcessState::Restart => true,
-            ProcessState::Down => false,
+            ProcessState::Down(_) | ProcessState::CrashLoop => false,
         };
         (healthy, status)
     }
@@ -182,6 +326,12 @@ impl Supervisor {
     pub fn start(&mut self) -> Result<()> {
         if self.pid.is_none() {
             outputln!(preamble & self.package_ident.name, "Starting");
+            if let ProcessState::CrashLoop = self.state {
+                self.failures.clear();
+            }
+            if self.next_start.take().is_some() {
+                self.restart_count += 1;
+            }
             self.enter_state(ProcessState::Start);
 
             let mut cmd = Command::new(self.run_cmd());
@@ -190,18 +340,32 @@ impl Supervisor {
 
             self.pid = Some(child.id());
             try!(self.create_pidfile());
-            let package_name = self.package_ident.name.clone();
-            try!(thread::Builder::new()
-                .name(String::from("sup-service-read"))
-                .spawn(move || -> Result<()> { child_reader(&mut child, package_name) }));
+            if let Some(stdout) = child.stdout.take() {
+                try!(self.spawn_child_reader(stdout, logs::Stream::Stdout));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                try!(self.spawn_child_reader(stderr, logs::Stream::Stderr));
+            }
             self.enter_state(ProcessState::Up);
             self.has_started = true;
+            run_hook(&self.package_ident.name, &self.hooks.post_run);
         } else {
             outputln!(preamble & self.package_ident.name, "Already started");
         }
         Ok(())
     }
 
+    fn spawn_child_reader<R>(&self, output: R, stream: logs::Stream) -> Result<()>
+        where R: Read + Send + 'static
+    {
+        let package_name = self.package_ident.name.clone();
+        let rotation = self.log_rotation.clone();
+        try!(thread::Builder::new()
+            .name(format!("sup-service-{}", stream))
+            .spawn(move || -> Result<()> { child_reader(output, package_name, stream, rotation) }));
+        Ok(())
+    }
+
     #[cfg(any(target_os="linux", target_os="macos"))]
     fn start_platform(&mut self, cmd: &mut Command) -> Result<()> {
         use std::os::unix::process::CommandExt;
@@ -217,7 +381,16 @@ impl Supervisor {
 
         let uid = uid.unwrap();
         let gid = gid.unwrap();
-        cmd.stdin(Stdio::null())
+        // Run the process in a process group of its own, so we can kill everything it started if
+        // it will not stop
+        cmd.before_exec(|| {
+            unsafe {
+                libc::setpgid(0, 0);
+            }
+            Ok(())
+        });
+        cmd.env("PATH", &self.runtime_config.run_path)
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .uid(uid)
@@ -230,39 +403,12 @@ impl Supervisor {
         unimplemented!();
     }
 
-    /// Send a SIGTERM to a process, wait 8 seconds, then send SIGKILL
+    /// Run the pre_stop hook, and send the process its shutdown signal. If it has not stopped
+    /// once its shutdown timeout passes, send SIGKILL to its whole process group. Once the
+    /// process has stopped, run the post_stop hook. A process we stop has not failed, so it is
+    /// not backed off or counted towards a crash loop.
     pub fn stop(&mut self) -> Result<()> {
-        let wait = match self.pid {
-            Some(ref pid) => {
-                outputln!(preamble & self.package_ident.name, "Stopping");
-                try!(signals::send_signal_to_pid(*pid, signals::Signal::SIGTERM));
-                true
-            }
-            None => {
-                outputln!(preamble & self.package_ident.name, "Already stopped");
-                false
-            }
-        };
-        if wait {
-            let stop_time = SteadyTime::now() + Duration::seconds(8);
-            loop {
-                try!(self.check_process());
-                if SteadyTime::now() > stop_time {
-                    outputln!(preamble & self.package_ident.name,
-                              "Process failed to stop with SIGTERM; sending SIGKILL");
-                    if let Some(pid) = self.pid {
-                        try!(signals::send_signal_to_pid(pid, signals::Signal::SIGKILL));
-                    }
-                    break;
-                }
-                if self.pid.is_none() {
-                    break;
-                } else {
-                    continue;
-                }
-            }
-        }
-        Ok(())
+        stop_process(self)
     }
 
     pub fn is_up(&self) -> bool {
@@ -273,8 +419,22 @@ impl Supervisor {
         }
     }
 
+    pub fn is_crash_looping(&self) -> bool {
+        if let ProcessState::CrashLoop = self.state {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true once any backoff after the process died has passed, and it may be started
+    /// again.
+    pub fn restart_due(&self) -> bool {
+        self.next_start.map_or(true, |next_start| SteadyTime::now() >= next_start)
+    }
+
     pub fn is_down(&self) -> bool {
-        if let ProcessState::Down = self.state {
+        if let ProcessState::Down(_) = self.state {
             true
         } else {
             false
@@ -282,17 +442,28 @@ impl Supervisor {
     }
 
     pub fn down(&mut self) -> Result<()> {
-        self.enter_state(ProcessState::Down);
-        try!(self.stop());
-        self.cleanup_pidfile();
-        Ok(())
+        down_process(self)
     }
 
     pub fn restart(&mut self) -> Result<()> {
-        self.enter_state(ProcessState::Restart);
-        try!(self.stop());
-        try!(self.start());
-        Ok(())
+        restart_process(self)
+    }
+
+    /// Like `stop`, for a supervisor shared with other threads. It is only locked while its state
+    /// changes, not while the stop hooks run or the process is waited for, so its status can be
+    /// read meanwhile.
+    pub fn stop_shared(supervisor: &RwLock<Supervisor>) -> Result<()> {
+        stop_process(&mut &*supervisor)
+    }
+
+    /// Like `down`, for a supervisor shared with other threads; see `stop_shared`.
+    pub fn down_shared(supervisor: &RwLock<Supervisor>) -> Result<()> {
+        down_process(&mut &*supervisor)
+    }
+
+    /// Like `restart`, for a supervisor shared with other threads; see `stop_shared`.
+    pub fn restart_shared(supervisor: &RwLock<Supervisor>) -> Result<()> {
+        restart_process(&mut &*supervisor)
     }
 
     /// Pass through a Unix signal to a process
@@ -317,30 +488,42 @@ impl Supervisor {
             match waitpid(cpid, &mut status, 1 as c_int) {
                 0 => {} // Nothing returned,
                 pid if pid == cpid => {
-                    if WIFEXITED(status) {
+                    let termination = if WIFEXITED(status) {
                         let exit_code = WEXITSTATUS(status);
                         outputln!("{} - process {} died with exit code {}",
                                   self.package_ident.name,
                                   pid,
                                   exit_code);
+                        Some(Termination::Exited(exit_code))
                     } else if WIFSIGNALED(status) {
                         let exit_signal = WTERMSIG(status);
                         outputln!("{} - process {} died with signal {}",
                                   self.package_ident.name,
                                   pid,
                                   exit_signal);
+                        Some(Termination::Signaled(exit_signal))
                     } else {
                         outputln!("{} - process {} died, but I don't know how.",
                                   self.package_ident.name,
                                   pid);
-                    }
+                        None
+                    };
+                    self.last_termination = termination.clone();
+                    let stopped = self.stopping == self.pid;
                     match self.state {
+                        // We stopped it ourselves; it did not fail
+                        ProcessState::Up | ProcessState::Start | ProcessState::Restart
+                            if stopped => {
+                            outputln!("{} - Service stopped", self.package_ident.name);
+                            self.pid = None;
+                        }
                         ProcessState::Up | ProcessState::Start | ProcessState::Restart => {
                             outputln!("{} - Service exited", self.package_ident.name);
                             self.pid = None;
+                            self.process_died();
                         }
-                        ProcessState::Down => {
-                            self.enter_state(ProcessState::Down);
+                        ProcessState::Down(_) | ProcessState::CrashLoop => {
+                            self.enter_state(ProcessState::Down(termination));
                             self.pid = None;
                         }
                     }
@@ -362,6 +545,34 @@ impl Supervisor {
         Ok(())
     }
 
+    /// Count a failure of a process that died while it should have been up, and decide when to
+    /// restart it. If it has failed too often within the crash loop window, stop restarting it.
+    fn process_died(&mut self) {
+        let now = SteadyTime::now();
+        let window = Duration::seconds(self.restart_policy.crash_loop_window_secs as i64);
+        self.failures.push_back(now);
+        while self.failures.front().map_or(false, |failed| now - *failed > window) {
+            self.failures.pop_front();
+        }
+        let failures = self.failures.len() as u32;
+        let limit = self.restart_policy.crash_loop_restarts;
+        if limit > 0 && failures >= limit {
+            outputln!(preamble & self.package_ident.name,
+                      "Process died {} times in {} seconds; it will not be restarted until it is \
+                       started again, or its package or configuration changes",
+                      failures,
+                      self.restart_policy.crash_loop_window_secs);
+            self.next_start = None;
+            self.enter_state(ProcessState::CrashLoop);
+        } else {
+            let backoff = self.restart_policy.backoff_secs(failures);
+            outputln!(preamble & self.package_ident.name,
+                      "Restarting in {} seconds",
+                      backoff);
+            self.next_start = Some(now + Duration::seconds(backoff as i64));
+        }
+    }
+
     pub fn run_cmd(&self) -> PathBuf {
         self.service_dir().join("run")
     }
@@ -429,28 +640,305 @@ impl Supervisor {
     }
 }
 
+/// A supervisor we can change for a moment: one we own, or one shared with other threads, which
+/// is locked only for that moment.
+trait Lock {
+    fn with<T, F: FnOnce(&mut Supervisor) -> T>(&mut self, f: F) -> T;
+}
+
+impl Lock for Supervisor {
+    fn with<T, F: FnOnce(&mut Supervisor) -> T>(&mut self, f: F) -> T {
+        f(self)
+    }
+}
+
+impl<'a> Lock for &'a RwLock<Supervisor> {
+    fn with<T, F: FnOnce(&mut Supervisor) -> T>(&mut self, f: F) -> T {
+        f(&mut self.write().unwrap())
+    }
+}
+
+fn stop_process<L: Lock>(supervisor: &mut L) -> Result<()> {
+    let stopping = supervisor.with(|s| {
+        match s.pid {
+            Some(pid) => {
+                s.stopping = Some(pid);
+                Some((pid,
+                      s.package_ident.name.clone(),
+                      s.shutdown.clone(),
+                      s.hooks.pre_stop.clone(),
+                      s.hooks.post_stop.clone()))
+            }
+            None => {
+                outputln!(preamble & s.package_ident.name, "Already stopped");
+                None
+            }
+        }
+    });
+    let (pid, name, shutdown, pre_stop, post_stop) = match stopping {
+        Some(stopping) => stopping,
+        None => return Ok(()),
+    };
+    run_hook(&name, &pre_stop);
+    outputln!(preamble & name, "Stopping with {:?}", shutdown.signal);
+    let result = wait_for_stop(supervisor, pid, &name, &shutdown);
+    supervisor.with(|s| if s.stopping == Some(pid) {
+        s.stopping = None;
+    });
+    try!(result);
+    run_hook(&name, &post_stop);
+    Ok(())
+}
+
+fn wait_for_stop<L: Lock>(supervisor: &mut L,
+                          pid: Pid,
+                          name: &str,
+                          shutdown: &Shutdown)
+                          -> Result<()> {
+    try!(signals::send_signal_to_pid(pid, shutdown.signal.clone()));
+    let stop_time = SteadyTime::now() + Duration::seconds(shutdown.timeout_secs as i64);
+    let mut killed = false;
+    loop {
+        // Once another thread has started a new process, ours is long gone
+        if try!(supervisor.with(|s| s.check_process().map(|_| s.pid != Some(pid)))) {
+            break;
+        }
+        if !killed && SteadyTime::now() > stop_time {
+            outputln!(preamble & name,
+                      "Process failed to stop within {} seconds; sending SIGKILL to its process \
+                       group",
+                      shutdown.timeout_secs);
+            // If the process group is already gone, the process may still be around
+            if signals::send_signal_to_process_group(pid, signals::Signal::SIGKILL).is_err() {
+                try!(signals::send_signal_to_pid(pid, signals::Signal::SIGKILL));
+            }
+            killed = true;
+        }
+        thread::sleep(StdDuration::from_millis(STOP_POLL_MS));
+    }
+    if killed {
+        supervisor.with(|s| {
+            s.last_termination = Some(Termination::Killed);
+            if let ProcessState::Down(_) = s.state {
+                s.state = ProcessState::Down(Some(Termination::Killed));
+            }
+        });
+    }
+    Ok(())
+}
+
+fn down_process<L: Lock>(supervisor: &mut L) -> Result<()> {
+    supervisor.with(|s| {
+        s.enter_state(ProcessState::Down(None));
+        s.next_start = None;
+    });
+    try!(stop_process(supervisor));
+    supervisor.with(|s| s.cleanup_pidfile());
+    Ok(())
+}
+
+fn restart_process<L: Lock>(supervisor: &mut L) -> Result<()> {
+    supervisor.with(|s| s.enter_state(ProcessState::Restart));
+    try!(stop_process(supervisor));
+    supervisor.with(|s| s.start())
+}
+
+/// Run one of the process hooks, if the package has it. A failing hook is reported, but does not
+/// stop the process from starting or stopping.
+fn run_hook(name: &str, hook: &Option<Hook>) {
+    if let Some(ref hook) = *hook {
+        if let Err(e) = hook.exec() {
+            outputln!(preamble & name, "The {} hook failed: {}", hook.htype, e);
+        }
+    }
+}
+
 impl Drop for Supervisor {
     fn drop(&mut self) {
         let _ = self.cleanup_pidfile();
     }
 }
 
-/// Consume output from a child process until EOF, then finish
-fn child_reader(child: &mut Child, package_name: String) -> Result<()> {
-    let c_stdout = match child.stdout {
-        Some(ref mut s) => s,
-        None => return Err(sup_error!(Error::UnpackFailed)),
+/// Prints the output of the child process, and writes it to the service's log, until EOF.
+fn child_reader<R: Read>(output: R,
+                         package_name: String,
+                         stream: logs::Stream,
+                         rotation: logs::Rotation)
+                         -> Result<()> {
+    let mut log = match LogFile::open(logs::log_path(&package_name, stream), rotation) {
+        Ok(log) => Some(log),
+        Err(e) => {
+            outputln!(preamble & package_name, "Can't write the {} log: {}", stream, e);
+            None
+        }
     };
-
-    let mut reader = BufReader::new(c_stdout);
-    let mut buffer = String::new();
-
-    while reader.read_line(&mut buffer).unwrap() > 0 {
-        let mut line = output_format!(preamble &package_name, logkey "O");
-        line.push_str(&buffer);
-        print!("{}", line);
+    let mut reader = BufReader::new(output);
+    let mut buffer = Vec::new();
+
+    while try!(reader.read_until(b'\n', &mut buffer)) > 0 {
+        let line = String::from_utf8_lossy(&buffer).into_owned();
+        println!("{}",
+                 output_format!(preamble &package_name,
+                                logkey stream.logkey(),
+                                line.trim_right_matches('\n')));
+        let failed = match log {
+            Some(ref mut log) => log.write_line(&buffer).err(),
+            None => None,
+        };
+        if let Some(e) = failed {
+            outputln!(preamble & package_name,
+                      "Can't write the {} log, so no longer writing it: {}",
+                      stream,
+                      e);
+            log = None;
+        }
         buffer.clear();
     }
     debug!("child_reader exiting");
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+    use std::str::FromStr;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::Duration as StdDuration;
+
+    use hcore::package::PackageIdent;
+    use time::{Duration, SteadyTime};
+
+    use util::signals;
+    use super::{ProcessState, RestartPolicy, RuntimeConfig, Supervisor, Termination};
+
+    fn supervisor(policy: RestartPolicy) -> Supervisor {
+        let mut supervisor = Supervisor::new(PackageIdent::from_str("core/crashy").unwrap(),
+                                             RuntimeConfig::new(String::from("root"),
+                                                                String::from("root"),
+                                                                String::new()));
+        supervisor.restart_policy = policy;
+        supervisor.enter_state(ProcessState::Up);
+        supervisor
+    }
+
+    fn policy(crash_loop_restarts: u32) -> RestartPolicy {
+        RestartPolicy { crash_loop_restarts: crash_loop_restarts, ..RestartPolicy::default() }
+    }
+
+    fn spawn_sleep(supervisor: &mut Supervisor) {
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        supervisor.pid = Some(child.id());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RestartPolicy { backoff_max_secs: 10, ..RestartPolicy::default() };
+        assert_eq!(policy.backoff_secs(0), 0);
+        assert_eq!(policy.backoff_secs(1), 1);
+        assert_eq!(policy.backoff_secs(2), 2);
+        assert_eq!(policy.backoff_secs(4), 8);
+        assert_eq!(policy.backoff_secs(5), 10);
+        assert_eq!(policy.backoff_secs(100), 10);
+    }
+
+    #[test]
+    fn failures_are_counted_within_the_window() {
+        let mut supervisor = supervisor(policy(3));
+        supervisor.failures.push_back(SteadyTime::now() - Duration::seconds(301));
+        supervisor.process_died();
+        assert_eq!(supervisor.failures.len(), 1);
+        assert!(supervisor.next_start.is_some());
+        supervisor.process_died();
+        assert_eq!(supervisor.failures.len(), 2);
+        assert!(supervisor.is_up());
+    }
+
+    #[test]
+    fn crash_loop_is_entered_at_the_limit() {
+        let mut supervisor = supervisor(policy(3));
+        supervisor.process_died();
+        supervisor.process_died();
+        assert!(!supervisor.is_crash_looping());
+        supervisor.process_died();
+        assert!(supervisor.is_crash_looping());
+        assert!(supervisor.next_start.is_none());
+    }
+
+    #[test]
+    fn no_crash_loop_without_a_limit() {
+        let mut supervisor = supervisor(policy(0));
+        for _ in 0..20 {
+            supervisor.process_died();
+        }
+        assert!(!supervisor.is_crash_looping());
+        assert!(supervisor.next_start.is_some());
+    }
+
+    #[test]
+    fn start_clears_a_crash_loop() {
+        let mut supervisor = supervisor(policy(1));
+        supervisor.process_died();
+        assert!(supervisor.is_crash_looping());
+        // There is no run hook to start, but the crash loop is forgotten first
+        let _ = supervisor.start();
+        assert!(supervisor.failures.is_empty());
+        assert!(!supervisor.is_crash_looping());
+    }
+
+    #[test]
+    fn stopping_is_not_a_failure() {
+        let mut supervisor = supervisor(policy(1));
+        spawn_sleep(&mut supervisor);
+        supervisor.stop().unwrap();
+        assert!(supervisor.pid.is_none());
+        assert!(supervisor.is_up());
+        assert!(supervisor.failures.is_empty());
+        assert!(supervisor.next_start.is_none());
+
+        supervisor.enter_state(ProcessState::Restart);
+        spawn_sleep(&mut supervisor);
+        supervisor.stop().unwrap();
+        assert!(supervisor.failures.is_empty());
+        assert!(supervisor.next_start.is_none());
+        assert_eq!(supervisor.restart_count, 0);
+    }
+
+    #[test]
+    fn status_can_be_read_while_stopping() {
+        let mut supervisor = supervisor(policy(1));
+        supervisor.shutdown.timeout_secs = 1;
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg("trap '' TERM; exec sleep 30")
+            .spawn()
+            .unwrap();
+        supervisor.pid = Some(child.id());
+        // Give the shell time to ignore SIGTERM
+        thread::sleep(StdDuration::from_millis(100));
+        let supervisor = Arc::new(RwLock::new(supervisor));
+        let shared = supervisor.clone();
+        let stopping = thread::spawn(move || Supervisor::stop_shared(&shared).is_ok());
+        thread::sleep(StdDuration::from_millis(300));
+        assert!(supervisor.read().unwrap().pid.is_some());
+        assert!(stopping.join().unwrap());
+        let stopped = supervisor.read().unwrap();
+        assert!(stopped.pid.is_none());
+        assert_eq!(stopped.last_termination, Some(Termination::Killed));
+        assert!(stopped.failures.is_empty());
+    }
+
+    #[test]
+    fn dying_is_a_failure() {
+        let mut supervisor = supervisor(policy(5));
+        spawn_sleep(&mut supervisor);
+        let pid = supervisor.pid.unwrap();
+        signals::send_signal_to_pid(pid, signals::Signal::SIGKILL).unwrap();
+        while supervisor.pid.is_some() {
+            supervisor.check_process().unwrap();
+            thread::sleep(StdDuration::from_millis(10));
+        }
+        assert_eq!(supervisor.failures.len(), 1);
+        assert!(supervisor.next_start.is_some());
+    }
+}
@@ -24,6 +24,7 @@ use error::{Result, SupError};
 use state_machine::StateMachine;
 use topology::{self, standalone, State, Worker};
 use package::Package;
+use manager::ServiceContext;
 
 static LOGKEY: &'static str = "TI";
 
@@ -33,10 +34,11 @@ enum InitGate {
     Done,
 }
 
-pub fn run(package: Package) -> Result<()> {
-    let mut worker = try!(Worker::new(package, String::from("initializer")));
+pub fn run(package: Package, ctx: ServiceContext) -> Result<()> {
+    let mut worker = try!(Worker::new(package, String::from("initializer"), ctx));
     let mut sm: StateMachine<State, Worker, SupError> =
-        StateMachine::new(State::DetermineViability);
+        StateMachine::new(State::WaitingForBinds);
+    sm.add_dispatch(State::WaitingForBinds, state_waiting_for_binds);
     sm.add_dispatch(State::DetermineViability, state_determine_viability);
     sm.add_dispatch(State::StartElection, state_start_election);
     sm.add_dispatch(State::InElection, state_in_election);
@@ -47,6 +49,10 @@ pub fn run(package: Package) -> Result<()> {
     topology::run_internal(&mut sm, &mut worker)
 }
 
+pub fn state_waiting_for_binds(worker: &mut Worker) -> Result<(State, u64)> {
+    topology::state_waiting_for_binds(worker, State::DetermineViability)
+}
+
 pub fn state_determine_viability(worker: &mut Worker) -> Result<(State, u64)> {
     outputln!("Determining viability as a leader");
     {
@@ -68,6 +74,7 @@ pub fn state_determine_viability(worker: &mut Worker) -> Result<(State, u64)> {
 
 pub fn state_start_election(worker: &mut Worker) -> Result<(State, u64)> {
     outputln!("Starting an election");
+    worker.update_suitability();
     {
         let mut cl = worker.census_list.write().unwrap();
         let mut ce = cl.me_mut();
@@ -16,15 +16,17 @@ use topology::{self, standalone, State, Worker};
 use state_machine::StateMachine;
 use error::{Result, SupError};
 use package::Package;
+use manager::ServiceContext;
 use config::gconfig;
 use census::MIN_QUORUM;
 use gossip::server;
 
 static LOGKEY: &'static str = "TL";
 
-pub fn run(package: Package) -> Result<()> {
-    let mut worker = try!(Worker::new(package, String::from("leader")));
-    let mut sm: StateMachine<State, Worker, SupError> = StateMachine::new(State::Init);
+pub fn run(package: Package, ctx: ServiceContext) -> Result<()> {
+    let mut worker = try!(Worker::new(package, String::from("leader"), ctx));
+    let mut sm: StateMachine<State, Worker, SupError> = StateMachine::new(State::WaitingForBinds);
+    sm.add_dispatch(State::WaitingForBinds, state_waiting_for_binds);
     sm.add_dispatch(State::Init, state_init);
     sm.add_dispatch(State::MinimumQuorum, state_minimum_quorum);
     sm.add_dispatch(State::WaitingForQuorum, state_waiting_for_quorum);
@@ -38,6 +40,10 @@ pub fn run(package: Package) -> Result<()> {
     topology::run_internal(&mut sm, &mut worker)
 }
 
+fn state_waiting_for_binds(worker: &mut Worker) -> Result<(State, u64)> {
+    topology::state_waiting_for_binds(worker, State::Init)
+}
+
 fn state_init(worker: &mut Worker) -> Result<(State, u64)> {
     let cl = worker.census_list.read().unwrap();
     let census = cl.local_census();
@@ -94,14 +100,16 @@ fn state_restore_dataset(worker: &mut Worker) -> Result<(State, u64)> {
 }
 
 fn state_check_for_election(worker: &mut Worker) -> Result<(State, u64)> {
-    let (has_quorum, am_leader, am_follower, has_leader) = {
+    let (has_quorum, am_leader, am_follower, has_leader, leader_suspected, dead_leader) = {
         let cl = worker.census_list.read().unwrap();
         let census = cl.local_census();
         let has_quorum = census.has_quorum();
         let am_leader = census.me().leader;
         let am_follower = census.me().follower;
         let has_leader = census.has_leader();
-        (has_quorum, am_leader, am_follower, has_leader)
+        let leader_suspected = census.leader_suspected();
+        let dead_leader = census.dead_leader().map(|ce| ce.member_id.clone());
+        (has_quorum, am_leader, am_follower, has_leader, leader_suspected, dead_leader)
     };
 
     if has_quorum {
@@ -115,8 +123,21 @@ fn state_check_for_election(worker: &mut Worker) -> Result<(State, u64)> {
                 // If you aren't, you should be a follower!
                 Ok((State::BecomeFollower, 0))
             }
+        } else if leader_suspected {
+            debug!("The leader is suspected dead; waiting to see if it comes back");
+            Ok((State::CheckForElection, 500))
         } else {
-            outputln!("I have quorum, but no leader; starting an election!");
+            if let Some(dead_leader) = dead_leader {
+                outputln!("The leader {} is confirmed dead; starting a new election term",
+                          dead_leader);
+                let mut cl = worker.census_list.write().unwrap();
+                let mut census = cl.local_census_mut();
+                census.in_event = true;
+                census.no_leaders_allowed();
+                census.me_mut().follower(false);
+            } else {
+                outputln!("I have quorum, but no leader; starting an election!");
+            }
             Ok((State::StartElection, 0))
         }
     } else {
@@ -154,6 +175,7 @@ fn state_check_for_election(worker: &mut Worker) -> Result<(State, u64)> {
 
 pub fn state_start_election(worker: &mut Worker) -> Result<(State, u64)> {
     outputln!("Starting election");
+    worker.update_suitability();
     let rumor_list = {
         let el = worker.election_list.read().unwrap();
         el.generate_rumor_list_for(worker.package_name.clone(), gconfig().group().to_string())
@@ -161,9 +183,7 @@ pub fn state_start_election(worker: &mut Worker) -> Result<(State, u64)> {
     server::process_rumors(rumor_list,
                            worker.rumor_list.clone(),
                            worker.member_list.clone(),
-                           worker.census_list.clone(),
-                           worker.election_list.clone(),
-                           worker.gossip_file_list.clone());
+                           worker.gossip_services.clone());
     Ok((State::Election, 200))
 }
 
@@ -207,9 +227,7 @@ pub fn state_election(worker: &mut Worker) -> Result<(State, u64)> {
         server::process_rumors(rumor_list,
                                worker.rumor_list.clone(),
                                worker.member_list.clone(),
-                               worker.census_list.clone(),
-                               worker.election_list.clone(),
-                               worker.gossip_file_list.clone());
+                               worker.gossip_services.clone());
     }
 
     Ok((State::Election, 200))
@@ -270,6 +288,11 @@ pub fn state_starting(worker: &mut Worker) -> Result<(State, u64)> {
     if !is_running {
         try!(initialize(worker));
         try!(standalone::state_starting(worker));
+    } else {
+        // We were already running, so this was a new election term; the service has to learn
+        // about its new leader
+        outputln!("Running the reconfigure hook for the new leader");
+        try!(worker.reconfigure());
     }
     Ok((State::CheckForElection, 200))
 }
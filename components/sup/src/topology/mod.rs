@@ -20,43 +20,53 @@
 //! 1. Processing the main event loop
 //! 1. Registering callbacks with the [discovery](../discovery) system
 //!
-//! Typically, topologies are created from the [start](../command/start) command.
+//! Typically, topologies are run by the [service manager](../manager), one per service, from the
+//! [start](../command/start) command.
 
 pub mod standalone;
 pub mod leader;
 pub mod initializer;
 
 use std::mem;
-use std::net::SocketAddrV4;
+use std::u32;
 use std::ops::DerefMut;
-use std::str::FromStr;
 use std::sync::{Arc, RwLock};
-use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
 use std::thread;
 use std::time::Duration;
 
+use time;
 use wonder;
 
 use state_machine::StateMachine;
-use census::{self, CensusList};
+use census::{self, CensusList, UpdateTurn};
+use common::event::EventList;
+use common::config_history::ConfigHistory;
 use common::gossip_file::GossipFileList;
+use hcore::package::Bind;
 use package::{self, Package, PackageUpdaterActor};
-use util::signals::SignalNotifier;
-use error::{Result, SupError};
+use error::{Error, Result, SupError};
 use config::{gconfig, UpdateStrategy};
 use service_config::ServiceConfig;
 use sidecar;
-use supervisor::{RuntimeConfig, Supervisor};
+use supervisor::{RestartPolicy, RuntimeConfig, Supervisor};
 use gossip;
+use gossip::server::ServiceGossip;
 use gossip::rumor::{Rumor, RumorList};
 use gossip::member::MemberList;
 use election::ElectionList;
+use health_check;
+use logs;
+use manager::{ServiceContext, ServiceMessage};
 use time::SteadyTime;
-use util::signals;
 use util::users as hab_users;
 
 static LOGKEY: &'static str = "TP";
 static MINIMUM_LOOP_TIME_MS: i64 = 200;
+static UPDATE_HEALTH_CHECK_MS: i64 = 5_000;
+static BIND_WAIT_MS: u64 = 1_000;
+/// The longest the event hook may run for an event, in seconds, unless its timeout is shorter
+static EVENT_HOOK_TIMEOUT_SECS: u64 = 60;
 
 #[derive(PartialEq, Eq, Debug, RustcEncodable)]
 pub enum Topology {
@@ -74,6 +84,7 @@ impl Default for Topology {
 /// Viable states for the topologies. Not every topology will implement every state.
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 pub enum State {
+    WaitingForBinds,
     Init,
     MinimumQuorum,
     WaitingForQuorum,
@@ -103,28 +114,56 @@ pub struct Worker {
     pub topology: String,
     /// Our Service Configuration; manages changes to our configuration,
     pub service_config: Arc<RwLock<ServiceConfig>>,
-    /// The Gossip Server; listens for inbound gossip traffic
-    pub gossip_server: gossip::server::Server,
     pub census_list: Arc<RwLock<CensusList>>,
     pub rumor_list: Arc<RwLock<RumorList>>,
     pub election_list: Arc<RwLock<ElectionList>>,
     pub member_list: Arc<RwLock<MemberList>>,
     pub gossip_file_list: Arc<RwLock<GossipFileList>>,
-    /// Our Sidecar Actor; exposes a restful HTTP interface to the outside world
-    pub sidecar_actor: sidecar::SidecarActor,
+    /// The events gossiped to our service group
+    pub event_list: Arc<RwLock<EventList>>,
+    /// The gossip lists of every service in this supervisor; rumors we generate are processed
+    /// by all of them
+    pub gossip_services: Arc<RwLock<Vec<ServiceGossip>>>,
+    /// Messages from the service manager
+    pub control: Receiver<ServiceMessage>,
     /// Watches a package Depot for updates and signals the main thread when an update is available. Optionally
     /// started if a value is passed for the url option on startup.
     pub pkg_updater: Option<PackageUpdaterActor>,
+    /// A package update we are holding until it is our turn in a rolling or canary update
+    pub pending_update: Option<Package>,
+    /// The package we ran before our last update; what a failed canary rolls back to
+    pub previous_package: Option<Package>,
+    /// When we should next check our health, if we still have to report it for an update
+    pub update_health_check: Option<SteadyTime>,
+    /// The health check of an update, while it runs in the background
+    pub update_check: Option<health_check::PendingCheck>,
+    /// When our health checks started passing during a canary soak
+    pub soak_started: Option<SteadyTime>,
+    /// Whether we have already told the user the update is halted
+    pub update_halted: bool,
+    /// Whether our last update failed its health check or smoke test; we report ourselves
+    /// critical until the next one
+    pub update_failed: bool,
+    /// The periodic health check, while it runs in the background
+    pub health_check: Option<health_check::PendingCheck>,
+    /// Hangs up once the event hook has run for the events we last delivered, in the background
+    pub event_delivery: Option<Receiver<()>>,
+    /// When the periodic health check should next run
+    pub next_health_check: SteadyTime,
     /// The service supervisor
     pub supervisor: Arc<RwLock<Supervisor>>,
+    /// The binds the package requires, and the service group each is bound to
+    pub required_binds: Vec<(Bind, String)>,
+    /// When we started waiting for the required binds, if we are waiting
+    pub bind_wait_started: Option<SteadyTime>,
+    /// The binds we last told the user we are waiting for
+    pub binds_waiting: Vec<String>,
     pub return_state: Option<State>,
 }
 
 impl Worker {
-    /// Create a new worker
-    ///
-    /// Automatically sets the backend to Etcd.
-    pub fn new(package: Package, topology: String) -> Result<Worker> {
+    /// Create a new worker, sharing the gossip server and sidecar of the service manager.
+    pub fn new(package: Package, topology: String, ctx: ServiceContext) -> Result<Worker> {
         let mut pkg_updater = None;
         let package_name = package.name.clone();
 
@@ -132,14 +171,10 @@ impl Worker {
         outputln!("Child process will run as user={}, group={}",
                   &svc_user,
                   &svc_group);
-        let runtime_config = RuntimeConfig::new(svc_user, svc_group);
+        let runtime_config = RuntimeConfig::new(svc_user, svc_group, try!(package.run_path()));
 
-        let package_exposes = package.exposes().clone();
-        let package_port = package_exposes.first().map(|e| e.clone());
         let package_ident = package.ident().clone();
         let pkg_lock = Arc::new(RwLock::new(package));
-        let pkg_lock_1 = pkg_lock.clone();
-
 
         match gconfig().update_strategy() {
             UpdateStrategy::None => {}
@@ -149,89 +184,604 @@ impl Worker {
             }
         }
 
-        let gossip_server = gossip::server::Server::new(String::from(gconfig().gossip_listen_ip()),
-                                                        gconfig().gossip_listen_port(),
-                                                        gconfig().gossip_permanent(),
-                                                        gconfig().ring().clone(),
-                                                        package_name.clone(),
-                                                        gconfig().group().to_string(),
-                                                        gconfig().organization().clone(),
-                                                        Some(package_exposes),
-                                                        package_port);
+        census::start_health_adjuster(ctx.gossip.census_list.clone(), ctx.member_list.clone());
+        {
+            let mut cl = ctx.gossip.census_list.write().unwrap();
+            cl.me_mut().package(Some(package_ident.to_string()));
+        }
 
-        try!(gossip_server.start_inbound());
-        try!(gossip_server.initial_peers(gconfig().gossip_peer()));
-        gossip_server.start_outbound();
-        gossip_server.start_failure_detector();
-        census::start_health_adjuster(gossip_server.census_list.clone(),
-                                      gossip_server.member_list.clone());
+        // In a rolling or canary update, other members wait on our health; report it once we
+        // are up
+        let update_health_check = match gconfig().update_strategy() {
+            UpdateStrategy::Rolling | UpdateStrategy::Canary => {
+                Some(SteadyTime::now() + time::Duration::milliseconds(UPDATE_HEALTH_CHECK_MS))
+            }
+            _ => None,
+        };
+
+        let next_health_check =
+            SteadyTime::now() +
+            time::Duration::seconds(gconfig().health_check_interval_secs() as i64);
 
         // Setup the Service Configuration
         let service_config = {
-            let cl = gossip_server.census_list.read().unwrap();
+            let cl = ctx.gossip.census_list.read().unwrap();
             let pkg = pkg_lock.read().unwrap();
             let sc = try!(ServiceConfig::new(&pkg, &cl, gconfig().bind()));
             sc
         };
+        let required_binds = {
+            let pkg = pkg_lock.read().unwrap();
+            try!(required_binds(&pkg, &gconfig().bind()))
+        };
         let service_config_lock = Arc::new(RwLock::new(service_config));
-        let service_config_lock_1 = service_config_lock.clone();
-
-        let supervisor = Arc::new(RwLock::new(Supervisor::new(package_ident, runtime_config)));
-
-        let sidecar_ml = gossip_server.member_list.clone();
-        let sidecar_rl = gossip_server.rumor_list.clone();
-        let sidecar_cl = gossip_server.census_list.clone();
-        let sidecar_gfl = gossip_server.gossip_file_list.clone();
-        let sidecar_detector = gossip_server.detector.clone();
-        let sidecar_el = gossip_server.election_list.clone();
-        let sidecar_sup = supervisor.clone();
-        let sidecar_listen = try!(SocketAddrV4::from_str(&format!("{}:{}",
-                                                                  &gconfig().http_listen_ip(),
-                                                                  gconfig().http_listen_port())));
-        Ok(Worker {
+
+        let mut supervisor = Supervisor::new(package_ident, runtime_config);
+        supervisor.restart_policy = RestartPolicy {
+            backoff_max_secs: gconfig().restart_backoff_max_secs(),
+            crash_loop_restarts: gconfig().crash_loop_restarts(),
+            crash_loop_window_secs: gconfig().crash_loop_window_secs(),
+        };
+        supervisor.log_rotation = logs::Rotation {
+            max_bytes: gconfig().log_max_bytes(),
+            retain: gconfig().log_retain(),
+        };
+        let supervisor = Arc::new(RwLock::new(supervisor));
+
+        {
+            let mut sidecar_services = ctx.sidecar_services.write().unwrap();
+            sidecar_services.push(sidecar::SidecarService {
+                name: package_name.clone(),
+                package: pkg_lock.clone(),
+                config: service_config_lock.clone(),
+                census_list: ctx.gossip.census_list.clone(),
+                election_list: ctx.gossip.election_list.clone(),
+                supervisor: supervisor.clone(),
+                gossip_file_list: ctx.gossip.gossip_file_list.clone(),
+                event_list: ctx.gossip.event_list.clone(),
+            });
+        }
+
+        let worker = Worker {
             package: pkg_lock,
             package_name: package_name,
             topology: topology,
-            census_list: gossip_server.census_list.clone(),
-            rumor_list: gossip_server.rumor_list.clone(),
-            election_list: gossip_server.election_list.clone(),
-            gossip_file_list: gossip_server.gossip_file_list.clone(),
-            member_list: gossip_server.member_list.clone(),
-            gossip_server: gossip_server,
+            census_list: ctx.gossip.census_list,
+            rumor_list: ctx.rumor_list,
+            election_list: ctx.gossip.election_list,
+            gossip_file_list: ctx.gossip.gossip_file_list,
+            event_list: ctx.gossip.event_list,
+            member_list: ctx.member_list,
+            gossip_services: ctx.gossip_services,
+            control: ctx.control,
             service_config: service_config_lock,
-            sidecar_actor: sidecar::Sidecar::start(sidecar_listen,
-                                                   pkg_lock_1,
-                                                   service_config_lock_1,
-                                                   sidecar_ml,
-                                                   sidecar_rl,
-                                                   sidecar_cl,
-                                                   sidecar_detector,
-                                                   sidecar_el,
-                                                   sidecar_sup,
-                                                   sidecar_gfl),
             supervisor: supervisor,
             pkg_updater: pkg_updater,
+            pending_update: None,
+            previous_package: None,
+            update_health_check: update_health_check,
+            update_check: None,
+            soak_started: None,
+            health_check: None,
+            event_delivery: None,
+            next_health_check: next_health_check,
+            update_halted: false,
+            update_failed: false,
+            required_binds: required_binds,
+            bind_wait_started: None,
+            binds_waiting: Vec::new(),
             return_state: None,
-        })
+        };
+        try!(worker.publish_exports());
+        Ok(worker)
     }
 
-    /// update a package, but does NOT restart the service
-    pub fn update_package(&self, updated: Package) -> Result<()> {
-        let service_config = self.service_config.read().unwrap();
+    /// Publish the configuration the package exports in our census entry, so the services bound
+    /// to us see its current values.
+    pub fn publish_exports(&self) -> Result<()> {
+        let exported = {
+            let package = self.package.read().unwrap();
+            let exports = try!(package.pkg_install.exports());
+            let service_config = self.service_config.read().unwrap();
+            service_config.exported_cfg(&exports)
+        };
+        let mut cl = self.census_list.write().unwrap();
+        cl.me_mut().cfg(exported);
+        Ok(())
+    }
+
+    /// The required binds that have no member we can send traffic to exporting the keys they
+    /// need, as `name (service.group)`.
+    pub fn unsatisfied_binds(&self) -> Vec<String> {
+        let cl = self.census_list.read().unwrap();
+        self.required_binds
+            .iter()
+            .filter(|&&(ref bind, ref service_group)| {
+                cl.get(service_group).map_or(true, |census| !census.satisfies_bind(&bind.keys))
+            })
+            .map(|&(ref bind, ref service_group)| format!("{} ({})", bind.name, service_group))
+            .collect()
+    }
+
+    /// Render the configuration and hooks from the current census, and run the reconfigure
+    /// hook, whether or not any configuration file changed; the service may have to act on a
+    /// change it can't see in its files, such as a new leader.
+    pub fn reconfigure(&self) -> Result<()> {
+        let mut service_config = self.service_config.write().unwrap();
         {
-            let mut package = self.package.write().unwrap();
-            mem::replace(package.deref_mut(), updated);
+            let cl = self.census_list.read().unwrap();
+            service_config.svc(&cl);
+            service_config.bind(gconfig().bind(), &cl);
         }
         let package = self.package.read().unwrap();
-        try!(package.copy_run(&service_config));
+        try!(service_config.write(&package));
+        try!(prepare_run(&package, &service_config, &self.supervisor));
+        try!(package.reconfigure(&service_config));
         Ok(())
     }
+
+    /// update a package, but does NOT restart the service. Returns the package we replaced.
+    pub fn update_package(&self, updated: Package) -> Result<Package> {
+        let service_config = self.service_config.read().unwrap();
+        let previous = {
+            let mut package = self.package.write().unwrap();
+            mem::replace(package.deref_mut(), updated)
+        };
+        let package = self.package.read().unwrap();
+        try!(prepare_run(&package, &service_config, &self.supervisor));
+        let exported = service_config.exported_cfg(&try!(package.pkg_install.exports()));
+        let mut cl = self.census_list.write().unwrap();
+        let me = cl.me_mut();
+        me.package(Some(package.ident().to_string()));
+        me.health(None);
+        me.cfg(exported);
+        Ok(previous)
+    }
+
+    /// Apply a pending package update if it is our turn in the rolling or canary update. Returns
+    /// true if the package was updated, and the service needs to be restarted.
+    pub fn apply_pending_update(&mut self) -> Result<bool> {
+        let target = match self.pending_update {
+            Some(ref package) => package.ident().to_string(),
+            None => return Ok(false),
+        };
+        let turn = {
+            let cl = self.census_list.read().unwrap();
+            let census = cl.local_census();
+            match gconfig().update_strategy() {
+                UpdateStrategy::Canary => {
+                    census.canary_update_turn(&target, gconfig().canary_percent())
+                }
+                _ => census.rolling_update_turn(&target),
+            }
+        };
+        match turn {
+            UpdateTurn::Wait => Ok(false),
+            UpdateTurn::Halted(id) => {
+                if !self.update_halted {
+                    outputln!("Update to {} halted; {} failed its health check", target, id);
+                    self.update_halted = true;
+                }
+                Ok(false)
+            }
+            UpdateTurn::Rejected => {
+                let package = self.pending_update.take().unwrap();
+                outputln!("Skipping update to {}; the service group marked it as a bad release",
+                          target);
+                if let Some(ref updater) = self.pkg_updater {
+                    try!(package::PackageUpdater::reject(updater, package.ident().clone()));
+                    try!(package::PackageUpdater::run(updater));
+                }
+                Ok(false)
+            }
+            UpdateTurn::Go => {
+                let package = self.pending_update.take().unwrap();
+                self.update_halted = false;
+                self.update_failed = false;
+                self.previous_package = Some(try!(self.update_package(package)));
+                self.soak_started = None;
+                self.update_health_check =
+                    Some(SteadyTime::now() + time::Duration::milliseconds(UPDATE_HEALTH_CHECK_MS));
+                self.update_check = None;
+                if let Some(ref updater) = self.pkg_updater {
+                    try!(package::PackageUpdater::run(updater));
+                }
+                outputln!("Restarting because it is our turn in the update to {}", target);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Check our health after a rolling or canary update, and publish the result in the census.
+    ///
+    /// With the canary strategy we must stay healthy for the soak time before we report healthy,
+    /// and a critical result rolls us back to the previous package and marks the release as bad
+    /// for the whole service group. With the rolling strategy a critical result halts the update
+    /// for the rest of the group. Once we are healthy, the smoke_test hook gets the final say; if
+    /// it fails, we treat it like a critical result. Returns true if we rolled back, and the
+    /// service needs to be restarted.
+    ///
+    /// The health check runs in the background, and is critical if it takes longer than
+    /// `health_check_timeout_secs`.
+    pub fn check_update_health(&mut self) -> Result<bool> {
+        let canary = gconfig().update_strategy() == UpdateStrategy::Canary;
+        if canary && self.previous_package.is_some() {
+            let bad_release = {
+                let cl = self.census_list.read().unwrap();
+                let current = self.package.read().unwrap().ident().to_string();
+                cl.local_census().is_bad_release(&current)
+            };
+            if bad_release {
+                return self.rollback();
+            }
+        }
+
+        let result = match self.update_check.take() {
+            Some(pending) => {
+                let finished = pending.poll();
+                match finished {
+                    Some(result) => result,
+                    None => {
+                        self.update_check = Some(pending);
+                        return Ok(false);
+                    }
+                }
+            }
+            None => {
+                match self.update_health_check {
+                    Some(next_check) if SteadyTime::now() >= next_check => {}
+                    _ => return Ok(false),
+                }
+                self.update_check = Some(self.start_health_check());
+                return Ok(false);
+            }
+        };
+        let next_check = SteadyTime::now() + time::Duration::milliseconds(UPDATE_HEALTH_CHECK_MS);
+        match result.status {
+            health_check::Status::Ok => {
+                if canary && self.previous_package.is_some() {
+                    if self.soak_started.is_none() {
+                        self.soak_started = Some(SteadyTime::now());
+                    }
+                    let soak_started = self.soak_started.unwrap();
+                    let soak = time::Duration::seconds(gconfig().canary_soak_secs() as i64);
+                    if SteadyTime::now() - soak_started < soak {
+                        self.update_health_check = Some(next_check);
+                        return Ok(false);
+                    }
+                    outputln!("Healthy for the whole canary soak time");
+                }
+                let smoke_test = {
+                    let service_config = self.service_config.read().unwrap();
+                    let package = self.package.read().unwrap();
+                    try!(package.smoke_test(&service_config))
+                };
+                if smoke_test.status != health_check::Status::Ok {
+                    return self.update_failed(canary, "Smoke test", smoke_test);
+                }
+                let mut cl = self.census_list.write().unwrap();
+                cl.me_mut().health(Some(health_check::Status::Ok));
+                self.update_health_check = None;
+                self.soak_started = None;
+                self.previous_package = None;
+            }
+            health_check::Status::Critical => {
+                return self.update_failed(canary, "Health check", result);
+            }
+            _ => {
+                debug!("Health check inconclusive, checking again: {}", result);
+                self.update_health_check = Some(next_check);
+                self.soak_started = None;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs the health check every `health_check_interval_secs`, in the background, and records
+    /// its status in our census entry, so peers and templates can see how healthy we are.
+    fn run_health_check(&mut self) {
+        let interval = gconfig().health_check_interval_secs();
+        if interval == 0 {
+            return;
+        }
+        let finished = match self.health_check {
+            Some(ref pending) => pending.poll(),
+            None => None,
+        };
+        if let Some(result) = finished {
+            self.health_check = None;
+            self.record_health(result);
+        }
+        if self.health_check.is_none() && SteadyTime::now() >= self.next_health_check {
+            self.next_health_check = SteadyTime::now() + time::Duration::seconds(interval as i64);
+            self.health_check = Some(self.start_health_check());
+        }
+    }
+
+    /// Runs the event hook once for every event we received since the last delivery, in the
+    /// background, once the last delivery is done. The hook is killed if it runs longer than
+    /// `EVENT_HOOK_TIMEOUT_SECS` for an event.
+    fn deliver_events(&mut self) {
+        if let Some(ref delivery) = self.event_delivery {
+            if let Err(TryRecvError::Empty) = delivery.try_recv() {
+                return;
+            }
+        }
+        self.event_delivery = None;
+        let events = self.event_list.write().unwrap().undelivered();
+        if events.is_empty() {
+            return;
+        }
+        let hook = {
+            let service_config = self.service_config.read().unwrap();
+            let package = self.package.read().unwrap();
+            package.event_hook(&service_config)
+        };
+        let mut hook = match hook {
+            Ok(Some(hook)) => hook,
+            Ok(None) => return,
+            Err(e) => {
+                outputln!("Failed to deliver {} events: {}", events.len(), e);
+                return;
+            }
+        };
+        if hook.timeout_secs == 0 || hook.timeout_secs > EVENT_HOOK_TIMEOUT_SECS {
+            hook.timeout_secs = EVENT_HOOK_TIMEOUT_SECS;
+        }
+        let (tx, rx) = channel();
+        let spawned = thread::Builder::new().name(String::from("event-hook")).spawn(move || {
+            for event in events.iter() {
+                debug!("Delivering {}", event);
+                if let Err(e) = hook.exec_with_env(&package::event_env(event)) {
+                    outputln!("Failed to deliver {}: {}", event, e);
+                }
+            }
+            tx.send(()).unwrap_or(());
+        });
+        match spawned {
+            Ok(_) => self.event_delivery = Some(rx),
+            Err(e) => outputln!("Failed to start delivering events: {}", e),
+        }
+    }
+
+    fn start_health_check(&self) -> health_check::PendingCheck {
+        let timeout = time::Duration::seconds(gconfig().health_check_timeout_secs() as i64);
+        let package = self.package.read().unwrap();
+        match package.hooks().health_check_hook {
+            Some(mut hook) => {
+                // Don't leave a hung health check running once we have given up on it
+                let timeout_secs = gconfig().health_check_timeout_secs();
+                if hook.timeout_secs == 0 || hook.timeout_secs > timeout_secs {
+                    hook.timeout_secs = timeout_secs;
+                }
+                let compiled = {
+                    let service_config = self.service_config.read().unwrap();
+                    hook.compile(Some(&service_config))
+                };
+                match compiled {
+                    Ok(()) => {
+                        health_check::PendingCheck::start(timeout, move || {
+                            match package::health_check_result(hook.exec()) {
+                                Ok(result) => result,
+                                Err(e) => health_check::CheckResult::unknown(format!("{}", e)),
+                            }
+                        })
+                    }
+                    Err(e) => {
+                        let result = health_check::CheckResult::unknown(format!("{}", e));
+                        health_check::PendingCheck::start(timeout, move || result)
+                    }
+                }
+            }
+            None => {
+                let result = {
+                    let supervisor = self.supervisor.read().unwrap();
+                    match package.supervisor_health(&supervisor) {
+                        Ok(result) => result,
+                        Err(e) => health_check::CheckResult::unknown(format!("{}", e)),
+                    }
+                };
+                health_check::PendingCheck::start(timeout, move || result)
+            }
+        }
+    }
+
+    /// Records how many times our process has been restarted, and how it last ended, in our
+    /// census entry.
+    fn record_process_status(&self) {
+        let (restarts, last_exit) = {
+            let supervisor = self.supervisor.read().unwrap();
+            (supervisor.restart_count,
+             supervisor.last_termination.as_ref().map(|t| t.to_string()))
+        };
+        let mut cl = self.census_list.write().unwrap();
+        cl.me_mut().process_status(restarts, last_exit);
+    }
+
+    /// Records the result of the periodic health check in our census entry and elections. While
+    /// we still have to report our health for an update, or our last update failed, the update
+    /// decides the health our census entry reports.
+    fn record_health(&mut self, result: health_check::CheckResult) {
+        let status = result.status.clone();
+        if self.update_health_check.is_none() && !self.update_failed {
+            let mut cl = self.census_list.write().unwrap();
+            if cl.me().health.as_ref() != Some(&status) {
+                outputln!(preamble & self.package_name, "Health check {}", result);
+            }
+            cl.me_mut().health(Some(status.clone()));
+        }
+        let mut el = self.election_list.write().unwrap();
+        el.set_critical(status == health_check::Status::Critical);
+    }
+
+    /// Our updated package failed its health check or smoke test. With the canary strategy we
+    /// roll back, and mark the release as bad for the whole service group; otherwise we report
+    /// ourselves unhealthy, which halts the update for the rest of the group. Returns true if we
+    /// rolled back, and the service needs to be restarted.
+    fn update_failed(&mut self,
+                     canary: bool,
+                     check: &str,
+                     result: health_check::CheckResult)
+                     -> Result<bool> {
+        if canary && self.previous_package.is_some() {
+            outputln!("{} failed; rolling back the canary update: {}", check, result);
+            {
+                let current = self.package.read().unwrap().ident().to_string();
+                let mut cl = self.census_list.write().unwrap();
+                cl.me_mut().bad_release(current);
+            }
+            return self.rollback();
+        }
+        outputln!("{} failed; halting the update: {}", check, result);
+        let mut cl = self.census_list.write().unwrap();
+        cl.me_mut().health(Some(health_check::Status::Critical));
+        self.update_failed = true;
+        self.update_health_check = None;
+        self.soak_started = None;
+        Ok(false)
+    }
+
+    /// Run the suitability hook, and record the result in our census entry and in the elections
+    /// we start, so the most suitable member is elected leader.
+    pub fn update_suitability(&self) {
+        let suitability = {
+            let package = self.package.read().unwrap();
+            let service_config = self.service_config.read().unwrap();
+            package.suitability(&service_config)
+        };
+        match suitability {
+            Ok(Some(suitability)) => {
+                {
+                    let mut cl = self.census_list.write().unwrap();
+                    cl.me_mut().suitability(suitability);
+                }
+                let mut el = self.election_list.write().unwrap();
+                if suitability > u32::MAX as u64 {
+                    el.set_suitability(u32::MAX);
+                } else {
+                    el.set_suitability(suitability as u32);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                outputln!(preamble & self.package_name, "Keeping our last suitability: {}", e)
+            }
+        }
+    }
+
+    /// Go back to the package we ran before our last update, and make sure the updater never
+    /// offers the bad release again.
+    fn rollback(&mut self) -> Result<bool> {
+        let previous = self.previous_package.take().unwrap();
+        outputln!("Rolling back to {}", previous.ident());
+        let bad = try!(self.update_package(previous));
+        if let Some(ref updater) = self.pkg_updater {
+            try!(package::PackageUpdater::reject(updater, bad.ident().clone()));
+        }
+        self.soak_started = None;
+        self.update_health_check =
+            Some(SteadyTime::now() + time::Duration::milliseconds(UPDATE_HEALTH_CHECK_MS));
+        self.update_check = None;
+        Ok(true)
+    }
+}
+
+/// Match the binds given on the command line against those the package declares. Returns the
+/// required binds, with the service group each is bound to.
+///
+/// # Failures
+///
+/// * If a required bind was not given
+/// * If the package's bind metadata cannot be read
+fn required_binds(package: &Package, bindings: &[String]) -> Result<Vec<(Bind, String)>> {
+    let bindings: Vec<(&str, &str)> = bindings.iter()
+        .filter_map(|binding| {
+            let mut parts = binding.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(service_group)) => Some((name, service_group)),
+                _ => None,
+            }
+        })
+        .collect();
+    let required = try!(package.pkg_install.binds());
+    let optional = try!(package.pkg_install.binds_optional());
+
+    let mut result = Vec::new();
+    let mut missing = Vec::new();
+    for bind in required.iter() {
+        match bindings.iter().find(|&&(name, _)| name == bind.name) {
+            Some(&(_, service_group)) => result.push((bind.clone(), service_group.to_string())),
+            None => missing.push(bind.name.clone()),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(sup_error!(Error::MissingBinds(missing)));
+    }
+    if !required.is_empty() || !optional.is_empty() {
+        for &(name, _) in bindings.iter() {
+            if !required.iter().chain(optional.iter()).any(|bind| bind.name == name) {
+                outputln!("The package does not declare a bind named {}; binding it anyway",
+                          name);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Wait until each of the package's required binds has a member we can send traffic to that
+/// exports the keys it needs, then move on to `next`. Topologies start in this state.
+///
+/// # Failures
+///
+/// * If the binds are still not satisfied once the `--bind-wait` time has passed
+pub fn state_waiting_for_binds(worker: &mut Worker, next: State) -> Result<(State, u64)> {
+    let unsatisfied = worker.unsatisfied_binds();
+    if unsatisfied.is_empty() {
+        if worker.bind_wait_started.take().is_some() {
+            outputln!("Required binds are satisfied");
+        }
+        worker.binds_waiting = Vec::new();
+        return Ok((next, 0));
+    }
+    let now = SteadyTime::now();
+    let started = match worker.bind_wait_started {
+        Some(started) => started,
+        None => {
+            worker.bind_wait_started = Some(now);
+            now
+        }
+    };
+    if let Some(secs) = gconfig().bind_wait_secs() {
+        if now - started >= time::Duration::seconds(secs as i64) {
+            return Err(sup_error!(Error::BindsUnsatisfied(unsatisfied)));
+        }
+    }
+    if unsatisfied != worker.binds_waiting {
+        outputln!("Waiting for binds: {}", unsatisfied.join(", "));
+        worker.binds_waiting = unsatisfied;
+    }
+    Ok((State::WaitingForBinds, BIND_WAIT_MS))
+}
+
+/// Copy the run file into place, and compile the hooks the supervisor runs around the process
+/// and work out how it stops the process, for the current package and service config.
+fn prepare_run(package: &Package,
+               service_config: &ServiceConfig,
+               supervisor: &RwLock<Supervisor>)
+               -> Result<()> {
+    try!(package.copy_run(service_config));
+    let hooks = try!(package.process_hooks(service_config));
+    let shutdown = try!(package.shutdown(service_config));
+    let mut supervisor = supervisor.write().unwrap();
+    supervisor.hooks = hooks;
+    supervisor.shutdown = shutdown;
+    Ok(())
 }
 
 /// The main loop of a topology.
 ///
 /// 1. Loops forever
-/// 1. Checks if we have caught a signal; if so, acts on the signal. (May exit entirely)
+/// 1. Checks if the service manager has asked us to stop; if so, exits
 /// 1. Checks the current `state` of our [StateMachine](../state_machine)
 /// 1. If it is running, we run a non-blocking `waitpid`, and inspect why the child died;
 ///    depending on the circumstances, we may exit with an error here
@@ -252,41 +802,25 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
         let package = worker.package.read().unwrap();
         let service_config = worker.service_config.read().unwrap();
         try!(package.create_svc_path());
-        try!(package.copy_run(&service_config));
+        try!(prepare_run(&package, &service_config, &worker.supervisor));
     }
-    let handler = wonder::actor::Builder::new(SignalNotifier)
-        .name("signal-handler".to_string())
-        .start(())
-        .unwrap();
     loop {
         let start_time = SteadyTime::now();
-        match handler.receiver.try_recv() {
-            Ok(wonder::actor::Message::Cast(signals::Message::Signal(sig))) => {
-                debug!("SIG = {:?}", sig);
-                match sig {
-                    signals::Signal::SIGINT | signals::Signal::SIGTERM => {
-                        let mut supervisor = worker.supervisor.write().unwrap();
-                        try!(supervisor.down());
-                        break;
-                    }
-                    _ => {
-                        outputln!("Forwarding {:?} on to the supervised process", sig);
-                        let supervisor = worker.supervisor.write().unwrap();
-                        try!(supervisor.send_unix_signal(sig.clone()));
-                    }
-                };
-            }
-            Ok(_) => {}
-            Err(TryRecvError::Empty) => {}
+        match worker.control.try_recv() {
+            Ok(ServiceMessage::Stop) |
             Err(TryRecvError::Disconnected) => {
-                panic!("signal handler crashed!");
+                let mut supervisor = worker.supervisor.write().unwrap();
+                try!(supervisor.down());
+                break;
             }
+            Err(TryRecvError::Empty) => {}
         }
 
         {
             let mut supervisor = worker.supervisor.write().unwrap();
             try!(supervisor.check_process());
         }
+        worker.record_process_status();
 
         let mut restart_process = false;
 
@@ -312,13 +846,25 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
                     service_config.svc(&cl);
                     service_config.bind(gconfig().bind(), &cl);
                 }
+                {
+                    let mut cl = worker.census_list.write().unwrap();
+                    cl.written();
+                }
                 if write_rumor {
                     debug!("Writing our census rumor: {:#?}", me_clone);
-                    let mut rl = worker.rumor_list.write().unwrap();
-                    rl.add_rumor(Rumor::census_entry(me_clone));
+                    {
+                        let mut rl = worker.rumor_list.write().unwrap();
+                        rl.add_rumor(Rumor::census_entry(me_clone.clone()));
+                    }
+                    // Services running beside us in this supervisor learn about us the same way
+                    // remote members do
+                    let mut local_rumors = RumorList::new();
+                    local_rumors.add_rumor(Rumor::census_entry(me_clone));
+                    gossip::server::process_rumors(local_rumors,
+                                                   worker.rumor_list.clone(),
+                                                   worker.member_list.clone(),
+                                                   worker.gossip_services.clone());
                 }
-                let mut cl = worker.census_list.write().unwrap();
-                cl.written();
             }
 
             // Don't bother trying to reconfigure if we are in an event - just wait till
@@ -332,7 +878,7 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
                         let package = worker.package.read().unwrap();
                         // Write the configuration, and restart if needed
                         if try!(service_config.write(&package)) {
-                            try!(package.copy_run(&service_config));
+                            try!(prepare_run(&package, &service_config, &worker.supervisor));
                             try!(package.reconfigure(&service_config));
                             outputln!("Restarting because the service config was updated via the \
                                        census");
@@ -377,27 +923,49 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
             if needs_reconfigure {
                 let mut service_config = worker.service_config.write().unwrap();
                 let package = worker.package.read().unwrap();
-                service_config.cfg(&package);
+                let applied = service_config.cfg(&package);
+                let written_config = worker.gossip_file_list.write().unwrap().take_written_config();
+                if let Some(gf) = written_config {
+                    if applied {
+                        if let Err(e) = ConfigHistory::for_service(&package.name).record(&gf) {
+                            outputln!("Failed to record version {} of the configuration in the \
+                                       history: {}",
+                                      gf.version_number(),
+                                      e);
+                        }
+                    }
+                }
                 if try!(service_config.write(&package)) {
-                    try!(package.copy_run(&service_config));
+                    try!(prepare_run(&package, &service_config, &worker.supervisor));
                     let existed = try!(package.reconfigure(&service_config));
                     if !existed {
                         restart_process = true;
                     }
                 }
             }
+            if needs_reconfigure {
+                try!(worker.publish_exports());
+            }
         }
 
+        worker.deliver_events();
+
         if let Some(ref updater) = worker.pkg_updater {
             match updater.receiver.try_recv() {
                 Ok(wonder::actor::Message::Cast(package::UpdaterMessage::Update(package))) => {
                     debug!("Main loop received package update notification: {:?}",
                            &package);
-                    try!(worker.update_package(package));
-                    try!(package::PackageUpdater::run(&updater));
-                    // force the package to restart
-                    outputln!("Restarting because the package was updated");
-                    restart_process = true;
+                    let strategy = gconfig().update_strategy();
+                    if strategy == UpdateStrategy::Rolling || strategy == UpdateStrategy::Canary {
+                        outputln!("Waiting for our turn in the update to {}", package.ident());
+                        worker.pending_update = Some(package);
+                    } else {
+                        try!(worker.update_package(package));
+                        try!(package::PackageUpdater::run(&updater));
+                        // force the package to restart
+                        outputln!("Restarting because the package was updated");
+                        restart_process = true;
+                    }
                 }
                 Ok(_) => {}
                 Err(TryRecvError::Empty) => {}
@@ -407,14 +975,26 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
             }
         }
 
+        worker.run_health_check();
+
+        if try!(worker.apply_pending_update()) {
+            restart_process = true;
+        }
+        if try!(worker.check_update_health()) {
+            outputln!("Restarting because the package was rolled back");
+            restart_process = true;
+        }
+
         {
             let mut supervisor = worker.supervisor.write().unwrap();
             // If our target is that the process is up
             if supervisor.is_up() {
                 // And no process is running
                 if supervisor.pid.is_none() {
-                    // Start a new one
-                    try!(supervisor.start());
+                    // Start a new one, once we have backed off from its last failure
+                    if supervisor.restart_due() {
+                        try!(supervisor.start());
+                    }
                 } else {
                     // If we were supposed to restart
                     if restart_process {
@@ -425,6 +1005,10 @@ fn run_internal(sm: &mut StateMachine<State, Worker, SupError>,
                         }
                     }
                 }
+            } else if restart_process && supervisor.is_crash_looping() {
+                // A new package or configuration may be what it needed
+                outputln!("Starting the crash looping service again, since it changed");
+                try!(supervisor.start());
             }
         }
 
@@ -17,6 +17,8 @@
 //!
 //! ![Standalone Topology](../../images/standalone.png)
 //!
+//! * **WaitingForBinds**: Waits until the package's required binds have live members exporting
+//! the keys they need.
 //! * **Initializing**: Initializes the service by running the `init` hook if present.
 //! * **Starting**: Starts the service under `runsv`, and starts a thread to process output and
 //! handle errors.
@@ -24,6 +26,7 @@
 
 use error::{Result, SupError};
 use package::Package;
+use manager::ServiceContext;
 use state_machine::StateMachine;
 use topology::{self, State, Worker};
 
@@ -31,15 +34,21 @@ use topology::{self, State, Worker};
 ///
 /// Add's the state transitions to the state machine, sets up the signal handlers, and runs the
 /// `topology::run_internal` function.
-pub fn run(package: Package) -> Result<()> {
-    let mut worker = try!(Worker::new(package, String::from("standalone")));
-    let mut sm: StateMachine<State, Worker, SupError> = StateMachine::new(State::Initializing);
+pub fn run(package: Package, ctx: ServiceContext) -> Result<()> {
+    let mut worker = try!(Worker::new(package, String::from("standalone"), ctx));
+    let mut sm: StateMachine<State, Worker, SupError> = StateMachine::new(State::WaitingForBinds);
+    sm.add_dispatch(State::WaitingForBinds, state_waiting_for_binds);
     sm.add_dispatch(State::Initializing, state_initializing);
     sm.add_dispatch(State::Starting, state_starting);
     sm.add_dispatch(State::Running, state_running);
     topology::run_internal(&mut sm, &mut worker)
 }
 
+/// Wait for the required binds, then initialize the service.
+pub fn state_waiting_for_binds(worker: &mut Worker) -> Result<(State, u64)> {
+    topology::state_waiting_for_binds(worker, State::Initializing)
+}
+
 /// Initialize the service.
 pub fn state_initializing(worker: &mut Worker) -> Result<(State, u64)> {
     let service_config = worker.service_config.read().unwrap();
@@ -13,10 +13,15 @@
 // limitations under the License.
 
 use std::collections::BTreeMap;
+use std::i64;
 
 use toml;
 use rustc_serialize::json::Json;
 
+use error::{Error, Result};
+
+static LOGKEY: &'static str = "CV";
+
 pub fn toml_to_json(value: toml::Value) -> Json {
     match value {
         toml::Value::String(s) => Json::String(format!("{}", s)),
@@ -45,3 +50,55 @@ pub fn toml_table_to_json(toml: BTreeMap<String, toml::Value>) -> Json {
     }
     Json::Object(hashmap)
 }
+
+/// Translates JSON to TOML. TOML has no null, so nulls are left out.
+///
+/// # Failures
+///
+/// * A number is larger than the largest TOML integer
+pub fn json_to_toml(value: Json) -> Result<Option<toml::Value>> {
+    json_to_toml_at(value, &mut Vec::new())
+}
+
+/// Translates the JSON found at `path`, the keys of the objects it is in, to TOML.
+fn json_to_toml_at(value: Json, path: &mut Vec<String>) -> Result<Option<toml::Value>> {
+    match value {
+        Json::I64(i) => Ok(Some(toml::Value::Integer(i))),
+        Json::U64(u) if u > i64::MAX as u64 => {
+            let problem = format!("{} is {}, but integers can be at most {}",
+                                  path.join("."),
+                                  u,
+                                  i64::MAX);
+            Err(sup_error!(Error::InvalidConfig(vec![problem])))
+        }
+        Json::U64(u) => Ok(Some(toml::Value::Integer(u as i64))),
+        Json::F64(f) => Ok(Some(toml::Value::Float(f))),
+        Json::String(s) => Ok(Some(toml::Value::String(s))),
+        Json::Boolean(b) => Ok(Some(toml::Value::Boolean(b))),
+        Json::Array(a) => {
+            let mut array = Vec::new();
+            for (i, value) in a.into_iter().enumerate() {
+                path.push(i.to_string());
+                let value = json_to_toml_at(value, path);
+                path.pop();
+                if let Some(value) = try!(value) {
+                    array.push(value);
+                }
+            }
+            Ok(Some(toml::Value::Array(array)))
+        }
+        Json::Object(o) => {
+            let mut table = BTreeMap::new();
+            for (key, value) in o.into_iter() {
+                path.push(key.clone());
+                let value = json_to_toml_at(value, path);
+                path.pop();
+                if let Some(value) = try!(value) {
+                    table.insert(key, value);
+                }
+            }
+            Ok(Some(toml::Value::Table(table)))
+        }
+        Json::Null => Ok(None),
+    }
+}
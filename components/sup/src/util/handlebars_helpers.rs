@@ -12,8 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use handlebars::{Context, Handlebars, Helper, RenderContext, RenderError};
+use std::str::FromStr;
+
+use handlebars::{Context, Handlebars, Helper, RenderContext, RenderError, Renderable};
+use hcore::package::{Identifiable, PackageIdent, PackageInstall};
 use rustc_serialize::Encodable;
+use rustc_serialize::json::{Json, ToJson};
 use toml;
 
 pub fn json_helper(_: &Context,
@@ -43,11 +47,168 @@ pub fn toml_helper(_: &Context,
     Ok(())
 }
 
+/// `{{toUppercase value}}` renders a string in upper case.
+pub fn to_uppercase_helper(_: &Context,
+                           h: &Helper,
+                           _: &Handlebars,
+                           rc: &mut RenderContext)
+                           -> Result<(), RenderError> {
+    let value = try!(string_param(h, 0, "toUppercase"));
+    try!(rc.writer.write(value.to_uppercase().into_bytes().as_ref()));
+    Ok(())
+}
+
+/// `{{toLowercase value}}` renders a string in lower case.
+pub fn to_lowercase_helper(_: &Context,
+                           h: &Helper,
+                           _: &Handlebars,
+                           rc: &mut RenderContext)
+                           -> Result<(), RenderError> {
+    let value = try!(string_param(h, 0, "toLowercase"));
+    try!(rc.writer.write(value.to_lowercase().into_bytes().as_ref()));
+    Ok(())
+}
+
+/// `{{strReplace value "from" "to"}}` renders a string with every `from` replaced by `to`.
+pub fn str_replace_helper(_: &Context,
+                          h: &Helper,
+                          _: &Handlebars,
+                          rc: &mut RenderContext)
+                          -> Result<(), RenderError> {
+    let value = try!(string_param(h, 0, "strReplace"));
+    let from = try!(string_param(h, 1, "strReplace"));
+    let to = try!(string_param(h, 2, "strReplace"));
+    try!(rc.writer.write(value.replace(&from, &to).into_bytes().as_ref()));
+    Ok(())
+}
+
+/// `{{strJoin list ","}}` renders the items of a list, separated by a string.
+pub fn str_join_helper(_: &Context,
+                       h: &Helper,
+                       _: &Handlebars,
+                       rc: &mut RenderContext)
+                       -> Result<(), RenderError> {
+    let list = try!(h.param(0)
+            .ok_or_else(|| RenderError::new("Param not found for helper \"strJoin\"")))
+        .value();
+    let separator = try!(string_param(h, 1, "strJoin"));
+    let items: Vec<String> = match *list {
+        Json::Array(ref items) => items.iter().map(json_to_string).collect(),
+        _ => return Err(RenderError::new("Param of helper \"strJoin\" must be a list")),
+    };
+    try!(rc.writer.write(items.join(&separator).into_bytes().as_ref()));
+    Ok(())
+}
+
+/// `{{#eachAlive bind.redis.members}}...{{/eachAlive}}` renders its block for each census member
+/// in a list that is alive, like `each`. `@index`, `@first` and `@last` count only the members
+/// that are alive.
+pub fn each_alive_helper(c: &Context,
+                         h: &Helper,
+                         r: &Handlebars,
+                         rc: &mut RenderContext)
+                         -> Result<(), RenderError> {
+    let param = try!(h.param(0)
+        .ok_or_else(|| RenderError::new("Param not found for helper \"eachAlive\"")));
+    let template = match h.template() {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+    let members = match *param.value() {
+        Json::Array(ref members) => members,
+        _ => return Err(RenderError::new("Param of helper \"eachAlive\" must be a list")),
+    };
+    let path = try!(param.path()
+        .ok_or_else(|| RenderError::new("Param of helper \"eachAlive\" must be a path")));
+    let alive: Vec<usize> = members.iter()
+        .enumerate()
+        .filter(|&(_, member)| member.find("alive").and_then(|a| a.as_boolean()) == Some(true))
+        .map(|(i, _)| i)
+        .collect();
+    let base_path = rc.get_path().clone();
+    for (n, i) in alive.iter().enumerate() {
+        rc.set_local_var("@first".to_string(), (n == 0).to_json());
+        rc.set_local_var("@last".to_string(), (n == alive.len() - 1).to_json());
+        rc.set_local_var("@index".to_string(), n.to_json());
+        rc.set_path(format!("{}/{}/[{}]", base_path, path, i));
+        try!(template.render(c, r, rc));
+    }
+    rc.set_path(base_path);
+    Ok(())
+}
+
+/// `{{pkgPathFor "core/openssl"}}` renders the install path of a dependency of the package. If it
+/// is not a direct dependency, the path of the latest installed package matching it is rendered.
+pub fn pkg_path_for_helper(c: &Context,
+                           h: &Helper,
+                           _: &Handlebars,
+                           rc: &mut RenderContext)
+                           -> Result<(), RenderError> {
+    let param = try!(string_param(h, 0, "pkgPathFor"));
+    let ident = try!(PackageIdent::from_str(&param)
+        .map_err(|e| RenderError::new(format!("Invalid package for \"pkgPathFor\": {}", e))));
+    let deps = c.data().find_path(&["pkg", "deps"]).and_then(|d| d.as_array());
+    let dep_path = deps.and_then(|deps| {
+        deps.iter()
+            .find(|dep| {
+                dep.find("ident")
+                    .and_then(|i| i.as_string())
+                    .and_then(|i| PackageIdent::from_str(i).ok())
+                    .map_or(false, |i| i.satisfies(&ident))
+            })
+            .and_then(|dep| dep.find("path"))
+            .and_then(|p| p.as_string())
+            .map(|p| p.to_string())
+    });
+    let path = match dep_path {
+        Some(path) => path,
+        None => {
+            let pkg = try!(PackageInstall::load(&ident, None)
+                .map_err(|e| RenderError::new(format!("Can't find {} for \"pkgPathFor\": {}",
+                                                      ident,
+                                                      e))));
+            pkg.installed_path().to_string_lossy().into_owned()
+        }
+    };
+    try!(rc.writer.write(path.into_bytes().as_ref()));
+    Ok(())
+}
+
+fn string_param(h: &Helper, index: usize, helper: &str) -> Result<String, RenderError> {
+    let value = try!(h.param(index)
+            .ok_or_else(|| RenderError::new(format!("Param not found for helper \"{}\"", helper))))
+        .value();
+    Ok(json_to_string(value))
+}
+
+/// Strings are rendered as they are; anything else as JSON.
+fn json_to_string(value: &Json) -> String {
+    match *value {
+        Json::String(ref s) => s.clone(),
+        ref other => other.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use handlebars::{Handlebars, Template};
     use std::collections::BTreeMap;
-    use super::{json_helper, toml_helper};
+    use rustc_serialize::json::Json;
+
+    use super::{each_alive_helper, json_helper, pkg_path_for_helper, str_join_helper,
+                str_replace_helper, to_lowercase_helper, to_uppercase_helper, toml_helper};
+
+    fn render(template: &str, data: &Json) -> String {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("toUppercase", Box::new(to_uppercase_helper));
+        handlebars.register_helper("toLowercase", Box::new(to_lowercase_helper));
+        handlebars.register_helper("strReplace", Box::new(str_replace_helper));
+        handlebars.register_helper("strJoin", Box::new(str_join_helper));
+        handlebars.register_helper("eachAlive", Box::new(each_alive_helper));
+        handlebars.register_helper("pkgPathFor", Box::new(pkg_path_for_helper));
+        handlebars.register_template("t", Template::compile(template.to_string()).ok().unwrap());
+        handlebars.render("t", data).ok().unwrap()
+    }
 
     #[test]
     fn test_handlebars_json_helper() {
@@ -91,4 +252,47 @@ mod test {
 "#
                        .to_string());
     }
+
+    #[test]
+    fn test_handlebars_case_helpers() {
+        let data = Json::from_str(r#"{"level": "Warning"}"#).unwrap();
+        assert_eq!(render("{{toUppercase level}} {{toLowercase level}}", &data),
+                   "WARNING warning");
+    }
+
+    #[test]
+    fn test_handlebars_str_replace_helper() {
+        let data = Json::from_str(r#"{"host": "db.example.com"}"#).unwrap();
+        assert_eq!(render("{{strReplace host \".\" \"-\"}}", &data), "db-example-com");
+    }
+
+    #[test]
+    fn test_handlebars_str_join_helper() {
+        let data = Json::from_str(r#"{"hosts": ["a", "b", "c"], "ports": [1, 2]}"#).unwrap();
+        assert_eq!(render("{{strJoin hosts \",\"}} {{strJoin ports \":\"}}", &data),
+                   "a,b,c 1:2");
+    }
+
+    #[test]
+    fn test_handlebars_each_alive_helper() {
+        let data = Json::from_str(r#"{"svc": {"members": [
+            {"ip": "10.0.0.1", "alive": true},
+            {"ip": "10.0.0.2", "alive": false},
+            {"ip": "10.0.0.3", "alive": true}
+        ]}}"#)
+            .unwrap();
+        assert_eq!(render("{{#eachAlive svc.members}}{{@index}}={{ip}} {{/eachAlive}}", &data),
+                   "0=10.0.0.1 1=10.0.0.3 ");
+    }
+
+    #[test]
+    fn test_handlebars_pkg_path_for_helper() {
+        let data = Json::from_str(r#"{"pkg": {"deps": [
+            {"ident": "core/glibc/2.22/20160427193532", "path": "/hab/pkgs/core/glibc"},
+            {"ident": "core/openssl/1.0.2h/20160613184547", "path": "/hab/pkgs/core/openssl"}
+        ]}}"#)
+            .unwrap();
+        assert_eq!(render("{{pkgPathFor \"core/openssl\"}}", &data),
+                   "/hab/pkgs/core/openssl");
+    }
 }
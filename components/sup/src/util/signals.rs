@@ -18,6 +18,7 @@
 //! sent to the running process and notifies the receiver channel of a caught
 //! `signals::Signal`.
 
+use std::str::FromStr;
 use std::sync::{Once, ONCE_INIT};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_USIZE_INIT, ATOMIC_BOOL_INIT};
 
@@ -75,6 +76,27 @@ pub enum Signal {
     SIGUSR2 = 31,
 }
 
+impl FromStr for Signal {
+    type Err = SupError;
+
+    /// Parses a signal name, with or without its `SIG` prefix (ex: `HUP` or `SIGHUP`).
+    fn from_str(s: &str) -> Result<Signal> {
+        let name = s.to_uppercase();
+        let name = name.trim_left_matches("SIG");
+        match name {
+            "HUP" => Ok(Signal::SIGHUP),
+            "INT" => Ok(Signal::SIGINT),
+            "QUIT" => Ok(Signal::SIGQUIT),
+            "KILL" => Ok(Signal::SIGKILL),
+            "ALRM" => Ok(Signal::SIGALRM),
+            "TERM" => Ok(Signal::SIGTERM),
+            "USR1" => Ok(Signal::SIGUSR1),
+            "USR2" => Ok(Signal::SIGUSR2),
+            _ => Err(sup_error!(Error::InvalidSignal(s.to_string()))),
+        }
+    }
+}
+
 /// Thread worker that traps UNIX signals and sends a `Signal` down the receiver
 /// channel representing the trapped UNIX signal.
 pub struct SignalNotifier;
@@ -196,3 +218,16 @@ pub fn send_signal_to_pid(pid: u32, sig: Signal) -> Result<()> {
         }
     }
 }
+
+/// send a Unix signal to every process in a process group
+pub fn send_signal_to_process_group(pgid: u32, sig: Signal) -> Result<()> {
+    let s = sig as u32;
+    debug!("sending signal {} to process group {}", s, pgid);
+    unsafe {
+        let result = kill(-(pgid as i32), s);
+        match result {
+            0 => Ok(()),
+            _ => return Err(sup_error!(Error::SignalFailed)),
+        }
+    }
+}
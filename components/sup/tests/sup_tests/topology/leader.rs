@@ -358,3 +358,45 @@ fn leader_without_quorum_stops_service_remainder_elects_new_leader() {
     // assert!(og_leader.wait_for_leader());
     // assert!(og_leader.wait_for_leader());
 }
+
+// Start three supervisors; once they have a leader, kill it. Once the survivors confirm it is
+// dead, they should start a new election term, promote one of themselves, and run the
+// reconfigure hook so the service learns about its new leader.
+#[test]
+#[ignore]
+fn promotes_a_follower_when_the_leader_dies() {
+    setup::origin_setup();
+    setup::simple_service_gossip();
+
+    let sup_a = Supervisor::new_with_topology("leader");
+    let sup_b = Supervisor::with_peer_topology(&sup_a, "leader");
+    let sup_c = Supervisor::with_peer_topology(&sup_a, "leader");
+    let mut sups = vec![sup_a, sup_b, sup_c];
+
+    for sup in sups.iter() {
+        for other in sups.iter().filter(|other| other.id != sup.id) {
+            assert!(sup.wait_for_alive(other));
+        }
+        assert!(sup.wait_for_leader());
+    }
+
+    let term = sups[0].term();
+    let og_leader_id = sups[0].leader();
+    let og_leader_index = sups.iter()
+        .position(|sup| sup.id == og_leader_id)
+        .expect("We had a leader, but then.. we couldn't find it in our list");
+    let mut og_leader = sups.remove(og_leader_index);
+    og_leader.stop();
+
+    for sup in sups.iter() {
+        assert!(sup.wait_for_confirmed(&og_leader));
+        assert!(sup.wait_for_term_newer_than(term));
+        assert!(sup.wait_for_leader());
+        assert!(sup.leader() != og_leader.id);
+    }
+    assert_eq!(sups[0].leader(), sups[1].leader());
+
+    for sup in sups.iter() {
+        assert!(sup.docker.wait_until("Running the reconfigure hook for the new leader"));
+    }
+}